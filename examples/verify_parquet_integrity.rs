@@ -59,7 +59,8 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     while let Some(event) = stream.next() {
         match event? {
-            DataEvent::Orderbook(ob) => {
+            DataEvent::Orderbook(row) => {
+                let ob = row.view().to_snapshot()?;
                 orderbook_count += 1;
 
                 // Check timestamp ordering
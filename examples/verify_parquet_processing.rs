@@ -32,7 +32,9 @@ fn main() -> Result<(), Box<dyn Error>> {
                      return Err(format!("Invalid trade data: {:?}", t).into());
                 }
             },
-            DataEvent::Orderbook(ob) => {
+            DataEvent::Orderbook(row) => {
+                let ob = row.view().to_snapshot()?;
+
                 // 2. Top-of-Book Integrity
                 if ob.bids.is_empty() || ob.asks.is_empty() {
                     // It's possible to have empty side if market is illiquid or connection just started, 
@@ -0,0 +1,207 @@
+//! Adaptive spread floor from realised adverse selection
+//!
+//! Public trades near the touch are treated as fills our quotes would have received. After
+//! `adverse_selection_horizon_seconds` the mid is compared with the trade price: a trade
+//! that hit the bid followed by a lower mid (or lifted the ask followed by a higher mid) is
+//! a loss to the maker. Per-side losses are averaged with exponentially decaying weights,
+//! and the implied per-side spread floor decays back to the static floor when flow calms.
+
+use crate::model_types::{ASConfig, TradeEvent};
+use rust_decimal::prelude::*;
+use std::collections::VecDeque;
+
+/// Weight (in trades) of the zero-toxicity prior, so the floor relaxes as observations decay
+const PRIOR_WEIGHT: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MakerSide {
+    /// Aggressive sell hit the bid (maker bought)
+    Bid,
+    /// Aggressive buy lifted the ask (maker sold)
+    Ask,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingTrade {
+    timestamp: u64,
+    price: f64,
+    side: MakerSide,
+}
+
+/// Exponentially decayed sum of markout costs for one side
+#[derive(Debug, Clone, Copy, Default)]
+struct DecayedMarkout {
+    weighted_cost_bps: f64,
+    weight: f64,
+}
+
+impl DecayedMarkout {
+    fn decay(&mut self, factor: f64) {
+        self.weighted_cost_bps *= factor;
+        self.weight *= factor;
+    }
+
+    fn add(&mut self, cost_bps: f64) {
+        self.weighted_cost_bps += cost_bps;
+        self.weight += 1.0;
+    }
+
+    /// Mean markout cost shrunk toward zero by the prior
+    fn cost_bps(&self) -> f64 {
+        self.weighted_cost_bps / (self.weight + PRIOR_WEIGHT)
+    }
+}
+
+pub struct AdverseSelectionTracker {
+    horizon_ms: u64,
+    halflife_ms: f64,
+    touch_bps: f64,
+    multiplier: f64,
+    maker_fee_bps: f64,
+    pending: VecDeque<PendingTrade>,
+    bid: DecayedMarkout,
+    ask: DecayedMarkout,
+    last_decay_ts: u64,
+}
+
+impl AdverseSelectionTracker {
+    pub fn new(config: &ASConfig) -> Self {
+        Self {
+            horizon_ms: config.adverse_selection_horizon_seconds.saturating_mul(1000),
+            halflife_ms: config.adverse_selection_halflife_seconds as f64 * 1000.0,
+            touch_bps: config.adverse_selection_touch_bps,
+            multiplier: config.adverse_selection_multiplier,
+            maker_fee_bps: config.maker_fee_bps.max(0.0),
+            pending: VecDeque::new(),
+            bid: DecayedMarkout::default(),
+            ask: DecayedMarkout::default(),
+            last_decay_ts: 0,
+        }
+    }
+
+    /// Record a public trade if it printed near the touch of the current mid
+    pub fn add_trade(&mut self, trade: &TradeEvent, mid: f64) {
+        let price = trade.price.to_f64().unwrap_or(0.0);
+        if price <= 0.0 || mid <= 0.0 {
+            return;
+        }
+        if ((price - mid).abs() / mid) * 10_000.0 > self.touch_bps {
+            return;
+        }
+
+        let side = if trade.is_buyer_maker { MakerSide::Bid } else { MakerSide::Ask };
+        self.pending.push_back(PendingTrade { timestamp: trade.timestamp, price, side });
+    }
+
+    /// Advance to `timestamp` with the current mid, scoring trades whose markout horizon elapsed
+    pub fn update(&mut self, timestamp: u64, mid: f64) {
+        self.decay_to(timestamp);
+        if mid <= 0.0 {
+            return;
+        }
+
+        while let Some(trade) = self.pending.front() {
+            if trade.timestamp.saturating_add(self.horizon_ms) > timestamp {
+                break;
+            }
+            let trade = self.pending.pop_front().unwrap();
+
+            // Positive = the maker lost money against the later mid
+            let markout_bps = (mid - trade.price) / trade.price * 10_000.0;
+            match trade.side {
+                MakerSide::Bid => self.bid.add(-markout_bps),
+                MakerSide::Ask => self.ask.add(markout_bps),
+            }
+        }
+    }
+
+    fn decay_to(&mut self, timestamp: u64) {
+        if self.last_decay_ts == 0 || self.halflife_ms <= 0.0 {
+            self.last_decay_ts = timestamp;
+            return;
+        }
+        if timestamp <= self.last_decay_ts {
+            return;
+        }
+        let elapsed = (timestamp - self.last_decay_ts) as f64;
+        let factor = 0.5_f64.powf(elapsed / self.halflife_ms);
+        self.bid.decay(factor);
+        self.ask.decay(factor);
+        self.last_decay_ts = timestamp;
+    }
+
+    /// Decayed mean adverse-selection cost per side in bps (bid, ask); negative = favourable flow
+    pub fn markout_cost_bps(&self) -> (f64, f64) {
+        (self.bid.cost_bps(), self.ask.cost_bps())
+    }
+
+    /// Per-side spread floors in bps (bid, ask); 0 when flow is not toxic.
+    ///
+    /// The half-spread must cover the maker fee plus the scaled markout cost, so the
+    /// full-spread floor is twice that.
+    pub fn floor_bps(&self) -> (f64, f64) {
+        let floor = |cost: f64| {
+            if cost > 0.0 {
+                2.0 * (self.maker_fee_bps + self.multiplier * cost)
+            } else {
+                0.0
+            }
+        };
+        let (bid_cost, ask_cost) = self.markout_cost_bps();
+        (floor(bid_cost), floor(ask_cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn trade(timestamp: u64, price: f64, is_buyer_maker: bool) -> TradeEvent {
+        TradeEvent {
+            timestamp,
+            price: Decimal::from_f64(price).unwrap(),
+            quantity: Decimal::ONE,
+            is_buyer_maker,
+        }
+    }
+
+    #[test]
+    fn test_toxic_sells_widen_bid_floor_and_decay() {
+        let config = ASConfig::default();
+        let mut tracker = AdverseSelectionTracker::new(&config);
+        let mut ts = 1_000_000;
+
+        // Sellers hit the bid at 100 and the mid drops 5 bps after each trade
+        for _ in 0..50 {
+            tracker.update(ts, 100.0);
+            tracker.add_trade(&trade(ts, 100.0, true), 100.0);
+            ts += 5_000;
+            tracker.update(ts, 99.95);
+        }
+
+        let (bid_floor, ask_floor) = tracker.floor_bps();
+        assert!(bid_floor > 2.0 * config.maker_fee_bps + 5.0, "bid floor {}", bid_floor);
+        assert_eq!(ask_floor, 0.0);
+
+        // After many half-lives without trades the floor relaxes to the fee-only floor
+        tracker.update(ts + 20 * config.adverse_selection_halflife_seconds * 1000, 99.95);
+        let (relaxed, _) = tracker.floor_bps();
+        assert!(relaxed < 2.0 * config.maker_fee_bps + 0.01, "relaxed floor {}", relaxed);
+    }
+
+    #[test]
+    fn test_far_trades_and_favourable_flow_ignored() {
+        let config = ASConfig::default();
+        let mut tracker = AdverseSelectionTracker::new(&config);
+
+        // 50 bps away from the mid: not near the touch
+        tracker.add_trade(&trade(1_000, 100.5, false), 100.0);
+        // Buyer lifts the ask, then the mid falls: profitable for the maker
+        tracker.add_trade(&trade(1_000, 100.0, false), 100.0);
+        tracker.update(10_000, 99.9);
+
+        assert_eq!(tracker.floor_bps(), (0.0, 0.0));
+        assert!(tracker.markout_cost_bps().1 < 0.0);
+    }
+}
@@ -10,15 +10,23 @@
 /// 4. Reduced allocations in hot path
 /// 5. Conditional computation gating
 
-use crate::calibration_engine::CalibrationEngine;
+use crate::adverse_selection::AdverseSelectionTracker;
+use crate::calibration_cache::{calibration_settings, precompute_calibrations, CalibrationSource};
+use crate::calibration_engine::CalibrationResult;
 use crate::data_loader::DataEvent;
-use crate::model_types::ASConfig;
-use crate::spread_model::compute_optimal_quote;
+use crate::lead_lag::{LeadLagModel, LeadLagReference};
+use crate::model_types::{ASConfig, QuoteAdjustments, TradeEvent, DEFAULT_CLIP_NOTIONAL_USD};
+use crate::session::session_end_ms;
+use crate::spread_model::compute_optimal_quote_shaped;
+use crate::trading_rules::snap_order_size;
+use rayon::prelude::*;
 use rust_decimal::Decimal;
 use rust_decimal::prelude::*;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use std::time::{Duration, Instant};
 
@@ -100,6 +108,10 @@ pub struct BacktestParams<I> {
     pub output_csv_path: Option<String>,
     /// Enable verbose console output during backtest
     pub verbose: bool,
+    /// Optional leader-market reference that shifts the fair value before quoting
+    pub lead_lag: Option<LeadLagReference>,
+    /// Precomputed calibration series replayed instead of running the calibration engine
+    pub calibrations: Option<Arc<Vec<CalibrationResult>>>,
 }
 
 /// Results from backtest run
@@ -123,6 +135,10 @@ pub struct BacktestResults {
     pub final_inventory: Decimal,
     /// Final cash balance
     pub final_cash: Decimal,
+    /// Number of times inventory was flattened at a session boundary
+    pub session_flattens: u64,
+    /// Fills skipped because the snapped order size was below the exchange minimum
+    pub rejected_orders: u64,
     /// Configuration used
     pub config: ASConfig,
 }
@@ -146,6 +162,8 @@ struct BacktestState {
     total_notional_volume: Decimal,
     last_bid_fill_ts: u64,
     last_ask_fill_ts: u64,
+    session_flattens: u64,
+    rejected_orders: u64,
 }
 
 impl BacktestState {
@@ -159,9 +177,38 @@ impl BacktestState {
             total_notional_volume: Decimal::ZERO,
             last_bid_fill_ts: 0,
             last_ask_fill_ts: 0,
+            session_flattens: 0,
+            rejected_orders: 0,
         }
     }
 
+    /// Close the whole position at `price` paying `fee_multiplier` on the notional.
+    ///
+    /// Returns the closed (signed) inventory and the fee, or None when flat.
+    fn close_position(&mut self, price: Decimal, fee_multiplier: Decimal) -> Option<(Decimal, Decimal)> {
+        if self.inventory == Decimal::ZERO || price <= Decimal::ZERO {
+            return None;
+        }
+
+        let closed = self.inventory;
+        let abs_inventory = closed.abs();
+        let notional = price * abs_inventory;
+        let fee = notional * fee_multiplier;
+
+        if closed > Decimal::ZERO {
+            // Long position - sell to close
+            self.cash += notional - fee;
+        } else {
+            // Short position - buy to close
+            self.cash -= notional + fee;
+        }
+        self.total_volume += abs_inventory;
+        self.total_notional_volume += notional;
+        self.inventory = Decimal::ZERO;
+
+        Some((closed, fee))
+    }
+
     #[inline]
     fn mark_to_market_pnl(&self, mid_price: Decimal) -> Decimal {
         self.cash + (self.inventory * mid_price)
@@ -190,6 +237,9 @@ struct PrecomputedConfig {
     gap_threshold_ms: u64,
     warmup_period_ms: u64,
     cooldown_ms: u64,
+    loss_lookback_ms: u64,
+    min_order_size: Decimal,
+    order_size_step: Decimal,
 }
 
 impl PrecomputedConfig {
@@ -205,76 +255,445 @@ impl PrecomputedConfig {
             gap_threshold_ms: config.gap_threshold_seconds.saturating_mul(1000),
             warmup_period_ms: config.warmup_period_seconds.saturating_mul(1000),
             cooldown_ms: config.fill_cooldown_seconds.saturating_mul(1000),
+            loss_lookback_ms: config.loss_lookback_seconds.saturating_mul(1000),
+            min_order_size: Decimal::from_f64(config.min_order_size).unwrap_or(Decimal::ZERO),
+            order_size_step: Decimal::from_f64(config.order_size_step).unwrap_or(Decimal::ZERO),
         }
     }
 }
 
-/// Run backtest simulation with given parameters
+/// Trading state of one configuration, advanced event by event.
 ///
-/// Returns BacktestResults with performance metrics, or error if simulation fails
-pub fn run_backtest<I>(params: BacktestParams<I>) -> Result<BacktestResults, Box<dyn Error>>
-where
-    I: Iterator<Item = Result<DataEvent, Box<dyn Error>>>,
-{
-    let BacktestParams {
-        data_stream,
-        config,
-        initial_capital,
-        order_notional,
-        output_csv_path,
-        verbose,
-    } = params;
+/// Market data, calibration and the lead-lag reference are shared between runners, so
+/// several configurations can be simulated in lockstep over one pass of the data.
+struct ConfigRunner {
+    config: ASConfig,
+    precomputed: PrecomputedConfig,
+    initial_capital: Decimal,
+    order_notional: Decimal,
+    state: BacktestState,
+    verbose: bool,
+    output_file: Option<BufWriter<File>>,
+    row_count: u64,
+    active_bid_price: Option<Decimal>,
+    active_ask_price: Option<Decimal>,
+    active_quote_ts: u64,
+    last_orderbook_ts: u64,
+    warmup_end_ts: u64,
+    /// End of the current session, tracked only when flattening at session boundaries
+    session_end_ts: Option<u64>,
+    /// Mark-to-market PnL at each quote over the loss lookback window (for loss-adaptive gamma)
+    pnl_history: VecDeque<(u64, Decimal)>,
+    adverse_selection: Option<AdverseSelectionTracker>,
+}
 
-    if verbose {
-        println!("Running backtest with horizon={}s, gamma={}",
-            config.inventory_horizon_seconds, config.risk_aversion_gamma);
+impl ConfigRunner {
+    fn new(
+        config: ASConfig,
+        initial_capital: Decimal,
+        order_notional: Decimal,
+        output_csv_path: Option<&str>,
+        verbose: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Output file with buffered writer for better I/O performance
+        let output_file: Option<BufWriter<File>> = if let Some(path) = output_csv_path {
+            let file = File::create(path)?;
+            let mut writer = BufWriter::with_capacity(256 * 1024, file); // 256KB buffer for fewer syscalls
+            writeln!(
+                writer,
+                "timestamp,datetime,mid_price,inventory,cash,pnl,spread_bps,bid_price,ask_price,bid_fills,ask_fills,gamma,bid_kappa,ask_kappa,bid_a,ask_a,lead_lag_shift_bps,bid_floor_bps,ask_floor_bps,bid_kappa_se,ask_kappa_se,bid_a_se,ask_a_se,bid_ks,ask_ks,bid_n,ask_n,bid_fit_source,ask_fit_source,bid_branching_ratio,ask_branching_ratio,hawkes_near_critical"
+            )?;
+            Some(writer)
+        } else {
+            None
+        };
+
+        // Print header
+        if verbose {
+            println!("\n{:<15} | {:<24} | {:>12} | {:>10} | {:>10} | {:>12} | {:>12} | {:>8} | {:>8}",
+                "Timestamp", "DateTime", "Mid Price", "Inventory", "PnL", "Bid", "Ask", "BidFill", "AskFill");
+            println!("{:-<145}", "");
+        }
+
+        Ok(Self {
+            precomputed: PrecomputedConfig::from_config(&config),
+            adverse_selection: config.adverse_selection_enabled.then(|| AdverseSelectionTracker::new(&config)),
+            config,
+            initial_capital,
+            order_notional,
+            state: BacktestState::new(initial_capital),
+            verbose,
+            output_file,
+            row_count: 0,
+            active_bid_price: None,
+            active_ask_price: None,
+            active_quote_ts: 0,
+            last_orderbook_ts: 0,
+            warmup_end_ts: 0,
+            session_end_ts: None,
+            pnl_history: VecDeque::new(),
+        })
     }
 
-    // Precompute configuration values once
-    let precomputed = PrecomputedConfig::from_config(&config);
-
-    // Initialize state
-    let mut state = BacktestState::new(initial_capital);
-
-    // Initialize calibration engine
-    let mut calibration_engine = CalibrationEngine::new(&config);
-
-    // Output file with buffered writer for better I/O performance
-    let mut output_file: Option<BufWriter<File>> = if let Some(ref path) = output_csv_path {
-        let file = File::create(path)?;
-        let mut writer = BufWriter::with_capacity(256 * 1024, file); // 256KB buffer for fewer syscalls
-        writeln!(
-            writer,
-            "timestamp,datetime,mid_price,inventory,cash,pnl,spread_bps,bid_price,ask_price,bid_fills,ask_fills,gamma,bid_kappa,ask_kappa,bid_a,ask_a"
-        )?;
-        Some(writer)
-    } else {
-        None
-    };
+    /// Update trade-driven trackers and fill resting quotes against a market trade
+    fn on_trade(&mut self, trade: &TradeEvent, last_mid: Decimal, stats: &mut ProfilingStats) {
+        let current_ts = trade.timestamp;
+        let precomputed = &self.precomputed;
+        let state = &mut self.state;
 
-    // Print header
-    if verbose {
-        println!("\n{:<15} | {:<24} | {:>12} | {:>10} | {:>10} | {:>12} | {:>12} | {:>8} | {:>8}",
-            "Timestamp", "DateTime", "Mid Price", "Inventory", "PnL", "Bid", "Ask", "BidFill", "AskFill");
-        println!("{:-<145}", "");
+        let t0 = Instant::now();
+        if let Some(tracker) = self.adverse_selection.as_mut() {
+            tracker.add_trade(trade, last_mid.to_f64().unwrap_or(0.0));
+        }
+        stats.calibration_update += t0.elapsed();
+
+        // Skip trading if warming up (early exit for performance)
+        if current_ts < self.warmup_end_ts {
+            return;
+        }
+
+        // Quotes are pulled at the session boundary until the next orderbook flattens
+        if self.session_end_ts.is_some_and(|end| current_ts >= end) {
+            return;
+        }
+
+        // Only check for fills if we have active quotes
+        let (bid, ask) = match (self.active_bid_price, self.active_ask_price) {
+            (Some(b), Some(a)) => (b, a),
+            _ => return,
+        };
+
+        // Check quote validity
+        if self.active_quote_ts == 0 || trade.timestamp >= self.active_quote_ts + precomputed.quote_validity_ms {
+            return;
+        }
+
+        let trade_price = trade.price;
+        let trade_ts = trade.timestamp;
+
+        // SELL FILL: Market trade price >= our ask
+        if trade_price >= ask {
+            let ask_cooldown_active = state.last_ask_fill_ts > 0
+                && trade_ts < state.last_ask_fill_ts + precomputed.cooldown_ms;
+
+            if !ask_cooldown_active && state.inventory > -precomputed.max_inventory_decimal {
+                let unit_size = self.order_notional / trade_price;
+                let short_capacity = state.inventory + precomputed.max_inventory_decimal;
+                let sell_size = short_capacity.min(unit_size).max(Decimal::ZERO);
+
+                // Snap to the exchange size step; sub-minimum orders are rejected
+                let sell_size = match snap_order_size(sell_size, precomputed.order_size_step, precomputed.min_order_size) {
+                    Some(size) => size,
+                    None => {
+                        state.rejected_orders += 1;
+                        Decimal::ZERO
+                    }
+                };
+
+                if sell_size > Decimal::ZERO {
+                    let gross_proceeds = ask * sell_size;
+                    let fee = gross_proceeds * precomputed.fee_multiplier;
+
+                    state.inventory -= sell_size;
+                    state.cash += gross_proceeds - fee;
+                    state.ask_fills += 1;
+                    state.total_volume += sell_size;
+                    state.total_notional_volume += gross_proceeds;
+                    state.last_ask_fill_ts = trade_ts;
+                }
+            }
+        }
+        // BUY FILL: Market trade price <= our bid
+        else if trade_price <= bid {
+            let bid_cooldown_active = state.last_bid_fill_ts > 0
+                && trade_ts < state.last_bid_fill_ts + precomputed.cooldown_ms;
+
+            if !bid_cooldown_active && state.inventory < precomputed.max_inventory_decimal {
+                let unit_size = self.order_notional / trade_price;
+                let long_capacity = precomputed.max_inventory_decimal - state.inventory;
+                let buy_size = long_capacity.min(unit_size).max(Decimal::ZERO);
+
+                // Snap to the exchange size step; sub-minimum orders are rejected
+                let buy_size = match snap_order_size(buy_size, precomputed.order_size_step, precomputed.min_order_size) {
+                    Some(size) => size,
+                    None => {
+                        state.rejected_orders += 1;
+                        Decimal::ZERO
+                    }
+                };
+
+                if buy_size > Decimal::ZERO {
+                    let gross_cost = bid * buy_size;
+                    let fee = gross_cost * precomputed.fee_multiplier;
+                    let total_cost = gross_cost + fee;
+
+                    if state.cash >= total_cost {
+                        state.inventory += buy_size;
+                        state.cash -= total_cost;
+                        state.bid_fills += 1;
+                        state.total_volume += buy_size;
+                        state.total_notional_volume += gross_cost;
+                        state.last_bid_fill_ts = trade_ts;
+                    }
+                }
+            }
+        }
     }
 
-    let mut last_mid = Decimal::ZERO;
-    let mut row_count: u64 = 0;
+    /// Handle data gaps, session boundaries and markout tracking for a new orderbook
+    fn on_orderbook(&mut self, current_ts: u64, mid_price: Decimal, stats: &mut ProfilingStats) {
+        let precomputed = &self.precomputed;
+
+        // Check for data gaps
+        if self.last_orderbook_ts > 0 {
+            let time_delta = current_ts.saturating_sub(self.last_orderbook_ts);
+            if time_delta > precomputed.gap_threshold_ms {
+                self.warmup_end_ts = current_ts + precomputed.warmup_period_ms;
+                if self.verbose {
+                    println!("Gap detected ({}s). Entering warm-up until {}",
+                        time_delta / 1000,
+                        format_timestamp(self.warmup_end_ts));
+                }
 
-    // Event-driven loop state
-    let mut active_bid_price: Option<Decimal> = None;
-    let mut active_ask_price: Option<Decimal> = None;
-    let mut active_quote_ts: u64 = 0;
-    let mut last_orderbook_ts: u64 = 0;
-    let mut warmup_end_ts: u64 = 0;
+                // Invalidate quotes during gap/warmup
+                self.active_bid_price = None;
+                self.active_ask_price = None;
+                self.active_quote_ts = 0;
+            }
+        } else {
+            // Initial warm-up
+            self.warmup_end_ts = current_ts + precomputed.warmup_period_ms;
+            if self.verbose {
+                println!("Starting initial warm-up until {}", format_timestamp(self.warmup_end_ts));
+            }
+        }
+        self.last_orderbook_ts = current_ts;
 
-    // Track if we need to output (reduces conditional checks)
-    let needs_csv = output_file.is_some();
+        if self.config.flatten_at_session_end {
+            if self.session_end_ts.is_some_and(|end| current_ts >= end) {
+                if let Some((closed, fee)) = self.state.close_position(mid_price, precomputed.closing_fee_multiplier) {
+                    self.state.session_flattens += 1;
+                    if self.verbose {
+                        println!("Session end: flattened {} units at {} (fee: {})",
+                            closed, mid_price, fee);
+                    }
+                }
+                self.active_bid_price = None;
+                self.active_ask_price = None;
+                self.active_quote_ts = 0;
+            }
+            self.session_end_ts = session_end_ms(current_ts, &self.config);
+        }
 
-    // Profiling
-    let mut stats = ProfilingStats::default();
-    let overall_start = Instant::now();
+        let t_cal_up = Instant::now();
+        if let Some(tracker) = self.adverse_selection.as_mut() {
+            tracker.update(current_ts, mid_price.to_f64().unwrap_or(0.0));
+        }
+        stats.calibration_update += t_cal_up.elapsed();
+    }
+
+    /// Quote for the next interval from a fresh calibration
+    fn on_calibration(
+        &mut self,
+        current_ts: u64,
+        mid_price: Decimal,
+        cal_result: &CalibrationResult,
+        lead_lag_shift: f64,
+        stats: &mut ProfilingStats,
+    ) {
+        let t_strat = Instant::now();
+        let precomputed = &self.precomputed;
+        let state = &self.state;
+
+        // Shift fair value by leader moves the follower has not reacted to yet
+        let fair_mid = if lead_lag_shift != 0.0 {
+            Decimal::from_f64(lead_lag_shift.exp())
+                .map(|factor| mid_price * factor)
+                .unwrap_or(mid_price)
+        } else {
+            mid_price
+        };
+
+        let current_pnl = state.mark_to_market_pnl(mid_price);
+        self.pnl_history.push_back((current_ts, current_pnl));
+        while self.pnl_history.front().is_some_and(|(ts, _)| *ts + precomputed.loss_lookback_ms < current_ts) {
+            self.pnl_history.pop_front();
+        }
+        let window_start_pnl = self.pnl_history.front().map(|(_, pnl)| *pnl).unwrap_or(current_pnl);
+        let (bid_floor_bps, ask_floor_bps) = self.adverse_selection
+            .as_ref()
+            .map(|tracker| tracker.floor_bps())
+            .unwrap_or((0.0, 0.0));
+        let adjustments = QuoteAdjustments {
+            recent_pnl: (current_pnl - window_start_pnl).to_f64().unwrap_or(0.0),
+            bid_floor_bps,
+            ask_floor_bps,
+        };
+
+        let optimal = compute_optimal_quote_shaped(
+            current_ts,
+            fair_mid,
+            state.inventory,
+            cal_result.volatility,
+            &cal_result.bid_shape,
+            &cal_result.ask_shape,
+            &self.config,
+            &adjustments,
+        );
+
+        // Set active quotes for the NEXT interval
+        self.active_bid_price = Some(optimal.bid_price);
+        self.active_ask_price = Some(optimal.ask_price);
+        self.active_quote_ts = current_ts;
+
+        stats.strategy_logic += t_strat.elapsed();
+
+        // Only compute display values when actually needed
+        let should_print = self.verbose && self.row_count.is_multiple_of(10);
+
+        if self.output_file.is_some() || should_print {
+            let pnl = state.mark_to_market_pnl(mid_price);
+
+            let spread_bps = if mid_price > Decimal::ZERO {
+                (optimal.optimal_spread / mid_price) * DECIMAL_CONSTS.ten_thousand
+            } else {
+                Decimal::ZERO
+            };
+
+            // Write to CSV if enabled
+            if let Some(ref mut writer) = self.output_file {
+                let t_io = Instant::now();
+                let inventory_display = state.inventory.round_dp(6);
+                let fit = &cal_result.intensity_fit;
+                let (bid_branching, ask_branching) = cal_result.branching_ratios();
+                if let Err(e) = writeln!(
+                    writer,
+                    "{},{},{},{},{},{},{:.2},{},{},{},{},{:.6},{:.2},{:.2},{:.2},{:.2},{:.4},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.4},{:.4},{},{},{},{},{:.4},{:.4},{}",
+                    current_ts,
+                    format_timestamp(current_ts),
+                    mid_price,
+                    inventory_display,
+                    state.cash,
+                    pnl,
+                    spread_bps.to_f64().unwrap_or(0.0),
+                    optimal.bid_price,
+                    optimal.ask_price,
+                    state.bid_fills,
+                    state.ask_fills,
+                    optimal.gamma,
+                    cal_result.bid_kappa,
+                    cal_result.ask_kappa,
+                    cal_result.bid_a,
+                    cal_result.ask_a,
+                    lead_lag_shift * 10_000.0,
+                    adjustments.bid_floor_bps,
+                    adjustments.ask_floor_bps,
+                    fit.bid.kappa_se,
+                    fit.ask.kappa_se,
+                    fit.bid.a_se,
+                    fit.ask.a_se,
+                    fit.bid.ks_statistic,
+                    fit.ask.ks_statistic,
+                    fit.bid.trades,
+                    fit.ask.trades,
+                    fit.bid.source.label(),
+                    fit.ask.source.label(),
+                    bid_branching,
+                    ask_branching,
+                    cal_result.hawkes_near_critical(self.config.hawkes_critical_branching)
+                ) {
+                    eprintln!("Warning: Failed to write to CSV: {}", e);
+                }
+                stats.csv_io += t_io.elapsed();
+            }
+
+            if should_print {
+                let inventory_display = state.inventory.round_dp(6);
+                println!(
+                    "{:<15} | {:<24} | {:>12.2} | {:>10} | {:>10.2} | {:>12.2} | {:>12.2} | {:>8} | {:>8}",
+                    current_ts, format_timestamp(current_ts), mid_price, inventory_display, pnl, optimal.bid_price, optimal.ask_price, state.bid_fills, state.ask_fills
+                );
+            }
+        }
+        self.row_count += 1;
+    }
+
+    /// Requote the live quotes around a new lead-lag fair value, keeping the last calibration
+    fn on_lead_lag_shift(
+        &mut self,
+        current_ts: u64,
+        mid_price: Decimal,
+        cal_result: &CalibrationResult,
+        lead_lag_shift: f64,
+        stats: &mut ProfilingStats,
+    ) {
+        // Pulled quotes (warm-up after a gap, session end) wait for the next calibration
+        if self.active_quote_ts == 0 {
+            return;
+        }
+        self.on_calibration(current_ts, mid_price, cal_result, lead_lag_shift, stats);
+    }
+
+    /// Flush output, close the remaining position at the final mid and compute the results
+    fn finish(mut self, last_mid: Decimal) -> Result<BacktestResults, Box<dyn Error>> {
+        // Flush the buffered writer before closing
+        if let Some(ref mut writer) = self.output_file {
+            writer.flush()?;
+        }
+
+        // Force close any remaining position at final mid price
+        let state = &mut self.state;
+        if let Some((closed, fee)) = state.close_position(last_mid, self.precomputed.closing_fee_multiplier) {
+            if self.verbose {
+                if closed > Decimal::ZERO {
+                    println!("\nClosing long position: Sold {} units at {} (fee: {})",
+                        closed, last_mid, fee);
+                } else {
+                    println!("\nClosing short position: Bought {} units at {} (fee: {})",
+                        closed.abs(), last_mid, fee);
+                }
+            }
+        }
+
+        // Calculate final P&L
+        let initial_capital = self.initial_capital;
+        let final_pnl = state.mark_to_market_pnl(last_mid);
+        let total_return_pct = ((final_pnl - initial_capital) / initial_capital) * DECIMAL_CONSTS.hundred;
+
+        Ok(BacktestResults {
+            initial_capital,
+            final_pnl,
+            total_return_pct,
+            bid_fills: state.bid_fills,
+            ask_fills: state.ask_fills,
+            total_volume: state.total_volume,
+            total_notional_volume: state.total_notional_volume,
+            final_inventory: state.inventory,
+            final_cash: state.cash,
+            session_flattens: state.session_flattens,
+            rejected_orders: state.rejected_orders,
+            config: self.config,
+        })
+    }
+}
+
+/// Replay `data_stream` once, feeding the shared calibration to every runner.
+///
+/// Returns the last mid price.
+fn run_lockstep<I>(
+    data_stream: I,
+    runners: &mut [ConfigRunner],
+    calibration_engine: &mut CalibrationSource,
+    lead_lag: &mut Option<LeadLagReference>,
+    tick_size: f64,
+    stats: &mut ProfilingStats,
+) -> Result<Decimal, Box<dyn Error>>
+where
+    I: Iterator<Item = Result<DataEvent, Box<dyn Error>>>,
+{
+    let mut last_mid = Decimal::ZERO;
+    let mut last_calibration: Option<CalibrationResult> = None;
+    let mut last_lead_lag_shift = 0.0;
     let mut last_iter_time = Instant::now();
 
     for event_result in data_stream {
@@ -285,114 +704,22 @@ where
 
         match event {
             DataEvent::Trade(trade) => {
-                let current_ts = trade.timestamp;
-
-                // Add trade to calibration engine (only copies needed fields, no full clone)
                 // Add trade to calibration engine (only copies needed fields, no full clone)
                 let t0 = Instant::now();
                 calibration_engine.add_trade(&trade);
                 stats.calibration_update += t0.elapsed();
 
-                // Skip trading if warming up (early exit for performance)
-                if current_ts < warmup_end_ts {
-                    continue;
-                }
-
-                // Only check for fills if we have active quotes
-                let (bid, ask) = match (active_bid_price, active_ask_price) {
-                    (Some(b), Some(a)) => (b, a),
-                    _ => continue,
-                };
-
-                // Check quote validity
-                if active_quote_ts == 0 || trade.timestamp >= active_quote_ts + precomputed.quote_validity_ms {
-                    continue;
-                }
-
-                let trade_price = trade.price;
-                let trade_ts = trade.timestamp;
-
-                // SELL FILL: Market trade price >= our ask
-                if trade_price >= ask {
-                    let ask_cooldown_active = state.last_ask_fill_ts > 0 
-                        && trade_ts < state.last_ask_fill_ts + precomputed.cooldown_ms;
-
-                    if !ask_cooldown_active && state.inventory > -precomputed.max_inventory_decimal {
-                        let unit_size = order_notional / trade_price;
-                        let short_capacity = state.inventory + precomputed.max_inventory_decimal;
-                        let sell_size = short_capacity.min(unit_size).max(Decimal::ZERO);
-
-                        if sell_size > Decimal::ZERO {
-                            let gross_proceeds = ask * sell_size;
-                            let fee = gross_proceeds * precomputed.fee_multiplier;
-
-                            state.inventory -= sell_size;
-                            state.cash += gross_proceeds - fee;
-                            state.ask_fills += 1;
-                            state.total_volume += sell_size;
-                            state.total_notional_volume += gross_proceeds;
-                            state.last_ask_fill_ts = trade_ts;
-                        }
-                    }
-                }
-                // BUY FILL: Market trade price <= our bid
-                else if trade_price <= bid {
-                    let bid_cooldown_active = state.last_bid_fill_ts > 0 
-                        && trade_ts < state.last_bid_fill_ts + precomputed.cooldown_ms;
-
-                    if !bid_cooldown_active && state.inventory < precomputed.max_inventory_decimal {
-                        let unit_size = order_notional / trade_price;
-                        let long_capacity = precomputed.max_inventory_decimal - state.inventory;
-                        let buy_size = long_capacity.min(unit_size).max(Decimal::ZERO);
-
-                        if buy_size > Decimal::ZERO {
-                            let gross_cost = bid * buy_size;
-                            let fee = gross_cost * precomputed.fee_multiplier;
-                            let total_cost = gross_cost + fee;
-
-                            if state.cash >= total_cost {
-                                state.inventory += buy_size;
-                                state.cash -= total_cost;
-                                state.bid_fills += 1;
-                                state.total_volume += buy_size;
-                                state.total_notional_volume += gross_cost;
-                                state.last_bid_fill_ts = trade_ts;
-                            }
-                        }
-                    }
+                for runner in runners.iter_mut() {
+                    runner.on_trade(&trade, last_mid, stats);
                 }
             },
-            DataEvent::Orderbook(quote) => {
-                let current_ts = quote.timestamp;
-
-                // Check for data gaps
-                if last_orderbook_ts > 0 {
-                    let time_delta = current_ts.saturating_sub(last_orderbook_ts);
-                    if time_delta > precomputed.gap_threshold_ms {
-                        warmup_end_ts = current_ts + precomputed.warmup_period_ms;
-                        if verbose {
-                            println!("Gap detected ({}s). Entering warm-up until {}",
-                                time_delta / 1000,
-                                format_timestamp(warmup_end_ts));
-                        }
-
-                        // Invalidate quotes during gap/warmup
-                        active_bid_price = None;
-                        active_ask_price = None;
-                        active_quote_ts = 0;
-                    }
-                } else {
-                    // Initial warm-up
-                    warmup_end_ts = current_ts + precomputed.warmup_period_ms;
-                    if verbose {
-                        println!("Starting initial warm-up until {}", format_timestamp(warmup_end_ts));
-                    }
-                }
-                last_orderbook_ts = current_ts;
+            DataEvent::Orderbook(row) => {
+                let quote = row.view();
+                let current_ts = quote.timestamp();
 
                 // Update Market State with current orderbook
-                let best_bid = quote.bids.first().map(|(p, _)| *p).unwrap_or(Decimal::ZERO);
-                let best_ask = quote.asks.first().map(|(p, _)| *p).unwrap_or(Decimal::ZERO);
+                let best_bid = quote.best_bid_decimal().unwrap_or(Decimal::ZERO);
+                let best_ask = quote.best_ask_decimal().unwrap_or(Decimal::ZERO);
                 let mid_price = if best_bid > Decimal::ZERO && best_ask > Decimal::ZERO {
                     (best_bid + best_ask) / DECIMAL_CONSTS.two
                 } else {
@@ -401,151 +728,423 @@ where
 
                 last_mid = mid_price;
 
+                for runner in runners.iter_mut() {
+                    runner.on_orderbook(current_ts, mid_price, stats);
+                }
+
                 // Calibration & Quoting for NEXT interval
                 let t_cal_up = Instant::now();
                 calibration_engine.add_orderbook(&quote, mid_price);
-                calibration_engine.prune_windows(current_ts);
                 stats.calibration_update += t_cal_up.elapsed();
 
-                // Check if we should recalibrate
-                if calibration_engine.should_recalibrate(current_ts) {
-                    let t_cal_comp = Instant::now();
-                    let cal_option = calibration_engine.calibrate(current_ts, config.tick_size);
-                    stats.calibration_compute += t_cal_comp.elapsed();
-
-                    if let Some(cal_result) = cal_option {
-                        let t_strat = Instant::now();
-                        let optimal = compute_optimal_quote(
-                            current_ts,
-                            mid_price,
-                            state.inventory,
-                            cal_result.volatility,
-                            cal_result.bid_kappa,
-                            cal_result.ask_kappa,
-                            &config,
-                        );
-
-                        // Set active quotes for the NEXT interval
-                        active_bid_price = Some(optimal.bid_price);
-                        active_ask_price = Some(optimal.ask_price);
-                        active_quote_ts = current_ts;
-                        
-                        stats.strategy_logic += t_strat.elapsed();
-
-                        // Only compute display values when actually needed
-                        let should_print = verbose && row_count.is_multiple_of(10);
-                        
-                        if needs_csv || should_print {
-                            let pnl = state.mark_to_market_pnl(mid_price);
-                            
-                            let spread_bps = if mid_price > Decimal::ZERO {
-                                (optimal.optimal_spread / mid_price) * DECIMAL_CONSTS.ten_thousand
-                            } else {
-                                Decimal::ZERO
-                            };
-
-                            // Write to CSV if enabled
-                            if let Some(ref mut writer) = output_file {
-                                let t_io = Instant::now();
-                                let inventory_display = state.inventory.round_dp(6);
-                                if let Err(e) = writeln!(
-                                    writer,
-                                    "{},{},{},{},{},{},{:.2},{},{},{},{},{:.6},{:.2},{:.2},{:.2},{:.2}",
-                                    current_ts,
-                                    format_timestamp(current_ts),
-                                    mid_price,
-                                    inventory_display,
-                                    state.cash,
-                                    pnl,
-                                    spread_bps.to_f64().unwrap_or(0.0),
-                                    optimal.bid_price,
-                                    optimal.ask_price,
-                                    state.bid_fills,
-                                    state.ask_fills,
-                                    optimal.gamma,
-                                    cal_result.bid_kappa,
-                                    cal_result.ask_kappa,
-                                    cal_result.bid_a,
-                                    cal_result.ask_a
-                                ) {
-                                    eprintln!("Warning: Failed to write to CSV: {}", e);
-                                }
-                                stats.csv_io += t_io.elapsed();
-                            }
-
-                            if should_print {
-                                let inventory_display = state.inventory.round_dp(6);
-                                println!(
-                                    "{:<15} | {:<24} | {:>12.2} | {:>10} | {:>10.2} | {:>12.2} | {:>12.2} | {:>8} | {:>8}",
-                                    current_ts, format_timestamp(current_ts), mid_price, inventory_display, pnl, optimal.bid_price, optimal.ask_price, state.bid_fills, state.ask_fills
-                                );
-                            }
+                // Recalibrate when due (or take the next precomputed calibration)
+                let t_cal_comp = Instant::now();
+                let cal_option = calibration_engine.poll(current_ts, tick_size);
+                stats.calibration_compute += t_cal_comp.elapsed();
+
+                // Leader moves up to this event, applied on every update rather than only at recalibration
+                let lead_lag_shift = match lead_lag.as_mut() {
+                    Some(reference) => {
+                        reference.advance_to(current_ts)?;
+                        reference.fair_value_shift()
+                    }
+                    None => 0.0,
+                };
+
+                if let Some(cal_result) = cal_option {
+                    for runner in runners.iter_mut() {
+                        runner.on_calibration(current_ts, mid_price, &cal_result, lead_lag_shift, stats);
+                    }
+                    last_calibration = Some(cal_result);
+                } else if lead_lag_shift != last_lead_lag_shift {
+                    if let Some(cal_result) = last_calibration.as_ref() {
+                        for runner in runners.iter_mut() {
+                            runner.on_lead_lag_shift(current_ts, mid_price, cal_result, lead_lag_shift, stats);
                         }
-                        row_count += 1;
                     }
                 }
+                last_lead_lag_shift = lead_lag_shift;
             }
         }
 
-
-        
-        
         last_iter_time = Instant::now();
     }
-    
+
+    Ok(last_mid)
+}
+
+/// Run backtest simulation with given parameters
+///
+/// Returns BacktestResults with performance metrics, or error if simulation fails
+pub fn run_backtest<I>(params: BacktestParams<I>) -> Result<BacktestResults, Box<dyn Error>>
+where
+    I: Iterator<Item = Result<DataEvent, Box<dyn Error>>>,
+{
+    let BacktestParams {
+        data_stream,
+        mut config,
+        initial_capital,
+        order_notional,
+        output_csv_path,
+        verbose,
+        mut lead_lag,
+        calibrations,
+    } = params;
+
+    if verbose {
+        println!("Running backtest with horizon={}s, gamma={}",
+            config.inventory_horizon_seconds, config.risk_aversion_gamma);
+    }
+
+    // Initialize calibration engine, or replay a precomputed series
+    config.resolve_clip_notional(order_notional.to_f64().unwrap_or(DEFAULT_CLIP_NOTIONAL_USD));
+    let mut calibration_engine = CalibrationSource::new(&config, calibrations)?;
+    let tick_size = config.tick_size;
+    let mut runners = [ConfigRunner::new(config, initial_capital, order_notional, output_csv_path.as_deref(), verbose)?];
+
+    // Profiling
+    let mut stats = ProfilingStats::default();
+    let overall_start = Instant::now();
+    let last_mid = run_lockstep(data_stream, &mut runners, &mut calibration_engine, &mut lead_lag, tick_size, &mut stats)?;
+
     // Print stats
     stats.print_summary(overall_start.elapsed());
 
-    // Flush the buffered writer before closing
-    if let Some(ref mut writer) = output_file {
-        writer.flush()?;
+    let [runner] = runners;
+    runner.finish(last_mid)
+}
+
+/// Input parameters for a lockstep backtest of several configurations
+pub struct MultiBacktestParams<I> {
+    /// Stream of data events (trades and orderbooks), decoded once for all configurations
+    pub data_stream: I,
+    /// Configurations to simulate; they must share the calibration settings
+    pub configs: Vec<ASConfig>,
+    /// Starting capital in dollars
+    pub initial_capital: Decimal,
+    /// Order size in dollars
+    pub order_notional: Decimal,
+    /// Optional leader-market reference that shifts the fair value before quoting
+    pub lead_lag: Option<LeadLagReference>,
+    /// Precomputed calibration series replayed instead of running the calibration engine
+    pub calibrations: Option<Arc<Vec<CalibrationResult>>>,
+}
+
+/// Backtest several configurations in a single pass over the data.
+///
+/// Every configuration keeps its own state and quotes while the events, the calibration
+/// and the lead-lag reference are shared, so the cost of decoding and calibrating is paid
+/// once. Results are returned in the order of `configs` and match what `run_backtest`
+/// gives for each configuration alone.
+pub fn run_backtest_multi<I>(params: MultiBacktestParams<I>) -> Result<Vec<BacktestResults>, Box<dyn Error>>
+where
+    I: Iterator<Item = Result<DataEvent, Box<dyn Error>>>,
+{
+    let MultiBacktestParams {
+        data_stream,
+        mut configs,
+        initial_capital,
+        order_notional,
+        mut lead_lag,
+        calibrations,
+    } = params;
+
+    let clip_notional = order_notional.to_f64().unwrap_or(DEFAULT_CLIP_NOTIONAL_USD);
+    configs.iter_mut().for_each(|config| config.resolve_clip_notional(clip_notional));
+    let Some(first) = configs.first() else {
+        return Ok(Vec::new());
+    };
+    let settings = calibration_settings(first)?;
+    for config in &configs[1..] {
+        if calibration_settings(config)? != settings {
+            return Err("run_backtest_multi: configurations differ in calibration settings".into());
+        }
     }
 
-    // Force close any remaining position at final mid price
-    if state.inventory != Decimal::ZERO && last_mid > Decimal::ZERO {
-        if state.inventory > Decimal::ZERO {
-            // Long position - sell to close
-            let gross_proceeds = last_mid * state.inventory;
-            let fee = gross_proceeds * precomputed.closing_fee_multiplier;
-            state.cash += gross_proceeds - fee;
-            state.total_volume += state.inventory;
-            state.total_notional_volume += gross_proceeds;
-            if verbose {
-                println!("\nClosing long position: Sold {} units at {} (fee: {})",
-                    state.inventory, last_mid, fee);
-            }
-            state.inventory = Decimal::ZERO;
-        } else {
-            // Short position - buy to close
-            let abs_inventory = state.inventory.abs();
-            let gross_cost = last_mid * abs_inventory;
-            let fee = gross_cost * precomputed.closing_fee_multiplier;
-            state.cash -= gross_cost + fee;
-            state.total_volume += abs_inventory;
-            state.total_notional_volume += gross_cost;
-            if verbose {
-                println!("\nClosing short position: Bought {} units at {} (fee: {})",
-                    abs_inventory, last_mid, fee);
+    let mut calibration_engine = CalibrationSource::new(first, calibrations)?;
+    let tick_size = first.tick_size;
+    let mut runners = configs
+        .into_iter()
+        .map(|config| ConfigRunner::new(config, initial_capital, order_notional, None, false))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stats = ProfilingStats::default();
+    let last_mid = run_lockstep(data_stream, &mut runners, &mut calibration_engine, &mut lead_lag, tick_size, &mut stats)?;
+
+    runners.into_iter().map(|runner| runner.finish(last_mid)).collect()
+}
+
+/// Run `configs` as up to `groups` lockstep backtests in parallel over shared in-memory events.
+///
+/// The calibration series is computed once (unless given) and replayed by every group, so
+/// each event is decoded once and cloned once per group rather than once per configuration.
+/// With `lead_lag`, every group replays the in-memory leader mids through its own reference.
+pub fn run_backtest_multi_parallel(
+    events: &[DataEvent],
+    configs: Vec<ASConfig>,
+    groups: usize,
+    initial_capital: Decimal,
+    order_notional: Decimal,
+    lead_lag: Option<(&LeadLagModel, &[(u64, f64)])>,
+    calibrations: Option<Arc<Vec<CalibrationResult>>>,
+) -> Result<Vec<BacktestResults>, Box<dyn Error>> {
+    let Some(first) = configs.first() else {
+        return Ok(Vec::new());
+    };
+    let calibrations = match calibrations {
+        Some(series) => series,
+        None => {
+            let mut first = first.clone();
+            first.resolve_clip_notional(order_notional.to_f64().unwrap_or(DEFAULT_CLIP_NOTIONAL_USD));
+            Arc::new(precompute_calibrations(events.iter().cloned().map(Ok), &first)?)
+        }
+    };
+
+    let group_size = configs.len().div_ceil(groups.max(1));
+    let group_results: Vec<Result<Vec<BacktestResults>, String>> = configs
+        .par_chunks(group_size)
+        .map(|group| {
+            run_backtest_multi(MultiBacktestParams {
+                data_stream: events.iter().cloned().map(Ok),
+                configs: group.to_vec(),
+                initial_capital,
+                order_notional,
+                lead_lag: lead_lag.map(|(model, mids)| {
+                    let mids = mids.to_vec();
+                    LeadLagReference::new(model, Box::new(mids.into_iter().map(Ok)))
+                }),
+                calibrations: Some(Arc::clone(&calibrations)),
+            })
+            .map_err(|e| e.to_string())
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(configs.len());
+    for group in group_results {
+        results.extend(group?);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::XorShift;
+    use crate::data_loader::OrderbookSnapshot;
+    use crate::orderbook_batch::OrderbookRow;
+
+    /// Two hours of one-second snapshots around a random-walk mid, with a trade every other second
+    fn random_walk_events() -> Vec<DataEvent> {
+        let mut rng = XorShift::new(7);
+
+        let mut events = Vec::new();
+        let mut mid = 2000.0;
+        for s in 0..7200u64 {
+            let ts = 1_700_000_000_000 + s * 1000;
+            mid *= 1.0 + 2e-4 * (rng.uniform() - 0.5);
+            let price = |x: f64| Decimal::from_f64(x).unwrap().round_dp(2);
+            events.push(DataEvent::Orderbook(OrderbookRow::from_snapshot(&OrderbookSnapshot {
+                timestamp: ts,
+                bids: vec![(price(mid - 0.05), Decimal::ONE)],
+                asks: vec![(price(mid + 0.05), Decimal::ONE)],
+            })));
+            if s % 2 == 0 {
+                let is_buyer_maker = rng.uniform() < 0.5;
+                let depth = 0.05 + 20.0 * rng.uniform().powi(2);
+                let trade_price = if is_buyer_maker { mid - depth } else { mid + depth };
+                events.push(DataEvent::Trade(TradeEvent {
+                    timestamp: ts + 500,
+                    price: price(trade_price),
+                    quantity: Decimal::ONE,
+                    is_buyer_maker,
+                }));
             }
-            state.inventory = Decimal::ZERO;
         }
+        events
     }
 
-    // Calculate final P&L
-    let final_pnl = state.mark_to_market_pnl(last_mid);
-    let total_return_pct = ((final_pnl - initial_capital) / initial_capital) * DECIMAL_CONSTS.hundred;
+    /// Leader mids on the follower's one-second grid, random-walking independently
+    fn leader_mids(seed: u64) -> Vec<(u64, f64)> {
+        let mut rng = XorShift::new(seed);
+        let mut mid = 40_000.0;
+        (0..7200u64)
+            .map(|s| {
+                mid *= 1.0 + 4e-4 * (rng.uniform() - 0.5);
+                (1_700_000_000_000 + s * 1000 + 250, mid)
+            })
+            .collect()
+    }
 
-    Ok(BacktestResults {
-        initial_capital,
-        final_pnl,
-        total_return_pct,
-        bid_fills: state.bid_fills,
-        ask_fills: state.ask_fills,
-        total_volume: state.total_volume,
-        total_notional_volume: state.total_notional_volume,
-        final_inventory: state.inventory,
-        final_cash: state.cash,
-        config,
-    })
-}
+    fn leader_model() -> LeadLagModel {
+        LeadLagModel {
+            leader: "BTC-USD".into(),
+            follower: "ETH-USD".into(),
+            bar_ms: 5000,
+            beta_contemporaneous: 0.0,
+            betas: vec![0.5, 0.3],
+            correlations: vec![0.0; 2],
+            observations: 1000,
+        }
+    }
+
+    fn leader_reference(mids: Vec<(u64, f64)>) -> LeadLagReference {
+        LeadLagReference::new(&leader_model(), Box::new(mids.into_iter().map(Ok)))
+    }
+
+    #[test]
+    fn test_lead_lag_shift_requotes_between_calibrations() {
+        let config = ASConfig {
+            calibration_window_seconds: 600,
+            recalibration_interval_seconds: 60,
+            warmup_period_seconds: 600,
+            ..ASConfig::default()
+        };
+        let events = random_walk_events();
+        let dir = std::env::temp_dir().join(format!("backtest_lead_lag_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let quote_rows = |lead_lag: Option<LeadLagReference>, name: &str| -> Vec<(u64, f64)> {
+            let path = dir.join(name);
+            run_backtest(BacktestParams {
+                data_stream: events.iter().cloned().map(Ok),
+                config: config.clone(),
+                initial_capital: Decimal::from(1000),
+                order_notional: Decimal::from(20),
+                output_csv_path: Some(path.to_string_lossy().into_owned()),
+                verbose: false,
+                lead_lag,
+                calibrations: None,
+            })
+            .unwrap();
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .lines()
+                .skip(1)
+                .map(|line| {
+                    let fields: Vec<&str> = line.split(',').collect();
+                    (fields[0].parse().unwrap(), fields[16].parse().unwrap())
+                })
+                .collect()
+        };
+
+        let plain = quote_rows(None, "plain.csv");
+        let shifted = quote_rows(Some(leader_reference(leader_mids(11))), "shifted.csv");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Every calibration still quotes, and leader bars closing in between requote with the new shift
+        assert!(plain.iter().all(|(ts, _)| shifted.iter().any(|(t, _)| t == ts)));
+        assert!(shifted.len() > plain.len());
+        let requotes: Vec<&(u64, f64)> = shifted.iter().filter(|(ts, _)| !plain.iter().any(|(t, _)| t == ts)).collect();
+        assert!(requotes.iter().all(|(_, shift_bps)| *shift_bps != 0.0));
+    }
 
+    #[test]
+    fn test_multi_with_lead_lag_matches_individual_runs() {
+        let base = ASConfig {
+            calibration_window_seconds: 600,
+            recalibration_interval_seconds: 60,
+            warmup_period_seconds: 600,
+            ..ASConfig::default()
+        };
+        let configs: Vec<ASConfig> = [0.01, 0.1, 0.5]
+            .iter()
+            .map(|&gamma| ASConfig { risk_aversion_gamma: gamma, ..base.clone() })
+            .collect();
+        let events = random_walk_events();
+        let mids = leader_mids(11);
+
+        let multi = run_backtest_multi(MultiBacktestParams {
+            data_stream: events.iter().cloned().map(Ok),
+            configs: configs.clone(),
+            initial_capital: Decimal::from(1000),
+            order_notional: Decimal::from(20),
+            lead_lag: Some(leader_reference(mids.clone())),
+            calibrations: None,
+        })
+        .unwrap();
+
+        for (config, lockstep) in configs.iter().cloned().zip(&multi) {
+            let single = run_backtest(BacktestParams {
+                data_stream: events.iter().cloned().map(Ok),
+                config,
+                initial_capital: Decimal::from(1000),
+                order_notional: Decimal::from(20),
+                output_csv_path: None,
+                verbose: false,
+                lead_lag: Some(leader_reference(mids.clone())),
+                calibrations: None,
+            })
+            .unwrap();
+            assert_eq!(single.final_pnl, lockstep.final_pnl);
+        }
+
+        let model = leader_model();
+        let parallel =
+            run_backtest_multi_parallel(&events, configs, 2, Decimal::from(1000), Decimal::from(20), Some((&model, &mids)), None).unwrap();
+        let pnl = |results: &[BacktestResults]| results.iter().map(|r| r.final_pnl).collect::<Vec<_>>();
+        assert_eq!(pnl(&parallel), pnl(&multi));
+    }
+
+    #[test]
+    fn test_multi_matches_individual_runs() {
+        let base = ASConfig {
+            calibration_window_seconds: 600,
+            recalibration_interval_seconds: 60,
+            warmup_period_seconds: 600,
+            ..ASConfig::default()
+        };
+        let configs: Vec<ASConfig> = [(300, 0.01), (900, 0.1), (3600, 0.5)]
+            .iter()
+            .map(|&(horizon, gamma)| ASConfig {
+                inventory_horizon_seconds: horizon,
+                risk_aversion_gamma: gamma,
+                ..base.clone()
+            })
+            .collect();
+        let events = random_walk_events();
+
+        let multi = run_backtest_multi(MultiBacktestParams {
+            data_stream: events.iter().cloned().map(Ok),
+            configs: configs.clone(),
+            initial_capital: Decimal::from(1000),
+            order_notional: Decimal::from(20),
+            lead_lag: None,
+            calibrations: None,
+        })
+        .unwrap();
+        assert_eq!(multi.len(), configs.len());
+        assert!(multi.iter().any(|r| r.total_fills() > 0));
+
+        for (config, lockstep) in configs.iter().cloned().zip(&multi) {
+            let single = run_backtest(BacktestParams {
+                data_stream: events.iter().cloned().map(Ok),
+                config,
+                initial_capital: Decimal::from(1000),
+                order_notional: Decimal::from(20),
+                output_csv_path: None,
+                verbose: false,
+                lead_lag: None,
+                calibrations: None,
+            })
+            .unwrap();
+            assert_eq!(single.final_pnl, lockstep.final_pnl);
+            assert_eq!((single.bid_fills, single.ask_fills), (lockstep.bid_fills, lockstep.ask_fills));
+        }
+
+        let parallel = run_backtest_multi_parallel(&events, configs.clone(), 2, Decimal::from(1000), Decimal::from(20), None, None).unwrap();
+        let pnl = |results: &[BacktestResults]| results.iter().map(|r| r.final_pnl).collect::<Vec<_>>();
+        assert_eq!(pnl(&parallel), pnl(&multi));
+
+        // Configurations must share one calibration
+        let mismatched = vec![base.clone(), ASConfig { calibration_window_seconds: 1200, ..base }];
+        assert!(run_backtest_multi(MultiBacktestParams {
+            data_stream: events.into_iter().map(Ok),
+            configs: mismatched,
+            initial_capital: Decimal::from(1000),
+            order_notional: Decimal::from(20),
+            lead_lag: None,
+            calibrations: None,
+        })
+        .is_err());
+    }
+}
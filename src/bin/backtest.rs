@@ -1,9 +1,14 @@
 use extended_data_collector::backtest_engine::{run_backtest, BacktestParams};
-use extended_data_collector::data_loader::DataLoader;
+use extended_data_collector::calibration_cache::load_or_precompute;
+use extended_data_collector::data_loader::{parse_timestamp_arg, DataLoader};
+use extended_data_collector::lead_lag::{LeadLagModel, LeadLagReference};
 use extended_data_collector::model_types::ASConfig;
+use extended_data_collector::parquet_prefetch::DEFAULT_PREFETCH_DEPTH;
+use extended_data_collector::trading_rules::{apply_cached_rules, DEFAULT_TRADING_RULES_PATH};
 use rust_decimal::Decimal;
 use std::env;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Default values
 const DEFAULT_INITIAL_CAPITAL: i64 = 1000;
@@ -12,6 +17,8 @@ const DEFAULT_CONFIG_PATH: &str = "config.json";
 const DEFAULT_TRADES_PATH: &str = "data/eth_usd/trades_parts";
 const DEFAULT_ORDERBOOK_PATH: &str = "data/eth_usd/orderbook_parts";
 const DEFAULT_OUTPUT_PATH: &str = "data/eth_usd/backtest_results.csv";
+const DEFAULT_MARKET: &str = "ETH-USD";
+const DEFAULT_DATA_DIR: &str = "data";
 
 fn print_usage(program: &str) {
     eprintln!("Usage: {} [OPTIONS]", program);
@@ -23,7 +30,17 @@ fn print_usage(program: &str) {
     eprintln!("  --output <path>      Path to output CSV (default: {})", DEFAULT_OUTPUT_PATH);
     eprintln!("  --capital <amount>   Initial capital in dollars (default: {})", DEFAULT_INITIAL_CAPITAL);
     eprintln!("  --notional <amount>  Order notional in dollars (default: {})", DEFAULT_ORDER_NOTIONAL);
+    eprintln!("  --market <name>      Market being backtested, used for lead-lag lookup (default: {})", DEFAULT_MARKET);
+    eprintln!("  --lead-lag <path>    Lead-lag model JSON from estimate_lead_lag (enables leader reference pricing)");
+    eprintln!("  --data-dir <path>    Root data directory holding the leader market (default: {})", DEFAULT_DATA_DIR);
+    eprintln!("  --leader-orderbook <path>  Leader orderbook directory (default: <data-dir>/<leader>/orderbook_parts)");
     eprintln!("  --quiet              Disable verbose output");
+    eprintln!("  --rules <path>       Trading rules cache written by collect_data (default: {})", DEFAULT_TRADING_RULES_PATH);
+    eprintln!("  --calibration-cache <dir>  Reuse/store the calibration series in this directory");
+    eprintln!("  --prefetch <n>       Parquet part files decoded ahead on worker threads, 0 to disable (default: {})", DEFAULT_PREFETCH_DEPTH);
+    eprintln!("  --no-event-cache     Read the source files even when an up-to-date event cache exists");
+    eprintln!("  --start <time>       Only load events at or after this time (epoch ms, YYYY-MM-DD or RFC 3339)");
+    eprintln!("  --end <time>         Only load events before this time");
     eprintln!("  --help               Show this help message");
 }
 
@@ -35,9 +52,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut trades_path = DEFAULT_TRADES_PATH.to_string();
     let mut orderbook_path = DEFAULT_ORDERBOOK_PATH.to_string();
     let mut output_path = DEFAULT_OUTPUT_PATH.to_string();
+    let mut rules_path = DEFAULT_TRADING_RULES_PATH.to_string();
     let mut initial_capital = DEFAULT_INITIAL_CAPITAL;
     let mut order_notional = DEFAULT_ORDER_NOTIONAL;
     let mut verbose = true;
+    let mut market = DEFAULT_MARKET.to_string();
+    let mut lead_lag_path: Option<String> = None;
+    let mut leader_orderbook_path: Option<String> = None;
+    let mut data_dir = DEFAULT_DATA_DIR.to_string();
+    let mut calibration_cache: Option<String> = None;
+    let mut prefetch_depth = DEFAULT_PREFETCH_DEPTH;
+    let mut use_event_cache = true;
+    let mut start_ms = 0;
+    let mut end_ms = u64::MAX;
 
     let mut i = 1;
     while i < args.len() {
@@ -70,9 +97,50 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .and_then(|s| s.parse().ok())
                     .unwrap_or(DEFAULT_ORDER_NOTIONAL);
             }
+            "--market" => {
+                i += 1;
+                market = args.get(i).cloned().unwrap_or_default();
+            }
+            "--lead-lag" => {
+                i += 1;
+                lead_lag_path = args.get(i).cloned();
+            }
+            "--data-dir" => {
+                i += 1;
+                data_dir = args.get(i).cloned().unwrap_or_default();
+            }
+            "--leader-orderbook" => {
+                i += 1;
+                leader_orderbook_path = args.get(i).cloned();
+            }
             "--quiet" => {
                 verbose = false;
             }
+            "--rules" => {
+                i += 1;
+                rules_path = args.get(i).cloned().unwrap_or_default();
+            }
+            "--calibration-cache" => {
+                i += 1;
+                calibration_cache = args.get(i).cloned();
+            }
+            "--prefetch" => {
+                i += 1;
+                prefetch_depth = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_PREFETCH_DEPTH);
+            }
+            "--no-event-cache" => {
+                use_event_cache = false;
+            }
+            "--start" => {
+                i += 1;
+                start_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--end" => {
+                i += 1;
+                end_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
             "--help" | "-h" => {
                 print_usage(&args[0]);
                 return Ok(());
@@ -85,11 +153,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         i += 1;
     }
+    if start_ms >= end_ms {
+        return Err("--start must be before --end".into());
+    }
 
     println!("Loading data...");
 
     // Load configuration
-    let config = match std::fs::read_to_string(&config_path) {
+    let mut config = match std::fs::read_to_string(&config_path) {
         Ok(contents) => {
             match serde_json::from_str::<ASConfig>(&contents) {
                 Ok(cfg) => {
@@ -108,14 +179,56 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    // Exchange tick/size rules cached by collect_data
+    match apply_cached_rules(&mut config, Path::new(&rules_path), &market) {
+        Some(rules) => println!("Trading rules for {}: tick={}, min_size={}, size_step={}",
+            market, rules.tick_size, rules.min_order_size, rules.order_size_step),
+        None => println!("No trading rules for {} in {}. Using tick_size={}",
+            market, rules_path, config.tick_size),
+    }
+    // Size-aware calibration measures fills against the clip actually quoted
+    config.resolve_clip_notional(order_notional as f64);
+
     // Load data using DataLoader
-    let loader = DataLoader::new(
+    let mut loader = DataLoader::new(
         Path::new(&trades_path),
         Path::new(&orderbook_path),
-    );
+    )
+    .with_time_range(start_ms, end_ms)
+    .with_prefetch(prefetch_depth);
+    if !use_event_cache {
+        loader = loader.with_event_cache(None);
+    }
+
+    let calibrations = match &calibration_cache {
+        Some(dir) => {
+            let series = load_or_precompute(Path::new(dir), &config, &loader)?;
+            println!("Calibration series: {} calibrations (cache: {})", series.len(), dir);
+            Some(Arc::new(series))
+        }
+        None => None,
+    };
 
     let data_stream = loader.stream()?;
 
+    // Optional leader reference for cross-market fair value
+    let lead_lag = match lead_lag_path {
+        Some(path) => {
+            let model = LeadLagModel::load_for_follower(Path::new(&path), &market)?;
+            let leader_loader = model
+                .leader_loader(Path::new(&data_dir), leader_orderbook_path.as_deref().map(Path::new))
+                .with_time_range(start_ms, end_ms)
+                .with_prefetch(prefetch_depth);
+
+            println!(
+                "Lead-lag: {} leads {} (bar={}ms, betas={:?})",
+                model.leader, model.follower, model.bar_ms, model.betas
+            );
+            Some(LeadLagReference::new(&model, leader_loader.mids_iter()?))
+        }
+        None => None,
+    };
+
     println!("Config: gamma_min={}, max_inventory={}, horizon={}s",
         config.gamma_min, config.max_inventory, config.inventory_horizon_seconds);
     println!("Capital: ${}, Order Notional: ${}", initial_capital, order_notional);
@@ -128,6 +241,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         order_notional: Decimal::from(order_notional),
         output_csv_path: Some(output_path.clone()),
         verbose,
+        lead_lag,
+        calibrations,
     };
 
     let results = run_backtest(params)?;
@@ -145,6 +260,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Total Fills:           {}", results.total_fills());
     println!("Total Volume Traded:   {} units", results.total_volume);
     println!("Total Notional Volume: ${:.2}", results.total_notional_volume);
+    if results.rejected_orders > 0 {
+        println!("Rejected Orders:       {} (below min order size)", results.rejected_orders);
+    }
+    if results.config.flatten_at_session_end {
+        println!("Session Flattens:      {}", results.session_flattens);
+    }
     println!("\nResults written to {}", output_path);
 
     Ok(())
@@ -0,0 +1,89 @@
+//! Build a market's event cache for repeated backtests
+//!
+//! Replays the market's trade and orderbook parts once into `<market dir>/events.cache`, a
+//! pre-merged fixed-point event file that `DataLoader` memory-maps instead of decoding the
+//! parts, until new parts arrive and the cache needs rebuilding.
+
+use extended_data_collector::data_loader::{market_data_dir, DataLoader};
+use extended_data_collector::event_cache::{build_event_cache, event_cache_path};
+use extended_data_collector::parquet_prefetch::DEFAULT_PREFETCH_DEPTH;
+use std::env;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const DEFAULT_DATA_DIR: &str = "data";
+const DEFAULT_MARKET: &str = "ETH-USD";
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} [OPTIONS]", program);
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --data-dir <path>     Root data directory (default: {})", DEFAULT_DATA_DIR);
+    eprintln!("  --market <name>       Market to cache (default: {})", DEFAULT_MARKET);
+    eprintln!("  --output <path>       Cache file (default: <data-dir>/<market>/events.cache, where loaders look for it)");
+    eprintln!("  --prefetch <n>        Parquet part files decoded ahead on worker threads, 0 to disable (default: {})", DEFAULT_PREFETCH_DEPTH);
+    eprintln!("  --help                Show this help message");
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut data_dir = DEFAULT_DATA_DIR.to_string();
+    let mut market = DEFAULT_MARKET.to_string();
+    let mut output_path: Option<String> = None;
+    let mut prefetch_depth = DEFAULT_PREFETCH_DEPTH;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--data-dir" => {
+                i += 1;
+                data_dir = args.get(i).cloned().unwrap_or_default();
+            }
+            "--market" => {
+                i += 1;
+                market = args.get(i).cloned().unwrap_or_default();
+            }
+            "--output" => {
+                i += 1;
+                output_path = args.get(i).cloned();
+            }
+            "--prefetch" => {
+                i += 1;
+                prefetch_depth = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_PREFETCH_DEPTH);
+            }
+            "--help" | "-h" => {
+                print_usage(&args[0]);
+                return Ok(());
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", args[i]);
+                print_usage(&args[0]);
+                return Err("Invalid arguments".into());
+            }
+        }
+        i += 1;
+    }
+
+    let market_dir = market_data_dir(Path::new(&data_dir), &market);
+    let output = output_path.map(PathBuf::from).unwrap_or_else(|| event_cache_path(&market_dir));
+    let loader = DataLoader::new(&market_dir.join("trades_parts"), &market_dir.join("orderbook_parts"))
+        .with_prefetch(prefetch_depth);
+
+    println!("Building event cache for {} from {}...", market, market_dir.display());
+    let start = Instant::now();
+    let summary = build_event_cache(&loader, &output)?;
+
+    println!("Wrote {} in {:.2}s", output.display(), start.elapsed().as_secs_f64());
+    println!("  trades:     {}", summary.trades);
+    println!("  orderbooks: {} ({} levels per side)", summary.orderbooks, summary.depth);
+    if summary.raw_orderbooks > 0 {
+        println!("  {} snapshots held values with no exact fixed-point form and were stored as raw f64", summary.raw_orderbooks);
+    }
+    println!("  size:       {:.1} MiB", summary.bytes as f64 / (1024.0 * 1024.0));
+
+    Ok(())
+}
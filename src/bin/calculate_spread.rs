@@ -1,8 +1,11 @@
-use extended_data_collector::model_types::{ASConfig, GammaMode};
-use extended_data_collector::data_loader::DataLoader;
+use extended_data_collector::model_types::{ASConfig, GammaMode, QuoteAdjustments};
+use extended_data_collector::trading_rules::{apply_cached_rules, DEFAULT_TRADING_RULES_PATH};
+use extended_data_collector::data_loader::{parse_timestamp_arg, DataLoader};
 use extended_data_collector::metrics::calculate_effective_price;
-use extended_data_collector::calibration_engine::CalibrationEngine;
-use extended_data_collector::spread_model::compute_optimal_quote;
+use extended_data_collector::adverse_selection::AdverseSelectionTracker;
+use extended_data_collector::calibration_engine::{CalibrationEngine, CalibrationResult};
+use extended_data_collector::seasonality::SeasonalityProfile;
+use extended_data_collector::spread_model::{compute_optimal_quote_shaped, compute_quote_sensitivities_shaped};
 use rust_decimal::Decimal;
 use rust_decimal::prelude::*;
 use std::path::Path;
@@ -15,6 +18,7 @@ const DEFAULT_CONFIG_PATH: &str = "config.json";
 const DEFAULT_TRADES_PATH: &str = "data/eth_usd/trades_parts";
 const DEFAULT_ORDERBOOK_PATH: &str = "data/eth_usd/orderbook_parts";
 const DEFAULT_OUTPUT_PATH: &str = "data/eth_usd/as_results.csv";
+const DEFAULT_MARKET: &str = "ETH-USD";
 
 fn print_usage(program: &str) {
     eprintln!("Usage: {} [OPTIONS]", program);
@@ -24,6 +28,10 @@ fn print_usage(program: &str) {
     eprintln!("  --trades <path>      Path to trades CSV (default: {})", DEFAULT_TRADES_PATH);
     eprintln!("  --orderbook <path>   Path to orderbook directory (default: {})", DEFAULT_ORDERBOOK_PATH);
     eprintln!("  --output <path>      Path to output CSV (default: {})", DEFAULT_OUTPUT_PATH);
+    eprintln!("  --market <name>      Market name for trading rules lookup (default: {})", DEFAULT_MARKET);
+    eprintln!("  --rules <path>       Trading rules cache written by collect_data (default: {})", DEFAULT_TRADING_RULES_PATH);
+    eprintln!("  --start <time>       Only load events at or after this time (epoch ms, YYYY-MM-DD or RFC 3339)");
+    eprintln!("  --end <time>         Only load events before this time");
     eprintln!("  --help               Show this help message");
 }
 
@@ -35,6 +43,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut trades_path = DEFAULT_TRADES_PATH.to_string();
     let mut orderbook_path = DEFAULT_ORDERBOOK_PATH.to_string();
     let mut output_path = DEFAULT_OUTPUT_PATH.to_string();
+    let mut market = DEFAULT_MARKET.to_string();
+    let mut rules_path = DEFAULT_TRADING_RULES_PATH.to_string();
+    let mut start_ms = 0;
+    let mut end_ms = u64::MAX;
 
     let mut i = 1;
     while i < args.len() {
@@ -55,6 +67,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 i += 1;
                 output_path = args.get(i).cloned().unwrap_or_default();
             }
+            "--market" => {
+                i += 1;
+                market = args.get(i).cloned().unwrap_or_default();
+            }
+            "--rules" => {
+                i += 1;
+                rules_path = args.get(i).cloned().unwrap_or_default();
+            }
+            "--start" => {
+                i += 1;
+                start_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--end" => {
+                i += 1;
+                end_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
             "--help" | "-h" => {
                 print_usage(&args[0]);
                 return Ok(());
@@ -67,6 +95,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         i += 1;
     }
+    if start_ms >= end_ms {
+        return Err("--start must be before --end".into());
+    }
 
     // 1. Load Config
     let mut config = match std::fs::read_to_string(&config_path) {
@@ -87,6 +118,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             ASConfig::default()
         }
     };
+
+    // Exchange tick/size rules cached by collect_data
+    match apply_cached_rules(&mut config, Path::new(&rules_path), &market) {
+        Some(rules) => println!("Trading rules for {}: tick={}, min_size={}, size_step={}",
+            market, rules.tick_size, rules.min_order_size, rules.order_size_step),
+        None => println!("No trading rules for {} in {}. Using tick_size={}",
+            market, rules_path, config.tick_size),
+    }
+
     // Force usage of provided risk_aversion_gamma value (no inventory scaling)
     config.gamma_mode = GammaMode::Constant;
     config.gamma_min = config.risk_aversion_gamma;
@@ -98,17 +138,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let loader = DataLoader::new(
         Path::new(&trades_path),
         Path::new(&orderbook_path),
-    );
+    )
+    .with_time_range(start_ms, end_ms);
     let all_trades = loader.get_trades()?;
     println!("Loaded {} trades", all_trades.len());
 
     // 3. Initialize Calibration Engine
     let mut calibration_engine = CalibrationEngine::new(&config);
+    if let Some(path) = &config.seasonality_path {
+        calibration_engine.set_seasonality(SeasonalityProfile::load(Path::new(path))?);
+        println!("Loaded seasonality profile from {}", path);
+    }
+    let mut adverse_selection = config.adverse_selection_enabled.then(|| AdverseSelectionTracker::new(&config));
 
     // 4. Prepare Output with buffered writer
     let file = File::create(&output_path)?;
     let mut output_file = BufWriter::new(file);
-    writeln!(output_file, "timestamp,datetime,mid_price,volatility,bid_kappa,ask_kappa,bid_a,ask_a,gamma,optimal_spread_bps,bid_spread_bps,ask_spread_bps,bid_price,ask_price,reservation_price")?;
+    writeln!(output_file, "timestamp,datetime,mid_price,volatility,bid_kappa,ask_kappa,bid_a,ask_a,gamma,optimal_spread_bps,bid_spread_bps,ask_spread_bps,bid_price,ask_price,reservation_price,bid_half_spread_d_sigma,bid_half_spread_d_kappa,bid_half_spread_d_gamma,ask_half_spread_d_sigma,ask_half_spread_d_kappa,ask_half_spread_d_gamma,reservation_d_sigma,reservation_d_gamma,reservation_d_inventory,active_clamps,bid_floor_bps,ask_floor_bps,bid_kappa_se,ask_kappa_se,bid_a_se,ask_a_se,bid_ks,ask_ks,bid_n,ask_n,bid_fit_source,ask_fit_source,bid_shape,ask_shape,bid_shape_params,ask_shape_params,bid_branching_ratio,ask_branching_ratio,hawkes_near_critical")?;
 
     // Print Header to Terminal
     println!("{:-<155}", "");
@@ -130,42 +176,69 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let bps_multiplier = Decimal::from(10000);
 
     // 6. Process Orderbook Snapshots
-    for result in loader.orderbooks_iter()? {
-        let (ts, snapshot) = result?;
-        let current_ts = ts;
+    for result in loader.orderbook_rows()? {
+        let row = result?;
+        let book = row.view();
+        let current_ts = book.timestamp();
         last_ts = current_ts;
 
-        // Calculate Effective Price
+        // Calculate Effective Price (walks the whole book, so needs the Decimal levels)
+        let snapshot = book.to_snapshot()?;
         let effective_quote = calculate_effective_price(&snapshot, config.effective_volume_threshold);
 
         if let Some(quote) = effective_quote {
+            // Trades since the previous snapshot printed against the previous mid
+            let trade_mid = last_quote.map(|q| q.mid).unwrap_or(quote.mid).to_f64().unwrap_or(0.0);
             last_quote = Some(quote);
 
             // Add price to calibration engine
-            calibration_engine.add_orderbook(&snapshot, quote.mid);
+            calibration_engine.add_orderbook(&book, quote.mid);
 
             // Add trades that occurred since last update
             while trade_idx < all_trades.len() && all_trades[trade_idx].timestamp <= current_ts {
                 calibration_engine.add_trade(&all_trades[trade_idx]);
+                if let Some(tracker) = adverse_selection.as_mut() {
+                    tracker.add_trade(&all_trades[trade_idx], trade_mid);
+                }
                 trade_idx += 1;
             }
 
             // Prune old data from windows
             calibration_engine.prune_windows(current_ts);
+            if let Some(tracker) = adverse_selection.as_mut() {
+                tracker.update(current_ts, quote.mid.to_f64().unwrap_or(0.0));
+            }
 
             // Check if we should recalibrate
             if calibration_engine.should_recalibrate(current_ts) {
                 if let Some(cal_result) = calibration_engine.calibrate(current_ts, config.tick_size) {
+                    warn_near_critical(&cal_result, config.hawkes_critical_branching);
                     // Compute optimal quote with zero inventory
-                    let optimal = compute_optimal_quote(
+                    let adjustments = adverse_floor_adjustments(adverse_selection.as_ref());
+                    let optimal = compute_optimal_quote_shaped(
                         current_ts,
                         quote.mid,
                         Decimal::ZERO,
                         cal_result.volatility,
-                        cal_result.bid_kappa,
-                        cal_result.ask_kappa,
+                        &cal_result.bid_shape,
+                        &cal_result.ask_shape,
                         &config,
+                        &adjustments,
                     );
+                    let sensitivities = compute_quote_sensitivities_shaped(
+                        current_ts,
+                        quote.mid,
+                        Decimal::ZERO,
+                        cal_result.volatility,
+                        &cal_result.bid_shape,
+                        &cal_result.ask_shape,
+                        &config,
+                        &adjustments,
+                    );
+
+                    // κ of the shapes actually quoted (NaN for non-exponential shapes)
+                    let bid_kappa = cal_result.bid_shape.kappa().unwrap_or(f64::NAN);
+                    let ask_kappa = cal_result.ask_shape.kappa().unwrap_or(f64::NAN);
 
                     // Calculate spread metrics with division-by-zero guard
                     let reservation_price = optimal.reservation_price;
@@ -185,8 +258,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             bid_spread_bps.to_f64().unwrap_or(0.0),
                             ask_spread_bps.to_f64().unwrap_or(0.0),
                             cal_result.volatility,
-                            cal_result.bid_kappa,
-                            cal_result.ask_kappa,
+                            bid_kappa,
+                            ask_kappa,
                             cal_result.bid_a,
                             cal_result.ask_a,
                             optimal.gamma,
@@ -195,13 +268,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         // Output to CSV
                         writeln!(
                             output_file,
-                            "{},{},{},{:.6},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2}",
+                            "{},{},{},{:.6},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.4},{:.4},{},{},{},{},{},{},{},{},{:.4},{:.4},{}",
                             current_ts,
                             format_timestamp(current_ts),
                             quote.mid,
                             cal_result.volatility,
-                            cal_result.bid_kappa,
-                            cal_result.ask_kappa,
+                            bid_kappa,
+                            ask_kappa,
                             cal_result.bid_a,
                             cal_result.ask_a,
                             optimal.gamma,
@@ -210,13 +283,42 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             ask_spread_bps,
                             bid_price,
                             ask_price,
-                            reservation_price
+                            reservation_price,
+                            sensitivities.bid_half_spread_d_sigma,
+                            sensitivities.bid_half_spread_d_kappa,
+                            sensitivities.bid_half_spread_d_gamma,
+                            sensitivities.ask_half_spread_d_sigma,
+                            sensitivities.ask_half_spread_d_kappa,
+                            sensitivities.ask_half_spread_d_gamma,
+                            sensitivities.reservation_d_sigma,
+                            sensitivities.reservation_d_gamma,
+                            sensitivities.reservation_d_inventory,
+                            sensitivities.clamps.labels(),
+                            adjustments.bid_floor_bps,
+                            adjustments.ask_floor_bps,
+                            cal_result.intensity_fit.bid.kappa_se,
+                            cal_result.intensity_fit.ask.kappa_se,
+                            cal_result.intensity_fit.bid.a_se,
+                            cal_result.intensity_fit.ask.a_se,
+                            cal_result.intensity_fit.bid.ks_statistic,
+                            cal_result.intensity_fit.ask.ks_statistic,
+                            cal_result.intensity_fit.bid.trades,
+                            cal_result.intensity_fit.ask.trades,
+                            cal_result.intensity_fit.bid.source.label(),
+                            cal_result.intensity_fit.ask.source.label(),
+                            cal_result.bid_shape.name(),
+                            cal_result.ask_shape.name(),
+                            cal_result.bid_shape.parameters_label(),
+                            cal_result.ask_shape.parameters_label(),
+                            cal_result.branching_ratios().0,
+                            cal_result.branching_ratios().1,
+                            cal_result.hawkes_near_critical(config.hawkes_critical_branching)
                         )?;
 
                         // Output to terminal
                         println!(
                             "{:<15} | {:>12.2} | {:>10.6} | {:>8.2} | {:>8.2} | {:>8.2} | {:>8.2} | {:>6.2} | {:>12.2} | {:>12.2} | {:>12.2} | {:>12.2} | {:>12.2}",
-                            current_ts, quote.mid, cal_result.volatility, cal_result.bid_kappa, cal_result.ask_kappa,
+                            current_ts, quote.mid, cal_result.volatility, bid_kappa, ask_kappa,
                             cal_result.bid_a, cal_result.ask_a, optimal.gamma, total_spread_bps, bid_spread_bps, ask_spread_bps, bid_price, ask_price
                         );
                     }
@@ -228,16 +330,32 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 7. Final Output (for partial window at end)
     if let Some(quote) = last_quote {
         if let Some(cal_result) = calibration_engine.calibrate(last_ts, config.tick_size) {
-            let optimal = compute_optimal_quote(
+            warn_near_critical(&cal_result, config.hawkes_critical_branching);
+            let adjustments = adverse_floor_adjustments(adverse_selection.as_ref());
+            let optimal = compute_optimal_quote_shaped(
+                last_ts,
+                quote.mid,
+                Decimal::ZERO,
+                cal_result.volatility,
+                &cal_result.bid_shape,
+                &cal_result.ask_shape,
+                &config,
+                &adjustments,
+            );
+            let sensitivities = compute_quote_sensitivities_shaped(
                 last_ts,
                 quote.mid,
                 Decimal::ZERO,
                 cal_result.volatility,
-                cal_result.bid_kappa,
-                cal_result.ask_kappa,
+                &cal_result.bid_shape,
+                &cal_result.ask_shape,
                 &config,
+                &adjustments,
             );
 
+            // κ of the shapes actually quoted (NaN for non-exponential shapes)
+            let bid_kappa = cal_result.bid_shape.kappa().unwrap_or(f64::NAN);
+            let ask_kappa = cal_result.ask_shape.kappa().unwrap_or(f64::NAN);
             let reservation_price = optimal.reservation_price;
             
             if reservation_price > Decimal::ZERO {
@@ -254,8 +372,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     bid_spread_bps.to_f64().unwrap_or(0.0),
                     ask_spread_bps.to_f64().unwrap_or(0.0),
                     cal_result.volatility,
-                    cal_result.bid_kappa,
-                    cal_result.ask_kappa,
+                    bid_kappa,
+                    ask_kappa,
                     cal_result.bid_a,
                     cal_result.ask_a,
                     optimal.gamma,
@@ -263,13 +381,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                 writeln!(
                     output_file,
-                    "{},{},{},{:.6},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2}",
+                    "{},{},{},{:.6},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.4},{:.4},{},{},{},{},{},{},{},{},{:.4},{:.4},{}",
                     last_ts,
                     format_timestamp(last_ts),
                     quote.mid,
                     cal_result.volatility,
-                    cal_result.bid_kappa,
-                    cal_result.ask_kappa,
+                    bid_kappa,
+                    ask_kappa,
                     cal_result.bid_a,
                     cal_result.ask_a,
                     optimal.gamma,
@@ -278,12 +396,41 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     ask_spread_bps,
                     bid_price,
                     ask_price,
-                    reservation_price
+                    reservation_price,
+                    sensitivities.bid_half_spread_d_sigma,
+                    sensitivities.bid_half_spread_d_kappa,
+                    sensitivities.bid_half_spread_d_gamma,
+                    sensitivities.ask_half_spread_d_sigma,
+                    sensitivities.ask_half_spread_d_kappa,
+                    sensitivities.ask_half_spread_d_gamma,
+                    sensitivities.reservation_d_sigma,
+                    sensitivities.reservation_d_gamma,
+                    sensitivities.reservation_d_inventory,
+                    sensitivities.clamps.labels(),
+                    adjustments.bid_floor_bps,
+                    adjustments.ask_floor_bps,
+                    cal_result.intensity_fit.bid.kappa_se,
+                    cal_result.intensity_fit.ask.kappa_se,
+                    cal_result.intensity_fit.bid.a_se,
+                    cal_result.intensity_fit.ask.a_se,
+                    cal_result.intensity_fit.bid.ks_statistic,
+                    cal_result.intensity_fit.ask.ks_statistic,
+                    cal_result.intensity_fit.bid.trades,
+                    cal_result.intensity_fit.ask.trades,
+                    cal_result.intensity_fit.bid.source.label(),
+                    cal_result.intensity_fit.ask.source.label(),
+                    cal_result.bid_shape.name(),
+                    cal_result.ask_shape.name(),
+                    cal_result.bid_shape.parameters_label(),
+                    cal_result.ask_shape.parameters_label(),
+                    cal_result.branching_ratios().0,
+                    cal_result.branching_ratios().1,
+                    cal_result.hawkes_near_critical(config.hawkes_critical_branching)
                 )?;
 
                 println!(
                     "{:<15} | {:>12.2} | {:>10.6} | {:>8.2} | {:>8.2} | {:>8.2} | {:>8.2} | {:>6.2} | {:>12.2} | {:>12.2} | {:>12.2} | {:>12.2} | {:>12.2}",
-                    last_ts, quote.mid, cal_result.volatility, cal_result.bid_kappa, cal_result.ask_kappa,
+                    last_ts, quote.mid, cal_result.volatility, bid_kappa, ask_kappa,
                     cal_result.bid_a, cal_result.ask_a, optimal.gamma, total_spread_bps, bid_spread_bps, ask_spread_bps, bid_price, ask_price
                 );
             }
@@ -302,6 +449,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Per-side spread floors from measured adverse selection (none when disabled)
+fn adverse_floor_adjustments(tracker: Option<&AdverseSelectionTracker>) -> QuoteAdjustments {
+    let (bid_floor_bps, ask_floor_bps) = tracker.map(|t| t.floor_bps()).unwrap_or((0.0, 0.0));
+    QuoteAdjustments {
+        bid_floor_bps,
+        ask_floor_bps,
+        ..QuoteAdjustments::default()
+    }
+}
+
+/// Flag Hawkes fits whose branching ratio is close to 1 (explosive, self-excited flow)
+fn warn_near_critical(cal_result: &CalibrationResult, threshold: f64) {
+    for (side, fit) in [("bid", &cal_result.bid_hawkes), ("ask", &cal_result.ask_hawkes)] {
+        if let Some(fit) = fit.as_ref().filter(|f| f.is_near_critical(threshold)) {
+            println!(
+                "[WARN] {}: {} Hawkes branching ratio {:.3} >= {:.2} (baseline {:.4}/s, decay {:.3}/s); intensity estimates are unstable",
+                cal_result.timestamp, side, fit.branching_ratio, threshold, fit.baseline, fit.decay
+            );
+        }
+    }
+}
+
 /// Format timestamp as human-readable string
 fn format_timestamp(timestamp_ms: u64) -> String {
     use chrono::{DateTime, Utc};
@@ -358,8 +527,13 @@ impl SpreadStats {
         self.bid_spread_bps.push(bid_spread);
         self.ask_spread_bps.push(ask_spread);
         self.volatility.push(vol);
-        self.bid_kappa.push(bid_kappa);
-        self.ask_kappa.push(ask_kappa);
+        // Non-exponential shapes have no κ
+        if bid_kappa.is_finite() {
+            self.bid_kappa.push(bid_kappa);
+        }
+        if ask_kappa.is_finite() {
+            self.ask_kappa.push(ask_kappa);
+        }
         self.bid_a.push(bid_a);
         self.ask_a.push(ask_a);
         self.gamma.push(gamma);
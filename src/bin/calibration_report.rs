@@ -0,0 +1,315 @@
+//! Export calibration diagnostics to parquet
+//!
+//! Replays a dataset through `CalibrationEngine` with the settings a backtest uses and writes
+//! one row per calibration: per-side trade-delta histograms, exposure totals, the fitted
+//! curve and its log-likelihood, fit diagnostics, GARCH parameters, realized vs
+//! estimator sigma, Hawkes branching ratios and the per-size-bucket intensity curves. Histogram bin edges are stored in the file metadata (`delta_edges`).
+
+use arrow::array::{ArrayRef, BooleanArray, Float64Array, Float64Builder, Int64Array, Int64Builder, ListBuilder, StringArray, TimestampMillisecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use extended_data_collector::calibration::{
+    diagnostic_delta_edges, IntensityDiagnostics, SideIntensityDiagnostics, SideIntensityFit, SizeBucketIntensity,
+};
+use extended_data_collector::calibration_cache::replay_calibrations;
+use extended_data_collector::calibration_engine::CalibrationResult;
+use extended_data_collector::data_loader::{market_data_dir, parse_timestamp_arg, DataLoader};
+use extended_data_collector::model_types::ASConfig;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+const DEFAULT_CONFIG_PATH: &str = "config.json";
+const DEFAULT_DATA_DIR: &str = "data";
+const DEFAULT_MARKET: &str = "SOL-USD";
+const DEFAULT_ORDER_NOTIONAL: i64 = 20;
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} [OPTIONS]", program);
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --config <path>      Path to config file (default: {})", DEFAULT_CONFIG_PATH);
+    eprintln!("  --data-dir <path>    Root data directory (default: {})", DEFAULT_DATA_DIR);
+    eprintln!("  --market <name>      Market to replay (default: {})", DEFAULT_MARKET);
+    eprintln!("  --output <path>      Output parquet path (default: <data-dir>/<market>/calibration_report.parquet)");
+    eprintln!("  --notional <amount>  Order notional in dollars, the clip for size-aware calibration (default: {})", DEFAULT_ORDER_NOTIONAL);
+    eprintln!("  --start <time>       Only load events at or after this time (epoch ms, YYYY-MM-DD or RFC 3339)");
+    eprintln!("  --end <time>         Only load events before this time");
+    eprintln!("  --help               Show this help message");
+}
+
+/// One calibration with the diagnostics of its window
+struct ReportRow {
+    result: CalibrationResult,
+    realized_sigma: f64,
+    diagnostics: IntensityDiagnostics,
+}
+
+fn f64_column<F: Fn(&ReportRow) -> f64>(rows: &[ReportRow], f: F) -> ArrayRef {
+    Arc::new(Float64Array::from(rows.iter().map(f).collect::<Vec<_>>()))
+}
+
+/// Columns for one side, appended to `fields`/`columns` with the `side` prefix
+fn push_side_columns(
+    rows: &[ReportRow],
+    side: &str,
+    fit: fn(&CalibrationResult) -> &SideIntensityFit,
+    diagnostics: fn(&IntensityDiagnostics) -> &SideIntensityDiagnostics,
+    fields: &mut Vec<Field>,
+    columns: &mut Vec<ArrayRef>,
+) {
+    let mut float = |name: &str, f: &dyn Fn(&ReportRow) -> f64| {
+        fields.push(Field::new(format!("{}_{}", side, name), DataType::Float64, false));
+        columns.push(f64_column(rows, f));
+    };
+    float("kappa", &|r| fit(&r.result).kappa);
+    float("a", &|r| fit(&r.result).a);
+    float("kappa_se", &|r| fit(&r.result).kappa_se);
+    float("a_se", &|r| fit(&r.result).a_se);
+    float("ks", &|r| fit(&r.result).ks_statistic);
+    float("log_likelihood", &|r| diagnostics(&r.diagnostics).log_likelihood);
+    float("exposure_seconds", &|r| diagnostics(&r.diagnostics).exposure_seconds);
+    float("exposure_integral", &|r| diagnostics(&r.diagnostics).exposure_integral);
+
+    fields.push(Field::new(format!("{}_n", side), DataType::Int64, false));
+    columns.push(Arc::new(Int64Array::from(
+        rows.iter().map(|r| fit(&r.result).trades as i64).collect::<Vec<_>>(),
+    )));
+    fields.push(Field::new(format!("{}_fit_source", side), DataType::Utf8, false));
+    columns.push(Arc::new(StringArray::from(
+        rows.iter().map(|r| fit(&r.result).source.label()).collect::<Vec<_>>(),
+    )));
+
+    let mut counts = ListBuilder::new(Int64Builder::new());
+    let mut curve = ListBuilder::new(Float64Builder::new());
+    for row in rows {
+        let d = diagnostics(&row.diagnostics);
+        counts.values().append_slice(&d.delta_counts.iter().map(|c| *c as i64).collect::<Vec<_>>());
+        counts.append(true);
+        curve.values().append_slice(&d.fitted_intensity);
+        curve.append(true);
+    }
+    fields.push(Field::new(
+        format!("{}_delta_counts", side),
+        DataType::List(Arc::new(Field::new("item", DataType::Int64, true))),
+        false,
+    ));
+    columns.push(Arc::new(counts.finish()));
+    fields.push(Field::new(
+        format!("{}_fitted_intensity", side),
+        DataType::List(Arc::new(Field::new("item", DataType::Float64, true))),
+        false,
+    ));
+    columns.push(Arc::new(curve.finish()));
+}
+
+type BucketValue = fn(&SizeBucketIntensity) -> f64;
+
+/// Per-size-bucket intensity curves as list columns, one element per bucket (empty lists
+/// unless `intensity_size_buckets_usd` is set)
+fn push_size_bucket_columns(rows: &[ReportRow], fields: &mut Vec<Field>, columns: &mut Vec<ArrayRef>) {
+    let values: [(&str, BucketValue); 8] = [
+        ("min_notional", |b| b.min_notional),
+        ("max_notional", |b| b.max_notional),
+        ("bid_kappa", |b| b.fit.bid.kappa),
+        ("bid_a", |b| b.fit.bid.a),
+        ("ask_kappa", |b| b.fit.ask.kappa),
+        ("ask_a", |b| b.fit.ask.a),
+        ("bid_n", |b| b.fit.bid.trades as f64),
+        ("ask_n", |b| b.fit.ask.trades as f64),
+    ];
+    for (name, value) in values {
+        let mut list = ListBuilder::new(Float64Builder::new());
+        for row in rows {
+            list.values().append_slice(&row.result.size_buckets.iter().map(value).collect::<Vec<_>>());
+            list.append(true);
+        }
+        fields.push(Field::new(
+            format!("size_bucket_{}", name),
+            DataType::List(Arc::new(Field::new("item", DataType::Float64, true))),
+            false,
+        ));
+        columns.push(Arc::new(list.finish()));
+    }
+}
+
+fn write_report(
+    rows: &[ReportRow],
+    estimator: &str,
+    market: &str,
+    critical_branching: f64,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut fields = vec![
+        Field::new("timestamp", DataType::Timestamp(TimeUnit::Millisecond, None), false),
+        Field::new("sigma", DataType::Float64, false),
+        Field::new("realized_sigma", DataType::Float64, false),
+        Field::new("garch_omega", DataType::Float64, false),
+        Field::new("garch_alpha", DataType::Float64, false),
+        Field::new("garch_beta", DataType::Float64, false),
+        Field::new("bid_kappa_used", DataType::Float64, false),
+        Field::new("ask_kappa_used", DataType::Float64, false),
+        Field::new("bid_a_used", DataType::Float64, false),
+        Field::new("ask_a_used", DataType::Float64, false),
+        Field::new("bid_branching_ratio", DataType::Float64, false),
+        Field::new("ask_branching_ratio", DataType::Float64, false),
+        Field::new("hawkes_near_critical", DataType::Boolean, false),
+    ];
+    let garch = |r: &ReportRow| r.result.garch;
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(TimestampMillisecondArray::from(
+            rows.iter().map(|r| r.result.timestamp as i64).collect::<Vec<_>>(),
+        )),
+        f64_column(rows, |r| r.result.volatility),
+        f64_column(rows, |r| r.realized_sigma),
+        f64_column(rows, |r| garch(r).map(|g| g.omega).unwrap_or(f64::NAN)),
+        f64_column(rows, |r| garch(r).map(|g| g.alpha).unwrap_or(f64::NAN)),
+        f64_column(rows, |r| garch(r).map(|g| g.beta).unwrap_or(f64::NAN)),
+        f64_column(rows, |r| r.result.bid_kappa),
+        f64_column(rows, |r| r.result.ask_kappa),
+        f64_column(rows, |r| r.result.bid_a),
+        f64_column(rows, |r| r.result.ask_a),
+        f64_column(rows, |r| r.result.branching_ratios().0),
+        f64_column(rows, |r| r.result.branching_ratios().1),
+        Arc::new(BooleanArray::from(
+            rows.iter().map(|r| r.result.hawkes_near_critical(critical_branching)).collect::<Vec<_>>(),
+        )),
+    ];
+    push_side_columns(rows, "bid", |r| &r.intensity_fit.bid, |d| &d.bid, &mut fields, &mut columns);
+    push_side_columns(rows, "ask", |r| &r.intensity_fit.ask, |d| &d.ask, &mut fields, &mut columns);
+    push_size_bucket_columns(rows, &mut fields, &mut columns);
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::try_new(3)?))
+        .set_key_value_metadata(Some(vec![
+            KeyValue::new("market".to_string(), market.to_string()),
+            KeyValue::new("volatility_estimator".to_string(), estimator.to_string()),
+            KeyValue::new("delta_edges".to_string(), serde_json::to_string(&diagnostic_delta_edges())?),
+        ]))
+        .build();
+
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, Some(props))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut config_path = DEFAULT_CONFIG_PATH.to_string();
+    let mut data_dir = DEFAULT_DATA_DIR.to_string();
+    let mut market = DEFAULT_MARKET.to_string();
+    let mut output_path: Option<String> = None;
+    let mut order_notional = DEFAULT_ORDER_NOTIONAL;
+    let mut start_ms = 0;
+    let mut end_ms = u64::MAX;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--config" => {
+                i += 1;
+                config_path = args.get(i).cloned().unwrap_or_default();
+            }
+            "--data-dir" => {
+                i += 1;
+                data_dir = args.get(i).cloned().unwrap_or_default();
+            }
+            "--market" => {
+                i += 1;
+                market = args.get(i).cloned().unwrap_or_default();
+            }
+            "--output" => {
+                i += 1;
+                output_path = args.get(i).cloned();
+            }
+            "--notional" => {
+                i += 1;
+                order_notional = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_ORDER_NOTIONAL);
+            }
+            "--start" => {
+                i += 1;
+                start_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--end" => {
+                i += 1;
+                end_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--help" | "-h" => {
+                print_usage(&args[0]);
+                return Ok(());
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", args[i]);
+                print_usage(&args[0]);
+                return Err("Invalid arguments".into());
+            }
+        }
+        i += 1;
+    }
+    if start_ms >= end_ms {
+        return Err("--start must be before --end".into());
+    }
+
+    // Load configuration
+    let mut config = match std::fs::read_to_string(&config_path) {
+        Ok(contents) => {
+            match serde_json::from_str::<ASConfig>(&contents) {
+                Ok(cfg) => {
+                    println!("Loaded config from {}", config_path);
+                    cfg
+                }
+                Err(e) => {
+                    eprintln!("Error parsing {}: {}. Using defaults.", config_path, e);
+                    ASConfig::default()
+                }
+            }
+        }
+        Err(_) => {
+            println!("{} not found. Using defaults.", config_path);
+            ASConfig::default()
+        }
+    };
+    // Same clip as the backtest, so size-aware calibration matches what it quotes against
+    config.resolve_clip_notional(order_notional as f64);
+    let market_dir = market_data_dir(Path::new(&data_dir), &market);
+    let output_path = output_path
+        .unwrap_or_else(|| market_dir.join("calibration_report.parquet").to_string_lossy().into_owned());
+    let loader = DataLoader::new(
+        &market_dir.join("trades_parts"),
+        &market_dir.join("orderbook_parts"),
+    )
+    .with_time_range(start_ms, end_ms);
+
+    println!("Replaying {} through the calibration engine...", market);
+    let start = Instant::now();
+    let mut rows = Vec::new();
+    let mut estimator = "";
+    replay_calibrations(loader.stream()?, &config, |engine, result| {
+        let diagnostics = engine.intensity_diagnostics(result.timestamp, &result);
+        rows.push(ReportRow { realized_sigma: engine.realized_volatility(), diagnostics, result });
+        estimator = engine.volatility_estimator_name();
+    })?;
+    println!("Ran {} calibrations in {:.2}s", rows.len(), start.elapsed().as_secs_f64());
+
+    if rows.is_empty() {
+        return Err("No calibrations were produced; not enough data".into());
+    }
+
+    write_report(&rows, estimator, &market, config.hawkes_critical_branching, Path::new(&output_path))?;
+    println!("Wrote calibration report to {}", output_path);
+
+    Ok(())
+}
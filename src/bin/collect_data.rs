@@ -20,6 +20,7 @@
 use extended_data_collector::{
     init_logging, rest::RestClient, OrderbookParquetWriter, TradesParquetWriter, WebSocketClient,
 };
+use extended_data_collector::trading_rules::TradingRulesCache;
 use serde::Deserialize;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
@@ -391,6 +392,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let data_dir = Path::new(&config.data_directory);
     fs::create_dir_all(data_dir)?;
 
+    // Cache exchange tick/size rules for offline spread calculation and backtesting
+    let rules_path = data_dir.join("trading_rules.json");
+    match RestClient::new_mainnet(None) {
+        Ok(client) => match TradingRulesCache::refresh(&client, &config.markets, &rules_path).await {
+            Ok(cache) => println!("✅ Cached trading rules for {} markets in {}", cache.markets.len(), rules_path.display()),
+            Err(e) => eprintln!("⚠️ Failed to cache trading rules: {}", e),
+        },
+        Err(e) => eprintln!("⚠️ Failed to initialize REST client for trading rules: {}", e),
+    }
+
     // Create WebSocket client (no API key needed for public data)
     let ws_client = WebSocketClient::new_mainnet(None);
 
@@ -0,0 +1,163 @@
+//! Estimate cross-market lead-lag betas from collected orderbook data
+//!
+//! Resamples the leader and each follower's mid prices onto a fixed bar grid and fits
+//! the follower's bar returns on lagged leader returns. The resulting models are written
+//! to JSON and can be passed to `backtest --lead-lag`.
+
+use extended_data_collector::data_loader::{market_data_dir, parse_timestamp_arg, DataLoader};
+use extended_data_collector::lead_lag::{estimate_lead_lag, LeadLagModel};
+use std::env;
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+const DEFAULT_DATA_DIR: &str = "data";
+const DEFAULT_LEADER: &str = "BTC-USD";
+const DEFAULT_FOLLOWERS: &str = "ETH-USD,SOL-USD";
+const DEFAULT_BAR_MS: u64 = 1000;
+const DEFAULT_MAX_LAG: usize = 10;
+const DEFAULT_OUTPUT_PATH: &str = "data/lead_lag.json";
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} [OPTIONS]", program);
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --data-dir <path>     Root data directory (default: {})", DEFAULT_DATA_DIR);
+    eprintln!("  --leader <market>     Leader market (default: {})", DEFAULT_LEADER);
+    eprintln!("  --followers <list>    Comma-separated follower markets (default: {})", DEFAULT_FOLLOWERS);
+    eprintln!("  --bar-ms <ms>         Bar size in milliseconds (default: {})", DEFAULT_BAR_MS);
+    eprintln!("  --max-lag <n>         Number of lagged bars to fit (default: {})", DEFAULT_MAX_LAG);
+    eprintln!("  --output <path>       Output JSON path (default: {})", DEFAULT_OUTPUT_PATH);
+    eprintln!("  --start <time>        Only load events at or after this time (epoch ms, YYYY-MM-DD or RFC 3339)");
+    eprintln!("  --end <time>          Only load events before this time");
+    eprintln!("  --help                Show this help message");
+}
+
+fn load_mids(data_dir: &Path, market: &str, start_ms: u64, end_ms: u64) -> Result<Vec<(u64, f64)>, Box<dyn Error>> {
+    let market_dir = market_data_dir(data_dir, market);
+    let loader = DataLoader::new(
+        &market_dir.join("trades_parts"),
+        &market_dir.join("orderbook_parts"),
+    )
+    .with_time_range(start_ms, end_ms);
+
+    let mut mids = Vec::new();
+    for result in loader.mids_iter()? {
+        mids.push(result?);
+    }
+    mids.sort_by_key(|(ts, _)| *ts);
+    Ok(mids)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut data_dir = DEFAULT_DATA_DIR.to_string();
+    let mut leader = DEFAULT_LEADER.to_string();
+    let mut followers = DEFAULT_FOLLOWERS.to_string();
+    let mut bar_ms = DEFAULT_BAR_MS;
+    let mut max_lag = DEFAULT_MAX_LAG;
+    let mut output_path = DEFAULT_OUTPUT_PATH.to_string();
+    let mut start_ms = 0;
+    let mut end_ms = u64::MAX;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--data-dir" => {
+                i += 1;
+                data_dir = args.get(i).cloned().unwrap_or_default();
+            }
+            "--leader" => {
+                i += 1;
+                leader = args.get(i).cloned().unwrap_or_default();
+            }
+            "--followers" => {
+                i += 1;
+                followers = args.get(i).cloned().unwrap_or_default();
+            }
+            "--bar-ms" => {
+                i += 1;
+                bar_ms = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_BAR_MS);
+            }
+            "--max-lag" => {
+                i += 1;
+                max_lag = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_MAX_LAG);
+            }
+            "--output" => {
+                i += 1;
+                output_path = args.get(i).cloned().unwrap_or_default();
+            }
+            "--start" => {
+                i += 1;
+                start_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--end" => {
+                i += 1;
+                end_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--help" | "-h" => {
+                print_usage(&args[0]);
+                return Ok(());
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", args[i]);
+                print_usage(&args[0]);
+                return Err("Invalid arguments".into());
+            }
+        }
+        i += 1;
+    }
+    if start_ms >= end_ms {
+        return Err("--start must be before --end".into());
+    }
+
+    let data_dir = Path::new(&data_dir);
+
+    println!("Loading leader {} mids...", leader);
+    let start = Instant::now();
+    let leader_mids = load_mids(data_dir, &leader, start_ms, end_ms)?;
+    println!("Loaded {} leader mids in {:.2}s", leader_mids.len(), start.elapsed().as_secs_f64());
+
+    let mut models = Vec::new();
+    for follower in followers.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        if follower == leader {
+            continue;
+        }
+
+        println!("Loading follower {} mids...", follower);
+        let follower_mids = match load_mids(data_dir, follower, start_ms, end_ms) {
+            Ok(mids) => mids,
+            Err(e) => {
+                eprintln!("[WARN] Skipping {}: {}", follower, e);
+                continue;
+            }
+        };
+
+        match estimate_lead_lag(&leader, follower, &leader_mids, &follower_mids, bar_ms, max_lag) {
+            Some(model) => {
+                println!("\n{} -> {} ({} bars of {}ms)", leader, follower, model.observations, bar_ms);
+                println!("  lag 0 beta: {:>8.4}", model.beta_contemporaneous);
+                println!("{:>8} | {:>10} | {:>10}", "Lag", "Beta", "Corr");
+                for (lag, (beta, corr)) in model.betas.iter().zip(model.correlations.iter()).enumerate() {
+                    println!("{:>8} | {:>10.4} | {:>10.4}", lag + 1, beta, corr);
+                }
+                models.push(model);
+            }
+            None => eprintln!("[WARN] Not enough overlapping data for {} -> {}", leader, follower),
+        }
+    }
+
+    if models.is_empty() {
+        return Err("No lead-lag models could be estimated".into());
+    }
+
+    LeadLagModel::save_all(&models, Path::new(&output_path))?;
+    println!("\nWrote {} models to {}", models.len(), output_path);
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! Estimate hour-of-week seasonality of fill intensity and volatility
+//!
+//! Scans a market's full collected history and writes per-hour A and σ multipliers to JSON.
+//! Point `seasonality_path` in config.json at the output to let the calibration engine
+//! rescale its rolling estimates to the upcoming interval.
+
+use extended_data_collector::data_loader::{market_data_dir, parse_timestamp_arg, DataLoader};
+use extended_data_collector::seasonality::{SeasonalityEstimator, HOURS_PER_WEEK};
+use std::env;
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+const DEFAULT_DATA_DIR: &str = "data";
+const DEFAULT_MARKET: &str = "SOL-USD";
+const DEFAULT_MAX_GAP_SECONDS: u64 = 60;
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} [OPTIONS]", program);
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --data-dir <path>       Root data directory (default: {})", DEFAULT_DATA_DIR);
+    eprintln!("  --market <name>         Market to analyse (default: {})", DEFAULT_MARKET);
+    eprintln!("  --max-gap <seconds>     Mid gaps longer than this count as missing data (default: {})", DEFAULT_MAX_GAP_SECONDS);
+    eprintln!("  --output <path>         Output JSON path (default: <data-dir>/<market>/seasonality.json)");
+    eprintln!("  --start <time>          Only load events at or after this time (epoch ms, YYYY-MM-DD or RFC 3339)");
+    eprintln!("  --end <time>            Only load events before this time");
+    eprintln!("  --help                  Show this help message");
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut data_dir = DEFAULT_DATA_DIR.to_string();
+    let mut market = DEFAULT_MARKET.to_string();
+    let mut max_gap_seconds = DEFAULT_MAX_GAP_SECONDS;
+    let mut output_path: Option<String> = None;
+    let mut start_ms = 0;
+    let mut end_ms = u64::MAX;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--data-dir" => {
+                i += 1;
+                data_dir = args.get(i).cloned().unwrap_or_default();
+            }
+            "--market" => {
+                i += 1;
+                market = args.get(i).cloned().unwrap_or_default();
+            }
+            "--max-gap" => {
+                i += 1;
+                max_gap_seconds = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_MAX_GAP_SECONDS);
+            }
+            "--output" => {
+                i += 1;
+                output_path = args.get(i).cloned();
+            }
+            "--start" => {
+                i += 1;
+                start_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--end" => {
+                i += 1;
+                end_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--help" | "-h" => {
+                print_usage(&args[0]);
+                return Ok(());
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", args[i]);
+                print_usage(&args[0]);
+                return Err("Invalid arguments".into());
+            }
+        }
+        i += 1;
+    }
+    if start_ms >= end_ms {
+        return Err("--start must be before --end".into());
+    }
+
+    let market_dir = market_data_dir(Path::new(&data_dir), &market);
+    let output_path = output_path
+        .unwrap_or_else(|| market_dir.join("seasonality.json").to_string_lossy().into_owned());
+    let loader = DataLoader::new(
+        &market_dir.join("trades_parts"),
+        &market_dir.join("orderbook_parts"),
+    )
+    .with_time_range(start_ms, end_ms);
+
+    println!("Loading {} history...", market);
+    let start = Instant::now();
+    let mut mids = Vec::new();
+    for result in loader.mids_iter()? {
+        mids.push(result?);
+    }
+    mids.sort_by_key(|(ts, _)| *ts);
+    let trades = loader.get_trades()?;
+    println!("Loaded {} mids and {} trades in {:.2}s", mids.len(), trades.len(), start.elapsed().as_secs_f64());
+
+    let mut estimator = SeasonalityEstimator::new(max_gap_seconds);
+    for (ts, mid) in &mids {
+        estimator.observe_mid(*ts, *mid);
+    }
+    for trade in &trades {
+        estimator.observe_trade(trade.timestamp);
+    }
+    let profile = estimator.finish();
+
+    println!("\n{:>8} | {:>8} | {:>8} | {:>8}", "Hour", "A mult", "σ mult", "Hours");
+    println!("{:-<41}", "");
+    for hour in 0..HOURS_PER_WEEK {
+        println!(
+            "{:>4} {:02} | {:>8.3} | {:>8.3} | {:>8.1}",
+            DAYS[hour / 24],
+            hour % 24,
+            profile.a_multipliers[hour],
+            profile.sigma_multipliers[hour],
+            profile.observed_seconds[hour] / 3600.0,
+        );
+    }
+
+    profile.save(Path::new(&output_path))?;
+    println!("\nWrote seasonality profile to {}", output_path);
+
+    Ok(())
+}
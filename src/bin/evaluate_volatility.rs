@@ -0,0 +1,265 @@
+//! Out-of-sample comparison of the volatility estimators
+//!
+//! Replays each market's mids, and at every recalibration time asks all estimators for a σ
+//! forecast from the calibration window (falling back to realized σ as the engine does).
+//! Forecasts are scored against the realized variance over the next
+//! `inventory_horizon_seconds` with QLIKE and squared error, and tested against a baseline
+//! estimator with Diebold–Mariano. A per-market summary is printed and written to CSV.
+
+use extended_data_collector::calibration::RollingRealizedVariance;
+use extended_data_collector::data_loader::{market_data_dir, parse_timestamp_arg, DataLoader};
+use extended_data_collector::forecast_evaluation::{DieboldMariano, EstimatorScore, ForecastEvaluation, RealizedVarianceAhead};
+use extended_data_collector::model_types::{ASConfig, VolatilityEstimatorKind};
+use extended_data_collector::volatility::{build_estimator, VolatilityInputs};
+use rust_decimal::prelude::*;
+use std::collections::VecDeque;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+const DEFAULT_CONFIG_PATH: &str = "config.json";
+const DEFAULT_DATA_DIR: &str = "data";
+const DEFAULT_MARKETS: &str = "ETH-USD,SOL-USD";
+const DEFAULT_BASELINE: &str = "realized";
+const DEFAULT_MAX_GAP_SECONDS: u64 = 60;
+const DEFAULT_OUTPUT_PATH: &str = "data/volatility_evaluation.csv";
+
+fn print_usage(program: &str) {
+    eprintln!("Usage: {} [OPTIONS]", program);
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --config <path>       Path to config file (default: {})", DEFAULT_CONFIG_PATH);
+    eprintln!("  --data-dir <path>     Root data directory (default: {})", DEFAULT_DATA_DIR);
+    eprintln!("  --markets <list>      Comma-separated markets (default: {})", DEFAULT_MARKETS);
+    eprintln!("  --baseline <name>     Estimator the others are tested against (default: {})", DEFAULT_BASELINE);
+    eprintln!("  --max-gap <seconds>   Skip horizons containing a mid gap longer than this (default: {})", DEFAULT_MAX_GAP_SECONDS);
+    eprintln!("  --output <path>       Summary CSV path (default: {})", DEFAULT_OUTPUT_PATH);
+    eprintln!("  --start <time>        Only load events at or after this time (epoch ms, YYYY-MM-DD or RFC 3339)");
+    eprintln!("  --end <time>          Only load events before this time");
+    eprintln!("  --help                Show this help message");
+}
+
+fn load_mids(data_dir: &Path, market: &str, start_ms: u64, end_ms: u64) -> Result<Vec<(u64, f64)>, Box<dyn Error>> {
+    let market_dir = market_data_dir(data_dir, market);
+    let loader = DataLoader::new(
+        &market_dir.join("trades_parts"),
+        &market_dir.join("orderbook_parts"),
+    )
+    .with_time_range(start_ms, end_ms);
+
+    let mut mids = Vec::new();
+    for result in loader.mids_iter()? {
+        mids.push(result?);
+    }
+    mids.sort_by_key(|(ts, _)| *ts);
+    Ok(mids)
+}
+
+/// Replay `mids` and collect every estimator's forecast at each recalibration time
+fn evaluate_market(mids: &[(u64, f64)], config: &ASConfig, max_gap_ms: u64) -> ForecastEvaluation {
+    let mut estimators: Vec<_> = VolatilityEstimatorKind::ALL
+        .iter()
+        .map(|kind| build_estimator(&ASConfig { volatility_estimator: kind.clone(), ..config.clone() }))
+        .collect();
+    let mut evaluation = ForecastEvaluation::new(estimators.iter().map(|e| e.name().to_string()).collect());
+
+    let ahead = RealizedVarianceAhead::new(mids);
+    let window_ms = config.calibration_window_seconds.saturating_mul(1000);
+    let horizon_ms = config.inventory_horizon_seconds.saturating_mul(1000);
+    let interval_ms = config.recalibration_interval_seconds.max(1).saturating_mul(1000);
+
+    let mut window: VecDeque<(u64, Decimal)> = VecDeque::new();
+    let mut realized = RollingRealizedVariance::new();
+    let first_ts = mids.first().map(|(ts, _)| *ts).unwrap_or(0);
+    let mut next_evaluation = first_ts.saturating_add(window_ms);
+
+    for &(ts, mid) in mids {
+        let Some(price) = Decimal::from_f64(mid) else {
+            continue;
+        };
+        window.push_back((ts, price));
+        realized.push(ts, price);
+        for estimator in estimators.iter_mut() {
+            estimator.observe(ts, price);
+        }
+        while window.front().is_some_and(|(t, _)| ts.saturating_sub(*t) > window_ms) {
+            window.pop_front();
+        }
+        realized.prune(ts, window_ms);
+
+        if ts < next_evaluation {
+            continue;
+        }
+        next_evaluation = ts.saturating_add(interval_ms);
+
+        let Some(realized_ahead) = ahead.variance(ts, horizon_ms, max_gap_ms) else {
+            continue;
+        };
+        let realized_sigma = realized.sigma();
+        let inputs = VolatilityInputs { window: window.make_contiguous(), realized: Some(realized_sigma) };
+        let sigmas: Vec<Option<f64>> = estimators.iter_mut().map(|e| e.estimate(&inputs)).collect();
+        evaluation.record(&sigmas, realized_sigma, realized_ahead);
+    }
+
+    evaluation
+}
+
+fn format_dm(test: &Option<DieboldMariano>) -> String {
+    match test {
+        Some(dm) => format!("{:>7.2} ({:.3})", dm.statistic, dm.p_value),
+        None => format!("{:>15}", "-"),
+    }
+}
+
+fn print_summary(market: &str, points: usize, scores: &[EstimatorScore], baseline: &str) {
+    println!("\n{} ({} evaluation points, DM vs {}; negative statistic = better than baseline)", market,
+        points, baseline);
+    println!("{:<14} | {:>7} | {:>9} | {:>10} | {:>12} | {:>15} | {:>15}",
+        "Estimator", "Obs", "Fallbacks", "QLIKE", "MSE", "DM QLIKE (p)", "DM MSE (p)");
+    println!("{:-<100}", "");
+    for score in scores {
+        println!("{:<14} | {:>7} | {:>9} | {:>10.5} | {:>12.4e} | {} | {}",
+            score.name, score.observations, score.fallbacks, score.mean_qlike, score.mean_squared_error,
+            format_dm(&score.dm_qlike), format_dm(&score.dm_squared_error));
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut config_path = DEFAULT_CONFIG_PATH.to_string();
+    let mut data_dir = DEFAULT_DATA_DIR.to_string();
+    let mut markets = DEFAULT_MARKETS.to_string();
+    let mut baseline = DEFAULT_BASELINE.to_string();
+    let mut max_gap_seconds = DEFAULT_MAX_GAP_SECONDS;
+    let mut output_path = DEFAULT_OUTPUT_PATH.to_string();
+    let mut start_ms = 0;
+    let mut end_ms = u64::MAX;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--config" => {
+                i += 1;
+                config_path = args.get(i).cloned().unwrap_or_default();
+            }
+            "--data-dir" => {
+                i += 1;
+                data_dir = args.get(i).cloned().unwrap_or_default();
+            }
+            "--markets" => {
+                i += 1;
+                markets = args.get(i).cloned().unwrap_or_default();
+            }
+            "--baseline" => {
+                i += 1;
+                baseline = args.get(i).cloned().unwrap_or_default();
+            }
+            "--max-gap" => {
+                i += 1;
+                max_gap_seconds = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_MAX_GAP_SECONDS);
+            }
+            "--output" => {
+                i += 1;
+                output_path = args.get(i).cloned().unwrap_or_default();
+            }
+            "--start" => {
+                i += 1;
+                start_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--end" => {
+                i += 1;
+                end_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--help" | "-h" => {
+                print_usage(&args[0]);
+                return Ok(());
+            }
+            _ => {
+                eprintln!("Unknown argument: {}", args[i]);
+                print_usage(&args[0]);
+                return Err("Invalid arguments".into());
+            }
+        }
+        i += 1;
+    }
+    if start_ms >= end_ms {
+        return Err("--start must be before --end".into());
+    }
+
+    // Load configuration
+    let config = match std::fs::read_to_string(&config_path) {
+        Ok(contents) => {
+            match serde_json::from_str::<ASConfig>(&contents) {
+                Ok(cfg) => {
+                    println!("Loaded config from {}", config_path);
+                    cfg
+                }
+                Err(e) => {
+                    eprintln!("Error parsing {}: {}. Using defaults.", config_path, e);
+                    ASConfig::default()
+                }
+            }
+        }
+        Err(_) => {
+            println!("{} not found. Using defaults.", config_path);
+            ASConfig::default()
+        }
+    };
+    let estimator_names: Vec<String> = VolatilityEstimatorKind::ALL
+        .iter()
+        .map(|kind| build_estimator(&ASConfig { volatility_estimator: kind.clone(), ..config.clone() }).name().to_string())
+        .collect();
+    if !estimator_names.contains(&baseline) {
+        return Err(format!("Unknown --baseline {} (expected one of: {})", baseline, estimator_names.join(", ")).into());
+    }
+
+    let horizon = config.inventory_horizon_seconds;
+    let interval = config.recalibration_interval_seconds.max(1);
+    // Horizons overlap when they are longer than the evaluation interval
+    let lag = (horizon.div_ceil(interval) as usize).saturating_sub(1);
+    println!("Horizon {}s, evaluated every {}s, window {}s", horizon, interval, config.calibration_window_seconds);
+
+    let mut output = BufWriter::new(File::create(&output_path)?);
+    writeln!(output, "market,estimator,observations,fallbacks,mean_qlike,mean_mse,dm_qlike_stat,dm_qlike_p,dm_mse_stat,dm_mse_p")?;
+
+    for market in markets.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let start = Instant::now();
+        let mids = match load_mids(Path::new(&data_dir), market, start_ms, end_ms) {
+            Ok(mids) => mids,
+            Err(e) => {
+                eprintln!("[WARN] Skipping {}: {}", market, e);
+                continue;
+            }
+        };
+        let evaluation = evaluate_market(&mids, &config, max_gap_seconds.saturating_mul(1000));
+        println!("{}: {} mids, {} evaluation points in {:.2}s", market, mids.len(), evaluation.len(),
+            start.elapsed().as_secs_f64());
+        if evaluation.is_empty() {
+            eprintln!("[WARN] Not enough data to evaluate {}", market);
+            continue;
+        }
+
+        let scores = evaluation.summarize(&baseline, lag);
+        print_summary(market, evaluation.len(), &scores, &baseline);
+        for score in &scores {
+            let dm = |test: &Option<DieboldMariano>| match test {
+                Some(dm) => (dm.statistic, dm.p_value),
+                None => (f64::NAN, f64::NAN),
+            };
+            let (q_stat, q_p) = dm(&score.dm_qlike);
+            let (m_stat, m_p) = dm(&score.dm_squared_error);
+            writeln!(output, "{},{},{},{},{},{},{},{},{},{}", market, score.name, score.observations,
+                score.fallbacks, score.mean_qlike, score.mean_squared_error, q_stat, q_p, m_stat, m_p)?;
+        }
+    }
+
+    output.flush()?;
+    println!("\nWrote summary to {}", output_path);
+    Ok(())
+}
@@ -3,16 +3,21 @@
 /// Tests multiple inventory_horizon_seconds values to find the optimal horizon
 /// for the given market conditions and parameters.
 
-use extended_data_collector::backtest_engine::{run_backtest, BacktestParams, BacktestResults};
-use extended_data_collector::data_loader::{DataLoader, DataEvent};
+use extended_data_collector::backtest_engine::{
+    run_backtest_multi, run_backtest_multi_parallel, BacktestResults, MultiBacktestParams,
+};
+use extended_data_collector::calibration_cache::load_or_precompute;
+use extended_data_collector::data_loader::{parse_timestamp_arg, DataLoader};
+use extended_data_collector::lead_lag::{LeadLagModel, LeadLagReference};
 use extended_data_collector::model_types::ASConfig;
-use rayon::prelude::*;
+use extended_data_collector::parquet_prefetch::DEFAULT_PREFETCH_DEPTH;
+use extended_data_collector::trading_rules::{apply_cached_rules, DEFAULT_TRADING_RULES_PATH};
 use rust_decimal::Decimal;
 use std::env;
 use std::fs::File;
-use std::io::{self, Write};
+use std::io::Write;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::time::Instant;
 
 // =============================================================================
@@ -23,6 +28,8 @@ const DEFAULT_CONFIG_PATH: &str = "config.json";
 const DEFAULT_TRADES_PATH: &str = "data/eth_usd/trades_parts";
 const DEFAULT_ORDERBOOK_PATH: &str = "data/eth_usd/orderbook_parts";
 const DEFAULT_OUTPUT_PATH: &str = "data/eth_usd/grid_search_results.csv";
+const DEFAULT_MARKET: &str = "ETH-USD";
+const DEFAULT_DATA_DIR: &str = "data";
 
 const DEFAULT_INITIAL_CAPITAL: i64 = 1000;
 const DEFAULT_ORDER_NOTIONAL: i64 = 20;
@@ -65,7 +72,19 @@ fn print_usage(program: &str) {
     eprintln!("  --horizons <list>     Comma-separated list of horizons in seconds");
     eprintln!("                        (default: {})", DEFAULT_HORIZONS.iter()
         .map(|h| h.to_string()).collect::<Vec<_>>().join(","));
-    eprintln!("  --parallel            Run backtests in parallel");
+    eprintln!("  --parallel            Split the horizons across threads (data held in memory)");
+    eprintln!("  --market <name>       Market name for trading rules and lead-lag lookup (default: {})", DEFAULT_MARKET);
+    eprintln!("  --lead-lag <path>     Lead-lag model JSON from estimate_lead_lag (enables leader reference pricing)");
+    eprintln!("  --data-dir <path>     Root data directory holding the leader market (default: {})", DEFAULT_DATA_DIR);
+    eprintln!("  --leader-orderbook <path>");
+    eprintln!("                        Leader orderbook directory (default: <data-dir>/<leader>/orderbook_parts)");
+    eprintln!("  --rules <path>        Trading rules cache written by collect_data (default: {})", DEFAULT_TRADING_RULES_PATH);
+    eprintln!("  --calibration-cache <dir>");
+    eprintln!("                        Reuse/store the calibration series in this directory");
+    eprintln!("  --prefetch <n>        Parquet part files decoded ahead on worker threads, 0 to disable (default: {})", DEFAULT_PREFETCH_DEPTH);
+    eprintln!("  --no-event-cache      Read the source files even when an up-to-date event cache exists");
+    eprintln!("  --start <time>        Only load events at or after this time (epoch ms, YYYY-MM-DD or RFC 3339)");
+    eprintln!("  --end <time>          Only load events before this time");
     eprintln!("  --help                Show this help message");
 }
 
@@ -91,11 +110,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut trades_path = DEFAULT_TRADES_PATH.to_string();
     let mut orderbook_path = DEFAULT_ORDERBOOK_PATH.to_string();
     let mut output_path = DEFAULT_OUTPUT_PATH.to_string();
+    let mut market = DEFAULT_MARKET.to_string();
+    let mut rules_path = DEFAULT_TRADING_RULES_PATH.to_string();
     let mut initial_capital = DEFAULT_INITIAL_CAPITAL;
     let mut order_notional = DEFAULT_ORDER_NOTIONAL;
     let mut min_fills = DEFAULT_MIN_FILLS;
     let mut horizons: Vec<u64> = DEFAULT_HORIZONS.to_vec();
     let mut parallel = false;
+    let mut lead_lag_path: Option<String> = None;
+    let mut leader_orderbook_path: Option<String> = None;
+    let mut data_dir = DEFAULT_DATA_DIR.to_string();
+    let mut calibration_cache: Option<String> = None;
+    let mut prefetch_depth = DEFAULT_PREFETCH_DEPTH;
+    let mut use_event_cache = true;
+    let mut start_ms = 0;
+    let mut end_ms = u64::MAX;
 
     let mut i = 1;
     while i < args.len() {
@@ -143,6 +172,47 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "--parallel" => {
                 parallel = true;
             }
+            "--market" => {
+                i += 1;
+                market = args.get(i).cloned().unwrap_or_default();
+            }
+            "--lead-lag" => {
+                i += 1;
+                lead_lag_path = args.get(i).cloned();
+            }
+            "--data-dir" => {
+                i += 1;
+                data_dir = args.get(i).cloned().unwrap_or_default();
+            }
+            "--leader-orderbook" => {
+                i += 1;
+                leader_orderbook_path = args.get(i).cloned();
+            }
+            "--rules" => {
+                i += 1;
+                rules_path = args.get(i).cloned().unwrap_or_default();
+            }
+            "--calibration-cache" => {
+                i += 1;
+                calibration_cache = args.get(i).cloned();
+            }
+            "--prefetch" => {
+                i += 1;
+                prefetch_depth = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_PREFETCH_DEPTH);
+            }
+            "--no-event-cache" => {
+                use_event_cache = false;
+            }
+            "--start" => {
+                i += 1;
+                start_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--end" => {
+                i += 1;
+                end_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
             "--help" | "-h" => {
                 print_usage(&args[0]);
                 return Ok(());
@@ -155,13 +225,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         i += 1;
     }
+    if start_ms >= end_ms {
+        return Err("--start must be before --end".into());
+    }
 
     println!("============================================================");
     println!("       AS Market-Making Strategy - Grid Search");
     println!("============================================================\n");
 
     // Load configuration (as base config)
-    let base_config = match std::fs::read_to_string(&config_path) {
+    let mut base_config = match std::fs::read_to_string(&config_path) {
         Ok(contents) => {
             match serde_json::from_str::<ASConfig>(&contents) {
                 Ok(cfg) => {
@@ -180,18 +253,55 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    // Exchange tick/size rules cached by collect_data
+    match apply_cached_rules(&mut base_config, Path::new(&rules_path), &market) {
+        Some(rules) => println!("[OK] Trading rules for {}: tick={}, min_size={}, size_step={}",
+            market, rules.tick_size, rules.min_order_size, rules.order_size_step),
+        None => println!("[WARN] No trading rules for {} in {}. Using tick_size={}",
+            market, rules_path, base_config.tick_size),
+    }
+    // Size-aware calibration measures fills against the clip actually quoted
+    base_config.resolve_clip_notional(order_notional as f64);
+
     // Load data loader
     println!("Initializing data loader...");
-    let loader = DataLoader::new(
+    let mut loader = DataLoader::new(
         Path::new(&trades_path),
         Path::new(&orderbook_path),
-    );
+    )
+    .with_time_range(start_ms, end_ms)
+    .with_prefetch(prefetch_depth);
+    if !use_event_cache {
+        loader = loader.with_event_cache(None);
+    }
+
+    // Calibration does not depend on the horizon: every run shares one calibration
+    let calibrations = match &calibration_cache {
+        Some(dir) => {
+            let start_calibration = Instant::now();
+            let series = load_or_precompute(Path::new(dir), &base_config, &loader)?;
+            println!("Prepared {} calibrations in {:.2}s", series.len(), start_calibration.elapsed().as_secs_f64());
+            Some(Arc::new(series))
+        }
+        None => None,
+    };
 
-    println!("Loading all data into memory (raw order, no sorting/dedup) for performance...");
-    let start_load = Instant::now();
-    let events = loader.load_events_raw()?;
-    let duration = start_load.elapsed();
-    println!("Loaded {} events in {:.2}s", events.len(), duration.as_secs_f64());
+    // Optional leader reference for cross-market fair value, shared by every configuration
+    let lead_lag = match &lead_lag_path {
+        Some(path) => {
+            let model = LeadLagModel::load_for_follower(Path::new(path), &market)?;
+            let leader_loader = model
+                .leader_loader(Path::new(&data_dir), leader_orderbook_path.as_deref().map(Path::new))
+                .with_time_range(start_ms, end_ms)
+                .with_prefetch(prefetch_depth);
+            println!(
+                "[OK] Lead-lag: {} leads {} (bar={}ms, betas={:?})",
+                model.leader, model.follower, model.bar_ms, model.betas
+            );
+            Some((model, leader_loader))
+        }
+        None => None,
+    };
 
     println!("Testing {} time horizons...", horizons.len());
     println!(
@@ -206,38 +316,57 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Backtest parameters
     let initial_capital_dec = Decimal::from(initial_capital);
     let order_notional_dec = Decimal::from(order_notional);
+    let configs: Vec<ASConfig> = horizons
+        .iter()
+        .map(|&horizon| ASConfig { inventory_horizon_seconds: horizon, ..base_config.clone() })
+        .collect();
 
     // Run backtests
-    let results: Vec<(u64, Result<BacktestResults, String>)> = if parallel {
-        println!("Running in parallel mode...\n");
-        run_parallel_backtests(
-            &horizons,
-            &base_config,
-            &events,
-            initial_capital_dec,
-            order_notional_dec,
-        )
-    } else {
-        run_sequential_backtests(
-            &horizons,
-            &base_config,
+    let start_run = Instant::now();
+    let results = if parallel {
+        println!("Running in parallel mode...");
+        println!("Loading all data into memory (raw order, no sorting/dedup) to share across threads...");
+        let events = loader.load_events_raw()?;
+        let leader = match &lead_lag {
+            Some((model, leader_loader)) => Some((model, leader_loader.mids_iter()?.collect::<Result<Vec<_>, _>>()?)),
+            None => None,
+        };
+        println!("Loaded {} events in {:.2}s\n", events.len(), start_run.elapsed().as_secs_f64());
+        run_backtest_multi_parallel(
             &events,
+            configs,
+            rayon::current_num_threads(),
             initial_capital_dec,
             order_notional_dec,
+            leader.as_ref().map(|(model, mids)| (*model, mids.as_slice())),
+            calibrations,
         )?
+    } else {
+        println!("Running all horizons in lockstep over one pass of the data...\n");
+        run_backtest_multi(MultiBacktestParams {
+            data_stream: loader.stream()?,
+            configs,
+            initial_capital: initial_capital_dec,
+            order_notional: order_notional_dec,
+            lead_lag: match &lead_lag {
+                Some((model, leader_loader)) => Some(LeadLagReference::new(model, leader_loader.mids_iter()?)),
+                None => None,
+            },
+            calibrations,
+        })?
     };
+    println!("Completed {} backtests in {:.2}s", results.len(), start_run.elapsed().as_secs_f64());
 
-    // Collect successful results
-    let successful_results: Vec<(u64, BacktestResults)> = results
-        .into_iter()
-        .filter_map(|(h, r)| match r {
-            Ok(result) => Some((h, result)),
-            Err(e) => {
-                eprintln!("[ERROR] Horizon {}: {}", format_duration(h), e);
-                None
-            }
-        })
-        .collect();
+    let successful_results: Vec<(u64, BacktestResults)> = horizons.iter().copied().zip(results).collect();
+    for (horizon, result) in &successful_results {
+        println!(
+            "Horizon {:<6} PnL: ${:.2}, Fills: {}, Vol: ${:.2}",
+            format_duration(*horizon),
+            result.final_pnl,
+            result.total_fills(),
+            result.total_notional_volume
+        );
+    }
 
     // Print and save results
     print_results_summary(&successful_results, min_fills);
@@ -248,112 +377,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-// =============================================================================
-// Backtest Runners
-// =============================================================================
-
-fn run_sequential_backtests(
-    horizons: &[u64],
-    base_config: &ASConfig,
-    events: &[DataEvent],
-    initial_capital: Decimal,
-    order_notional: Decimal,
-) -> io::Result<Vec<(u64, Result<BacktestResults, String>)>> {
-    let mut results = Vec::with_capacity(horizons.len());
-
-    for (i, &horizon) in horizons.iter().enumerate() {
-        print!(
-            "[{}/{}] Testing horizon={}... ",
-            i + 1,
-            horizons.len(),
-            format_duration(horizon)
-        );
-        io::stdout().flush()?;
-
-        let mut config = base_config.clone();
-        config.inventory_horizon_seconds = horizon;
-
-        let data_stream = events.iter().cloned().map(Ok);
-        let params = BacktestParams {
-            data_stream,
-            config,
-            initial_capital,
-            order_notional,
-            output_csv_path: None,
-            verbose: false,
-        };
-
-        let result = match run_backtest(params) {
-            Ok(result) => {
-                println!(
-                    "[OK] PnL: ${:.2}, Fills: {}, Vol: ${:.2}",
-                    result.final_pnl,
-                    result.total_fills(),
-                    result.total_notional_volume
-                );
-                Ok(result)
-            }
-            Err(e) => {
-                println!("[FAIL] Error: {}", e);
-                Err(e.to_string())
-            }
-        };
-
-        results.push((horizon, result));
-    }
-
-    Ok(results)
-}
-
-fn run_parallel_backtests(
-    horizons: &[u64],
-    base_config: &ASConfig,
-    events: &[DataEvent],
-    initial_capital: Decimal,
-    order_notional: Decimal,
-) -> Vec<(u64, Result<BacktestResults, String>)> {
-    let progress = Arc::new(Mutex::new(0usize));
-    let total = horizons.len();
-
-    // Note: We don't clone the entire events vector, just the slice reference is passed
-    // But par_iter works on horizons. Inside the closure, we need to iterate over events.
-    // events is a &Vec<DataEvent>. We can read it from multiple threads safely.
-
-    horizons
-        .par_iter()
-        .map(|&horizon| {
-            let mut config = base_config.clone();
-            config.inventory_horizon_seconds = horizon;
-
-            let data_stream = events.iter().cloned().map(Ok);
-            let params = BacktestParams {
-                data_stream,
-                config,
-                initial_capital,
-                order_notional,
-                output_csv_path: None,
-                verbose: false,
-            };
-
-            let result = run_backtest(params).map_err(|e| e.to_string());
-
-            // Update progress
-            let mut prog = progress.lock().unwrap();
-            *prog += 1;
-            let status = if result.is_ok() { "OK" } else { "FAIL" };
-            println!(
-                "[{}/{}] Horizon {} - {}",
-                *prog,
-                total,
-                format_duration(horizon),
-                status
-            );
-
-            (horizon, result)
-        })
-        .collect()
-}
-
 // =============================================================================
 // Results Display
 // =============================================================================
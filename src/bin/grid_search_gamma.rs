@@ -9,16 +9,21 @@
 // - 0.10: Moderate risk aversion (typical default)
 // - 0.20: High risk aversion, strong inventory penalty
 
-use extended_data_collector::backtest_engine::{run_backtest, BacktestParams, BacktestResults};
-use extended_data_collector::data_loader::DataLoader;
+use extended_data_collector::backtest_engine::{
+    run_backtest_multi, run_backtest_multi_parallel, BacktestResults, MultiBacktestParams,
+};
+use extended_data_collector::calibration_cache::load_or_precompute;
+use extended_data_collector::data_loader::{parse_timestamp_arg, DataLoader};
+use extended_data_collector::lead_lag::{LeadLagModel, LeadLagReference};
 use extended_data_collector::model_types::ASConfig;
-use rayon::prelude::*;
+use extended_data_collector::parquet_prefetch::DEFAULT_PREFETCH_DEPTH;
+use extended_data_collector::trading_rules::{apply_cached_rules, DEFAULT_TRADING_RULES_PATH};
 use rust_decimal::Decimal;
 use std::env;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::time::Instant;
 
 // =============================================================================
@@ -29,6 +34,8 @@ const DEFAULT_CONFIG_PATH: &str = "config.json";
 const DEFAULT_TRADES_PATH: &str = "data/eth_usd/trades_parts";
 const DEFAULT_ORDERBOOK_PATH: &str = "data/eth_usd/orderbook_parts";
 const DEFAULT_OUTPUT_PATH: &str = "data/eth_usd/grid_search_2d_results.csv";
+const DEFAULT_MARKET: &str = "ETH-USD";
+const DEFAULT_DATA_DIR: &str = "data";
 
 const DEFAULT_INITIAL_CAPITAL: i64 = 1000;
 const DEFAULT_ORDER_NOTIONAL: i64 = 20;
@@ -91,6 +98,18 @@ fn print_usage(program: &str) {
     eprintln!("  --horizons <list>     Comma-separated horizons in seconds");
     eprintln!("  --gammas <list>       Comma-separated gamma values");
     eprintln!("  --threads <n>         Number of parallel threads (default: from config)");
+    eprintln!("  --market <name>       Market name for trading rules and lead-lag lookup (default: {})", DEFAULT_MARKET);
+    eprintln!("  --lead-lag <path>     Lead-lag model JSON from estimate_lead_lag (enables leader reference pricing)");
+    eprintln!("  --data-dir <path>     Root data directory holding the leader market (default: {})", DEFAULT_DATA_DIR);
+    eprintln!("  --leader-orderbook <path>");
+    eprintln!("                        Leader orderbook directory (default: <data-dir>/<leader>/orderbook_parts)");
+    eprintln!("  --rules <path>        Trading rules cache written by collect_data (default: {})", DEFAULT_TRADING_RULES_PATH);
+    eprintln!("  --calibration-cache <dir>");
+    eprintln!("                        Reuse/store the calibration series in this directory");
+    eprintln!("  --prefetch <n>        Parquet part files decoded ahead on worker threads, 0 to disable (default: {})", DEFAULT_PREFETCH_DEPTH);
+    eprintln!("  --no-event-cache      Read the source files even when an up-to-date event cache exists");
+    eprintln!("  --start <time>        Only load events at or after this time (epoch ms, YYYY-MM-DD or RFC 3339)");
+    eprintln!("  --end <time>          Only load events before this time");
     eprintln!("  --help                Show this help message");
 }
 
@@ -118,12 +137,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut trades_path = DEFAULT_TRADES_PATH.to_string();
     let mut orderbook_path = DEFAULT_ORDERBOOK_PATH.to_string();
     let mut output_path = DEFAULT_OUTPUT_PATH.to_string();
+    let mut market = DEFAULT_MARKET.to_string();
+    let mut rules_path = DEFAULT_TRADING_RULES_PATH.to_string();
     let mut initial_capital = DEFAULT_INITIAL_CAPITAL;
     let mut order_notional = DEFAULT_ORDER_NOTIONAL;
     let mut min_fills = DEFAULT_MIN_FILLS;
     let mut horizons: Vec<u64> = DEFAULT_HORIZONS.to_vec();
     let mut gammas: Vec<f64> = Vec::new();
     let mut num_threads: Option<usize> = None;
+    let mut lead_lag_path: Option<String> = None;
+    let mut leader_orderbook_path: Option<String> = None;
+    let mut data_dir = DEFAULT_DATA_DIR.to_string();
+    let mut calibration_cache: Option<String> = None;
+    let mut prefetch_depth = DEFAULT_PREFETCH_DEPTH;
+    let mut use_event_cache = true;
+    let mut start_ms = 0;
+    let mut end_ms = u64::MAX;
 
     let mut i = 1;
     while i < args.len() {
@@ -178,6 +207,47 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 i += 1;
                 num_threads = args.get(i).and_then(|s| s.parse().ok());
             }
+            "--market" => {
+                i += 1;
+                market = args.get(i).cloned().unwrap_or_default();
+            }
+            "--lead-lag" => {
+                i += 1;
+                lead_lag_path = args.get(i).cloned();
+            }
+            "--data-dir" => {
+                i += 1;
+                data_dir = args.get(i).cloned().unwrap_or_default();
+            }
+            "--leader-orderbook" => {
+                i += 1;
+                leader_orderbook_path = args.get(i).cloned();
+            }
+            "--rules" => {
+                i += 1;
+                rules_path = args.get(i).cloned().unwrap_or_default();
+            }
+            "--calibration-cache" => {
+                i += 1;
+                calibration_cache = args.get(i).cloned();
+            }
+            "--prefetch" => {
+                i += 1;
+                prefetch_depth = args.get(i)
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(DEFAULT_PREFETCH_DEPTH);
+            }
+            "--no-event-cache" => {
+                use_event_cache = false;
+            }
+            "--start" => {
+                i += 1;
+                start_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
+            "--end" => {
+                i += 1;
+                end_ms = parse_timestamp_arg(args.get(i).map(String::as_str).unwrap_or_default())?;
+            }
             "--help" | "-h" => {
                 print_usage(&args[0]);
                 return Ok(());
@@ -190,6 +260,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         i += 1;
     }
+    if start_ms >= end_ms {
+        return Err("--start must be before --end".into());
+    }
 
     println!("============================================================");
     println!("    AS Market-Making Strategy - Parallel 2D Grid Search");
@@ -197,7 +270,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("============================================================\n");
 
     // Load configuration (as base config)
-    let base_config = match std::fs::read_to_string(&config_path) {
+    let mut base_config = match std::fs::read_to_string(&config_path) {
         Ok(contents) => {
             match serde_json::from_str::<ASConfig>(&contents) {
                 Ok(cfg) => {
@@ -216,6 +289,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    // Exchange tick/size rules cached by collect_data
+    match apply_cached_rules(&mut base_config, Path::new(&rules_path), &market) {
+        Some(rules) => println!("[OK] Trading rules for {}: tick={}, min_size={}, size_step={}",
+            market, rules.tick_size, rules.min_order_size, rules.order_size_step),
+        None => println!("[WARN] No trading rules for {} in {}. Using tick_size={}",
+            market, rules_path, base_config.tick_size),
+    }
+    // Size-aware calibration measures fills against the clip actually quoted
+    base_config.resolve_clip_notional(order_notional as f64);
+
     // If gammas not provided via CLI, prefer config gamma bounds; otherwise fallback defaults
     if gammas.is_empty() {
         if base_config.gamma_max > base_config.gamma_min && base_config.gamma_max > 0.0 {
@@ -283,109 +366,108 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         eprintln!("[WARN] Could not configure thread pool: {}. Using default.", e);
     }
 
-    println!("Starting parallel execution...\n");
+    // Calibration does not depend on gamma or the horizon: every run shares one calibration
+    let mut loader = DataLoader::new(Path::new(&trades_path), Path::new(&orderbook_path))
+        .with_time_range(start_ms, end_ms)
+        .with_prefetch(prefetch_depth);
+    if !use_event_cache {
+        loader = loader.with_event_cache(None);
+    }
+    let calibrations = match &calibration_cache {
+        Some(dir) => {
+            let start_calibration = Instant::now();
+            let series = load_or_precompute(Path::new(dir), &base_config, &loader)?;
+            println!("Prepared {} calibrations in {:.2}s", series.len(), start_calibration.elapsed().as_secs_f64());
+            Some(Arc::new(series))
+        }
+        None => None,
+    };
+
+    // Optional leader reference for cross-market fair value, shared by every configuration
+    let lead_lag = match &lead_lag_path {
+        Some(path) => {
+            let model = LeadLagModel::load_for_follower(Path::new(path), &market)?;
+            let leader_loader = model
+                .leader_loader(Path::new(&data_dir), leader_orderbook_path.as_deref().map(Path::new))
+                .with_time_range(start_ms, end_ms)
+                .with_prefetch(prefetch_depth);
+            println!(
+                "[OK] Lead-lag: {} leads {} (bar={}ms, betas={:?})",
+                model.leader, model.follower, model.bar_ms, model.betas
+            );
+            Some((model, leader_loader))
+        }
+        None => None,
+    };
 
     // Backtest parameters
     let initial_capital_dec = Decimal::from(initial_capital);
     let order_notional_dec = Decimal::from(order_notional);
+    let backtest_configs: Vec<ASConfig> = configs
+        .iter()
+        .map(|grid_config| {
+            let mut config = base_config.clone();
+            config.inventory_horizon_seconds = grid_config.horizon;
+            config.risk_aversion_gamma = grid_config.gamma;
+            // Ensure gamma is applied directly without scaling/clamping in the quote model
+            config.gamma_mode = extended_data_collector::model_types::GammaMode::Constant;
+            config.gamma_min = grid_config.gamma;
+            config.gamma_max = grid_config.gamma;
+            config
+        })
+        .collect();
 
-    // Shared state for results and progress
-    let results: Arc<Mutex<Vec<GridResult>>> = Arc::new(Mutex::new(Vec::with_capacity(total_runs)));
-    let completed_count = Arc::new(Mutex::new(0usize));
     let start_time = Instant::now();
-
-    // Clone paths for use in parallel closure
-    let trades_path = Arc::new(trades_path);
-    let orderbook_path = Arc::new(orderbook_path);
-
-    // Run backtests in parallel
-    configs.par_iter().for_each(|grid_config| {
-        let mut config = base_config.clone();
-        config.inventory_horizon_seconds = grid_config.horizon;
-        config.risk_aversion_gamma = grid_config.gamma;
-        // Ensure gamma is applied directly without scaling/clamping in the quote model
-        config.gamma_mode = extended_data_collector::model_types::GammaMode::Constant;
-        config.gamma_min = grid_config.gamma;
-        config.gamma_max = grid_config.gamma;
-
-        // Each thread needs its own data loader
-        let loader = DataLoader::new(
-            Path::new(trades_path.as_str()),
-            Path::new(orderbook_path.as_str()),
-        );
-
-        let data_stream = match loader.stream() {
-            Ok(stream) => stream,
-            Err(e) => {
-                eprintln!(
-                    "[FAIL] H={}, g={:.2} - Failed to load data: {}",
-                    format_duration(grid_config.horizon),
-                    grid_config.gamma,
-                    e
-                );
-                return;
-            }
+    let results = if num_threads > 1 {
+        // One lockstep group per thread over events decoded once and held in memory
+        println!("Loading all data into memory (raw order, no sorting/dedup) to share across threads...");
+        let events = loader.load_events_raw()?;
+        let leader = match &lead_lag {
+            Some((model, leader_loader)) => Some((model, leader_loader.mids_iter()?.collect::<Result<Vec<_>, _>>()?)),
+            None => None,
         };
-
-        let params = BacktestParams {
-            data_stream,
-            config,
+        println!("Loaded {} events in {:.2}s", events.len(), start_time.elapsed().as_secs_f64());
+        println!("Starting parallel execution...\n");
+        run_backtest_multi_parallel(
+            &events,
+            backtest_configs,
+            num_threads,
+            initial_capital_dec,
+            order_notional_dec,
+            leader.as_ref().map(|(model, mids)| (*model, mids.as_slice())),
+            calibrations,
+        )?
+    } else {
+        println!("Running all configurations in lockstep over one pass of the data...\n");
+        run_backtest_multi(MultiBacktestParams {
+            data_stream: loader.stream()?,
+            configs: backtest_configs,
             initial_capital: initial_capital_dec,
             order_notional: order_notional_dec,
-            output_csv_path: None,
-            verbose: false,
-        };
-
-        match run_backtest(params) {
-            Ok(result) => {
-                // Update progress
-                let count = {
-                    let mut c = completed_count.lock().unwrap();
-                    *c += 1;
-                    *c
-                };
-
-                let elapsed = start_time.elapsed();
-                let avg_time = elapsed.as_secs_f64() / count as f64;
-                let remaining = ((total_runs - count) as f64 * avg_time) as u64;
-
-                println!(
-                    "[{:2}/{:2}] H={:>4}, g={:.2} -> PnL: ${:>7.2}, Fills: {:>3}, Return: {:>6.2}% (ETA: {}s)",
-                    count,
-                    total_runs,
-                    format_duration(grid_config.horizon),
-                    grid_config.gamma,
-                    result.final_pnl,
-                    result.total_fills(),
-                    result.total_return_pct,
-                    remaining
-                );
-
-                // Store result
-                let grid_result = GridResult {
-                    horizon: grid_config.horizon,
-                    gamma: grid_config.gamma,
-                    result,
-                };
-                results.lock().unwrap().push(grid_result);
-            }
-            Err(e) => {
-                eprintln!(
-                    "[FAIL] H={}, g={:.2} - Error: {}",
-                    format_duration(grid_config.horizon),
-                    grid_config.gamma,
-                    e
-                );
-            }
-        }
-    });
-
-    // Extract results - handle potential failures gracefully
-    let final_results: Vec<GridResult> = {
-        let guard = results.lock().map_err(|e| format!("Mutex poisoned: {}", e))?;
-        guard.clone()
+            lead_lag: match &lead_lag {
+                Some((model, leader_loader)) => Some(LeadLagReference::new(model, leader_loader.mids_iter()?)),
+                None => None,
+            },
+            calibrations,
+        })?
     };
 
+    let final_results: Vec<GridResult> = configs
+        .iter()
+        .zip(results)
+        .map(|(grid_config, result)| GridResult { horizon: grid_config.horizon, gamma: grid_config.gamma, result })
+        .collect();
+    for grid_result in &final_results {
+        println!(
+            "H={:>4}, g={:.2} -> PnL: ${:>7.2}, Fills: {:>3}, Return: {:>6.2}%",
+            format_duration(grid_result.horizon),
+            grid_result.gamma,
+            grid_result.result.final_pnl,
+            grid_result.result.total_fills(),
+            grid_result.result.total_return_pct
+        );
+    }
+
     let total_elapsed = start_time.elapsed();
     println!(
         "\n[OK] All {} configurations completed in {:.1}s",
@@ -402,11 +484,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     save_results_to_csv(&final_results, &output_path)?;
 
     println!("\nResults saved to {}", output_path);
-    println!(
-        "\n[PERF] ~{:.1}x speedup vs sequential execution (using {} threads)",
-        num_threads as f64 * 0.85, // Account for overhead
-        num_threads
-    );
 
     Ok(())
 }
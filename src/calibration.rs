@@ -1,12 +1,18 @@
+use crate::intensity_shape::IntensityShape;
+use crate::model_types::{InformationCriterion, IntensityShapeKind};
+use crate::optimizer::{nelder_mead, NelderMeadOptions};
 use rust_decimal::Decimal;
 use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
-/// Lightweight trade data for calibration (excludes unused quantity field).
-/// Saves ~16 bytes per trade vs TradeEvent.
+/// Lightweight trade data for calibration.
+/// The quantity is kept for size-aware calibration (parent-order aggregation and clip filtering).
 #[derive(Debug, Clone)]
 pub struct CalibrationTrade {
     pub timestamp: u64,
     pub price: Decimal,
+    pub quantity: Decimal,
     pub is_buyer_maker: bool,
 }
 
@@ -20,6 +26,8 @@ const MIN_SAMPLES_FOR_VARIANCE: usize = 2;
 const MIN_RETURNS_FOR_GARCH: usize = 5;
 /// Upper bound for alpha+beta to keep stationarity
 const MAX_ALPHA_BETA_SUM: f64 = 0.999;
+/// Sampling step of the GARCH return grid
+const GARCH_STEP_MS: u64 = 1000;
 /// ln(2π) for normal log-likelihood (precomputed to keep const)
 const LOG_2PI: f64 = 1.837_877_066_409_345_3_f64;
 
@@ -45,6 +53,17 @@ struct ExposureInterval {
     delta_max: f64,
 }
 
+/// Lower edge of the exposure histogram grid (return space); smaller deltas share the first bin
+const EXPOSURE_GRID_MIN_DELTA: f64 = 1e-7;
+/// Decades covered by the exposure histogram grid (1e-7 .. 1)
+const EXPOSURE_GRID_DECADES: usize = 7;
+/// Log-spaced bins per decade of the exposure histogram
+const EXPOSURE_BINS_PER_DECADE: usize = 32;
+/// Finer bins kept inside each histogram bin for steep decays
+const EXPOSURE_FINE_BINS_PER_BIN: usize = 16;
+/// Decay × within-bin standard deviation above which a bin is evaluated on its finer bins
+const EXPOSURE_REFINE_THRESHOLD: f64 = 0.1;
+
 /// Minimal orderbook information needed for intensity calibration.
 ///
 /// All delta values are in return space (relative to mid), i.e., δ = |price - mid| / mid.
@@ -68,7 +87,7 @@ pub struct OrderbookPoint {
 // ============================================================================
 
 /// Sanitize price series to positive finite f64 values, dropping invalid points.
-fn sanitize_prices(prices: &[(u64, Decimal)]) -> Vec<(u64, f64)> {
+pub(crate) fn sanitize_prices(prices: &[(u64, Decimal)]) -> Vec<(u64, f64)> {
     let mut cleaned = Vec::with_capacity(prices.len());
     for (ts, p) in prices {
         if let Some(v) = p.to_f64() {
@@ -143,22 +162,114 @@ pub fn calculate_volatility(prices: &[(u64, Decimal)]) -> f64 {
     if sigma.is_finite() { sigma } else { 0.0 }
 }
 
-/// Fit a simple GARCH(1,1) via grid-based MLE and return the next-step sigma forecast (per-second).
+/// Realized variance Σr²/Σdt over a sliding time window, maintained incrementally.
+///
+/// Matches `calculate_volatility` on the same window: each pair of consecutive valid prices
+/// contributes one return, which leaves the window with its earlier price. Timestamps must be
+/// non-decreasing so eviction happens at the front.
+#[derive(Debug, Clone, Default)]
+pub struct RollingRealizedVariance {
+    /// (start timestamp, r², dt seconds) per return
+    returns: VecDeque<(u64, f64, f64)>,
+    last: Option<(u64, f64)>,
+    sum_squared_returns: f64,
+    total_duration_seconds: f64,
+}
+
+impl RollingRealizedVariance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, timestamp: u64, price: Decimal) {
+        let price = match price.to_f64() {
+            Some(p) if p.is_finite() && p > 0.0 => p,
+            _ => return,
+        };
+
+        if let Some((prev_ts, prev_price)) = self.last {
+            let dt_seconds = timestamp.saturating_sub(prev_ts) as f64 / 1000.0;
+            let lr = (price / prev_price).ln();
+            if dt_seconds > 0.0 && lr.is_finite() {
+                self.returns.push_back((prev_ts, lr * lr, dt_seconds));
+                self.sum_squared_returns += lr * lr;
+                self.total_duration_seconds += dt_seconds;
+            }
+        }
+        self.last = Some((timestamp, price));
+    }
+
+    /// Drop returns whose start is more than `window_ms` before `current_ts`
+    pub fn prune(&mut self, current_ts: u64, window_ms: u64) {
+        while let Some(&(ts, r2, dt)) = self.returns.front() {
+            if current_ts.saturating_sub(ts) <= window_ms {
+                break;
+            }
+            self.returns.pop_front();
+            self.sum_squared_returns -= r2;
+            self.total_duration_seconds -= dt;
+        }
+        if self.returns.is_empty() {
+            // Clear accumulated rounding error
+            self.sum_squared_returns = 0.0;
+            self.total_duration_seconds = 0.0;
+        }
+        if let Some((ts, _)) = self.last {
+            if current_ts.saturating_sub(ts) > window_ms {
+                self.last = None;
+            }
+        }
+    }
+
+    /// σ in 1/√s, 0.0 when there are too few returns (same contract as `calculate_volatility`)
+    pub fn sigma(&self) -> f64 {
+        if self.returns.len() < MIN_SAMPLES_FOR_VARIANCE
+            || self.total_duration_seconds <= 0.0
+            || self.sum_squared_returns <= 0.0
+        {
+            return 0.0;
+        }
+        let sigma = (self.sum_squared_returns / self.total_duration_seconds).sqrt();
+        if sigma.is_finite() { sigma } else { 0.0 }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// GARCH(1,1) parameters for 1-second log returns: σ²ₜ₊₁ = ω + α·r²ₜ + β·σ²ₜ
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GarchParams {
+    pub omega: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+/// Result of a GARCH(1,1) maximum-likelihood fit
+#[derive(Debug, Clone, Copy)]
+pub struct GarchFit {
+    pub params: GarchParams,
+    pub log_likelihood: f64,
+    /// Conditional variance of the next 1s return
+    pub next_variance: f64,
+    /// Likelihood evaluations used by the optimiser
+    pub evaluations: usize,
+}
+
+/// Fit GARCH(1,1) by MLE on 1s-resampled returns and return the next-step sigma forecast (per-second).
 pub fn forecast_garch_volatility(prices: &[(u64, Decimal)]) -> Option<f64> {
     let returns = build_fixed_step_returns(prices, 1.0)?; // 1-second grid
     if returns.len() < MIN_RETURNS_FOR_GARCH {
         return None;
     }
-    fit_garch_forecast(&returns)
+    fit_garch(&returns, None).map(|fit| fit.next_variance.sqrt())
 }
 
-fn garch_loglik(returns: &[f64], alpha: f64, beta: f64, var0: f64) -> Option<(f64, f64)> {
-    if alpha < 0.0 || beta < 0.0 || alpha + beta >= MAX_ALPHA_BETA_SUM {
-        return None;
-    }
-
-    let omega = var0 * (1.0 - alpha - beta);
-    if omega <= 0.0 {
+/// Gaussian log-likelihood of `returns` and the next-step variance, starting from `var0`
+fn garch_loglik(returns: &[f64], params: &GarchParams, var0: f64) -> Option<(f64, f64)> {
+    let GarchParams { omega, alpha, beta } = *params;
+    if omega <= 0.0 || alpha < 0.0 || beta < 0.0 || alpha + beta >= MAX_ALPHA_BETA_SUM {
         return None;
     }
 
@@ -180,7 +291,43 @@ fn garch_loglik(returns: &[f64], alpha: f64, beta: f64, var0: f64) -> Option<(f6
     Some((loglik, sigma2))
 }
 
-fn fit_garch_forecast(returns: &[f64]) -> Option<f64> {
+#[inline]
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[inline]
+fn logit(p: f64) -> f64 {
+    let p = p.clamp(1e-9, 1.0 - 1e-9);
+    (p / (1.0 - p)).ln()
+}
+
+/// Unconstrained coordinates: persistence α+β (logistic, below MAX_ALPHA_BETA_SUM),
+/// α's share of persistence (logistic) and ln ω.
+fn garch_from_unconstrained(u: &[f64]) -> GarchParams {
+    let persistence = MAX_ALPHA_BETA_SUM * logistic(u[0]);
+    let alpha_share = logistic(u[1]);
+    GarchParams {
+        omega: u[2].exp(),
+        alpha: persistence * alpha_share,
+        beta: persistence * (1.0 - alpha_share),
+    }
+}
+
+fn garch_to_unconstrained(params: &GarchParams) -> [f64; 3] {
+    let persistence = (params.alpha + params.beta).max(1e-9);
+    [
+        logit(persistence / MAX_ALPHA_BETA_SUM),
+        logit(params.alpha / persistence),
+        params.omega.max(1e-300).ln(),
+    ]
+}
+
+/// Fit GARCH(1,1) by maximum likelihood with Nelder–Mead.
+///
+/// `warm_start` (e.g. the previous fit) seeds the optimiser; otherwise it starts from
+/// α=0.05, β=0.90 with ω targeting the sample variance.
+pub fn fit_garch(returns: &[f64], warm_start: Option<GarchParams>) -> Option<GarchFit> {
     if returns.len() < MIN_RETURNS_FOR_GARCH {
         return None;
     }
@@ -190,58 +337,140 @@ fn fit_garch_forecast(returns: &[f64]) -> Option<f64> {
         return None;
     }
 
-    let mut best_loglik = f64::NEG_INFINITY;
-    let mut best_alpha = 0.1;
-    let mut best_beta = 0.85;
-    let mut best_sigma2_next = mean_sq;
+    let cold_start = GarchParams {
+        omega: mean_sq * (1.0 - 0.95),
+        alpha: 0.05,
+        beta: 0.90,
+    };
+    let start = warm_start
+        .filter(|p| garch_loglik(returns, p, mean_sq).is_some())
+        .unwrap_or(cold_start);
+
+    let options = NelderMeadOptions {
+        // Smaller steps around a warm start, which is usually close to the optimum
+        initial_step: if warm_start.is_some() { 0.1 } else { 0.5 },
+        max_evaluations: 600,
+        value_tolerance: 1e-10,
+    };
+    let objective = |u: &[f64]| {
+        garch_loglik(returns, &garch_from_unconstrained(u), mean_sq)
+            .map(|(ll, _)| -ll)
+            .unwrap_or(f64::INFINITY)
+    };
+    let minimum = nelder_mead(objective, &garch_to_unconstrained(&start), options);
 
-    // Coarse grid search
-    for alpha in (0..=25).map(|i| i as f64 * 0.02) {
-        for beta in (0..=49).map(|i| i as f64 * 0.02) {
-            if alpha + beta >= MAX_ALPHA_BETA_SUM {
-                continue;
-            }
-            if let Some((ll, sigma2_next)) = garch_loglik(returns, alpha, beta, mean_sq) {
-                if ll > best_loglik {
-                    best_loglik = ll;
-                    best_alpha = alpha;
-                    best_beta = beta;
-                    best_sigma2_next = sigma2_next;
-                }
-            }
+    let params = garch_from_unconstrained(&minimum.x);
+    let (log_likelihood, next_variance) = garch_loglik(returns, &params, mean_sq)?;
+    if log_likelihood.is_finite() && next_variance.is_finite() && next_variance > 0.0 {
+        Some(GarchFit { params, log_likelihood, next_variance, evaluations: minimum.evaluations })
+    } else {
+        None
+    }
+}
+
+/// Incremental GARCH(1,1) state on a bounded lookback of 1s returns.
+///
+/// Prices are resampled to a 1s grid as they arrive (previous-tick), the conditional
+/// variance is rolled forward with the current parameters, and `refit` re-estimates the
+/// parameters on the lookback only, warm-started from the previous fit.
+#[derive(Debug, Clone)]
+pub struct GarchState {
+    max_returns: usize,
+    returns: VecDeque<f64>,
+    last_price: Option<f64>,
+    bucket_price: f64,
+    next_bucket: u64,
+    params: Option<GarchParams>,
+    variance: Option<f64>,
+}
+
+impl GarchState {
+    pub fn new(lookback_seconds: u64) -> Self {
+        let max_returns = (lookback_seconds as usize).max(MIN_RETURNS_FOR_GARCH);
+        Self {
+            max_returns,
+            returns: VecDeque::with_capacity(max_returns),
+            last_price: None,
+            bucket_price: 0.0,
+            next_bucket: 0,
+            params: None,
+            variance: None,
         }
     }
 
-    // Local refinement around the best point
-    let refine_steps = [-0.02, -0.01, -0.005, 0.0, 0.005, 0.01, 0.02];
-    for da in refine_steps.iter() {
-        for db in refine_steps.iter() {
-            let alpha = (best_alpha + da).max(0.0);
-            let beta = (best_beta + db).max(0.0);
-            if alpha + beta >= MAX_ALPHA_BETA_SUM {
-                continue;
+    /// Add a price observation (timestamps must be non-decreasing)
+    pub fn add_price(&mut self, timestamp: u64, price: Decimal) {
+        let price = match price.to_f64() {
+            Some(p) if p.is_finite() && p > 0.0 => p,
+            _ => return,
+        };
+
+        let last = match self.last_price {
+            Some(last) => last,
+            None => {
+                self.last_price = Some(price);
+                self.bucket_price = price;
+                self.next_bucket = timestamp + GARCH_STEP_MS;
+                return;
             }
-            if let Some((ll, sigma2_next)) = garch_loglik(returns, alpha, beta, mean_sq) {
-                if ll > best_loglik {
-                    best_loglik = ll;
-                    best_alpha = alpha;
-                    best_beta = beta;
-                    best_sigma2_next = sigma2_next;
-                }
+        };
+
+        if self.next_bucket <= timestamp {
+            let steps = (timestamp - self.next_bucket) / GARCH_STEP_MS + 1;
+            self.push_return((last / self.bucket_price).ln());
+            // Flat buckets across a gap; more than the lookback would all be evicted anyway
+            for _ in 0..(steps - 1).min(self.max_returns as u64) {
+                self.push_return(0.0);
             }
+            self.bucket_price = last;
+            self.next_bucket += steps * GARCH_STEP_MS;
         }
+        self.last_price = Some(price);
     }
 
-    if best_loglik.is_finite() && best_sigma2_next.is_finite() && best_sigma2_next > 0.0 {
-        Some(best_sigma2_next.sqrt())
-    } else {
-        None
+    fn push_return(&mut self, r: f64) {
+        if !r.is_finite() {
+            return;
+        }
+        if self.returns.len() == self.max_returns {
+            self.returns.pop_front();
+        }
+        self.returns.push_back(r);
+
+        if let (Some(p), Some(v)) = (self.params, self.variance) {
+            self.variance = Some(p.omega + p.alpha * r * r + p.beta * v);
+        }
+    }
+
+    /// Re-estimate the parameters on the lookback, warm-started from the previous fit
+    pub fn refit(&mut self) -> Option<GarchFit> {
+        let fit = fit_garch(self.returns.make_contiguous(), self.params)?;
+        self.params = Some(fit.params);
+        self.variance = Some(fit.next_variance);
+        Some(fit)
+    }
+
+    /// Next-step sigma (per second) from the rolled-forward conditional variance
+    pub fn forecast_sigma(&self) -> Option<f64> {
+        self.variance.filter(|v| v.is_finite() && *v > 0.0).map(f64::sqrt)
+    }
+
+    pub fn params(&self) -> Option<GarchParams> {
+        self.params
+    }
+
+    pub fn return_count(&self) -> usize {
+        self.returns.len()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new(self.max_returns as u64);
     }
 }
 
 /// Build fixed-step (e.g., 1s) log returns using previous-tick interpolation.
 /// Returns per-second log returns on a uniform grid.
-fn build_fixed_step_returns(prices: &[(u64, Decimal)], step_seconds: f64) -> Option<Vec<f64>> {
+pub(crate) fn build_fixed_step_returns(prices: &[(u64, Decimal)], step_seconds: f64) -> Option<Vec<f64>> {
     if step_seconds <= 0.0 {
         return None;
     }
@@ -373,16 +602,27 @@ fn build_side_exposures(
 ///
 /// Returns deltas in return space (relative to mid): δ = |trade_price - mid| / mid.
 /// This ensures kappa is calibrated as dimensionless.
-fn collect_trade_deltas(
+pub(crate) fn collect_trade_deltas(
     trades: &[CalibrationTrade],
     orderbooks: &[OrderbookPoint],
     is_bid: bool,
 ) -> Vec<f64> {
+    let mut deltas = Vec::with_capacity(trades.len());
+    visit_trade_deltas(trades, orderbooks, is_bid, |_, delta| deltas.push(delta));
+    deltas
+}
+
+/// `collect_trade_deltas`, handing each trade with its delta to `visit`
+fn visit_trade_deltas(
+    trades: &[CalibrationTrade],
+    orderbooks: &[OrderbookPoint],
+    is_bid: bool,
+    mut visit: impl FnMut(&CalibrationTrade, f64),
+) {
     if orderbooks.is_empty() {
-        return Vec::new();
+        return;
     }
 
-    let mut deltas = Vec::with_capacity(trades.len());
     let mut ob_idx = 0;
 
     for trade in trades {
@@ -415,12 +655,149 @@ fn collect_trade_deltas(
         if let Some(delta_price) = delta_dec.to_f64() {
             let delta_ret = delta_price / mid_f64; // Convert to return space
             if delta_ret.is_finite() && delta_ret > 0.0 {
-                deltas.push(delta_ret);
+                visit(trade, delta_ret);
             }
         }
     }
+}
 
-    deltas
+/// Duration mass of one histogram bin with its duration-weighted mean and variance of δ
+#[derive(Debug, Clone)]
+struct ExposureBin {
+    weight: f64,
+    mean: f64,
+    variance: f64,
+    /// Non-empty finer bins covering this bin (empty for the finer bins themselves)
+    fine: Vec<ExposureBin>,
+}
+
+impl ExposureBin {
+    /// Bin from (Σd, Σdδ, Σdδ²)
+    fn from_sums((weight, sum, sum_sq): (f64, f64, f64), fine: Vec<ExposureBin>) -> Self {
+        let mean = sum / weight;
+        Self { weight, mean, variance: (sum_sq / weight - mean * mean).max(0.0), fine }
+    }
+}
+
+/// Exposure intervals aggregated on a fixed log-spaced δ grid.
+///
+/// The exposure term Σ d·(e^(-κδ_min) − e^(-κδ_max)) is linear in the intervals, so the
+/// δ_min and δ_max ends are binned separately. Within a bin e^(-κδ) is expanded around the
+/// weighted mean (second order), which keeps the likelihood cost independent of the number
+/// of snapshots. The expansion degrades once κ·σ_bin approaches 1, so each bin also keeps
+/// finer bins that replace it at steep decays (large κ up to `KAPPA_MAX`).
+#[derive(Debug, Clone, Default)]
+struct ExposureHistogram {
+    lower: Vec<ExposureBin>,
+    upper: Vec<ExposureBin>,
+}
+
+impl ExposureHistogram {
+    fn new(exposures: &[ExposureInterval]) -> Self {
+        let fine_bins = EXPOSURE_GRID_DECADES * EXPOSURE_BINS_PER_DECADE * EXPOSURE_FINE_BINS_PER_BIN;
+        // (Σd, Σdδ, Σdδ²) per fine bin
+        let mut lower = vec![(0.0, 0.0, 0.0); fine_bins];
+        let mut upper = vec![(0.0, 0.0, 0.0); fine_bins];
+
+        for e in exposures {
+            for (grid, delta) in [(&mut lower, e.delta_min), (&mut upper, e.delta_max)] {
+                let bin = &mut grid[exposure_bin_index(delta)];
+                bin.0 += e.duration_sec;
+                bin.1 += e.duration_sec * delta;
+                bin.2 += e.duration_sec * delta * delta;
+            }
+        }
+
+        let compact = |grid: Vec<(f64, f64, f64)>| -> Vec<ExposureBin> {
+            grid.chunks(EXPOSURE_FINE_BINS_PER_BIN)
+                .filter_map(|chunk| {
+                    let fine: Vec<ExposureBin> = chunk
+                        .iter()
+                        .filter(|(w, _, _)| *w > 0.0)
+                        .map(|sums| ExposureBin::from_sums(*sums, Vec::new()))
+                        .collect();
+                    if fine.is_empty() {
+                        return None;
+                    }
+                    let sums = chunk.iter().fold((0.0, 0.0, 0.0), |acc, s| (acc.0 + s.0, acc.1 + s.1, acc.2 + s.2));
+                    // A single finer bin holds the same intervals
+                    let fine = if fine.len() > 1 { fine } else { Vec::new() };
+                    Some(ExposureBin::from_sums(sums, fine))
+                })
+                .collect()
+        };
+
+        Self { lower: compact(lower), upper: compact(upper) }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lower.is_empty()
+    }
+
+    /// Σ term(bin) over one side, using the finer bins where `decay(δ)`·σ_bin is too steep
+    /// for the in-bin expansion
+    fn side_sum(bins: &[ExposureBin], decay: &impl Fn(f64) -> f64, term: &impl Fn(&ExposureBin) -> f64) -> f64 {
+        bins.iter()
+            .map(|b| {
+                if !b.fine.is_empty() && decay(b.mean) * b.variance.sqrt() > EXPOSURE_REFINE_THRESHOLD {
+                    b.fine.iter().map(term).sum()
+                } else {
+                    term(b)
+                }
+            })
+            .sum()
+    }
+
+    /// Approximate Σ d·(S(δ_min) − S(δ_max)) for any survival curve with hazard `hazard`,
+    /// evaluating each bin at mean ± one standard deviation (exact for quadratics, like the
+    /// expansion above)
+    fn exposure_with(&self, survival: impl Fn(f64) -> f64, hazard: impl Fn(f64) -> f64) -> f64 {
+        let term = |b: &ExposureBin| {
+            let sd = b.variance.sqrt();
+            0.5 * b.weight * (survival((b.mean - sd).max(0.0)) + survival(b.mean + sd))
+        };
+        Self::side_sum(&self.lower, &hazard, &term) - Self::side_sum(&self.upper, &hazard, &term)
+    }
+
+    /// Approximate Σ d·(e^(-κδ_min) − e^(-κδ_max))
+    fn exposure(&self, kappa: f64) -> f64 {
+        let term = |b: &ExposureBin| b.weight * (-kappa * b.mean).exp() * (1.0 + 0.5 * kappa * kappa * b.variance);
+        let decay = |_: f64| kappa;
+        Self::side_sum(&self.lower, &decay, &term) - Self::side_sum(&self.upper, &decay, &term)
+    }
+}
+
+/// Index of `delta` among the finer bins of the exposure grid
+#[inline]
+fn exposure_bin_index(delta: f64) -> usize {
+    let bins_per_decade = EXPOSURE_BINS_PER_DECADE * EXPOSURE_FINE_BINS_PER_BIN;
+    let bins = EXPOSURE_GRID_DECADES * bins_per_decade;
+    if delta <= EXPOSURE_GRID_MIN_DELTA {
+        return 0;
+    }
+    let position = (delta / EXPOSURE_GRID_MIN_DELTA).log10() * bins_per_decade as f64;
+    (position as usize).min(bins - 1)
+}
+
+/// Sufficient statistics of one side's trade deltas for the exponential-intensity likelihood
+#[derive(Debug, Clone, Copy)]
+struct TradeDeltaStats {
+    count: f64,
+    sum: f64,
+}
+
+impl TradeDeltaStats {
+    fn new(deltas: &[f64]) -> Self {
+        Self { count: deltas.len() as f64, sum: deltas.iter().sum() }
+    }
+
+    /// Each trade counts as `weight` fills: count = Σw, sum = Σwδ
+    fn weighted(deltas: &[f64], weights: &[f64]) -> Self {
+        Self {
+            count: weights.iter().sum(),
+            sum: deltas.iter().zip(weights).map(|(d, w)| d * w).sum(),
+        }
+    }
 }
 
 #[inline]
@@ -437,36 +814,43 @@ fn exposure_term(kappa: f64, exposures: &[ExposureInterval]) -> f64 {
 
 fn log_likelihood(
     kappa: f64,
-    n: f64,
-    sum_deltas: f64,
-    exposures: &[ExposureInterval],
+    trades: &TradeDeltaStats,
+    histogram: &ExposureHistogram,
 ) -> f64 {
-    if kappa <= 0.0 || !kappa.is_finite() || exposures.is_empty() || n <= 0.0 {
+    if kappa <= 0.0 || !kappa.is_finite() || histogram.is_empty() || trades.count <= 0.0 {
         return f64::NEG_INFINITY;
     }
 
-    let exposure = exposure_term(kappa, exposures);
+    let exposure = histogram.exposure(kappa);
     if !exposure.is_finite() || exposure <= 0.0 {
         return f64::NEG_INFINITY;
     }
 
     // Log-likelihood up to additive constant: n*(ln k - ln exposure) - k*sum_delta
-    n * (kappa.ln() - exposure.ln()) - kappa * sum_deltas
+    trades.count * (kappa.ln() - exposure.ln()) - kappa * trades.sum
 }
 
 fn estimate_mle_side_exposure(
     deltas: &[f64],
     exposures: &[ExposureInterval],
 ) -> Option<(f64, f64)> {
-    if deltas.len() < MIN_TRADES_FOR_ESTIMATION || exposures.is_empty() {
+    estimate_mle_side_stats(deltas.len(), TradeDeltaStats::new(deltas), exposures)
+}
+
+/// MLE of (kappa, A) from the sufficient statistics of `trades` trade deltas
+fn estimate_mle_side_stats(
+    trades: usize,
+    stats: TradeDeltaStats,
+    exposures: &[ExposureInterval],
+) -> Option<(f64, f64)> {
+    if trades < MIN_TRADES_FOR_ESTIMATION || exposures.is_empty() {
         return None;
     }
 
-    let n = deltas.len() as f64;
-    let sum_deltas: f64 = deltas.iter().sum();
-    if sum_deltas <= 0.0 || !sum_deltas.is_finite() {
+    if stats.count <= 0.0 || stats.sum <= 0.0 || !stats.sum.is_finite() {
         return None;
     }
+    let histogram = ExposureHistogram::new(exposures);
 
     // Coarse log-space search to bracket a good region
     let mut best_kappa = None;
@@ -477,7 +861,7 @@ fn estimate_mle_side_exposure(
     for i in 0..=60 {
         let frac = i as f64 / 60.0;
         let kappa = 10f64.powf(log_min + frac * (log_max - log_min));
-        let ll = log_likelihood(kappa, n, sum_deltas, exposures);
+        let ll = log_likelihood(kappa, &stats, &histogram);
         if ll.is_finite() && ll > best_ll {
             best_ll = ll;
             best_kappa = Some(kappa);
@@ -495,8 +879,8 @@ fn estimate_mle_side_exposure(
     const PHI: f64 = 0.618_033_988_75; // golden ratio conjugate
     let mut c = high - (high - low) * PHI;
     let mut d = low + (high - low) * PHI;
-    let mut fc = log_likelihood(c, n, sum_deltas, exposures);
-    let mut fd = log_likelihood(d, n, sum_deltas, exposures);
+    let mut fc = log_likelihood(c, &stats, &histogram);
+    let mut fd = log_likelihood(d, &stats, &histogram);
 
     for _ in 0..32 {
         if fc > fd {
@@ -504,24 +888,25 @@ fn estimate_mle_side_exposure(
             d = c;
             fd = fc;
             c = high - (high - low) * PHI;
-            fc = log_likelihood(c, n, sum_deltas, exposures);
+            fc = log_likelihood(c, &stats, &histogram);
         } else {
             low = c;
             c = d;
             fc = fd;
             d = low + (high - low) * PHI;
-            fd = log_likelihood(d, n, sum_deltas, exposures);
+            fd = log_likelihood(d, &stats, &histogram);
         }
     }
 
     best_kappa = if fc > fd { c } else { d };
 
+    // A from the exact exposure at the fitted kappa (one pass over the intervals)
     let exposure = exposure_term(best_kappa, exposures);
     if !exposure.is_finite() || exposure <= 0.0 {
         return None;
     }
 
-    let a = (n * best_kappa) / exposure;
+    let a = (stats.count * best_kappa) / exposure;
     if !a.is_finite() || a <= 0.0 || !best_kappa.is_finite() || best_kappa <= 0.0 {
         return None;
     }
@@ -529,6 +914,213 @@ fn estimate_mle_side_exposure(
     Some((best_kappa, a))
 }
 
+/// How one side's intensity parameters were obtained
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum IntensityFitSource {
+    /// MLE on this side's trades
+    Fitted,
+    /// This side could not be fitted; the other side's estimates are reused
+    OtherSide,
+    /// Neither side could be fitted; DEFAULT_KAPPA / DEFAULT_A
+    Default,
+}
+
+impl IntensityFitSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Fitted => "fitted",
+            Self::OtherSide => "other_side",
+            Self::Default => "default",
+        }
+    }
+}
+
+/// Intensity estimates for one side with their uncertainty and goodness of fit.
+///
+/// Standard errors and the KS statistic describe this side's own MLE; they are NaN when
+/// the side was not fitted (`source` is not `Fitted`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SideIntensityFit {
+    pub kappa: f64,
+    pub a: f64,
+    /// Standard error of kappa from the observed Fisher information
+    pub kappa_se: f64,
+    /// Standard error of A from the observed Fisher information
+    pub a_se: f64,
+    /// Kolmogorov-Smirnov distance between trade deltas and the fitted exposure-weighted depth law
+    pub ks_statistic: f64,
+    /// Trades used for this side
+    pub trades: usize,
+    pub source: IntensityFitSource,
+}
+
+impl SideIntensityFit {
+    fn fallback(kappa: f64, a: f64, trades: usize, source: IntensityFitSource) -> Self {
+        Self { kappa, a, kappa_se: f64::NAN, a_se: f64::NAN, ks_statistic: f64::NAN, trades, source }
+    }
+}
+
+/// Bid and ask intensity fits
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct IntensityFit {
+    pub bid: SideIntensityFit,
+    pub ask: SideIntensityFit,
+}
+
+/// Standard errors of (kappa, A) from the observed information of the exposure likelihood
+/// LL = n·ln A − κΣδ − A·G(κ) with G = E(κ)/κ (trade depths have density A·e^(-κδ)),
+/// evaluated exactly over the exposure intervals
+fn intensity_standard_errors(kappa: f64, a: f64, n: f64, exposures: &[ExposureInterval]) -> (f64, f64) {
+    let (mut exposure, mut d_exposure, mut d2_exposure) = (0.0, 0.0, 0.0);
+    for e in exposures {
+        let (lower, upper) = ((-kappa * e.delta_min).exp(), (-kappa * e.delta_max).exp());
+        exposure += e.duration_sec * (lower - upper);
+        d_exposure += e.duration_sec * (e.delta_max * upper - e.delta_min * lower);
+        d2_exposure += e.duration_sec * (e.delta_min.powi(2) * lower - e.delta_max.powi(2) * upper);
+    }
+
+    // Derivatives of G(κ) = E(κ)/κ
+    let d_g = d_exposure / kappa - exposure / kappa.powi(2);
+    let d2_g = d2_exposure / kappa - 2.0 * d_exposure / kappa.powi(2) + 2.0 * exposure / kappa.powi(3);
+
+    let info_kk = a * d2_g;
+    let info_aa = n / (a * a);
+    let info_ka = d_g;
+    let det = info_kk * info_aa - info_ka * info_ka;
+    if !det.is_finite() || det <= 0.0 {
+        return (f64::NAN, f64::NAN);
+    }
+    ((info_aa / det).sqrt(), (info_kk / det).sqrt())
+}
+
+/// KS statistic of trade deltas against the depth law implied by the fit: density
+/// ∝ κe^(-κδ) weighted by the time δ was inside the book's [δ_min, δ_max].
+///
+/// The CDF uses prefix sums over intervals sorted by δ_min and by δ_max:
+/// E·F(x) = Σ_{δmin<x} d·e^(-κδmin) − Σ_{δmax<x} d·e^(-κδmax) − e^(-κx)·Σ_{δmin<x≤δmax} d.
+fn intensity_ks_statistic(kappa: f64, deltas: &[f64], exposures: &[ExposureInterval]) -> f64 {
+    let total = exposure_term(kappa, exposures);
+    if deltas.is_empty() || !total.is_finite() || total <= 0.0 {
+        return f64::NAN;
+    }
+
+    let mut by_min: Vec<(f64, f64)> = exposures.iter().map(|e| (e.delta_min, e.duration_sec)).collect();
+    let mut by_max: Vec<(f64, f64)> = exposures.iter().map(|e| (e.delta_max, e.duration_sec)).collect();
+    by_min.sort_by(|x, y| x.0.total_cmp(&y.0));
+    by_max.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let mut sorted = deltas.to_vec();
+    sorted.sort_by(|x, y| x.total_cmp(y));
+
+    let (mut i_min, mut i_max) = (0, 0);
+    let (mut sum_min_exp, mut sum_min_dur, mut sum_max_exp, mut sum_max_dur) = (0.0, 0.0, 0.0, 0.0);
+    let n = sorted.len() as f64;
+    let mut statistic: f64 = 0.0;
+
+    for (i, &x) in sorted.iter().enumerate() {
+        while i_min < by_min.len() && by_min[i_min].0 < x {
+            sum_min_exp += by_min[i_min].1 * (-kappa * by_min[i_min].0).exp();
+            sum_min_dur += by_min[i_min].1;
+            i_min += 1;
+        }
+        while i_max < by_max.len() && by_max[i_max].0 < x {
+            sum_max_exp += by_max[i_max].1 * (-kappa * by_max[i_max].0).exp();
+            sum_max_dur += by_max[i_max].1;
+            i_max += 1;
+        }
+
+        let cdf = ((sum_min_exp - sum_max_exp - (-kappa * x).exp() * (sum_min_dur - sum_max_dur)) / total).clamp(0.0, 1.0);
+        statistic = statistic.max((i + 1) as f64 / n - cdf).max(cdf - i as f64 / n);
+    }
+
+    statistic
+}
+
+/// Fit one side and attach its diagnostics. With per-trade fill weights the weighted fill
+/// count replaces n in the likelihood and standard errors; the KS statistic stays unweighted.
+fn fit_side_detailed(deltas: &[f64], weights: Option<&[f64]>, exposures: &[ExposureInterval]) -> Option<SideIntensityFit> {
+    let stats = match weights {
+        Some(weights) => TradeDeltaStats::weighted(deltas, weights),
+        None => TradeDeltaStats::new(deltas),
+    };
+    let (kappa, a) = estimate_mle_side_stats(deltas.len(), stats, exposures)?;
+    let (kappa_se, a_se) = intensity_standard_errors(kappa, a, stats.count, exposures);
+    Some(SideIntensityFit {
+        kappa,
+        a,
+        kappa_se,
+        a_se,
+        ks_statistic: intensity_ks_statistic(kappa, deltas, exposures),
+        trades: deltas.len(),
+        source: IntensityFitSource::Fitted,
+    })
+}
+
+/// `fit_intensity_parameters` with standard errors, KS statistics, sample sizes and the
+/// fallback path taken for each side.
+pub fn fit_intensity_parameters_detailed(
+    trades: &[CalibrationTrade],
+    orderbooks: &[OrderbookPoint],
+    window_end_ts: u64,
+) -> IntensityFit {
+    if trades.is_empty() || orderbooks.is_empty() {
+        return fit_intensity_from_deltas(&[], &[], &[], window_end_ts);
+    }
+
+    // Collect trade deltas by side using the most recent book state
+    let bid_deltas = collect_trade_deltas(trades, orderbooks, true);
+    let ask_deltas = collect_trade_deltas(trades, orderbooks, false);
+
+    fit_intensity_from_deltas(&bid_deltas, &ask_deltas, orderbooks, window_end_ts)
+}
+
+/// Fit both sides from execution depths (return space) already collected by the caller,
+/// against the exposure of `orderbooks`. Same units and fallbacks as
+/// `fit_intensity_parameters_detailed`.
+pub fn fit_intensity_from_deltas(
+    bid_deltas: &[f64],
+    ask_deltas: &[f64],
+    orderbooks: &[OrderbookPoint],
+    window_end_ts: u64,
+) -> IntensityFit {
+    fit_intensity_sides((bid_deltas, None), (ask_deltas, None), orderbooks, window_end_ts)
+}
+
+/// Both sides from (deltas, optional fill weights), with the usual fallbacks
+fn fit_intensity_sides(
+    (bid_deltas, bid_weights): (&[f64], Option<&[f64]>),
+    (ask_deltas, ask_weights): (&[f64], Option<&[f64]>),
+    orderbooks: &[OrderbookPoint],
+    window_end_ts: u64,
+) -> IntensityFit {
+    let default_side = |trades: usize| SideIntensityFit::fallback(DEFAULT_KAPPA, DEFAULT_A, trades, IntensityFitSource::Default);
+    let (bid_n, ask_n) = (bid_deltas.len(), ask_deltas.len());
+    if orderbooks.is_empty() {
+        return IntensityFit { bid: default_side(bid_n), ask: default_side(ask_n) };
+    }
+
+    // Build exposure integrals for each side
+    let bid_exposures = build_side_exposures(orderbooks, window_end_ts, true);
+    let ask_exposures = build_side_exposures(orderbooks, window_end_ts, false);
+
+    // Fit both sides separately
+    let bid_fit = fit_side_detailed(bid_deltas, bid_weights, &bid_exposures);
+    let ask_fit = fit_side_detailed(ask_deltas, ask_weights, &ask_exposures);
+
+    // Side-specific results; a side without a fit borrows the other side's estimates
+    match (bid_fit, ask_fit) {
+        (Some(bid), Some(ask)) => IntensityFit { bid, ask },
+        (Some(bid), None) => IntensityFit {
+            bid,
+            ask: SideIntensityFit::fallback(bid.kappa, bid.a, ask_n, IntensityFitSource::OtherSide),
+        },
+        (None, Some(ask)) => IntensityFit {
+            bid: SideIntensityFit::fallback(ask.kappa, ask.a, bid_n, IntensityFitSource::OtherSide),
+            ask,
+        },
+        (None, None) => IntensityFit { bid: default_side(bid_n), ask: default_side(ask_n) },
+    }
+}
+
 /// Calibrates intensity parameters A and kappa using MLE on trade arrival rates.
 ///
 /// Fits λ(δ) = A·exp(-κδ) for each side, where δ is in return space (relative to mid).
@@ -546,41 +1138,621 @@ pub fn fit_intensity_parameters(
     orderbooks: &[OrderbookPoint],
     window_end_ts: u64,
 ) -> (f64, f64, f64, f64) {
+    let fit = fit_intensity_parameters_detailed(trades, orderbooks, window_end_ts);
+    (fit.bid.kappa, fit.bid.a, fit.ask.kappa, fit.ask.a)
+}
+
+/// Lower edge, decades and bins per decade of the diagnostic trade-delta histogram
+const DIAGNOSTIC_MIN_DELTA: f64 = 1e-6;
+const DIAGNOSTIC_DECADES: usize = 5;
+const DIAGNOSTIC_BINS_PER_DECADE: usize = 4;
+
+/// Edges (return space) of the diagnostic trade-delta histogram, 1e-6 .. 1e-1. Deltas
+/// outside the range are counted in the first or last bin.
+pub fn diagnostic_delta_edges() -> Vec<f64> {
+    (0..=DIAGNOSTIC_DECADES * DIAGNOSTIC_BINS_PER_DECADE)
+        .map(|i| DIAGNOSTIC_MIN_DELTA * 10f64.powf(i as f64 / DIAGNOSTIC_BINS_PER_DECADE as f64))
+        .collect()
+}
+
+/// What one side's intensity fit saw in a window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SideIntensityDiagnostics {
+    /// Deltas per `diagnostic_delta_edges` bin
+    pub delta_counts: Vec<u64>,
+    /// Seconds of book exposure on this side
+    pub exposure_seconds: f64,
+    /// Exposure integral Σd(S(δmin) − S(δmax)) of the side's shape (for the exponential,
+    /// Σd(e^(-κδmin) − e^(-κδmax)))
+    pub exposure_integral: f64,
+    /// λ(δ) = A·S(δ) at the geometric centre of each histogram bin
+    pub fitted_intensity: Vec<f64>,
+    /// Poisson log-likelihood n·ln c + Σ ln(-S'(δᵢ)) − c·E with c = A/(-S'(0)); for the
+    /// exponential, n·ln A − κΣδ − (A/κ)·E
+    pub log_likelihood: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IntensityDiagnostics {
+    pub bid: SideIntensityDiagnostics,
+    pub ask: SideIntensityDiagnostics,
+}
+
+/// Diagnostics of `deltas` against λ(δ) = A·S(δ). Without `a`, A is profiled on the window
+/// (A = n·(-S'(0))/E, as in the shape fits).
+fn side_diagnostics(
+    deltas: &[f64],
+    exposures: &[ExposureInterval],
+    shape: &IntensityShape,
+    a: Option<f64>,
+    edges: &[f64],
+) -> SideIntensityDiagnostics {
+    let bins = edges.len() - 1;
+    let mut delta_counts = vec![0u64; bins];
+    for delta in deltas {
+        let bin = edges.partition_point(|edge| edge <= delta).saturating_sub(1).min(bins - 1);
+        delta_counts[bin] += 1;
+    }
+
+    let exposure_integral: f64 = exposures
+        .iter()
+        .map(|e| e.duration_sec * (shape.survival(e.delta_min) - shape.survival(e.delta_max)))
+        .sum();
+    let n = deltas.len() as f64;
+    let density0 = shape.density(0.0);
+    let a = a.unwrap_or_else(|| if exposure_integral > 0.0 { n * density0 / exposure_integral } else { f64::NAN });
+    let log_likelihood = if shape.is_valid() && a > 0.0 && density0 > 0.0 {
+        let scale = a / density0;
+        let log_density: f64 = deltas.iter().map(|d| shape.density(*d).ln()).sum();
+        n * scale.ln() + log_density - scale * exposure_integral
+    } else {
+        f64::NAN
+    };
+
+    SideIntensityDiagnostics {
+        delta_counts,
+        exposure_seconds: exposures.iter().map(|e| e.duration_sec).sum(),
+        exposure_integral,
+        fitted_intensity: edges.windows(2).map(|w| a * shape.survival((w[0] * w[1]).sqrt())).collect(),
+        log_likelihood,
+    }
+}
+
+/// Diagnostics of one side's `deltas` (trade or execution depths) against the curve the
+/// calibration uses: `shape` with scale `a`, or with A profiled on the window when `a` is None
+pub fn side_intensity_diagnostics(
+    deltas: &[f64],
+    orderbooks: &[OrderbookPoint],
+    window_end_ts: u64,
+    is_bid: bool,
+    shape: &IntensityShape,
+    a: Option<f64>,
+) -> SideIntensityDiagnostics {
+    let exposures = build_side_exposures(orderbooks, window_end_ts, is_bid);
+    side_diagnostics(deltas, &exposures, shape, a, &diagnostic_delta_edges())
+}
+
+/// Trade-delta histograms, exposure totals, fitted curves and log-likelihoods behind the
+/// exponential `fit`
+pub fn intensity_diagnostics(
+    trades: &[CalibrationTrade],
+    orderbooks: &[OrderbookPoint],
+    window_end_ts: u64,
+    fit: &IntensityFit,
+) -> IntensityDiagnostics {
+    let side = |is_bid: bool, side_fit: &SideIntensityFit| {
+        let deltas = collect_trade_deltas(trades, orderbooks, is_bid);
+        let shape = IntensityShape::Exponential { kappa: side_fit.kappa };
+        side_intensity_diagnostics(&deltas, orderbooks, window_end_ts, is_bid, &shape, Some(side_fit.a))
+    };
+    IntensityDiagnostics { bid: side(true, &fit.bid), ask: side(false, &fit.ask) }
+}
+
+// ============================================================================
+// Intensity Shapes
+// ============================================================================
+
+/// Bounds for the power-law scale δ₀ (return space) and exponent k
+const POWER_LAW_DELTA0_RANGE: (f64, f64) = (1e-8, 1.0);
+const POWER_LAW_EXPONENT_RANGE: (f64, f64) = (1e-3, 1e4);
+
+/// One side's fitted intensity shape with its information criteria
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IntensityShapeFit {
+    pub shape: IntensityShape,
+    /// Intensity scale A: density of trade depths at δ=0 per second, A = n·(-S'(0))/E.
+    /// Same convention as the exponential fit (A = nκ/E).
+    pub a: f64,
+    /// Full point-process log-likelihood (comparable across shapes)
+    pub log_likelihood: f64,
+    pub aic: f64,
+    pub bic: f64,
+    pub trades: usize,
+}
+
+impl IntensityShapeFit {
+    /// Score `shape` on the exact exposure: LL = Σ ln(-S'(δᵢ)) + n·ln(n/E) − n with A = n/E
+    fn new(shape: IntensityShape, deltas: &[f64], exposures: &[ExposureInterval]) -> Option<Self> {
+        if !shape.is_valid() || deltas.is_empty() {
+            return None;
+        }
+
+        let n = deltas.len() as f64;
+        let exposure: f64 = exposures
+            .iter()
+            .map(|e| e.duration_sec * (shape.survival(e.delta_min) - shape.survival(e.delta_max)))
+            .sum();
+        if !exposure.is_finite() || exposure <= 0.0 {
+            return None;
+        }
+
+        let log_density: f64 = deltas.iter().map(|d| shape.density(*d).ln()).sum();
+        let log_likelihood = log_density + n * (n / exposure).ln() - n;
+        if !log_likelihood.is_finite() {
+            return None;
+        }
+
+        // Shape parameters plus A
+        let k = (shape.parameter_count() + 1) as f64;
+        let a = n * shape.density(0.0) / exposure;
+        Some(Self {
+            shape,
+            a,
+            log_likelihood,
+            aic: 2.0 * k - 2.0 * log_likelihood,
+            bic: k * n.ln() - 2.0 * log_likelihood,
+            trades: deltas.len(),
+        })
+    }
+
+    pub fn criterion(&self, criterion: &InformationCriterion) -> f64 {
+        match criterion {
+            InformationCriterion::Aic => self.aic,
+            InformationCriterion::Bic => self.bic,
+        }
+    }
+}
+
+/// Profile log-likelihood (A maximised out, constants dropped) on the binned exposure
+fn shape_profile_loglik(shape: &IntensityShape, deltas: &[f64], histogram: &ExposureHistogram) -> f64 {
+    if !shape.is_valid() {
+        return f64::NEG_INFINITY;
+    }
+    let exposure = histogram.exposure_with(|d| shape.survival(d), |d| shape.hazard(d));
+    if !exposure.is_finite() || exposure <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    let log_density: f64 = deltas.iter().map(|d| shape.density(*d).ln()).sum();
+    log_density - deltas.len() as f64 * exposure.ln()
+}
+
+fn fit_power_law(deltas: &[f64], histogram: &ExposureHistogram) -> Option<IntensityShape> {
+    let mean_delta = deltas.iter().sum::<f64>() / deltas.len() as f64;
+    let unpack = |u: &[f64]| IntensityShape::PowerLaw { delta0: u[0].exp(), exponent: u[1].exp() };
+    let objective = |u: &[f64]| {
+        let (delta0, exponent) = (u[0].exp(), u[1].exp());
+        if !(POWER_LAW_DELTA0_RANGE.0..=POWER_LAW_DELTA0_RANGE.1).contains(&delta0)
+            || !(POWER_LAW_EXPONENT_RANGE.0..=POWER_LAW_EXPONENT_RANGE.1).contains(&exponent)
+        {
+            return f64::INFINITY;
+        }
+        -shape_profile_loglik(&unpack(u), deltas, histogram)
+    };
+
+    let x0 = [mean_delta.max(POWER_LAW_DELTA0_RANGE.0).ln(), 2f64.ln()];
+    let minimum = nelder_mead(objective, &x0, NelderMeadOptions { max_evaluations: 600, ..Default::default() });
+    minimum.value.is_finite().then(|| unpack(&minimum.x))
+}
+
+/// Piecewise-constant hazard with knots at empirical quantiles of the trade depths
+fn fit_piecewise(deltas: &[f64], histogram: &ExposureHistogram, segments: usize) -> Option<IntensityShape> {
+    let mut sorted = deltas.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mut knots = vec![0.0];
+    for j in 1..segments.max(1) {
+        let knot = sorted[j * sorted.len() / segments];
+        if knot > *knots.last().unwrap() {
+            knots.push(knot);
+        }
+    }
+
+    let mean_delta = sorted.iter().sum::<f64>() / sorted.len() as f64;
+    let unpack = |u: &[f64]| IntensityShape::Piecewise { knots: knots.clone(), hazards: u.iter().map(|x| x.exp()).collect() };
+    let objective = |u: &[f64]| -shape_profile_loglik(&unpack(u), deltas, histogram);
+
+    let x0 = vec![(1.0 / mean_delta).ln(); knots.len()];
+    let options = NelderMeadOptions { max_evaluations: 400 * knots.len(), ..Default::default() };
+    let minimum = nelder_mead(objective, &x0, options);
+    minimum.value.is_finite().then(|| unpack(&minimum.x))
+}
+
+/// Fit one side's shape of the given family, or the best family by `criterion` for `auto`
+fn fit_shape_side(
+    deltas: &[f64],
+    exposures: &[ExposureInterval],
+    kind: &IntensityShapeKind,
+    criterion: &InformationCriterion,
+    piecewise_segments: usize,
+) -> Option<IntensityShapeFit> {
+    if deltas.len() < MIN_TRADES_FOR_ESTIMATION || exposures.is_empty() {
+        return None;
+    }
+    let histogram = ExposureHistogram::new(exposures);
+
+    let fit_kind = |kind: &IntensityShapeKind| -> Option<IntensityShapeFit> {
+        let shape = match kind {
+            IntensityShapeKind::Exponential => {
+                let (kappa, _) = estimate_mle_side_exposure(deltas, exposures)?;
+                IntensityShape::Exponential { kappa }
+            }
+            IntensityShapeKind::PowerLaw => fit_power_law(deltas, &histogram)?,
+            IntensityShapeKind::Piecewise => fit_piecewise(deltas, &histogram, piecewise_segments)?,
+            IntensityShapeKind::Auto => return None,
+        };
+        IntensityShapeFit::new(shape, deltas, exposures)
+    };
+
+    match kind {
+        IntensityShapeKind::Auto => [
+            IntensityShapeKind::Exponential,
+            IntensityShapeKind::PowerLaw,
+            IntensityShapeKind::Piecewise,
+        ]
+        .iter()
+        .filter_map(fit_kind)
+        .min_by(|a, b| a.criterion(criterion).total_cmp(&b.criterion(criterion))),
+        kind => fit_kind(kind),
+    }
+}
+
+/// Fit the configured intensity family per side (or pick the best one by AIC/BIC for `auto`).
+///
+/// Uses the same trade deltas and exposure as `fit_intensity_parameters`. Returns (bid, ask);
+/// a side is None when it has too few trades or no family fits.
+pub fn fit_intensity_shapes(
+    trades: &[CalibrationTrade],
+    orderbooks: &[OrderbookPoint],
+    window_end_ts: u64,
+    kind: &IntensityShapeKind,
+    criterion: &InformationCriterion,
+    piecewise_segments: usize,
+) -> (Option<IntensityShapeFit>, Option<IntensityShapeFit>) {
     if trades.is_empty() || orderbooks.is_empty() {
-        return (DEFAULT_KAPPA, DEFAULT_A, DEFAULT_KAPPA, DEFAULT_A);
+        return (None, None);
     }
 
-    // Collect trade deltas by side using the most recent book state
-    let bid_deltas = collect_trade_deltas(trades, orderbooks, true);
-    let ask_deltas = collect_trade_deltas(trades, orderbooks, false);
+    let fit_side = |is_bid: bool| {
+        let deltas = collect_trade_deltas(trades, orderbooks, is_bid);
+        let exposures = build_side_exposures(orderbooks, window_end_ts, is_bid);
+        fit_shape_side(&deltas, &exposures, kind, criterion, piecewise_segments)
+    };
 
-    // Build exposure integrals for each side
-    let bid_exposures = build_side_exposures(orderbooks, window_end_ts, true);
-    let ask_exposures = build_side_exposures(orderbooks, window_end_ts, false);
+    (fit_side(true), fit_side(false))
+}
 
-    // Fit both sides separately
-    let bid_fit = estimate_mle_side_exposure(&bid_deltas, &bid_exposures);
-    let ask_fit = estimate_mle_side_exposure(&ask_deltas, &ask_exposures);
+// ============================================================================
+// Hawkes Intensity
+// ============================================================================
 
-    // Return side-specific results (no averaging)
-    match (bid_fit, ask_fit) {
-        (Some((k_b, a_b)), Some((k_a, a_a))) => {
-            // Both sides have valid fits - return separate values
-            (k_b, a_b, k_a, a_a)
+/// Minimum arrivals per side for a Hawkes fit
+const MIN_EVENTS_FOR_HAWKES: usize = 20;
+/// Upper bound on the branching ratio (stationarity requires < 1)
+const MAX_BRANCHING_RATIO: f64 = 0.999;
+/// Kernel decay bounds in 1/s
+const HAWKES_DECAY_MIN: f64 = 1e-3;
+const HAWKES_DECAY_MAX: f64 = 1e3;
+
+/// Exponential-kernel Hawkes fit of one side's trade arrivals:
+/// λ(t) = μ + Σ n·β·e^(-β(t - tᵢ)).
+///
+/// The baseline's depth profile is fitted by `fit_hawkes_baseline_intensity` on trades
+/// weighted by the probability that their arrival was exogenous.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HawkesFit {
+    /// Baseline (exogenous) arrival rate μ in events per second
+    pub baseline: f64,
+    /// Expected number of arrivals triggered by each arrival (n < 1)
+    pub branching_ratio: f64,
+    /// Kernel decay β in 1/s (mean excitation lifetime 1/β)
+    pub decay: f64,
+    pub log_likelihood: f64,
+    pub events: usize,
+}
+
+impl HawkesFit {
+    /// Stationary mean arrival rate μ/(1 - n)
+    pub fn mean_rate(&self) -> f64 {
+        self.baseline / (1.0 - self.branching_ratio)
+    }
+
+    /// Factor converting the Poisson A (mean rate) into the baseline cluster rate, for
+    /// intensities not fitted under the baseline (shapes, queue depletion)
+    pub fn effective_intensity_scale(&self) -> f64 {
+        1.0 - self.branching_ratio
+    }
+
+    /// True when the branching ratio reaches `threshold`: flow is dominated by
+    /// self-excitation and rate estimates are unstable
+    pub fn is_near_critical(&self, threshold: f64) -> bool {
+        self.branching_ratio >= threshold
+    }
+}
+
+/// Hawkes log-likelihood over [0, horizon] with the O(N) recursion for the excitation sum
+fn hawkes_loglik(times: &[f64], horizon: f64, baseline: f64, branching: f64, decay: f64) -> f64 {
+    let mut loglik = 0.0;
+    let mut excitation = 0.0; // Σ_{j<i} e^(-β(tᵢ - tⱼ))
+    let mut prev: Option<f64> = None;
+
+    for &t in times {
+        if let Some(p) = prev {
+            excitation = (-decay * (t - p)).exp() * (1.0 + excitation);
+        }
+        let intensity = baseline + branching * decay * excitation;
+        if intensity <= 0.0 || !intensity.is_finite() {
+            return f64::NEG_INFINITY;
         }
-        (Some((k_b, a_b)), None) => {
-            // Only bid side has valid fit - use bid for both sides
-            (k_b, a_b, k_b, a_b)
+        loglik += intensity.ln();
+        prev = Some(t);
+    }
+
+    let compensator = baseline * horizon
+        + branching * times.iter().map(|t| 1.0 - (-decay * (horizon - t)).exp()).sum::<f64>();
+    loglik - compensator
+}
+
+/// Fit an exponential-kernel Hawkes process to arrival times (seconds from window start).
+///
+/// Times must be sorted and lie in [0, horizon]. Returns None with too few events.
+pub fn fit_hawkes(times: &[f64], horizon: f64) -> Option<HawkesFit> {
+    if times.len() < MIN_EVENTS_FOR_HAWKES || horizon <= 0.0 {
+        return None;
+    }
+
+    let rate = times.len() as f64 / horizon;
+    // μ = e^u0, n = MAX·logistic(u1), β = e^u2
+    let unpack = |u: &[f64]| (u[0].exp(), MAX_BRANCHING_RATIO * logistic(u[1]), u[2].exp());
+    let objective = |u: &[f64]| {
+        let (baseline, branching, decay) = unpack(u);
+        if !(HAWKES_DECAY_MIN..=HAWKES_DECAY_MAX).contains(&decay) {
+            return f64::INFINITY;
         }
-        (None, Some((k_a, a_a))) => {
-            // Only ask side has valid fit - use ask for both sides
-            (k_a, a_a, k_a, a_a)
+        -hawkes_loglik(times, horizon, baseline, branching, decay)
+    };
+
+    // Start halfway between Poisson and strongly clustered, with a 1s excitation lifetime
+    let x0 = [(0.5 * rate).ln(), logit(0.5 / MAX_BRANCHING_RATIO), 0.0];
+    let options = NelderMeadOptions { max_evaluations: 800, value_tolerance: 1e-10, ..Default::default() };
+    let minimum = nelder_mead(objective, &x0, options);
+    if !minimum.value.is_finite() {
+        return None;
+    }
+
+    let (baseline, branching_ratio, decay) = unpack(&minimum.x);
+    Some(HawkesFit {
+        baseline,
+        branching_ratio,
+        decay,
+        log_likelihood: -minimum.value,
+        events: times.len(),
+    })
+}
+
+/// Fit Hawkes processes to bid-side and ask-side trade arrivals over [window_start, window_end].
+///
+/// Prints sharing a timestamp on one side come from a single aggressive order and count as
+/// one arrival. Returns (bid, ask).
+pub fn fit_hawkes_intensity(
+    trades: &[CalibrationTrade],
+    window_start_ts: u64,
+    window_end_ts: u64,
+) -> (Option<HawkesFit>, Option<HawkesFit>) {
+    if window_end_ts <= window_start_ts {
+        return (None, None);
+    }
+    let horizon = (window_end_ts - window_start_ts) as f64 / 1000.0;
+    let side_times = |is_bid: bool| -> Vec<f64> {
+        hawkes_arrivals(trades, is_bid, window_start_ts, window_end_ts)
+            .into_iter()
+            .map(|ts| (ts - window_start_ts) as f64 / 1000.0)
+            .collect()
+    };
+
+    (fit_hawkes(&side_times(true), horizon), fit_hawkes(&side_times(false), horizon))
+}
+
+/// Sorted distinct arrival timestamps (ms) of one side inside the window; prints sharing a
+/// timestamp are one arrival
+fn hawkes_arrivals(trades: &[CalibrationTrade], is_bid: bool, window_start_ts: u64, window_end_ts: u64) -> Vec<u64> {
+    let mut times: Vec<u64> = trades
+        .iter()
+        .filter(|t| t.is_buyer_maker == is_bid)
+        .map(|t| t.timestamp)
+        .filter(|ts| (window_start_ts..=window_end_ts).contains(ts))
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+    times
+}
+
+/// Probability that each arrival is exogenous rather than triggered: μ/λ(tᵢ), with λ
+/// excited by the earlier arrivals only. `times` are sorted, in seconds.
+pub fn hawkes_baseline_probabilities(times: &[f64], fit: &HawkesFit) -> Vec<f64> {
+    let mut excitation = 0.0; // Σ_{j<i} e^(-β(tᵢ - tⱼ))
+    let mut prev: Option<f64> = None;
+    times
+        .iter()
+        .map(|&t| {
+            if let Some(p) = prev {
+                excitation = (-fit.decay * (t - p)).exp() * (1.0 + excitation);
+            }
+            prev = Some(t);
+            let intensity = fit.baseline + fit.branching_ratio * fit.decay * excitation;
+            if intensity > 0.0 && intensity.is_finite() { fit.baseline / intensity } else { 1.0 }
+        })
+        .collect()
+}
+
+/// Intensity fit of the Hawkes baseline: each trade counts with the probability that its
+/// arrival was exogenous (`hawkes_baseline_probabilities`), so κ is the depth decay of
+/// baseline arrivals and A their rate at δ=0. A side without a Hawkes fit is fitted as usual.
+pub fn fit_hawkes_baseline_intensity(
+    trades: &[CalibrationTrade],
+    orderbooks: &[OrderbookPoint],
+    window_start_ts: u64,
+    window_end_ts: u64,
+    bid_hawkes: Option<&HawkesFit>,
+    ask_hawkes: Option<&HawkesFit>,
+) -> IntensityFit {
+    let side = |is_bid: bool, hawkes: Option<&HawkesFit>| {
+        let weights_by_arrival = hawkes.map(|fit| {
+            let arrivals = hawkes_arrivals(trades, is_bid, window_start_ts, window_end_ts);
+            let seconds: Vec<f64> = arrivals.iter().map(|ts| (ts - window_start_ts) as f64 / 1000.0).collect();
+            (arrivals, hawkes_baseline_probabilities(&seconds, fit))
+        });
+        let (mut deltas, mut weights) = (Vec::new(), Vec::new());
+        visit_trade_deltas(trades, orderbooks, is_bid, |trade, delta| {
+            let weight = weights_by_arrival.as_ref().map_or(1.0, |(arrivals, probabilities)| {
+                arrivals.binary_search(&trade.timestamp).map_or(1.0, |i| probabilities[i])
+            });
+            deltas.push(delta);
+            weights.push(weight);
+        });
+        (deltas, weights)
+    };
+    let (bid_deltas, bid_weights) = side(true, bid_hawkes);
+    let (ask_deltas, ask_weights) = side(false, ask_hawkes);
+    fit_intensity_sides(
+        (&bid_deltas, Some(&bid_weights)),
+        (&ask_deltas, Some(&ask_weights)),
+        orderbooks,
+        window_end_ts,
+    )
+}
+
+// ============================================================================
+// Size-Aware Intensity
+// ============================================================================
+
+/// Merge prints into parent aggressor orders.
+///
+/// Consecutive same-side prints sharing a timestamp are one market order sweeping the book:
+/// the parent carries the summed quantity and the deepest price reached (lowest for bid hits,
+/// highest for ask lifts).
+pub fn aggregate_parent_orders(trades: &[CalibrationTrade]) -> Vec<CalibrationTrade> {
+    let mut parents: Vec<CalibrationTrade> = Vec::with_capacity(trades.len());
+    for trade in trades {
+        if let Some(parent) = parents.last_mut() {
+            if parent.timestamp == trade.timestamp && parent.is_buyer_maker == trade.is_buyer_maker {
+                parent.quantity += trade.quantity;
+                parent.price = if trade.is_buyer_maker {
+                    parent.price.min(trade.price)
+                } else {
+                    parent.price.max(trade.price)
+                };
+                continue;
+            }
         }
-        (None, None) => {
-            // Neither side has valid fit - use defaults
-            (DEFAULT_KAPPA, DEFAULT_A, DEFAULT_KAPPA, DEFAULT_A)
+        parents.push(trade.clone());
+    }
+    parents
+}
+
+/// Parent orders large enough to have filled a resting clip of `clip_notional` (quote currency).
+///
+/// A parent whose notional is below the clip would only have partially filled us, so it
+/// does not count as a fill at its depth.
+pub fn filter_clip_fills(trades: &[CalibrationTrade], clip_notional: Decimal) -> Vec<CalibrationTrade> {
+    aggregate_parent_orders(trades)
+        .into_iter()
+        .filter(|t| t.price * t.quantity >= clip_notional)
+        .collect()
+}
+
+/// Fraction of a resting clip of `clip_notional` that a parent order would fill, in [0, 1]
+pub fn clip_fill_fraction(parent: &CalibrationTrade, clip_notional: Decimal) -> f64 {
+    if clip_notional <= Decimal::ZERO {
+        return 1.0;
+    }
+    ((parent.price * parent.quantity) / clip_notional).to_f64().unwrap_or(0.0).clamp(0.0, 1.0)
+}
+
+/// Intensity fit on parent orders, each counted as the fraction of a `clip_notional` clip it
+/// would fill (see `clip_fill_fraction`), instead of dropping the parents below the clip.
+///
+/// κ is the weighted MLE and A the weighted fill rate: a window of parents that each fill
+/// half the clip has the depth decay of the unweighted fit and half its A.
+pub fn fit_clip_weighted_intensity(
+    trades: &[CalibrationTrade],
+    orderbooks: &[OrderbookPoint],
+    window_end_ts: u64,
+    clip_notional: Decimal,
+) -> IntensityFit {
+    let parents = aggregate_parent_orders(trades);
+    let side = |is_bid: bool| {
+        let (mut deltas, mut weights) = (Vec::new(), Vec::new());
+        visit_trade_deltas(&parents, orderbooks, is_bid, |parent, delta| {
+            let weight = clip_fill_fraction(parent, clip_notional);
+            if weight > 0.0 {
+                deltas.push(delta);
+                weights.push(weight);
+            }
+        });
+        (deltas, weights)
+    };
+    let ((bid_deltas, bid_weights), (ask_deltas, ask_weights)) = (side(true), side(false));
+    fit_intensity_sides(
+        (&bid_deltas, Some(&bid_weights)),
+        (&ask_deltas, Some(&ask_weights)),
+        orderbooks,
+        window_end_ts,
+    )
+}
+
+/// Intensity fit for parent orders with notional in [min_notional, max_notional)
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeBucketIntensity {
+    pub min_notional: f64,
+    pub max_notional: f64,
+    pub fit: IntensityFit,
+}
+
+/// Fit one intensity curve per parent-order size bucket.
+///
+/// `bucket_edges` are increasing notional edges; buckets are [0, e₀), [e₀, e₁), …, [e_last, ∞).
+/// Buckets with too few trades carry the usual fallback estimates (see `IntensityFitSource`).
+pub fn fit_intensity_by_size(
+    trades: &[CalibrationTrade],
+    orderbooks: &[OrderbookPoint],
+    window_end_ts: u64,
+    bucket_edges: &[f64],
+) -> Vec<SizeBucketIntensity> {
+    let parents = aggregate_parent_orders(trades);
+    let notionals: Vec<f64> = parents
+        .iter()
+        .map(|t| (t.price * t.quantity).to_f64().unwrap_or(0.0))
+        .collect();
+
+    let mut lower = 0.0;
+    let mut buckets = Vec::with_capacity(bucket_edges.len() + 1);
+    for upper in bucket_edges.iter().copied().chain(std::iter::once(f64::INFINITY)) {
+        if upper <= lower {
+            continue;
         }
+        let members: Vec<CalibrationTrade> = parents
+            .iter()
+            .zip(&notionals)
+            .filter(|(_, n)| (lower..upper).contains(*n))
+            .map(|(t, _)| t.clone())
+            .collect();
+        buckets.push(SizeBucketIntensity {
+            min_notional: lower,
+            max_notional: upper,
+            fit: fit_intensity_parameters_detailed(&members, orderbooks, window_end_ts),
+        });
+        lower = upper;
     }
+    buckets
 }
 
 // ============================================================================
@@ -590,6 +1762,7 @@ pub fn fit_intensity_parameters(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_rng::XorShift;
 
     #[test]
     fn test_volatility_empty() {
@@ -694,6 +1867,77 @@ mod tests {
         assert!(sigma > 0.0);
     }
 
+    #[test]
+    fn test_rolling_realized_matches_batch() {
+        let window_ms = 30_000;
+        let mut rolling = RollingRealizedVariance::new();
+        let mut prices: Vec<(u64, Decimal)> = Vec::new();
+        let mut p = 100.0f64;
+        let mut ts = 0u64;
+
+        for i in 0..500u64 {
+            ts += 250 + (i * 7919) % 1500;
+            p *= 1.0 + ((i * 104_729) % 21) as f64 * 1e-5 - 1e-4;
+            let price = Decimal::from_f64(p).unwrap();
+            rolling.push(ts, price);
+            rolling.prune(ts, window_ms);
+            prices.push((ts, price));
+            prices.retain(|(t, _)| ts.saturating_sub(*t) <= window_ms);
+
+            let batch = calculate_volatility(&prices);
+            assert!((rolling.sigma() - batch).abs() <= 1e-9 * batch.max(1e-12), "{} vs {}", rolling.sigma(), batch);
+        }
+    }
+
+    /// Simulated GARCH(1,1) returns with a deterministic xorshift/Box-Muller generator
+    fn simulate_garch(params: GarchParams, n: usize, seed: u64) -> Vec<f64> {
+        let mut rng = XorShift::new(seed);
+        let mut variance = params.omega / (1.0 - params.alpha - params.beta);
+        (0..n)
+            .map(|_| {
+                let z = rng.normal();
+                let r = variance.sqrt() * z;
+                variance = params.omega + params.alpha * r * r + params.beta * variance;
+                r
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fit_garch_recovers_parameters_and_warm_start_is_cheaper() {
+        let truth = GarchParams { omega: 1e-9, alpha: 0.10, beta: 0.85 };
+        let returns = simulate_garch(truth, 8000, 0x2545_F491_4F6C_DD1D);
+
+        let cold = fit_garch(&returns, None).unwrap();
+        assert!((cold.params.alpha - truth.alpha).abs() < 0.04, "{:?}", cold.params);
+        assert!((cold.params.beta - truth.beta).abs() < 0.06, "{:?}", cold.params);
+
+        // Refit on a shifted window, warm-started from the previous fit
+        let shifted = &returns[500..];
+        let warm = fit_garch(shifted, Some(cold.params)).unwrap();
+        let refit_cold = fit_garch(shifted, None).unwrap();
+        assert!(warm.evaluations <= refit_cold.evaluations, "{} > {}", warm.evaluations, refit_cold.evaluations);
+        assert!(warm.log_likelihood >= refit_cold.log_likelihood - 1e-3);
+    }
+
+    #[test]
+    fn test_garch_state_is_bounded() {
+        let mut state = GarchState::new(600);
+        let mut p = 100.0f64;
+        for (i, r) in simulate_garch(GarchParams { omega: 1e-9, alpha: 0.1, beta: 0.85 }, 5000, 7).iter().enumerate() {
+            p *= r.exp();
+            state.add_price(i as u64 * 1_000, Decimal::from_f64(p).unwrap());
+        }
+        assert_eq!(state.return_count(), 600);
+        state.refit().unwrap();
+        assert!(state.params().is_some());
+
+        // A long gap fills at most one lookback of flat returns; the variance keeps rolling
+        state.add_price(10_000_000_000, Decimal::from_f64(p).unwrap());
+        assert_eq!(state.return_count(), 600);
+        assert!(state.forecast_sigma().unwrap() > 0.0);
+    }
+
     #[test]
     fn test_build_fixed_step_returns_handles_irregular() {
         // Large gap should produce zeros between buckets, but still yield returns when price moves
@@ -721,11 +1965,13 @@ mod tests {
             trades.push(CalibrationTrade {
                 timestamp: 1_000 + i * 200,
                 price: Decimal::from_str("99.9").unwrap(),
+                quantity: Decimal::ONE,
                 is_buyer_maker: true, // hits bid
             });
             trades.push(CalibrationTrade {
                 timestamp: 2_000 + i * 200,
                 price: Decimal::from_str("100.1").unwrap(),
+                quantity: Decimal::ONE,
                 is_buyer_maker: false, // hits ask
             });
         }
@@ -757,10 +2003,347 @@ mod tests {
         assert!(ask_k > 0.0 && ask_a > 0.0);
     }
 
+    #[test]
+    fn test_exposure_histogram_matches_exact() {
+        // Deterministic spread of book states: best level 0.5..20 bps, far level 20..200 bps
+        let mut rng = XorShift::new(0xDEAD_BEEF_CAFE_F00D);
+        let exposures: Vec<ExposureInterval> = (0..20_000)
+            .map(|_| ExposureInterval {
+                duration_sec: 0.05 + rng.uniform(),
+                delta_min: 5e-5 + 2e-3 * rng.uniform(),
+                delta_max: 2e-3 + 1.8e-2 * rng.uniform(),
+            })
+            .collect();
+        let histogram = ExposureHistogram::new(&exposures);
+        assert!(histogram.lower.len() + histogram.upper.len() < 300);
+
+        // Up to KAPPA_MAX, where the in-bin expansion needs the finer bins
+        let kappas = || (0..=60).map(|i| 10f64.powf(i as f64 / 10.0)).take_while(|k| *k <= KAPPA_MAX * (1.0 + 1e-9));
+        assert_eq!(kappas().count(), 61);
+        for kappa in kappas() {
+            let exact = exposure_term(kappa, &exposures);
+            let binned = histogram.exposure(kappa);
+            assert!((binned / exact - 1.0).abs() < 1e-3, "kappa {}: {} vs {}", kappa, binned, exact);
+        }
+
+        // Book states concentrated on two tick levels inside one coarse bin: the coarse
+        // expansion alone is percent-level off at KAPPA_MAX
+        let ticks: Vec<ExposureInterval> = (0..2_000)
+            .map(|i| ExposureInterval {
+                duration_sec: 0.05 + rng.uniform(),
+                delta_min: if i % 2 == 0 { 1.00e-4 } else { 1.06e-4 },
+                delta_max: 1e-2,
+            })
+            .collect();
+        let histogram = ExposureHistogram::new(&ticks);
+        for kappa in kappas() {
+            let exact = exposure_term(kappa, &ticks);
+            let binned = histogram.exposure(kappa);
+            assert!((binned / exact - 1.0).abs() < 1e-3, "kappa {}: {} vs {}", kappa, binned, exact);
+            let shape = IntensityShape::Exponential { kappa };
+            let with = histogram.exposure_with(|d| shape.survival(d), |d| shape.hazard(d));
+            assert!((with / exact - 1.0).abs() < 1e-3, "kappa {}: {} vs {}", kappa, with, exact);
+        }
+
+        // The fitted kappa maximises the exact likelihood to within the grid resolution
+        let deltas: Vec<f64> = (0..500).map(|i| 2e-4 + (i % 50) as f64 * 4e-5).collect();
+        let (kappa, _) = estimate_mle_side_exposure(&deltas, &exposures).unwrap();
+        let stats = TradeDeltaStats::new(&deltas);
+        let exact_ll = |k: f64| stats.count * (k.ln() - exposure_term(k, &exposures).ln()) - k * stats.sum;
+        for factor in [0.98, 1.02] {
+            assert!(exact_ll(kappa) >= exact_ll(kappa * factor), "kappa {} not optimal", kappa);
+        }
+    }
+
+    /// Simulate an exponential-kernel Hawkes process by Ogata thinning
+    fn simulate_hawkes(baseline: f64, branching: f64, decay: f64, horizon: f64, seed: u64) -> Vec<f64> {
+        let mut rng = XorShift::new(seed);
+
+        let mut times = Vec::new();
+        let (mut t, mut excitation) = (0.0, 0.0); // excitation = Σ n·β·e^(-β(t - tᵢ))
+        loop {
+            let bound = baseline + excitation;
+            let wait = -rng.uniform().ln() / bound;
+            t += wait;
+            if t > horizon {
+                return times;
+            }
+            excitation *= (-decay * wait).exp();
+            if rng.uniform() * bound <= baseline + excitation {
+                times.push(t);
+                excitation += branching * decay;
+            }
+        }
+    }
+
+    #[test]
+    fn test_fit_hawkes_recovers_parameters() {
+        let times = simulate_hawkes(0.5, 0.6, 2.0, 20_000.0, 0x1234_5678_9ABC_DEF1);
+        let fit = fit_hawkes(&times, 20_000.0).unwrap();
+
+        assert!((fit.branching_ratio - 0.6).abs() < 0.06, "{:?}", fit);
+        assert!((fit.baseline / 0.5 - 1.0).abs() < 0.15, "{:?}", fit);
+        assert!((fit.decay / 2.0 - 1.0).abs() < 0.25, "{:?}", fit);
+        assert!(!fit.is_near_critical(0.9));
+        assert!((fit.mean_rate() - times.len() as f64 / 20_000.0).abs() / fit.mean_rate() < 0.1);
+    }
+
+    #[test]
+    fn test_hawkes_baseline_fits_depth_of_exogenous_arrivals() {
+        // Isolated deep arrivals every 10s, each followed by three shallow triggered ones
+        let depth = |i: usize, mean: f64| -mean * (1.0 - (i as f64 * 0.618_033_988_75).fract()).ln();
+        let mut trades = Vec::new();
+        for cluster in 0..300 {
+            let start = 1_000 + cluster as u64 * 10_000;
+            for (k, offset) in [0, 40, 80, 120].into_iter().enumerate() {
+                let mean = if k == 0 { 1e-3 } else { 1e-4 };
+                let delta = depth(cluster * 4 + k, mean).max(1e-6);
+                trades.push(CalibrationTrade {
+                    timestamp: start + offset,
+                    price: Decimal::from_f64(100.0 * (1.0 - delta)).unwrap(),
+                    quantity: Decimal::ONE,
+                    is_buyer_maker: true,
+                });
+            }
+        }
+        let end = 3_001_000;
+        let orderbooks = vec![OrderbookPoint {
+            timestamp: 0,
+            mid: Decimal::from(100),
+            bid_min: 0.0,
+            bid_max: 0.05,
+            ask_min: 0.0,
+            ask_max: 0.05,
+        }];
+
+        let (bid_hawkes, _) = fit_hawkes_intensity(&trades, 0, end);
+        let bid_hawkes = bid_hawkes.unwrap();
+        assert!(bid_hawkes.branching_ratio > 0.5, "{:?}", bid_hawkes);
+
+        let times: Vec<f64> = hawkes_arrivals(&trades, true, 0, end).iter().map(|ts| *ts as f64 / 1000.0).collect();
+        let probabilities = hawkes_baseline_probabilities(&times, &bid_hawkes);
+        assert_eq!(probabilities[0], 1.0);
+        assert!(probabilities[1] < 0.5 && probabilities[4] > 0.9, "{:?}", &probabilities[..5]);
+
+        // Poisson κ mixes in the shallow triggered trades; the baseline κ follows the deep ones
+        let poisson = fit_intensity_parameters_detailed(&trades, &orderbooks, end);
+        let baseline = fit_hawkes_baseline_intensity(&trades, &orderbooks, 0, end, Some(&bid_hawkes), None);
+        assert!(baseline.bid.kappa < 0.5 * poisson.bid.kappa, "{:?} vs {:?}", baseline.bid, poisson.bid);
+        assert!((baseline.bid.kappa * 1e-3 - 1.0).abs() < 0.3, "{:?}", baseline.bid);
+        assert!(baseline.bid.a < poisson.bid.a);
+    }
+
+    #[test]
+    fn test_fit_hawkes_poisson_has_low_branching() {
+        let times = simulate_hawkes(1.0, 0.0, 1.0, 5_000.0, 42);
+        let fit = fit_hawkes(&times, 5_000.0).unwrap();
+        // On Poisson data a slow kernel with small branching fits almost as well, so allow some slack
+        assert!(fit.branching_ratio < 0.2, "{:?}", fit);
+        assert!(fit_hawkes(&times[..5], 5_000.0).is_none());
+    }
+
+    /// Trade depths drawn from `shape` truncated to [0, delta_max], with a constant book
+    /// exposing [0, delta_max] for `seconds`
+    fn simulate_depths(shape: &IntensityShape, delta_max: f64, n: usize, seconds: f64) -> (Vec<f64>, Vec<ExposureInterval>) {
+        let floor = shape.survival(delta_max);
+        let deltas = (0..n)
+            .map(|i| {
+                // Stratified uniforms through the inverse survival (bisection)
+                let target = 1.0 - (1.0 - floor) * (i as f64 + 0.5) / n as f64;
+                let (mut low, mut high) = (0.0, delta_max);
+                for _ in 0..60 {
+                    let mid = 0.5 * (low + high);
+                    if shape.survival(mid) > target { low = mid } else { high = mid }
+                }
+                0.5 * (low + high)
+            })
+            .collect();
+        let exposures = (0..(seconds as usize))
+            .map(|_| ExposureInterval { duration_sec: 1.0, delta_min: 0.0, delta_max })
+            .collect();
+        (deltas, exposures)
+    }
+
+    #[test]
+    fn test_shape_selection_picks_generating_family() {
+        let bic = InformationCriterion::Bic;
+
+        let power_law = IntensityShape::PowerLaw { delta0: 1e-4, exponent: 1.2 };
+        let (deltas, exposures) = simulate_depths(&power_law, 0.02, 2000, 3600.0);
+        let fit = fit_shape_side(&deltas, &exposures, &IntensityShapeKind::Auto, &bic, 4).unwrap();
+        assert_eq!(fit.shape.name(), "power_law", "{:?}", fit);
+        if let IntensityShape::PowerLaw { exponent, .. } = fit.shape {
+            assert!((exponent / 1.2 - 1.0).abs() < 0.15, "{:?}", fit);
+        }
+        // A = n·(-S'(0)) / exposure-weighted survival
+        let expected_a = 2000.0 * power_law.density(0.0) / (3600.0 * (1.0 - power_law.survival(0.02)));
+        assert!((fit.a / expected_a - 1.0).abs() < 0.1, "{} vs {}", fit.a, expected_a);
+
+        let exponential = IntensityShape::Exponential { kappa: 2000.0 };
+        let (deltas, exposures) = simulate_depths(&exponential, 0.01, 2000, 3600.0);
+        let fit = fit_shape_side(&deltas, &exposures, &IntensityShapeKind::Auto, &bic, 4).unwrap();
+        assert_eq!(fit.shape.name(), "exponential", "{:?}", fit);
+    }
+
+    #[test]
+    fn test_intensity_standard_errors_and_ks() {
+        let kappa = 2000.0;
+        let (deltas, exposures) = simulate_depths(&IntensityShape::Exponential { kappa }, 0.01, 1600, 3600.0);
+        let fit = fit_side_detailed(&deltas, None, &exposures).unwrap();
+
+        // Exposure covers the whole tail: se(κ) ≈ κ/√n and, since A = nκ/T, se(A) ≈ √2·A/√n
+        assert!((fit.kappa_se / (fit.kappa / 40.0) - 1.0).abs() < 0.05, "{:?}", fit);
+        assert!((fit.a_se / (std::f64::consts::SQRT_2 * fit.a / 40.0) - 1.0).abs() < 0.05, "{:?}", fit);
+        assert!(fit.ks_statistic < 0.02, "{:?}", fit);
+        assert_eq!(fit.trades, 1600);
+
+        // An exponential fit to power-law depths is rejected by KS
+        let (deltas, exposures) = simulate_depths(&IntensityShape::PowerLaw { delta0: 1e-4, exponent: 1.2 }, 0.02, 1600, 3600.0);
+        let misfit = fit_side_detailed(&deltas, None, &exposures).unwrap();
+        assert!(misfit.ks_statistic > 0.1, "{:?}", misfit);
+    }
+
+    #[test]
+    fn test_intensity_fallback_sources() {
+        let orderbooks = vec![OrderbookPoint {
+            timestamp: 0,
+            mid: Decimal::from_str("100").unwrap(),
+            bid_min: 0.0005,
+            bid_max: 0.01,
+            ask_min: 0.0005,
+            ask_max: 0.01,
+        }];
+        // Only sellers hitting the bid
+        let trades: Vec<CalibrationTrade> = (0..10)
+            .map(|i| CalibrationTrade {
+                timestamp: 1_000 + i * 100,
+                price: Decimal::from_str("99.9").unwrap() - Decimal::from(i) / Decimal::from(100),
+                quantity: Decimal::ONE,
+                is_buyer_maker: true,
+            })
+            .collect();
+
+        let fit = fit_intensity_parameters_detailed(&trades, &orderbooks, 5_000);
+        assert_eq!(fit.bid.source, IntensityFitSource::Fitted);
+        assert_eq!(fit.ask.source, IntensityFitSource::OtherSide);
+        assert_eq!((fit.ask.kappa, fit.ask.trades), (fit.bid.kappa, 0));
+        assert!(fit.ask.kappa_se.is_nan() && fit.bid.kappa_se.is_finite(), "{:?}", fit);
+
+        let empty = fit_intensity_parameters_detailed(&[], &orderbooks, 5_000);
+        assert_eq!(empty.bid.source, IntensityFitSource::Default);
+        assert_eq!(empty.bid.kappa, DEFAULT_KAPPA);
+    }
+
     #[test]
     fn test_default_constants() {
         // Ensure defaults are reasonable
         assert!(DEFAULT_KAPPA > 0.0);
         assert!(DEFAULT_A > 0.0);
     }
+
+    #[test]
+    fn test_parent_aggregation_and_size_buckets() {
+        let dec = |s: &str| Decimal::from_str(s).unwrap();
+        let trade = |timestamp: u64, price: &str, quantity: &str, is_buyer_maker: bool| CalibrationTrade {
+            timestamp,
+            price: dec(price),
+            quantity: dec(quantity),
+            is_buyer_maker,
+        };
+        // One sell sweeping three bid levels, a small sell, then a buy at the same timestamp
+        let trades = vec![
+            trade(1_000, "99.9", "0.1", true),
+            trade(1_000, "99.8", "0.2", true),
+            trade(1_000, "99.7", "0.3", true),
+            trade(2_000, "99.9", "0.01", true),
+            trade(2_000, "100.1", "0.5", false),
+        ];
+
+        let parents = aggregate_parent_orders(&trades);
+        assert_eq!(parents.len(), 3);
+        assert_eq!(parents[0].quantity, dec("0.6"));
+        assert_eq!(parents[0].price, dec("99.7"));
+
+        // A $20 clip is only filled by the sweep ($59.82) and the buy ($50.05)
+        let fills = filter_clip_fills(&trades, dec("20"));
+        assert_eq!(fills.len(), 2);
+        assert!(fills.iter().all(|t| t.price * t.quantity >= dec("20")));
+
+        let orderbooks = vec![OrderbookPoint {
+            timestamp: 0,
+            mid: dec("100"),
+            bid_min: 0.0005,
+            bid_max: 0.01,
+            ask_min: 0.0005,
+            ask_max: 0.01,
+        }];
+        let buckets = fit_intensity_by_size(&trades, &orderbooks, 5_000, &[10.0, 55.0]);
+        let bid_counts: Vec<usize> = buckets.iter().map(|b| b.fit.bid.trades).collect();
+        let ask_counts: Vec<usize> = buckets.iter().map(|b| b.fit.ask.trades).collect();
+        assert_eq!(bid_counts, vec![1, 0, 1]);
+        assert_eq!(ask_counts, vec![0, 1, 0]);
+        assert_eq!(buckets[2].max_notional, f64::INFINITY);
+
+        // Weighting keeps every parent, the small sell as about 0.05 of a fill
+        assert!((clip_fill_fraction(&parents[1], dec("20")) - 0.04995).abs() < 1e-9);
+        assert_eq!(clip_fill_fraction(&parents[0], dec("20")), 1.0);
+        let weighted = fit_clip_weighted_intensity(&trades, &orderbooks, 5_000, dec("20"));
+        assert_eq!((weighted.bid.trades, weighted.ask.trades), (2, 1));
+    }
+
+    #[test]
+    fn test_clip_weights_scale_a_but_not_kappa() {
+        let kappa = 2000.0;
+        let (deltas, exposures) = simulate_depths(&IntensityShape::Exponential { kappa }, 0.01, 400, 3600.0);
+        let full = fit_side_detailed(&deltas, None, &exposures).unwrap();
+        let half = fit_side_detailed(&deltas, Some(&vec![0.5; deltas.len()]), &exposures).unwrap();
+        assert!((half.kappa / full.kappa - 1.0).abs() < 1e-6, "{:?} {:?}", half, full);
+        assert!((half.a / full.a - 0.5).abs() < 1e-6, "{:?} {:?}", half, full);
+        assert_eq!(half.trades, full.trades);
+    }
+
+    #[test]
+    fn test_intensity_diagnostics_histogram_and_likelihood() {
+        let kappa = 2000.0;
+        let (deltas, exposures) = simulate_depths(&IntensityShape::Exponential { kappa }, 0.01, 400, 3600.0);
+        let fit = fit_side_detailed(&deltas, None, &exposures).unwrap();
+        let edges = diagnostic_delta_edges();
+        assert_eq!(edges.len(), 21);
+        assert!((edges[20] - 0.1).abs() < 1e-12);
+
+        let exponential = |k: f64| IntensityShape::Exponential { kappa: k };
+        let diagnostics = side_diagnostics(&deltas, &exposures, &exponential(fit.kappa), Some(fit.a), &edges);
+        assert_eq!(diagnostics.delta_counts.iter().sum::<u64>(), 400);
+        assert!((diagnostics.exposure_seconds - 3600.0).abs() < 1e-6);
+        assert!(diagnostics.fitted_intensity.windows(2).all(|w| w[1] < w[0]));
+
+        // Matches the closed form n·ln A − κΣδ − (A/κ)·E
+        let sum: f64 = deltas.iter().sum();
+        let closed = 400.0 * fit.a.ln() - fit.kappa * sum - fit.a / fit.kappa * exposure_term(fit.kappa, &exposures);
+        assert!((diagnostics.log_likelihood - closed).abs() < 1e-6 * closed.abs(), "{} {}", diagnostics.log_likelihood, closed);
+
+        // The MLE beats nearby kappa values (with A re-profiled)
+        let at = |k: f64| side_diagnostics(&deltas, &exposures, &exponential(k), None, &edges).log_likelihood;
+        assert!(diagnostics.log_likelihood > at(fit.kappa * 1.2));
+        assert!(diagnostics.log_likelihood > at(fit.kappa / 1.2));
+    }
+
+    #[test]
+    fn test_intensity_diagnostics_score_the_fitted_shape() {
+        let shape = IntensityShape::PowerLaw { delta0: 1e-4, exponent: 1.5 };
+        let (deltas, exposures) = simulate_depths(&shape, 0.01, 400, 3600.0);
+        let edges = diagnostic_delta_edges();
+        let fit = IntensityShapeFit::new(shape.clone(), &deltas, &exposures).unwrap();
+
+        // With A profiled, the diagnostics reproduce the shape fit's likelihood and A
+        let diagnostics = side_diagnostics(&deltas, &exposures, &shape, None, &edges);
+        assert!((diagnostics.log_likelihood - fit.log_likelihood).abs() < 1e-6 * fit.log_likelihood.abs());
+        let centre = (edges[0] * edges[1]).sqrt();
+        assert!((diagnostics.fitted_intensity[0] - fit.a * shape.survival(centre)).abs() < 1e-9 * fit.a);
+
+        // and the power law scores better than an exponential on its own depths
+        let kappa = fit_side_detailed(&deltas, None, &exposures).unwrap().kappa;
+        let exponential = side_diagnostics(&deltas, &exposures, &IntensityShape::Exponential { kappa }, None, &edges);
+        assert!(diagnostics.log_likelihood > exponential.log_likelihood);
+    }
 }
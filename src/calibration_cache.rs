@@ -0,0 +1,664 @@
+//! Calibration results cached across backtests
+//!
+//! Calibration depends only on the data and the calibration settings, not on gamma, the
+//! horizon, fees or sizing, so a parameter sweep can run `CalibrationEngine` once and replay
+//! the resulting series in every backtest. Series are stored as parquet, one row per
+//! calibration, under a key built from a fingerprint of the data files (names, sizes and
+//! modification times), the loaded time range and the calibration settings.
+
+use crate::calibration::{
+    GarchParams, HawkesFit, IntensityFit, IntensityFitSource, SideIntensityFit, SizeBucketIntensity,
+};
+use crate::calibration_engine::{CalibrationEngine, CalibrationResult};
+use crate::data_loader::{DataEvent, DataLoader};
+use crate::intensity_shape::IntensityShape;
+use crate::model_types::{ASConfig, TradeEvent};
+use crate::orderbook_batch::OrderbookView;
+use crate::seasonality::{SeasonalAdjustment, SeasonalityProfile};
+use arrow::array::{Array, ArrayRef, Float64Array, Float64Builder, Int64Array, ListArray, ListBuilder, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use rust_decimal::Decimal;
+use serde_json::json;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+/// Bumped whenever the file layout or the calibration itself changes meaning
+const CACHE_FORMAT_VERSION: &str = "1";
+const KEY_METADATA: &str = "cache_key";
+const SETTINGS_METADATA: &str = "calibration_settings";
+
+const HEADLINE_FIELDS: [&str; 5] = ["volatility", "bid_kappa", "bid_a", "ask_kappa", "ask_a"];
+const GARCH_FIELDS: [&str; 3] = ["omega", "alpha", "beta"];
+const HAWKES_FIELDS: [&str; 5] = ["baseline", "branching_ratio", "decay", "log_likelihood", "events"];
+const SIDE_FIT_FIELDS: [&str; 7] = ["kappa", "a", "kappa_se", "a_se", "ks", "trades", "source"];
+const SEASONALITY_FIELDS: [&str; 4] = ["a_window", "a_next", "sigma_window", "sigma_next"];
+/// Packed size bucket: min and max notional, then the bid and ask fits
+const SIZE_BUCKET_STRIDE: usize = 2 + 2 * SIDE_FIT_FIELDS.len();
+
+/// 64-bit FNV-1a: stable across runs and platforms, unlike `DefaultHasher`
+pub(crate) struct Fnv64(pub(crate) u64);
+
+impl Fnv64 {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// Hash the name, size and modification time of a data file, or of every file in a directory
+pub(crate) fn hash_data_path(hasher: &mut Fnv64, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = if path.is_dir() {
+        fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    files.sort();
+
+    for file in files {
+        let metadata = fs::metadata(&file)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_nanos();
+        hasher.write(file.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default().as_bytes());
+        hasher.write(&metadata.len().to_le_bytes());
+        hasher.write(&modified.to_le_bytes());
+    }
+    Ok(())
+}
+
+/// The config fields calibration depends on, with the seasonality profile's contents hashed
+pub fn calibration_settings(config: &ASConfig) -> Result<serde_json::Value, Box<dyn Error>> {
+    let seasonality = match &config.seasonality_path {
+        Some(path) => {
+            let mut hasher = Fnv64::new();
+            hasher.write(&fs::read(path)?);
+            Some(format!("{:016x}", hasher.0))
+        }
+        None => None,
+    };
+    Ok(json!({
+        "calibration_window_seconds": config.calibration_window_seconds,
+        "recalibration_interval_seconds": config.recalibration_interval_seconds,
+        "volatility_estimator": config.volatility_estimator,
+        "ewma_halflife_seconds": config.ewma_halflife_seconds,
+        "range_bar_seconds": config.range_bar_seconds,
+        "tsrv_slow_scale": config.tsrv_slow_scale,
+        "garch_lookback_seconds": config.garch_lookback_seconds,
+        "intensity_model": config.intensity_model,
+        "intensity_source": config.intensity_source,
+        "intensity_shape": config.intensity_shape,
+        "intensity_selection_criterion": config.intensity_selection_criterion,
+        "intensity_piecewise_segments": config.intensity_piecewise_segments,
+        "size_aware_intensity": config.size_aware_intensity,
+        "intensity_clip_notional_usd": config.intensity_clip_notional_usd,
+        "intensity_clip_weighting": config.intensity_clip_weighting,
+        "intensity_size_buckets_usd": config.intensity_size_buckets_usd,
+        "parameter_smoothing": config.parameter_smoothing,
+        "smoothing_halflife_calibrations": config.smoothing_halflife_calibrations,
+        "kalman_process_std_log": config.kalman_process_std_log,
+        "intensity_shrinkage": config.intensity_shrinkage,
+        "seasonality": seasonality,
+    }))
+}
+
+/// Cache key of the calibration series for the loader's data files and time range and these settings
+pub fn cache_key(config: &ASConfig, loader: &DataLoader) -> Result<String, Box<dyn Error>> {
+    let mut hasher = Fnv64::new();
+    hasher.write(CACHE_FORMAT_VERSION.as_bytes());
+    hash_data_path(&mut hasher, loader.trades_path())?;
+    hash_data_path(&mut hasher, loader.orderbook_path())?;
+    let range = loader.time_range();
+    hasher.write(&range.start_ms.to_le_bytes());
+    hasher.write(&range.end_ms.to_le_bytes());
+    hasher.write(calibration_settings(config)?.to_string().as_bytes());
+    Ok(format!("{:016x}", hasher.0))
+}
+
+/// Parquet file holding the series for `key` inside `cache_dir`
+pub fn cache_path(cache_dir: &Path, key: &str) -> PathBuf {
+    cache_dir.join(format!("calibration_{}.parquet", key))
+}
+
+/// Where a backtest gets its calibrations: a live engine fed with the replayed events, or a
+/// precomputed series released at the timestamps it was computed at
+pub enum CalibrationSource {
+    Live(Box<CalibrationEngine>),
+    Cached { series: Arc<Vec<CalibrationResult>>, next: usize },
+}
+
+impl CalibrationSource {
+    /// Replay `cached` when given, otherwise calibrate live (loading the seasonality profile)
+    pub fn new(config: &ASConfig, cached: Option<Arc<Vec<CalibrationResult>>>) -> Result<Self, Box<dyn Error>> {
+        if let Some(series) = cached {
+            return Ok(Self::Cached { series, next: 0 });
+        }
+        let mut engine = CalibrationEngine::new(config);
+        if let Some(path) = &config.seasonality_path {
+            engine.set_seasonality(SeasonalityProfile::load(Path::new(path))?);
+        }
+        Ok(Self::Live(Box::new(engine)))
+    }
+
+    pub fn add_trade(&mut self, trade: &TradeEvent) {
+        if let Self::Live(engine) = self {
+            engine.add_trade(trade);
+        }
+    }
+
+    /// Add a snapshot and prune the windows to its timestamp
+    pub fn add_orderbook(&mut self, book: &OrderbookView, mid_price: Decimal) {
+        if let Self::Live(engine) = self {
+            engine.add_orderbook(book, mid_price);
+            engine.prune_windows(book.timestamp());
+        }
+    }
+
+    /// Calibration due at `current_ts`, if any
+    pub fn poll(&mut self, current_ts: u64, tick_size: f64) -> Option<CalibrationResult> {
+        match self {
+            Self::Live(engine) => {
+                if engine.should_recalibrate(current_ts) {
+                    engine.calibrate(current_ts, tick_size)
+                } else {
+                    None
+                }
+            }
+            Self::Cached { series, next } => {
+                let mut due = None;
+                while let Some(result) = series.get(*next).filter(|r| r.timestamp <= current_ts) {
+                    due = Some(result);
+                    *next += 1;
+                }
+                due.cloned()
+            }
+        }
+    }
+}
+
+/// Run the calibration engine over `events` exactly as `run_backtest` does and collect
+/// every calibration
+pub fn precompute_calibrations<I>(events: I, config: &ASConfig) -> Result<Vec<CalibrationResult>, Box<dyn Error>>
+where
+    I: IntoIterator<Item = Result<DataEvent, Box<dyn Error>>>,
+{
+    let mut results = Vec::new();
+    replay_calibrations(events, config, |_, result| results.push(result))?;
+    Ok(results)
+}
+
+/// Run the calibration engine over `events` exactly as `run_backtest` does, handing each
+/// calibration to `on_calibration` together with the engine that produced it
+pub fn replay_calibrations<I, F>(events: I, config: &ASConfig, mut on_calibration: F) -> Result<(), Box<dyn Error>>
+where
+    I: IntoIterator<Item = Result<DataEvent, Box<dyn Error>>>,
+    F: FnMut(&mut CalibrationEngine, CalibrationResult),
+{
+    let mut source = CalibrationSource::new(config, None)?;
+    let mut last_mid = Decimal::ZERO;
+    for event in events {
+        match event? {
+            DataEvent::Trade(trade) => source.add_trade(&trade),
+            DataEvent::Orderbook(row) => {
+                let book = row.view();
+                if let (Some(bid), Some(ask)) = (book.best_bid_decimal(), book.best_ask_decimal()) {
+                    if bid > Decimal::ZERO && ask > Decimal::ZERO {
+                        last_mid = (bid + ask) / Decimal::TWO;
+                    }
+                }
+                source.add_orderbook(&book, last_mid);
+                let due = source.poll(book.timestamp(), config.tick_size);
+                if let (Some(result), CalibrationSource::Live(engine)) = (due, &mut source) {
+                    on_calibration(engine, result);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Load the cached series for the loader's data and these settings, or compute it from the
+/// loader's stream and write it to `cache_dir`
+pub fn load_or_precompute(
+    cache_dir: &Path,
+    config: &ASConfig,
+    loader: &DataLoader,
+) -> Result<Vec<CalibrationResult>, Box<dyn Error>> {
+    let key = cache_key(config, loader)?;
+    let path = cache_path(cache_dir, &key);
+    if path.exists() {
+        match load_calibrations(&path, Some(&key)) {
+            Ok(results) => return Ok(results),
+            Err(e) => eprintln!("Ignoring calibration cache {:?}: {}", path, e),
+        }
+    }
+    let results = precompute_calibrations(loader.stream()?, config)?;
+    save_calibrations(&path, &key, config, &results)?;
+    Ok(results)
+}
+
+// =============================================================================
+// Parquet layout
+// =============================================================================
+
+fn fit_source_code(source: IntensityFitSource) -> f64 {
+    match source {
+        IntensityFitSource::Fitted => 0.0,
+        IntensityFitSource::OtherSide => 1.0,
+        IntensityFitSource::Default => 2.0,
+    }
+}
+
+fn fit_source_from_code(code: f64) -> Result<IntensityFitSource, Box<dyn Error>> {
+    match code as i64 {
+        0 => Ok(IntensityFitSource::Fitted),
+        1 => Ok(IntensityFitSource::OtherSide),
+        2 => Ok(IntensityFitSource::Default),
+        other => Err(format!("Unknown intensity fit source code {}", other).into()),
+    }
+}
+
+fn pack_side_fit(fit: &SideIntensityFit) -> Vec<f64> {
+    vec![fit.kappa, fit.a, fit.kappa_se, fit.a_se, fit.ks_statistic, fit.trades as f64, fit_source_code(fit.source)]
+}
+
+fn unpack_side_fit(values: &[f64]) -> Result<SideIntensityFit, Box<dyn Error>> {
+    Ok(SideIntensityFit {
+        kappa: values[0],
+        a: values[1],
+        kappa_se: values[2],
+        a_se: values[3],
+        ks_statistic: values[4],
+        trades: values[5] as usize,
+        source: fit_source_from_code(values[6])?,
+    })
+}
+
+fn pack_hawkes(fit: &HawkesFit) -> Vec<f64> {
+    vec![fit.baseline, fit.branching_ratio, fit.decay, fit.log_likelihood, fit.events as f64]
+}
+
+fn unpack_hawkes(values: &[f64]) -> HawkesFit {
+    HawkesFit {
+        baseline: values[0],
+        branching_ratio: values[1],
+        decay: values[2],
+        log_likelihood: values[3],
+        events: values[4] as usize,
+    }
+}
+
+/// Shape parameters in declaration order; piecewise stores the knots then the hazards
+fn shape_params(shape: &IntensityShape) -> Vec<f64> {
+    match shape {
+        IntensityShape::Exponential { kappa } => vec![*kappa],
+        IntensityShape::PowerLaw { delta0, exponent } => vec![*delta0, *exponent],
+        IntensityShape::Piecewise { knots, hazards } => knots.iter().chain(hazards).copied().collect(),
+    }
+}
+
+fn shape_from_params(name: &str, params: &[f64]) -> Result<IntensityShape, Box<dyn Error>> {
+    match (name, params.len()) {
+        ("exponential", 1) => Ok(IntensityShape::Exponential { kappa: params[0] }),
+        ("power_law", 2) => Ok(IntensityShape::PowerLaw { delta0: params[0], exponent: params[1] }),
+        ("piecewise", n) if n > 0 && n.is_multiple_of(2) => {
+            let (knots, hazards) = params.split_at(n / 2);
+            Ok(IntensityShape::Piecewise { knots: knots.to_vec(), hazards: hazards.to_vec() })
+        }
+        _ => Err(format!("Invalid intensity shape '{}' with {} parameters", name, params.len()).into()),
+    }
+}
+
+fn pack_size_buckets(buckets: &[SizeBucketIntensity]) -> Vec<f64> {
+    let mut packed = Vec::with_capacity(buckets.len() * SIZE_BUCKET_STRIDE);
+    for bucket in buckets {
+        packed.push(bucket.min_notional);
+        packed.push(bucket.max_notional);
+        packed.extend(pack_side_fit(&bucket.fit.bid));
+        packed.extend(pack_side_fit(&bucket.fit.ask));
+    }
+    packed
+}
+
+fn unpack_size_buckets(packed: &[f64]) -> Result<Vec<SizeBucketIntensity>, Box<dyn Error>> {
+    if !packed.len().is_multiple_of(SIZE_BUCKET_STRIDE) {
+        return Err(format!("Size bucket column length {} is not a multiple of {}", packed.len(), SIZE_BUCKET_STRIDE).into());
+    }
+    let side = SIDE_FIT_FIELDS.len();
+    packed
+        .chunks(SIZE_BUCKET_STRIDE)
+        .map(|chunk| {
+            Ok(SizeBucketIntensity {
+                min_notional: chunk[0],
+                max_notional: chunk[1],
+                fit: IntensityFit { bid: unpack_side_fit(&chunk[2..2 + side])?, ask: unpack_side_fit(&chunk[2 + side..])? },
+            })
+        })
+        .collect()
+}
+
+fn column_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() { name.to_string() } else { format!("{}_{}", prefix, name) }
+}
+
+/// One nullable Float64 column per field; a row is null when its group is absent
+fn push_group(rows: &[Option<Vec<f64>>], prefix: &str, names: &[&str], fields: &mut Vec<Field>, columns: &mut Vec<ArrayRef>) {
+    for (j, name) in names.iter().enumerate() {
+        fields.push(Field::new(column_name(prefix, name), DataType::Float64, true));
+        columns.push(Arc::new(Float64Array::from(
+            rows.iter().map(|r| r.as_ref().map(|values| values[j])).collect::<Vec<_>>(),
+        )));
+    }
+}
+
+fn push_list(rows: &[Vec<f64>], name: &str, fields: &mut Vec<Field>, columns: &mut Vec<ArrayRef>) {
+    let mut builder = ListBuilder::new(Float64Builder::new());
+    for values in rows {
+        builder.values().append_slice(values);
+        builder.append(true);
+    }
+    fields.push(Field::new(name, DataType::List(Arc::new(Field::new("item", DataType::Float64, true))), false));
+    columns.push(Arc::new(builder.finish()));
+}
+
+fn float_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a Float64Array, Box<dyn Error>> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<Float64Array>())
+        .ok_or_else(|| format!("Missing or invalid column '{}'", name).into())
+}
+
+fn read_group(batch: &RecordBatch, prefix: &str, names: &[&str]) -> Result<Vec<Option<Vec<f64>>>, Box<dyn Error>> {
+    let columns = names
+        .iter()
+        .map(|name| float_column(batch, &column_name(prefix, name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((0..batch.num_rows())
+        .map(|i| (!columns[0].is_null(i)).then(|| columns.iter().map(|c| c.value(i)).collect()))
+        .collect())
+}
+
+fn read_list(batch: &RecordBatch, name: &str) -> Result<Vec<Vec<f64>>, Box<dyn Error>> {
+    let list = batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<ListArray>())
+        .ok_or_else(|| format!("Missing or invalid column '{}'", name))?;
+    (0..list.len())
+        .map(|i| {
+            let values = list.value(i);
+            let values = values
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .ok_or_else(|| format!("Column '{}' does not hold floats", name))?;
+            Ok(values.values().to_vec())
+        })
+        .collect()
+}
+
+fn read_strings(batch: &RecordBatch, name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let column = batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+        .ok_or_else(|| format!("Missing or invalid column '{}'", name))?;
+    Ok((0..column.len()).map(|i| column.value(i).to_string()).collect())
+}
+
+/// Write a calibration series with its key and settings in the file metadata. The file is
+/// written under a temporary name and renamed into place, so readers never see a partial series.
+pub fn save_calibrations(path: &Path, key: &str, config: &ASConfig, results: &[CalibrationResult]) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    let temp_path = path.with_extension("parquet.tmp");
+    match write_calibrations(&temp_path, key, config, results) {
+        Ok(()) => {
+            fs::rename(&temp_path, path)?;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+fn write_calibrations(path: &Path, key: &str, config: &ASConfig, results: &[CalibrationResult]) -> Result<(), Box<dyn Error>> {
+
+    let mut fields = vec![Field::new("timestamp", DataType::Int64, false)];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(Int64Array::from(
+        results.iter().map(|r| r.timestamp as i64).collect::<Vec<_>>(),
+    ))];
+
+    let group = |f: &dyn Fn(&CalibrationResult) -> Option<Vec<f64>>| results.iter().map(f).collect::<Vec<_>>();
+    push_group(&group(&|r| Some(vec![r.volatility, r.bid_kappa, r.bid_a, r.ask_kappa, r.ask_a])), "", &HEADLINE_FIELDS, &mut fields, &mut columns);
+    push_group(&group(&|r| r.garch.map(|g| vec![g.omega, g.alpha, g.beta])), "garch", &GARCH_FIELDS, &mut fields, &mut columns);
+    push_group(&group(&|r| r.bid_hawkes.as_ref().map(pack_hawkes)), "bid_hawkes", &HAWKES_FIELDS, &mut fields, &mut columns);
+    push_group(&group(&|r| r.ask_hawkes.as_ref().map(pack_hawkes)), "ask_hawkes", &HAWKES_FIELDS, &mut fields, &mut columns);
+    push_group(&group(&|r| Some(pack_side_fit(&r.intensity_fit.bid))), "bid_fit", &SIDE_FIT_FIELDS, &mut fields, &mut columns);
+    push_group(&group(&|r| Some(pack_side_fit(&r.intensity_fit.ask))), "ask_fit", &SIDE_FIT_FIELDS, &mut fields, &mut columns);
+    push_group(
+        &group(&|r| r.seasonality.map(|s| vec![s.a_window, s.a_next, s.sigma_window, s.sigma_next])),
+        "seasonality",
+        &SEASONALITY_FIELDS,
+        &mut fields,
+        &mut columns,
+    );
+
+    for (side, shape) in [("bid", (|r: &CalibrationResult| &r.bid_shape) as fn(&CalibrationResult) -> &IntensityShape), ("ask", |r| &r.ask_shape)] {
+        fields.push(Field::new(format!("{}_shape", side), DataType::Utf8, false));
+        columns.push(Arc::new(StringArray::from(results.iter().map(|r| shape(r).name()).collect::<Vec<_>>())));
+        let params: Vec<Vec<f64>> = results.iter().map(|r| shape_params(shape(r))).collect();
+        push_list(&params, &format!("{}_shape_params", side), &mut fields, &mut columns);
+    }
+    let buckets: Vec<Vec<f64>> = results.iter().map(|r| pack_size_buckets(&r.size_buckets)).collect();
+    push_list(&buckets, "size_buckets", &mut fields, &mut columns);
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::try_new(3)?))
+        .set_key_value_metadata(Some(vec![
+            KeyValue::new(KEY_METADATA.to_string(), key.to_string()),
+            KeyValue::new(SETTINGS_METADATA.to_string(), calibration_settings(config)?.to_string()),
+        ]))
+        .build();
+
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, Some(props))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+/// Read a calibration series, checking its key when `expected_key` is given
+pub fn load_calibrations(path: &Path, expected_key: Option<&str>) -> Result<Vec<CalibrationResult>, Box<dyn Error>> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+    if let Some(expected) = expected_key {
+        let stored = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .and_then(|kv| kv.iter().find(|e| e.key == KEY_METADATA).and_then(|e| e.value.clone()));
+        if stored.as_deref() != Some(expected) {
+            return Err(format!("{:?} was written for cache key {:?}, expected {}", path, stored, expected).into());
+        }
+    }
+
+    let mut results = Vec::new();
+    for batch in builder.build()? {
+        let batch = batch?;
+        let timestamps = batch
+            .column_by_name("timestamp")
+            .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+            .ok_or("Missing or invalid column 'timestamp'")?;
+        let headline = read_group(&batch, "", &HEADLINE_FIELDS)?;
+        let garch = read_group(&batch, "garch", &GARCH_FIELDS)?;
+        let bid_hawkes = read_group(&batch, "bid_hawkes", &HAWKES_FIELDS)?;
+        let ask_hawkes = read_group(&batch, "ask_hawkes", &HAWKES_FIELDS)?;
+        let bid_fit = read_group(&batch, "bid_fit", &SIDE_FIT_FIELDS)?;
+        let ask_fit = read_group(&batch, "ask_fit", &SIDE_FIT_FIELDS)?;
+        let seasonality = read_group(&batch, "seasonality", &SEASONALITY_FIELDS)?;
+        let bid_shape = read_strings(&batch, "bid_shape")?;
+        let ask_shape = read_strings(&batch, "ask_shape")?;
+        let bid_shape_params = read_list(&batch, "bid_shape_params")?;
+        let ask_shape_params = read_list(&batch, "ask_shape_params")?;
+        let size_buckets = read_list(&batch, "size_buckets")?;
+
+        for i in 0..batch.num_rows() {
+            let (Some(h), Some(bid), Some(ask)) = (&headline[i], &bid_fit[i], &ask_fit[i]) else {
+                return Err(format!("Row {} of {:?} has null calibration values", i, path).into());
+            };
+            results.push(CalibrationResult {
+                timestamp: timestamps.value(i) as u64,
+                volatility: h[0],
+                bid_kappa: h[1],
+                bid_a: h[2],
+                ask_kappa: h[3],
+                ask_a: h[4],
+                garch: garch[i].as_ref().map(|g| GarchParams { omega: g[0], alpha: g[1], beta: g[2] }),
+                bid_hawkes: bid_hawkes[i].as_deref().map(unpack_hawkes),
+                ask_hawkes: ask_hawkes[i].as_deref().map(unpack_hawkes),
+                bid_shape: shape_from_params(&bid_shape[i], &bid_shape_params[i])?,
+                ask_shape: shape_from_params(&ask_shape[i], &ask_shape_params[i])?,
+                intensity_fit: IntensityFit { bid: unpack_side_fit(bid)?, ask: unpack_side_fit(ask)? },
+                size_buckets: unpack_size_buckets(&size_buckets[i])?,
+                seasonality: seasonality[i].as_ref().map(|s| SeasonalAdjustment {
+                    a_window: s[0],
+                    a_next: s[1],
+                    sigma_window: s[2],
+                    sigma_next: s[3],
+                }),
+            });
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_loader::OrderbookSnapshot;
+    use crate::orderbook_batch::OrderbookRow;
+
+    /// One-second snapshots around a drifting mid with trades hitting both sides
+    fn synthetic_events(seconds: u64) -> Vec<DataEvent> {
+        let mut events = Vec::new();
+        for s in 0..seconds {
+            let ts = 1_700_000_000_000 + s * 1000;
+            let mid = Decimal::from(2000) + Decimal::from((s * 7) % 13) / Decimal::from(10);
+            let half = Decimal::new(5, 2);
+            events.push(DataEvent::Orderbook(OrderbookRow::from_snapshot(&OrderbookSnapshot {
+                timestamp: ts,
+                bids: vec![(mid - half, Decimal::ONE), (mid - half * Decimal::TWO, Decimal::TWO)],
+                asks: vec![(mid + half, Decimal::ONE), (mid + half * Decimal::TWO, Decimal::TWO)],
+            })));
+            if s % 3 == 0 {
+                let depth = Decimal::from(s % 5 + 1) / Decimal::from(20);
+                let is_buyer_maker = s.is_multiple_of(2);
+                let price = if is_buyer_maker { mid - depth } else { mid + depth };
+                events.push(DataEvent::Trade(TradeEvent { timestamp: ts + 500, price, quantity: Decimal::ONE, is_buyer_maker }));
+            }
+        }
+        events
+    }
+
+    fn test_config() -> ASConfig {
+        ASConfig { calibration_window_seconds: 600, recalibration_interval_seconds: 60, ..ASConfig::default() }
+    }
+
+    #[test]
+    fn test_cached_series_replays_live_calibrations() {
+        let config = test_config();
+        let events = synthetic_events(1800);
+        let series = precompute_calibrations(events.iter().cloned().map(Ok), &config).unwrap();
+        assert!(series.len() > 10);
+
+        let mut cached = CalibrationSource::new(&config, Some(Arc::new(series.clone()))).unwrap();
+        let mut replayed = Vec::new();
+        for event in &events {
+            if let DataEvent::Orderbook(row) = event {
+                replayed.extend(cached.poll(row.timestamp(), config.tick_size));
+            }
+        }
+        assert_eq!(format!("{:?}", replayed), format!("{:?}", series));
+
+        // A backtest on the cached series matches one calibrating live
+        let run = |calibrations: Option<Arc<Vec<CalibrationResult>>>| {
+            crate::backtest_engine::run_backtest(crate::backtest_engine::BacktestParams {
+                data_stream: events.iter().cloned().map(Ok),
+                config: config.clone(),
+                initial_capital: Decimal::from(1000),
+                order_notional: Decimal::from(20),
+                output_csv_path: None,
+                verbose: false,
+                lead_lag: None,
+                calibrations,
+            })
+            .unwrap()
+        };
+        let (live, replay) = (run(None), run(Some(Arc::new(series))));
+        assert_eq!(live.final_pnl, replay.final_pnl);
+        assert_eq!((live.bid_fills, live.ask_fills), (replay.bid_fills, replay.ask_fills));
+    }
+
+    #[test]
+    fn test_parquet_round_trip_and_key() {
+        let config = test_config();
+        let mut series = precompute_calibrations(synthetic_events(900).into_iter().map(Ok), &config).unwrap();
+        // Exercise the optional and packed columns
+        let first = &mut series[0];
+        first.garch = Some(GarchParams { omega: 1e-9, alpha: 0.05, beta: 0.9 });
+        first.bid_hawkes = Some(HawkesFit { baseline: 0.2, branching_ratio: 0.4, decay: 1.5, log_likelihood: -12.0, events: 40 });
+        first.ask_shape = IntensityShape::Piecewise { knots: vec![0.0, 1e-4], hazards: vec![3000.0, 800.0] };
+        first.size_buckets = vec![SizeBucketIntensity { min_notional: 100.0, max_notional: f64::INFINITY, fit: first.intensity_fit }];
+        first.seasonality = Some(SeasonalAdjustment { a_window: 0.8, a_next: 1.2, sigma_window: 0.9, sigma_next: 1.1 });
+
+        let dir = std::env::temp_dir().join(format!("calibration_cache_test_{}", std::process::id()));
+        let data = dir.join("trades.csv");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&data, "timestamp_ms,price,quantity,is_buyer_maker\n").unwrap();
+
+        let loader = DataLoader::new(&data, &data);
+        let key = cache_key(&config, &loader).unwrap();
+        let path = cache_path(&dir, &key);
+        save_calibrations(&path, &key, &config, &series).unwrap();
+        let loaded = load_calibrations(&path, Some(&key)).unwrap();
+        assert_eq!(format!("{:?}", loaded), format!("{:?}", series));
+        assert!(!path.with_extension("parquet.tmp").exists());
+
+        // An unreadable cache file is recomputed and replaced
+        fs::write(&path, b"not parquet").unwrap();
+        assert!(load_or_precompute(&dir, &config, &loader).unwrap().is_empty());
+        assert!(load_calibrations(&path, Some(&key)).unwrap().is_empty());
+
+        // Settings, range and data changes give a new key; a stale file is rejected
+        let wider = ASConfig { calibration_window_seconds: 1200, ..test_config() };
+        assert_ne!(cache_key(&wider, &loader).unwrap(), key);
+        let gamma = ASConfig { risk_aversion_gamma: 0.05, ..test_config() };
+        assert_eq!(cache_key(&gamma, &loader).unwrap(), key);
+        let day = DataLoader::new(&data, &data).with_time_range(0, 86_400_000);
+        assert_ne!(cache_key(&config, &day).unwrap(), key);
+        fs::write(&data, "timestamp_ms,price,quantity,is_buyer_maker\n1,2,3,true\n").unwrap();
+        assert_ne!(cache_key(&config, &loader).unwrap(), key);
+        assert!(load_calibrations(&path, Some("0000000000000000")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,11 +4,25 @@
 /// of prices and trades, and performs periodic recalibration of volatility (σ) and
 /// intensity parameters (κ, A).
 
-use crate::calibration::{calculate_volatility, fit_intensity_parameters, forecast_garch_volatility, CalibrationTrade, OrderbookPoint};
-use crate::data_loader::OrderbookSnapshot;
-use crate::model_types::{ASConfig, TradeEvent};
+use crate::calibration::{
+    filter_clip_fills, fit_clip_weighted_intensity, fit_hawkes_baseline_intensity, fit_hawkes_intensity,
+    fit_intensity_by_size, fit_intensity_parameters_detailed,
+    collect_trade_deltas, fit_intensity_shapes, side_intensity_diagnostics, CalibrationTrade, GarchParams, HawkesFit,
+    IntensityDiagnostics, IntensityFit, OrderbookPoint, RollingRealizedVariance, SideIntensityFit, SizeBucketIntensity,
+};
+use crate::orderbook_batch::OrderbookView;
+use crate::intensity_shape::IntensityShape;
+use crate::model_types::{
+    ASConfig, ClipWeighting, InformationCriterion, IntensityModel, IntensityShapeKind, IntensitySource, TradeEvent,
+    DEFAULT_CLIP_NOTIONAL_USD,
+};
+use crate::parameter_smoothing::ParameterSmoother;
+use crate::queue_depletion::QueueDepletionTracker;
+use crate::seasonality::{SeasonalAdjustment, SeasonalityProfile};
+use crate::volatility::{build_estimator, VolatilityEstimator, VolatilityInputs};
 use rust_decimal::Decimal;
 use rust_decimal::prelude::*;
+use std::collections::VecDeque;
 
 /// Minimum number of price observations required for calibration
 const MIN_PRICES_FOR_CALIBRATION: usize = 10;
@@ -28,20 +42,54 @@ pub struct CalibrationResult {
     pub ask_kappa: f64,
     /// Intensity scale A for ask side (trades per second at δ=0)
     pub ask_a: f64,
+    /// GARCH(1,1) parameters of the last fit (GARCH estimator only)
+    pub garch: Option<GarchParams>,
+    /// Hawkes fits per side (Hawkes intensity model only); κ and A then describe the baseline
+    /// arrivals
+    pub bid_hawkes: Option<HawkesFit>,
+    pub ask_hawkes: Option<HawkesFit>,
+    /// Intensity shape per side used for quoting (exponential with the kappa above by default)
+    pub bid_shape: IntensityShape,
+    pub ask_shape: IntensityShape,
+    /// This window's exponential fit per side: standard errors, KS statistic, sample size and
+    /// which fallback (if any) produced kappa/A
+    pub intensity_fit: IntensityFit,
+    /// Per-size-bucket intensity curves (empty unless `intensity_size_buckets_usd` is set)
+    pub size_buckets: Vec<SizeBucketIntensity>,
+    /// Hour-of-week factors applied to σ and A (only with a seasonality profile)
+    pub seasonality: Option<SeasonalAdjustment>,
+}
+
+impl CalibrationResult {
+    /// Hawkes branching ratio per side (bid, ask), NaN for a side without a Hawkes fit
+    pub fn branching_ratios(&self) -> (f64, f64) {
+        let ratio = |fit: &Option<HawkesFit>| fit.map(|f| f.branching_ratio).unwrap_or(f64::NAN);
+        (ratio(&self.bid_hawkes), ratio(&self.ask_hawkes))
+    }
+
+    /// True when either side's Hawkes fit is near-critical (see `HawkesFit::is_near_critical`)
+    pub fn hawkes_near_critical(&self, threshold: f64) -> bool {
+        [&self.bid_hawkes, &self.ask_hawkes]
+            .iter()
+            .any(|fit| fit.is_some_and(|f| f.is_near_critical(threshold)))
+    }
 }
 
 /// Stateful calibration engine for AS model
 ///
 /// Manages rolling windows of prices and trades, and performs periodic recalibration.
+///
+/// Windows are time-ordered ring buffers evicted from the front, so pruning is O(1)
+/// amortized per event and realized volatility comes from running sums.
 pub struct CalibrationEngine {
     /// Rolling window of prices for volatility calculation (timestamp, price)
-    calibration_prices: Vec<(u64, Decimal)>,
-    /// Growing history of prices for GARCH forecasting (no pruning to avoid forward-looking bias)
-    full_price_history: Vec<(u64, Decimal)>,
+    calibration_prices: VecDeque<(u64, Decimal)>,
+    /// Running realized-variance sums over the price window
+    realized_variance: RollingRealizedVariance,
     /// Rolling window of orderbook exposure points for intensity fitting
-    orderbook_points: Vec<OrderbookPoint>,
+    orderbook_points: VecDeque<OrderbookPoint>,
     /// Rolling window of trades for intensity parameter fitting (lightweight struct)
-    window_trades: Vec<CalibrationTrade>,
+    window_trades: VecDeque<CalibrationTrade>,
     /// Current kappa parameter for bid side
     bid_kappa: f64,
     /// Current A parameter for bid side
@@ -50,12 +98,36 @@ pub struct CalibrationEngine {
     ask_kappa: f64,
     /// Current A parameter for ask side
     ask_a: f64,
+    /// Current intensity shapes (exponential unless another family is configured)
+    bid_shape: IntensityShape,
+    ask_shape: IntensityShape,
     /// Timestamp of last calibration
     last_calibration_ts: Option<u64>,
     /// Calibration window size in milliseconds
     calibration_window_ms: u64,
     /// Recalibration interval in milliseconds
     recalibration_interval_ms: u64,
+    /// Volatility estimator selected in the config
+    volatility_estimator: Box<dyn VolatilityEstimator>,
+    /// Arrival model for the intensity A
+    intensity_model: IntensityModel,
+    /// Intensity family fitted per side, and how `auto` picks one
+    intensity_shape: IntensityShapeKind,
+    selection_criterion: InformationCriterion,
+    piecewise_segments: usize,
+    /// Optional smoothing/shrinkage of kappa and A across windows
+    smoother: Option<ParameterSmoother>,
+    /// Clip notional for size-aware calibration (None = every print counts)
+    clip_notional: Option<Decimal>,
+    /// Threshold or weight parents by the clip in the κ/A fit; shape, Hawkes and diagnostic
+    /// fits always use the parents that fill the clip
+    clip_weighting: ClipWeighting,
+    /// Notional edges of the per-size-bucket intensity fits
+    size_bucket_edges: Vec<f64>,
+    /// Execution/cancellation split of book depletions (queue-depletion intensity source only)
+    queue_depletion: Option<QueueDepletionTracker>,
+    /// Hour-of-week multipliers used to deseasonalise window estimates
+    seasonality: Option<SeasonalityProfile>,
 }
 
 impl CalibrationEngine {
@@ -70,45 +142,78 @@ impl CalibrationEngine {
         let estimated_trades = estimated_prices * 10; // Rough estimate
 
         Self {
-            calibration_prices: Vec::with_capacity(estimated_prices),
-            full_price_history: Vec::with_capacity(estimated_prices),
-            orderbook_points: Vec::with_capacity(estimated_prices),
-            window_trades: Vec::with_capacity(estimated_trades),
+            calibration_prices: VecDeque::with_capacity(estimated_prices),
+            realized_variance: RollingRealizedVariance::new(),
+            orderbook_points: VecDeque::with_capacity(estimated_prices),
+            window_trades: VecDeque::with_capacity(estimated_trades),
             bid_kappa: 100.0,  // Default starting value (dimensionless, in return space)
             bid_a: 10.0,       // Default starting value (trades per second at δ=0)
             ask_kappa: 100.0,  // Default starting value (dimensionless, in return space)
             ask_a: 10.0,       // Default starting value (trades per second at δ=0)
+            bid_shape: IntensityShape::Exponential { kappa: 100.0 },
+            ask_shape: IntensityShape::Exponential { kappa: 100.0 },
             last_calibration_ts: None,
             calibration_window_ms,
             recalibration_interval_ms,
+            volatility_estimator: build_estimator(config),
+            intensity_model: config.intensity_model.clone(),
+            intensity_shape: config.intensity_shape.clone(),
+            selection_criterion: config.intensity_selection_criterion.clone(),
+            piecewise_segments: config.intensity_piecewise_segments,
+            smoother: ParameterSmoother::is_enabled(config).then(|| ParameterSmoother::new(config)),
+            clip_notional: config.size_aware_intensity.then(|| {
+                let clip = config.intensity_clip_notional_usd.unwrap_or(DEFAULT_CLIP_NOTIONAL_USD);
+                Decimal::from_f64(clip).unwrap_or(Decimal::ZERO)
+            }),
+            clip_weighting: config.intensity_clip_weighting.clone(),
+            size_bucket_edges: config.intensity_size_buckets_usd.clone(),
+            queue_depletion: (config.intensity_source == IntensitySource::QueueDepletion)
+                .then(QueueDepletionTracker::new),
+            seasonality: None,
         }
     }
 
+    /// Rescale σ and A from the calibration window to the upcoming recalibration interval
+    /// using hour-of-week multipliers (see `seasonality_path`)
+    pub fn set_seasonality(&mut self, profile: SeasonalityProfile) {
+        self.seasonality = Some(profile);
+    }
+
     /// Add a price observation to the calibration window
     #[inline]
     pub fn add_price(&mut self, timestamp: u64, price: Decimal) {
-        self.calibration_prices.push((timestamp, price));
-        self.full_price_history.push((timestamp, price));
+        self.calibration_prices.push_back((timestamp, price));
+        self.realized_variance.push(timestamp, price);
+        self.volatility_estimator.observe(timestamp, price);
     }
 
-    /// Add a full orderbook snapshot to the calibration window (captures exposure and mid).
+    /// Add an orderbook snapshot to the calibration window (captures exposure and mid).
     ///
     /// Deltas are stored in return space (relative to mid) so that kappa is dimensionless.
+    /// Only the touch and the deepest level are converted to `Decimal`, unless queue depletion
+    /// needs the whole book.
     #[inline]
-    pub fn add_orderbook(&mut self, snapshot: &OrderbookSnapshot, mid_price: Decimal) {
-        let timestamp = snapshot.timestamp;
-        self.calibration_prices.push((timestamp, mid_price));
-        self.full_price_history.push((timestamp, mid_price));
+    pub fn add_orderbook(&mut self, book: &OrderbookView, mid_price: Decimal) {
+        let timestamp = book.timestamp();
+        self.calibration_prices.push_back((timestamp, mid_price));
+        self.realized_variance.push(timestamp, mid_price);
+        self.volatility_estimator.observe(timestamp, mid_price);
 
         let mid_f64 = mid_price.to_f64().unwrap_or(0.0);
         if mid_f64 <= 0.0 {
             return; // Can't compute return-space deltas without valid mid
         }
+        if let Some(tracker) = self.queue_depletion.as_mut() {
+            // A book with an unrepresentable level is skipped, as the snapshot reader rejected it
+            if let Ok(snapshot) = book.to_snapshot() {
+                tracker.add_snapshot(&snapshot, mid_price);
+            }
+        }
 
-        let best_bid = snapshot.bids.first().map(|(p, _)| *p).unwrap_or(Decimal::ZERO);
-        let best_ask = snapshot.asks.first().map(|(p, _)| *p).unwrap_or(Decimal::ZERO);
-        let far_bid = snapshot.bids.last().map(|(p, _)| *p).unwrap_or(best_bid);
-        let far_ask = snapshot.asks.last().map(|(p, _)| *p).unwrap_or(best_ask);
+        let best_bid = book.best_bid_decimal().unwrap_or(Decimal::ZERO);
+        let best_ask = book.best_ask_decimal().unwrap_or(Decimal::ZERO);
+        let far_bid = book.far_bid().and_then(Decimal::from_f64).unwrap_or(best_bid);
+        let far_ask = book.far_ask().and_then(Decimal::from_f64).unwrap_or(best_ask);
 
         // Compute deltas in return space: δ = |price - mid| / mid
         let bid_min = (mid_price - best_bid)
@@ -128,7 +233,7 @@ impl CalibrationEngine {
             .to_f64()
             .unwrap_or(0.0) / mid_f64;
 
-        self.orderbook_points.push(OrderbookPoint {
+        self.orderbook_points.push_back(OrderbookPoint {
             timestamp,
             mid: mid_price,
             bid_min,
@@ -140,33 +245,45 @@ impl CalibrationEngine {
 
     /// Add a trade to the calibration window.
     ///
-    /// Takes a reference and copies only the fields needed for calibration (timestamp, price,
-    /// is_buyer_maker), avoiding the need to clone the entire TradeEvent including quantity.
+    /// Takes a reference and copies only the fields needed for calibration, avoiding the need
+    /// to clone the entire TradeEvent.
     #[inline]
     pub fn add_trade(&mut self, trade: &TradeEvent) {
-        self.window_trades.push(CalibrationTrade {
+        let trade = CalibrationTrade {
             timestamp: trade.timestamp,
             price: trade.price,
+            quantity: trade.quantity,
             is_buyer_maker: trade.is_buyer_maker,
-        });
+        };
+        if let Some(tracker) = self.queue_depletion.as_mut() {
+            tracker.add_trade(trade.clone());
+        }
+        self.window_trades.push_back(trade);
     }
 
-    /// Prune old data from windows based on current timestamp
+    /// Prune old data from windows based on current timestamp.
+    ///
+    /// Events arrive in time order, so only the front of each window can expire.
     pub fn prune_windows(&mut self, current_ts: u64) {
-        // Remove prices older than calibration window
-        self.calibration_prices.retain(|(ts, _)| {
-            current_ts.saturating_sub(*ts) <= self.calibration_window_ms
-        });
+        let window_ms = self.calibration_window_ms;
+        let expired = |ts: u64| current_ts.saturating_sub(ts) > window_ms;
 
-        // Remove orderbooks older than calibration window
-        self.orderbook_points.retain(|p| {
-            current_ts.saturating_sub(p.timestamp) <= self.calibration_window_ms
-        });
+        while self.calibration_prices.front().is_some_and(|(ts, _)| expired(*ts)) {
+            self.calibration_prices.pop_front();
+        }
+        self.realized_variance.prune(current_ts, window_ms);
 
-        // Remove trades older than calibration window
-        self.window_trades.retain(|t| {
-            current_ts.saturating_sub(t.timestamp) <= self.calibration_window_ms
-        });
+        while self.orderbook_points.front().is_some_and(|p| expired(p.timestamp)) {
+            self.orderbook_points.pop_front();
+        }
+
+        while self.window_trades.front().is_some_and(|t| expired(t.timestamp)) {
+            self.window_trades.pop_front();
+        }
+
+        if let Some(tracker) = self.queue_depletion.as_mut() {
+            tracker.prune(current_ts, window_ms);
+        }
     }
 
     /// Check if it's time to recalibrate
@@ -192,25 +309,130 @@ impl CalibrationEngine {
             return None;
         }
 
-        // Calculate volatility (GARCH forecast if available, otherwise realized)
-        let volatility = forecast_garch_volatility(&self.full_price_history)
-            .unwrap_or_else(|| calculate_volatility(&self.calibration_prices));
+        // Seasonal factors of the window just observed and of the interval until the next calibration
+        let window_start = self.calibration_prices.front().map(|(ts, _)| *ts).unwrap_or(current_ts);
+        let seasonality = self.seasonality.as_ref().map(|profile| {
+            profile.adjustment(window_start, current_ts, current_ts + self.recalibration_interval_ms)
+        });
 
-        // Fit intensity parameters (returns separate bid/ask values)
-        let (new_bid_kappa, new_bid_a, new_ask_kappa, new_ask_a) = fit_intensity_parameters(
-            &self.window_trades,
-            &self.orderbook_points,
-            current_ts,
-        );
+        // Calculate volatility with the configured estimator, falling back to realized
+        let realized = self.realized_variance.sigma();
+        let inputs = VolatilityInputs {
+            window: self.calibration_prices.make_contiguous(),
+            realized: Some(realized),
+        };
+        let volatility = self.volatility_estimator.estimate(&inputs).unwrap_or(realized);
+
+        // Size-aware mode fits on parent orders that would have filled our clip
+        let window_trades: &[CalibrationTrade] = self.window_trades.make_contiguous();
+        let clip_fills = self.clip_notional.map(|clip| filter_clip_fills(window_trades, clip));
+        let trades = clip_fills.as_deref().unwrap_or(window_trades);
+        let orderbooks: &[OrderbookPoint] = self.orderbook_points.make_contiguous();
+
+        // Hawkes arrivals per side (Hawkes intensity model only)
+        let window_start_ts = window_trades
+            .first()
+            .map(|t| t.timestamp)
+            .unwrap_or(current_ts)
+            .max(current_ts.saturating_sub(self.calibration_window_ms));
+        let (bid_hawkes, ask_hawkes) = match self.intensity_model {
+            IntensityModel::Poisson => (None, None),
+            IntensityModel::Hawkes => fit_hawkes_intensity(trades, window_start_ts, current_ts),
+        };
+
+        // Fit intensity parameters (returns separate bid/ask values). Under Hawkes, κ and A
+        // come from the baseline arrivals when fitted on trades; other sources are rescaled below.
+        let mut fitted_under_baseline = false;
+        let intensity_fit = match (&self.queue_depletion, self.clip_notional) {
+            (Some(tracker), _) => tracker.fit(orderbooks, current_ts),
+            (None, Some(clip)) if self.clip_weighting == ClipWeighting::Weight => {
+                fit_clip_weighted_intensity(window_trades, orderbooks, current_ts, clip)
+            }
+            (None, _) if bid_hawkes.is_some() || ask_hawkes.is_some() => {
+                fitted_under_baseline = true;
+                fit_hawkes_baseline_intensity(
+                    trades,
+                    orderbooks,
+                    window_start_ts,
+                    current_ts,
+                    bid_hawkes.as_ref(),
+                    ask_hawkes.as_ref(),
+                )
+            }
+            (None, _) => fit_intensity_parameters_detailed(trades, orderbooks, current_ts),
+        };
+        let size_buckets = if self.size_bucket_edges.is_empty() {
+            Vec::new()
+        } else {
+            fit_intensity_by_size(window_trades, orderbooks, current_ts, &self.size_bucket_edges)
+        };
+        let (mut new_bid_kappa, mut new_bid_a) = (intensity_fit.bid.kappa, intensity_fit.bid.a);
+        let (mut new_ask_kappa, mut new_ask_a) = (intensity_fit.ask.kappa, intensity_fit.ask.a);
+        if let Some(smoother) = self.smoother.as_mut() {
+            // Smooth deseasonalised A so hour-of-week swings are not filtered away
+            let a_window = seasonality.map(|s| s.a_window).unwrap_or(1.0);
+            let mut deseasonalised = intensity_fit;
+            for side in [&mut deseasonalised.bid, &mut deseasonalised.ask] {
+                side.a /= a_window;
+                side.a_se /= a_window;
+            }
+            let smoothed = smoother.update(&deseasonalised);
+            new_bid_kappa = smoothed.bid_kappa;
+            new_bid_a = smoothed.bid_a * a_window;
+            new_ask_kappa = smoothed.ask_kappa;
+            new_ask_a = smoothed.ask_a * a_window;
+        }
+
+        // Non-exponential families replace the shape and A where they fit
+        let (bid_shape_fit, ask_shape_fit) = match self.intensity_shape {
+            IntensityShapeKind::Exponential => (None, None),
+            _ => fit_intensity_shapes(
+                trades,
+                orderbooks,
+                current_ts,
+                &self.intensity_shape,
+                &self.selection_criterion,
+                self.piecewise_segments,
+            ),
+        };
+        if let Some(fit) = &bid_shape_fit {
+            new_bid_a = fit.a;
+        }
+        if let Some(fit) = &ask_shape_fit {
+            new_ask_a = fit.a;
+        }
+
+        // Hawkes: scale A not fitted under the baseline from the mean rate down to the
+        // baseline (cluster) rate
+        if let Some(fit) = bid_hawkes.as_ref().filter(|_| !fitted_under_baseline || bid_shape_fit.is_some()) {
+            new_bid_a *= fit.effective_intensity_scale();
+        }
+        if let Some(fit) = ask_hawkes.as_ref().filter(|_| !fitted_under_baseline || ask_shape_fit.is_some()) {
+            new_ask_a *= fit.effective_intensity_scale();
+        }
+
+        // Reseasonalise for the upcoming interval
+        let mut volatility = volatility;
+        if let Some(adjustment) = &seasonality {
+            volatility *= adjustment.sigma_ratio();
+            new_bid_a *= adjustment.a_ratio();
+            new_ask_a *= adjustment.a_ratio();
+        }
 
         // Update stored parameters if valid
         if new_bid_kappa > 0.0 && new_bid_a > 0.0 {
             self.bid_kappa = new_bid_kappa;
             self.bid_a = new_bid_a;
+            self.bid_shape = bid_shape_fit
+                .map(|fit| fit.shape)
+                .unwrap_or(IntensityShape::Exponential { kappa: new_bid_kappa });
         }
         if new_ask_kappa > 0.0 && new_ask_a > 0.0 {
             self.ask_kappa = new_ask_kappa;
             self.ask_a = new_ask_a;
+            self.ask_shape = ask_shape_fit
+                .map(|fit| fit.shape)
+                .unwrap_or(IntensityShape::Exponential { kappa: new_ask_kappa });
         }
 
         // Update last calibration timestamp
@@ -223,6 +445,14 @@ impl CalibrationEngine {
             bid_a: self.bid_a,
             ask_kappa: self.ask_kappa,
             ask_a: self.ask_a,
+            garch: self.volatility_estimator.garch_params(),
+            bid_hawkes,
+            ask_hawkes,
+            bid_shape: self.bid_shape.clone(),
+            ask_shape: self.ask_shape.clone(),
+            intensity_fit,
+            size_buckets,
+            seasonality,
         })
     }
 
@@ -262,6 +492,50 @@ impl CalibrationEngine {
         self.calibration_prices.len()
     }
 
+    /// Name of the configured volatility estimator
+    #[inline]
+    pub fn volatility_estimator_name(&self) -> &'static str {
+        self.volatility_estimator.name()
+    }
+
+    /// Realized σ over the current price window (1/√s), independent of the configured estimator
+    #[inline]
+    pub fn realized_volatility(&self) -> f64 {
+        self.realized_variance.sigma()
+    }
+
+    /// Delta histograms, exposure totals, fitted curves and log-likelihoods of `result` over
+    /// the current window, on the inputs its fit used: a non-exponential side is scored as
+    /// its shape (A profiled on the window) against trade deltas; an exponential side against
+    /// its fitted κ/A on execution depths (queue-depletion source) or trade deltas. Trade
+    /// deltas are clip fills in size-aware mode.
+    pub fn intensity_diagnostics(&mut self, current_ts: u64, result: &CalibrationResult) -> IntensityDiagnostics {
+        let window_trades: &[CalibrationTrade] = self.window_trades.make_contiguous();
+        let clip_fills = self.clip_notional.map(|clip| filter_clip_fills(window_trades, clip));
+        let trades = clip_fills.as_deref().unwrap_or(window_trades);
+        let orderbooks: &[OrderbookPoint] = self.orderbook_points.make_contiguous();
+        let tracker = &self.queue_depletion;
+
+        let side = |is_bid: bool, shape: &IntensityShape, fit: &SideIntensityFit| {
+            let (deltas, shape, a) = match (shape.kappa(), tracker) {
+                (None, _) => (collect_trade_deltas(trades, orderbooks, is_bid), shape.clone(), None),
+                (Some(_), Some(tracker)) => {
+                    (tracker.execution_deltas(is_bid), IntensityShape::Exponential { kappa: fit.kappa }, Some(fit.a))
+                }
+                (Some(_), None) => (
+                    collect_trade_deltas(trades, orderbooks, is_bid),
+                    IntensityShape::Exponential { kappa: fit.kappa },
+                    Some(fit.a),
+                ),
+            };
+            side_intensity_diagnostics(&deltas, orderbooks, current_ts, is_bid, &shape, a)
+        };
+        IntensityDiagnostics {
+            bid: side(true, &result.bid_shape, &result.intensity_fit.bid),
+            ask: side(false, &result.ask_shape, &result.intensity_fit.ask),
+        }
+    }
+
     /// Get number of trades in calibration window
     #[inline]
     pub fn trade_count(&self) -> usize {
@@ -271,13 +545,22 @@ impl CalibrationEngine {
     /// Reset the engine state (useful for reusing across multiple backtests)
     pub fn reset(&mut self) {
         self.calibration_prices.clear();
-        self.full_price_history.clear();
+        self.realized_variance.clear();
+        self.volatility_estimator.reset();
+        if let Some(smoother) = self.smoother.as_mut() {
+            smoother.reset();
+        }
         self.orderbook_points.clear();
         self.window_trades.clear();
+        if let Some(tracker) = self.queue_depletion.as_mut() {
+            tracker.clear();
+        }
         self.bid_kappa = 100.0;  // Default (dimensionless, in return space)
         self.bid_a = 10.0;
         self.ask_kappa = 100.0;  // Default (dimensionless, in return space)
         self.ask_a = 10.0;
+        self.bid_shape = IntensityShape::Exponential { kappa: 100.0 };
+        self.ask_shape = IntensityShape::Exponential { kappa: 100.0 };
         self.last_calibration_ts = None;
     }
 }
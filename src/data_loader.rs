@@ -1,9 +1,16 @@
+use crate::event_cache::{event_cache_path, source_fingerprint, CachedEvents, EventCache};
 use crate::model_types::TradeEvent;
+use crate::orderbook_batch::{OrderbookBatch, OrderbookRow, RawOrderbookRow};
+use crate::parquet_prefetch::{PartBatches, PartOpener, PartReader, DEFAULT_PREFETCH_DEPTH};
 use arrow::array::{Array, BooleanArray, Float64Array, TimestampMillisecondArray};
+use arrow::compute::filter_record_batch;
 use arrow::datatypes::{DataType, TimeUnit};
 use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, NaiveDate};
 use csv::{ReaderBuilder, StringRecord};
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::statistics::Statistics;
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use serde::Deserialize;
@@ -12,18 +19,19 @@ use std::error::Error;
 use std::fs::{self, File};
 use std::iter::Peekable;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub enum DataEvent {
     Trade(TradeEvent),
-    Orderbook(OrderbookSnapshot),
+    Orderbook(OrderbookRow),
 }
 
 impl DataEvent {
     pub fn timestamp(&self) -> u64 {
         match self {
             DataEvent::Trade(t) => t.timestamp,
-            DataEvent::Orderbook(o) => o.timestamp,
+            DataEvent::Orderbook(o) => o.timestamp(),
         }
     }
 }
@@ -43,10 +51,118 @@ struct RawTrade {
     side: String,
 }
 
+/// CSV orderbook rows grouped into one `OrderbookBatch`
+const CSV_ORDERBOOK_BATCH_ROWS: usize = 1024;
+
+/// Stream of (timestamp_ms, mid) observations derived from orderbook snapshots
+pub type MidStream = Box<dyn Iterator<Item = Result<(u64, f64), Box<dyn Error>>>>;
+
+/// Directory holding a market's collected data, e.g. `data/eth_usd` for "ETH-USD"
+pub fn market_data_dir(data_dir: &Path, market: &str) -> PathBuf {
+    data_dir.join(market.replace("-", "_").to_lowercase())
+}
+
+/// Half-open interval [start_ms, end_ms) of event timestamps to load
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeRange {
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+impl TimeRange {
+    /// Every timestamp
+    pub const ALL: TimeRange = TimeRange { start_ms: 0, end_ms: u64::MAX };
+
+    pub fn new(start_ms: u64, end_ms: u64) -> Self {
+        Self { start_ms, end_ms }
+    }
+
+    pub fn is_all(&self) -> bool {
+        *self == Self::ALL
+    }
+
+    #[inline]
+    pub fn contains(&self, timestamp_ms: u64) -> bool {
+        timestamp_ms >= self.start_ms && timestamp_ms < self.end_ms
+    }
+
+    /// Whether any timestamp in [min_ms, max_ms] is in the range
+    pub fn overlaps(&self, min_ms: u64, max_ms: u64) -> bool {
+        max_ms >= self.start_ms && min_ms < self.end_ms
+    }
+}
+
+/// Parse a `--start`/`--end` argument: epoch milliseconds, `YYYY-MM-DD` (UTC midnight) or RFC 3339
+pub fn parse_timestamp_arg(value: &str) -> Result<u64, String> {
+    if let Ok(ms) = value.parse::<u64>() {
+        return Ok(ms);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).ok_or_else(|| format!("Invalid date '{}'", value))?;
+        return u64::try_from(midnight.and_utc().timestamp_millis()).map_err(|_| format!("Date before 1970: '{}'", value));
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map_err(|e| format!("Invalid timestamp '{}': {}", value, e))
+        .and_then(|dt| u64::try_from(dt.timestamp_millis()).map_err(|_| format!("Timestamp before 1970: '{}'", value)))
+}
+
+/// First timestamp encoded in a `part_{timestamp}_...parquet` file name
+fn part_first_timestamp(path: &Path) -> Option<u64> {
+    path.file_stem()?.to_str()?.strip_prefix("part_")?.split('_').next()?.parse().ok()
+}
+
+/// Keep the sorted part files that can hold rows in `range`: a part starts at the timestamp
+/// in its name and ends no later than the next part's first timestamp. Files without a
+/// parseable name are kept.
+fn prune_part_files(files: Vec<PathBuf>, range: TimeRange) -> Vec<PathBuf> {
+    if range.is_all() {
+        return files;
+    }
+    let firsts: Vec<Option<u64>> = files.iter().map(|f| part_first_timestamp(f)).collect();
+    files
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            let starts_before_end = firsts[*i].is_none_or(|first| first < range.end_ms);
+            let ends_after_start = firsts.get(i + 1).copied().flatten().is_none_or(|next| next >= range.start_ms);
+            starts_before_end && ends_after_start
+        })
+        .map(|(_, file)| file)
+        .collect()
+}
+
+/// Row groups whose `timestamp_ms` statistics overlap `range`; groups without statistics are kept
+fn row_groups_in_range(metadata: &ParquetMetaData, range: TimeRange) -> Vec<usize> {
+    let column = metadata
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .position(|c| c.name() == "timestamp_ms");
+    metadata
+        .row_groups()
+        .iter()
+        .enumerate()
+        .filter(|(_, group)| {
+            match column.and_then(|c| group.column(c).statistics()) {
+                Some(Statistics::Int64(stats)) => match (stats.min_opt(), stats.max_opt()) {
+                    (Some(min), Some(max)) => range.overlaps((*min).max(0) as u64, (*max).max(0) as u64),
+                    _ => true,
+                },
+                _ => true,
+            }
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
 pub struct DataLoader {
     trades_path: PathBuf,
     orderbook_path: PathBuf,
     suppress_warnings: bool,
+    time_range: TimeRange,
+    prefetch_depth: usize,
+    event_cache: Option<PathBuf>,
 }
 
 impl DataLoader {
@@ -55,9 +171,45 @@ impl DataLoader {
             trades_path: trades_path.to_path_buf(),
             orderbook_path: orderbook_path.to_path_buf(),
             suppress_warnings: true,
+            time_range: TimeRange::ALL,
+            prefetch_depth: DEFAULT_PREFETCH_DEPTH,
+            event_cache: trades_path.parent().map(event_cache_path),
         }
     }
 
+    /// Only load events with timestamps in [start_ms, end_ms). Parquet part files outside the
+    /// range are skipped by name and row groups by their `timestamp_ms` statistics.
+    pub fn with_time_range(mut self, start_ms: u64, end_ms: u64) -> Self {
+        self.time_range = TimeRange::new(start_ms, end_ms);
+        self
+    }
+
+    /// Number of Parquet part files decoded ahead on worker threads (0 decodes on the
+    /// caller's thread). Output order does not depend on it.
+    pub fn with_prefetch(mut self, prefetch_depth: usize) -> Self {
+        self.prefetch_depth = prefetch_depth;
+        self
+    }
+
+    /// Event cache `stream` replays while it matches the source files (default: `events.cache`
+    /// next to the trades source; None always reads the sources)
+    pub fn with_event_cache(mut self, path: Option<&Path>) -> Self {
+        self.event_cache = path.map(Path::to_path_buf);
+        self
+    }
+
+    pub fn trades_path(&self) -> &Path {
+        &self.trades_path
+    }
+
+    pub fn orderbook_path(&self) -> &Path {
+        &self.orderbook_path
+    }
+
+    pub fn time_range(&self) -> TimeRange {
+        self.time_range
+    }
+
     /// Load all trades into memory (backward compatibility)
     pub fn get_trades(&self) -> Result<Vec<TradeEvent>, Box<dyn Error>> {
         // Detect format
@@ -66,7 +218,8 @@ impl DataLoader {
 
         if use_parquet {
             let mut trades = Vec::new();
-            let iter = ParquetTradeIterator::new(&self.trades_path, self.suppress_warnings)?;
+            let iter = ParquetTradeIterator::with_time_range(&self.trades_path, self.suppress_warnings, self.time_range)?
+                .with_prefetch(self.prefetch_depth);
             for result in iter {
                 trades.push(result?);
             }
@@ -79,7 +232,9 @@ impl DataLoader {
             let mut trades = Vec::new();
             for result in rdr.into_deserialize::<RawTrade>() {
                 let raw = result?;
-                trades.push(parse_trade(raw)?);
+                if self.time_range.contains(raw.timestamp_ms) {
+                    trades.push(parse_trade(raw)?);
+                }
             }
             Ok(trades)
         }
@@ -92,7 +247,8 @@ impl DataLoader {
             || self.orderbook_path.extension().and_then(|s| s.to_str()) == Some("parquet");
 
         if use_parquet {
-            let parquet_iter = ParquetOrderbookIterator::new(&self.orderbook_path, self.suppress_warnings)?;
+            let parquet_iter = ParquetOrderbookIterator::with_time_range(&self.orderbook_path, self.suppress_warnings, self.time_range)?
+                .with_prefetch(self.prefetch_depth);
             Ok(OrderbookIterator::Parquet(parquet_iter))
         } else {
             let file = File::open(&self.orderbook_path)?;
@@ -110,17 +266,113 @@ impl DataLoader {
             Ok(OrderbookIterator::Csv {
                 iter: rdr.into_records(),
                 max_levels,
+                time_range: self.time_range,
             })
         }
     }
 
-    pub fn stream(&self) -> Result<MergedDataIterator, Box<dyn Error>> {
+    /// Iterator over (timestamp, mid price) from orderbook snapshots with both sides present
+    pub fn mids_iter(&self) -> Result<MidStream, Box<dyn Error>> {
+        let iter = self.orderbook_rows()?.filter_map(|row| match row {
+            Ok(row) => {
+                let book = row.view();
+                let (best_bid, best_ask) = (book.best_bid()?, book.best_ask()?);
+                (best_bid > 0.0 && best_ask > 0.0).then(|| Ok((book.timestamp(), (best_bid + best_ask) / 2.0)))
+            }
+            Err(e) => Some(Err(e)),
+        });
+        Ok(Box::new(iter))
+    }
+
+    /// Orderbook snapshots as handles on shared batches, in file order
+    pub fn orderbook_rows(&self) -> Result<impl Iterator<Item = Result<OrderbookRow, Box<dyn Error>>>, Box<dyn Error>> {
+        Ok(self.orderbook_batches()?.flat_map(|result| {
+            let (batch, error) = match result {
+                Ok(batch) => (Some(Arc::new(batch)), None),
+                Err(e) => (None, Some(Err(e))),
+            };
+            let rows = batch
+                .into_iter()
+                .flat_map(|batch| (0..batch.len()).map(move |row| Ok(OrderbookRow::new(Arc::clone(&batch), row))));
+            error.into_iter().chain(rows)
+        }))
+    }
+
+    /// Orderbook snapshots in columnar batches, without per-snapshot `Decimal` conversion
+    pub fn orderbook_batches(&self) -> Result<OrderbookBatches, Box<dyn Error>> {
+        let use_parquet = self.orderbook_path.is_dir()
+            || self.orderbook_path.extension().and_then(|s| s.to_str()) == Some("parquet");
+
+        if use_parquet {
+            let parquet_iter = ParquetOrderbookIterator::with_time_range(&self.orderbook_path, self.suppress_warnings, self.time_range)?
+                .with_prefetch(self.prefetch_depth);
+            let max_levels = parquet_iter.max_levels;
+            Ok(OrderbookBatches {
+                source: OrderbookSource::Parquet(parquet_iter),
+                max_levels,
+                time_range: self.time_range,
+            })
+        } else {
+            let file = File::open(&self.orderbook_path)?;
+            let buf = std::io::BufReader::with_capacity(1024 * 1024, file);
+            let mut rdr = ReaderBuilder::new().from_reader(buf);
+
+            let headers = rdr.headers()?.clone();
+            let mut max_levels = 0;
+            for field in headers.iter() {
+                if field.starts_with("bid_price") {
+                    max_levels += 1;
+                }
+            }
+
+            Ok(OrderbookBatches {
+                source: OrderbookSource::Csv(rdr.into_records()),
+                max_levels,
+                time_range: self.time_range,
+            })
+        }
+    }
+
+    /// Trades and orderbooks merged in timestamp order, from the event cache when it is current
+    pub fn stream(&self) -> Result<EventStream, Box<dyn Error>> {
+        if let Some(cache) = self.current_event_cache() {
+            return Ok(EventStream::Cache(cache.events(self.time_range)));
+        }
+        Ok(EventStream::Sources(Box::new(self.source_stream()?)))
+    }
+
+    /// The event cache, if there is one and it was built from the current source files
+    fn current_event_cache(&self) -> Option<EventCache> {
+        let path = self.event_cache.as_deref().filter(|p| p.is_file())?;
+        let cache = match EventCache::open(path) {
+            Ok(cache) => cache,
+            Err(e) => {
+                eprintln!("Ignoring event cache: {}", e);
+                return None;
+            }
+        };
+        match source_fingerprint(&self.trades_path, &self.orderbook_path) {
+            Ok(fingerprint) if fingerprint == cache.fingerprint() => Some(cache),
+            Ok(_) => {
+                eprintln!(
+                    "Event cache {} is out of date, reading the source files (rebuild it with build_event_cache)",
+                    path.display()
+                );
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Trades and orderbooks merged from the source files, ignoring any event cache
+    pub fn source_stream(&self) -> Result<MergedDataIterator, Box<dyn Error>> {
         // Setup Trade Source
         let use_parquet_trades = self.trades_path.is_dir()
             || self.trades_path.extension().and_then(|s| s.to_str()) == Some("parquet");
 
         let trade_source = if use_parquet_trades {
-            let iter = ParquetTradeIterator::new(&self.trades_path, self.suppress_warnings)?;
+            let iter = ParquetTradeIterator::with_time_range(&self.trades_path, self.suppress_warnings, self.time_range)?
+                .with_prefetch(self.prefetch_depth);
             TradeSource::Parquet(iter)
         } else {
             let trades_file = File::open(&self.trades_path)?;
@@ -130,35 +382,12 @@ impl DataLoader {
             TradeSource::Csv(trades_iter.peekable())
         };
 
-        // Setup Orderbook Source
-        let use_parquet_ob = self.orderbook_path.is_dir()
-            || self.orderbook_path.extension().and_then(|s| s.to_str()) == Some("parquet");
-
-        let (orderbook_source, max_levels) = if use_parquet_ob {
-            let parquet_iter = ParquetOrderbookIterator::new(&self.orderbook_path, self.suppress_warnings)?;
-            let max = parquet_iter.max_levels;
-            (OrderbookSource::Parquet(parquet_iter), max)
-        } else {
-            let orderbook_file = File::open(&self.orderbook_path)?;
-            let orderbook_buf = std::io::BufReader::with_capacity(1024 * 1024, orderbook_file);
-            let mut orderbook_rdr = ReaderBuilder::new().from_reader(orderbook_buf);
-
-            let headers = orderbook_rdr.headers()?.clone();
-            let mut max = 0;
-            for field in headers.iter() {
-                if field.starts_with("bid_price") {
-                    max += 1;
-                }
-            }
-
-            let orderbook_iter = orderbook_rdr.into_records();
-            (OrderbookSource::Csv(orderbook_iter.peekable()), max)
-        };
-
         Ok(MergedDataIterator {
             trade_source,
-            orderbook_source,
-            max_levels,
+            orderbook_batches: self.orderbook_batches()?,
+            time_range: self.time_range,
+            orderbook_batch: None,
+            orderbook_row: 0,
             next_trade: None,
             next_orderbook: None,
         })
@@ -232,6 +461,7 @@ pub enum OrderbookIterator {
     Csv {
         iter: csv::StringRecordsIntoIter<std::io::BufReader<File>>,
         max_levels: usize,
+        time_range: TimeRange,
     },
     Parquet(ParquetOrderbookIterator),
 }
@@ -241,16 +471,23 @@ impl Iterator for OrderbookIterator {
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
-            OrderbookIterator::Csv { iter, max_levels } => match iter.next() {
-                Some(Ok(record)) => match parse_orderbook(record, *max_levels) {
-                    Ok(snapshot) => {
-                        let ts = snapshot.timestamp;
-                        Some(Ok((ts, snapshot)))
-                    }
-                    Err(e) => Some(Err(e)),
-                },
-                Some(Err(e)) => Some(Err(Box::new(e))),
-                None => None,
+            OrderbookIterator::Csv {
+                iter,
+                max_levels,
+                time_range,
+            } => loop {
+                return match iter.next() {
+                    Some(Ok(record)) => match parse_orderbook(record, *max_levels) {
+                        Ok(snapshot) if !time_range.contains(snapshot.timestamp) => continue,
+                        Ok(snapshot) => {
+                            let ts = snapshot.timestamp;
+                            Some(Ok((ts, snapshot)))
+                        }
+                        Err(e) => Some(Err(e)),
+                    },
+                    Some(Err(e)) => Some(Err(Box::new(e))),
+                    None => None,
+                };
             },
             OrderbookIterator::Parquet(iter) => match iter.next() {
                 Some(Ok(snapshot)) => {
@@ -265,10 +502,70 @@ impl Iterator for OrderbookIterator {
 }
 
 enum OrderbookSource {
-    Csv(Peekable<csv::StringRecordsIntoIter<std::io::BufReader<File>>>),
+    Csv(csv::StringRecordsIntoIter<std::io::BufReader<File>>),
     Parquet(ParquetOrderbookIterator),
 }
 
+/// Iterator over orderbook batches from CSV or Parquet
+pub struct OrderbookBatches {
+    source: OrderbookSource,
+    max_levels: usize,
+    // Applied to CSV sources; Parquet sources filter rows themselves
+    time_range: TimeRange,
+}
+
+impl OrderbookBatches {
+    pub fn max_levels(&self) -> usize {
+        self.max_levels
+    }
+}
+
+impl Iterator for OrderbookBatches {
+    type Item = Result<OrderbookBatch, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            OrderbookSource::Parquet(iter) => iter.next_batch(),
+            OrderbookSource::Csv(iter) => {
+                let mut rows = Vec::with_capacity(CSV_ORDERBOOK_BATCH_ROWS);
+                while rows.len() < CSV_ORDERBOOK_BATCH_ROWS {
+                    match iter.next() {
+                        Some(Ok(record)) => match parse_orderbook_row(&record, self.max_levels) {
+                            Ok(row) if !self.time_range.contains(row.0) => {}
+                            Ok(row) => rows.push(row),
+                            Err(e) => return Some(Err(e)),
+                        },
+                        Some(Err(e)) => return Some(Err(Box::new(e))),
+                        None => break, // End of orderbooks
+                    }
+                }
+                if rows.is_empty() {
+                    None
+                } else {
+                    Some(Ok(OrderbookBatch::from_rows(&rows, self.max_levels)))
+                }
+            }
+        }
+    }
+}
+
+/// Merged events, from the source files or replayed from the event cache
+pub enum EventStream {
+    Sources(Box<MergedDataIterator>),
+    Cache(CachedEvents),
+}
+
+impl Iterator for EventStream {
+    type Item = Result<DataEvent, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            EventStream::Sources(iter) => iter.next(),
+            EventStream::Cache(iter) => iter.next(),
+        }
+    }
+}
+
 enum TradeSource {
     Csv(Peekable<csv::DeserializeRecordsIntoIter<std::io::BufReader<File>, RawTrade>>),
     Parquet(ParquetTradeIterator),
@@ -276,11 +573,15 @@ enum TradeSource {
 
 pub struct MergedDataIterator {
     trade_source: TradeSource,
-    orderbook_source: OrderbookSource,
-    max_levels: usize,
+    orderbook_batches: OrderbookBatches,
+    // Applied to CSV trades; Parquet sources filter rows themselves
+    time_range: TimeRange,
+    // Orderbook batch being replayed and the next row to take from it
+    orderbook_batch: Option<Arc<OrderbookBatch>>,
+    orderbook_row: usize,
     // Buffers to hold the parsed next event to allow peeking/comparison
     next_trade: Option<TradeEvent>,
-    next_orderbook: Option<OrderbookSnapshot>,
+    next_orderbook: Option<OrderbookRow>,
 }
 
 impl Iterator for MergedDataIterator {
@@ -290,8 +591,9 @@ impl Iterator for MergedDataIterator {
         // Ensure we have the next trade buffered if available
         if self.next_trade.is_none() {
             match &mut self.trade_source {
-                TradeSource::Csv(iter) => {
+                TradeSource::Csv(iter) => loop {
                     match iter.next() {
+                        Some(Ok(raw)) if !self.time_range.contains(raw.timestamp_ms) => continue,
                         Some(Ok(raw)) => match parse_trade(raw) {
                             Ok(trade) => self.next_trade = Some(trade),
                             Err(e) => return Some(Err(e)),
@@ -299,7 +601,8 @@ impl Iterator for MergedDataIterator {
                         Some(Err(e)) => return Some(Err(Box::new(e))),
                         None => {} // End of trades
                     }
-                }
+                    break;
+                },
                 TradeSource::Parquet(iter) => match iter.next() {
                     Some(Ok(trade)) => self.next_trade = Some(trade),
                     Some(Err(e)) => return Some(Err(e)),
@@ -309,24 +612,24 @@ impl Iterator for MergedDataIterator {
         }
 
         // Ensure we have the next orderbook buffered if available
-        if self.next_orderbook.is_none() {
-            match &mut self.orderbook_source {
-                OrderbookSource::Csv(iter) => {
-                    match iter.next() {
-                        Some(Ok(record)) => match parse_orderbook(record, self.max_levels) {
-                            Ok(ob) => self.next_orderbook = Some(ob),
-                            Err(e) => return Some(Err(e)),
-                        },
-                        Some(Err(e)) => return Some(Err(Box::new(e))),
-                        None => {} // End of orderbooks
-                    }
+        while self.next_orderbook.is_none() {
+            if let Some(batch) = &self.orderbook_batch {
+                if self.orderbook_row < batch.len() {
+                    self.next_orderbook = Some(OrderbookRow::new(Arc::clone(batch), self.orderbook_row));
+                    self.orderbook_row += 1;
+                    break;
                 }
-                OrderbookSource::Parquet(iter) => {
-                    match iter.next() {
-                        Some(Ok(ob)) => self.next_orderbook = Some(ob),
-                        Some(Err(e)) => return Some(Err(e)),
-                        None => {} // End of orderbooks
-                    }
+            }
+            match self.orderbook_batches.next() {
+                Some(Ok(batch)) => {
+                    self.orderbook_batch = Some(Arc::new(batch));
+                    self.orderbook_row = 0;
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    // End of orderbooks
+                    self.orderbook_batch = None;
+                    break;
                 }
             }
         }
@@ -334,7 +637,7 @@ impl Iterator for MergedDataIterator {
         // Compare timestamps and yield the smaller one
         match (&self.next_trade, &self.next_orderbook) {
             (Some(t), Some(ob)) => {
-                if t.timestamp <= ob.timestamp {
+                if t.timestamp <= ob.timestamp() {
                     let trade = self.next_trade.take().unwrap();
                     Some(Ok(DataEvent::Trade(trade)))
                 } else {
@@ -402,6 +705,27 @@ fn parse_orderbook(
     })
 }
 
+/// Raw f64 levels of a CSV orderbook record
+fn parse_orderbook_row(record: &StringRecord, max_levels: usize) -> Result<RawOrderbookRow, Box<dyn Error>> {
+    let timestamp: u64 = record[0].parse()?;
+    let mut levels = Vec::with_capacity(max_levels);
+
+    for i in 0..max_levels {
+        let base_idx = 4 + (i * 4);
+        if base_idx + 3 >= record.len() {
+            break;
+        }
+        levels.push(Some([
+            record[base_idx].parse()?,
+            record[base_idx + 1].parse()?,
+            record[base_idx + 2].parse()?,
+            record[base_idx + 3].parse()?,
+        ]));
+    }
+
+    Ok((timestamp, levels))
+}
+
 fn validate_trade_schema(schema: &arrow::datatypes::Schema) -> Result<(), String> {
     let expected = [
         (
@@ -484,19 +808,117 @@ fn validate_orderbook_schema(
     Ok(())
 }
 
+/// Open a parquet part for decoding, keeping only the row groups that overlap `time_range`.
+/// `validate` checks the schema and reports a rejected file itself.
+fn open_part(
+    file_path: &Path,
+    time_range: TimeRange,
+    suppress_warnings: bool,
+    validate: impl FnOnce(&arrow::datatypes::Schema) -> bool,
+) -> Option<PartBatches> {
+    match File::open(file_path) {
+        Ok(file) => match ParquetRecordBatchReaderBuilder::try_new(file) {
+            Ok(builder) => {
+                if !validate(builder.schema().as_ref()) {
+                    return None;
+                }
+
+                let builder = if time_range.is_all() {
+                    builder
+                } else {
+                    let row_groups = row_groups_in_range(builder.metadata(), time_range);
+                    if row_groups.is_empty() {
+                        return None;
+                    }
+                    builder.with_row_groups(row_groups)
+                };
+
+                match builder.build() {
+                    Ok(reader) => Some(Box::new(reader)),
+                    Err(e) => {
+                        if !suppress_warnings {
+                            eprintln!("Warning: Skipping corrupt parquet file (build failed) {:?}: {}", file_path, e);
+                        }
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                if !suppress_warnings {
+                    eprintln!("Warning: Skipping corrupt parquet file (invalid footer/metadata) {:?}: {}", file_path, e);
+                }
+                None
+            }
+        },
+        Err(e) => {
+            if !suppress_warnings {
+                eprintln!(
+                    "Warning: Skipping unreadable parquet file {:?}: {}",
+                    file_path, e
+                );
+            }
+            None
+        }
+    }
+}
+
+fn trade_part_opener(time_range: TimeRange, suppress_warnings: bool) -> PartOpener {
+    Arc::new(move |file_path: &Path| {
+        open_part(file_path, time_range, suppress_warnings, |schema| match validate_trade_schema(schema) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!(
+                    "Warning: Skipping parquet trade file with invalid schema {:?}: {}",
+                    file_path.file_name().unwrap_or_default(),
+                    e
+                );
+                false
+            }
+        })
+    })
+}
+
+fn orderbook_part_opener(time_range: TimeRange, suppress_warnings: bool, max_levels: usize) -> PartOpener {
+    Arc::new(move |file_path: &Path| {
+        open_part(file_path, time_range, suppress_warnings, |schema| {
+            match validate_orderbook_schema(schema, max_levels) {
+                Ok(()) => true,
+                Err(e) => {
+                    if !suppress_warnings {
+                        eprintln!(
+                            "Warning: Skipping parquet orderbook file with invalid schema {:?}: {}",
+                            file_path,
+                            e
+                        );
+                    }
+                    false
+                }
+            }
+        })
+    })
+}
+
 /// Iterator over Parquet trade files
 pub struct ParquetTradeIterator {
-    files: Vec<PathBuf>,
-    current_file_idx: usize,
-    current_reader: Option<Box<dyn Iterator<Item = Result<RecordBatch, arrow::error::ArrowError>>>>,
+    parts: PartReader,
     current_batch: Option<RecordBatch>,
+    /// Timestamp column of `current_batch`, looked up once per batch for the range filter
+    current_timestamps: Option<TimestampMillisecondArray>,
     current_row_idx: usize,
-    warned_files: HashSet<PathBuf>,
-    suppress_warnings: bool,
+    time_range: TimeRange,
 }
 
 impl ParquetTradeIterator {
     pub fn new(path: &Path, suppress_warnings: bool) -> Result<Self, Box<dyn Error>> {
+        Self::with_time_range(path, suppress_warnings, TimeRange::ALL)
+    }
+
+    /// Iterate only over trades in `time_range`, skipping part files and row groups outside it
+    pub fn with_time_range(
+        path: &Path,
+        suppress_warnings: bool,
+        time_range: TimeRange,
+    ) -> Result<Self, Box<dyn Error>> {
         let files = if path.is_dir() {
             let mut files: Vec<PathBuf> = fs::read_dir(path)?
                 .filter_map(|entry| entry.ok())
@@ -513,78 +935,34 @@ impl ParquetTradeIterator {
             return Err("No Parquet trade files found".into());
         }
 
-        let mut iter = Self {
-            files,
-            current_file_idx: 0,
-            current_reader: None,
-            current_batch: None,
-            current_row_idx: 0,
-            warned_files: HashSet::new(),
-            suppress_warnings,
-        };
-
-        iter.advance_file()?;
-        if iter.current_reader.is_none() {
+        let parts = PartReader::new(
+            prune_part_files(files, time_range),
+            trade_part_opener(time_range, suppress_warnings),
+            DEFAULT_PREFETCH_DEPTH,
+        );
+        // A range with no data is an empty stream, not an error
+        if !parts.has_data() && time_range.is_all() {
             return Err("No valid Parquet trade files found".into());
         }
-        Ok(iter)
+        Ok(Self {
+            parts,
+            current_batch: None,
+            current_timestamps: None,
+            current_row_idx: 0,
+            time_range,
+        })
     }
 
-    fn advance_file(&mut self) -> Result<bool, Box<dyn Error>> {
-        loop {
-            if self.current_file_idx >= self.files.len() {
-                return Ok(false);
-            }
-
-            let file_path = &self.files[self.current_file_idx];
-            self.current_file_idx += 1;
-
-            match File::open(file_path) {
-                Ok(file) => match ParquetRecordBatchReaderBuilder::try_new(file) {
-                    Ok(builder) => {
-                        let schema = builder.schema();
-                        if let Err(e) = validate_trade_schema(schema.as_ref()) {
-                            eprintln!(
-                                "Warning: Skipping parquet trade file with invalid schema {:?}: {}",
-                                file_path.file_name().unwrap_or_default(),
-                                e
-                            );
-                            continue;
-                        }
+    /// Decode up to `prefetch_depth` files ahead on worker threads (0 decodes on the caller)
+    pub fn with_prefetch(mut self, prefetch_depth: usize) -> Self {
+        self.parts.set_prefetch_depth(prefetch_depth);
+        self
+    }
 
-                        match builder.build() {
-                            Ok(reader) => {
-                                self.current_reader = Some(Box::new(reader));
-                                self.current_batch = None;
-                                self.current_row_idx = 0;
-                                return Ok(true);
-                            }
-                            Err(e) => {
-                                if !self.suppress_warnings && self.warned_files.insert(file_path.clone()) {
-                                    eprintln!("Warning: Skipping corrupt parquet file (build failed) {:?}: {}", file_path, e);
-                                }
-                                continue;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        if !self.suppress_warnings && self.warned_files.insert(file_path.clone()) {
-                            eprintln!("Warning: Skipping corrupt parquet file (invalid footer/metadata) {:?}: {}", file_path, e);
-                        }
-                        continue;
-                    }
-                },
-                Err(e) => {
-                    if !self.suppress_warnings && self.warned_files.insert(file_path.clone()) {
-                        eprintln!(
-                            "Warning: Skipping unreadable parquet file {:?}: {}",
-                            file_path, e
-                        );
-                    }
-                    continue;
-                }
-            }
-        }
+    /// Whether a row's timestamp is in the iterator's range; rows without a readable timestamp
+    /// are passed through so the reader reports the error
+    fn row_in_range(&self, timestamps: Option<&TimestampMillisecondArray>, row_idx: usize) -> bool {
+        self.time_range.is_all() || timestamps.is_none_or(|c| self.time_range.contains(c.value(row_idx) as u64))
     }
 
     fn read_trade_from_batch(
@@ -642,35 +1020,28 @@ impl Iterator for ParquetTradeIterator {
         loop {
             if let Some(ref batch) = self.current_batch {
                 if self.current_row_idx < batch.num_rows() {
-                    let trade = self.read_trade_from_batch(batch, self.current_row_idx);
+                    let row_idx = self.current_row_idx;
                     self.current_row_idx += 1;
-                    return Some(trade);
+                    if !self.row_in_range(self.current_timestamps.as_ref(), row_idx) {
+                        continue;
+                    }
+                    return Some(self.read_trade_from_batch(batch, row_idx));
                 } else {
                     self.current_batch = None;
                     self.current_row_idx = 0;
                 }
             }
 
-            if let Some(ref mut reader) = self.current_reader {
-                match reader.next() {
-                    Some(Ok(batch)) => {
-                        self.current_batch = Some(batch);
-                        self.current_row_idx = 0;
-                        continue;
-                    }
-                    Some(Err(e)) => return Some(Err(Box::new(e))),
-                    None => {
-                        self.current_reader = None;
-                    }
-                }
-            }
-
-            if self.current_reader.is_none() {
-                match self.advance_file() {
-                    Ok(true) => continue,
-                    Ok(false) => return None,
-                    Err(e) => return Some(Err(e)),
+            match self.parts.next()? {
+                Ok(batch) => {
+                    self.current_timestamps = batch
+                        .column_by_name("timestamp_ms")
+                        .and_then(|c| c.as_any().downcast_ref::<TimestampMillisecondArray>())
+                        .cloned();
+                    self.current_batch = Some(batch);
+                    self.current_row_idx = 0;
                 }
+                Err(e) => return Some(Err(Box::new(e))),
             }
         }
     }
@@ -678,18 +1049,24 @@ impl Iterator for ParquetTradeIterator {
 
 /// Iterator over Parquet orderbook files
 pub struct ParquetOrderbookIterator {
-    files: Vec<PathBuf>,
-    current_file_idx: usize,
-    current_reader: Option<Box<dyn Iterator<Item = Result<RecordBatch, arrow::error::ArrowError>>>>,
-    current_batch: Option<RecordBatch>,
+    parts: PartReader,
+    current_batch: Option<OrderbookBatch>,
     current_row_idx: usize,
     pub max_levels: usize, // Made public to access from stream()
-    warned_files: HashSet<PathBuf>,
-    suppress_warnings: bool,
+    time_range: TimeRange,
 }
 
 impl ParquetOrderbookIterator {
     pub fn new(path: &Path, suppress_warnings: bool) -> Result<Self, Box<dyn Error>> {
+        Self::with_time_range(path, suppress_warnings, TimeRange::ALL)
+    }
+
+    /// Iterate only over snapshots in `time_range`, skipping part files and row groups outside it
+    pub fn with_time_range(
+        path: &Path,
+        suppress_warnings: bool,
+        time_range: TimeRange,
+    ) -> Result<Self, Box<dyn Error>> {
         // If path is a directory, collect all .parquet files
         let files = if path.is_dir() {
             let mut files: Vec<PathBuf> = fs::read_dir(path)?
@@ -706,6 +1083,8 @@ impl ParquetOrderbookIterator {
         if files.is_empty() {
             return Err("No Parquet files found".into());
         }
+        // Drop out-of-range parts before any footer is read
+        let files = prune_part_files(files, time_range);
 
         // Find the first valid file to determine max_levels
         let mut max_levels = 0;
@@ -779,166 +1158,79 @@ impl ParquetOrderbookIterator {
             }
         }
 
-        if valid_files.is_empty() {
+        if valid_files.is_empty() && time_range.is_all() {
             return Err("No valid Parquet files found".into());
         }
 
-        let mut iter = Self {
-            files: valid_files,
-            current_file_idx: 0,
-            current_reader: None,
+        let parts = PartReader::new(
+            valid_files,
+            orderbook_part_opener(time_range, suppress_warnings, max_levels),
+            DEFAULT_PREFETCH_DEPTH,
+        );
+        if !parts.has_data() && time_range.is_all() {
+            return Err("No valid Parquet orderbook files found".into());
+        }
+
+        Ok(Self {
+            parts,
             current_batch: None,
             current_row_idx: 0,
             max_levels,
-            warned_files,
-            suppress_warnings,
-        };
-
-        // Initialize first file
-        iter.advance_file()?;
-        if iter.current_reader.is_none() {
-            return Err("No valid Parquet orderbook files found".into());
-        }
+            time_range,
+        })
+    }
 
-        Ok(iter)
+    /// Decode up to `prefetch_depth` files ahead on worker threads (0 decodes on the caller)
+    pub fn with_prefetch(mut self, prefetch_depth: usize) -> Self {
+        self.parts.set_prefetch_depth(prefetch_depth);
+        self
     }
 
-    fn advance_file(&mut self) -> Result<bool, Box<dyn Error>> {
-        loop {
-            if self.current_file_idx >= self.files.len() {
-                return Ok(false);
+    /// Next batch of snapshots in the time range (the unread rest of the current batch first)
+    pub fn next_batch(&mut self) -> Option<Result<OrderbookBatch, Box<dyn Error>>> {
+        if let Some(batch) = self.current_batch.take() {
+            if self.current_row_idx < batch.len() {
+                let rest = batch.slice(self.current_row_idx, batch.len() - self.current_row_idx);
+                self.current_row_idx = 0;
+                return Some(Ok(rest));
             }
+        }
+        self.current_row_idx = 0;
 
-            let file_path = &self.files[self.current_file_idx];
-            self.current_file_idx += 1;
-
-            match File::open(file_path) {
-                Ok(file) => match ParquetRecordBatchReaderBuilder::try_new(file) {
-                    Ok(builder) => {
-                        let schema = builder.schema();
-                        if let Err(e) = validate_orderbook_schema(schema.as_ref(), self.max_levels)
-                        {
-                            if !self.suppress_warnings && self.warned_files.insert(file_path.clone()) {
-                                eprintln!(
-                                    "Warning: Skipping parquet orderbook file with invalid schema {:?}: {}",
-                                    file_path,
-                                    e
-                                );
-                            }
-                            continue;
-                        }
-
-                        match builder.build() {
-                            Ok(reader) => {
-                                self.current_reader = Some(Box::new(reader));
-                                self.current_batch = None;
-                                self.current_row_idx = 0;
-                                return Ok(true);
-                            }
-                            Err(e) => {
-                                if !self.suppress_warnings && self.warned_files.insert(file_path.clone()) {
-                                    eprintln!("Warning: Skipping corrupt parquet file (build failed) {:?}: {}", file_path, e);
-                                }
-                                continue;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        if !self.suppress_warnings && self.warned_files.insert(file_path.clone()) {
-                            eprintln!("Warning: Skipping corrupt parquet file (invalid footer/metadata) {:?}: {}", file_path, e);
-                        }
-                        continue;
-                    }
-                },
-                Err(e) => {
-                    if !self.suppress_warnings && self.warned_files.insert(file_path.clone()) {
-                        eprintln!(
-                            "Warning: Skipping unreadable parquet file {:?}: {}",
-                            file_path, e
-                        );
-                    }
-                    continue;
+        loop {
+            match self.parts.next()? {
+                Ok(batch) => {
+                    let batch = match self.select_rows_in_range(batch) {
+                        Ok(Some(batch)) => batch,
+                        Ok(None) => continue,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    return Some(OrderbookBatch::from_record_batch(&batch, self.max_levels));
                 }
+                Err(e) => return Some(Err(Box::new(e))),
             }
         }
     }
 
-    fn read_snapshot_from_batch(
-        &self,
-        batch: &RecordBatch,
-        row_idx: usize,
-    ) -> Result<OrderbookSnapshot, Box<dyn Error>> {
-        // Extract timestamp
-        let timestamp_col = batch
-            .column(0)
-            .as_any()
-            .downcast_ref::<TimestampMillisecondArray>()
+    /// Rows of `batch` inside the time range, None when there are none
+    fn select_rows_in_range(&self, batch: RecordBatch) -> Result<Option<RecordBatch>, Box<dyn Error>> {
+        if self.time_range.is_all() {
+            return Ok(Some(batch));
+        }
+        let timestamps = batch
+            .column_by_name("timestamp_ms")
+            .and_then(|c| c.as_any().downcast_ref::<TimestampMillisecondArray>())
             .ok_or("Invalid timestamp column")?;
-        let timestamp = timestamp_col.value(row_idx) as u64;
-
-        let mut bids = Vec::new();
-        let mut asks = Vec::new();
-
-        // Extract bid/ask prices and quantities
-        for i in 0..self.max_levels {
-            let bid_price_idx = 3 + (i * 4);
-            let bid_qty_idx = bid_price_idx + 1;
-            let ask_price_idx = bid_price_idx + 2;
-            let ask_qty_idx = bid_price_idx + 3;
-
-            if bid_price_idx >= batch.num_columns() {
-                break;
-            }
-
-            let bid_price_col = batch
-                .column(bid_price_idx)
-                .as_any()
-                .downcast_ref::<Float64Array>()
-                .ok_or("Invalid bid_price column")?;
-            let bid_qty_col = batch
-                .column(bid_qty_idx)
-                .as_any()
-                .downcast_ref::<Float64Array>()
-                .ok_or("Invalid bid_qty column")?;
-            let ask_price_col = batch
-                .column(ask_price_idx)
-                .as_any()
-                .downcast_ref::<Float64Array>()
-                .ok_or("Invalid ask_price column")?;
-            let ask_qty_col = batch
-                .column(ask_qty_idx)
-                .as_any()
-                .downcast_ref::<Float64Array>()
-                .ok_or("Invalid ask_qty column")?;
-
-            if !bid_price_col.is_null(row_idx) && !bid_qty_col.is_null(row_idx) {
-                let bid_price = Decimal::from_f64(bid_price_col.value(row_idx))
-                    .ok_or("Failed to convert bid price")?;
-                let bid_qty = Decimal::from_f64(bid_qty_col.value(row_idx))
-                    .ok_or("Failed to convert bid qty")?;
-
-                if bid_price > Decimal::ZERO {
-                    bids.push((bid_price, bid_qty));
-                }
-            }
-
-            if !ask_price_col.is_null(row_idx) && !ask_qty_col.is_null(row_idx) {
-                let ask_price = Decimal::from_f64(ask_price_col.value(row_idx))
-                    .ok_or("Failed to convert ask price")?;
-                let ask_qty = Decimal::from_f64(ask_qty_col.value(row_idx))
-                    .ok_or("Failed to convert ask qty")?;
-
-                if ask_price > Decimal::ZERO {
-                    asks.push((ask_price, ask_qty));
-                }
-            }
+        let mask: BooleanArray = timestamps
+            .values()
+            .iter()
+            .map(|ts| Some(self.time_range.contains(*ts as u64)))
+            .collect();
+        match mask.true_count() {
+            0 => Ok(None),
+            n if n == batch.num_rows() => Ok(Some(batch)),
+            _ => Ok(Some(filter_record_batch(&batch, &mask)?)),
         }
-
-        Ok(OrderbookSnapshot {
-            timestamp,
-            bids,
-            asks,
-        })
     }
 }
 
@@ -949,41 +1241,295 @@ impl Iterator for ParquetOrderbookIterator {
         loop {
             // If we have a current batch, try to read from it
             if let Some(ref batch) = self.current_batch {
-                if self.current_row_idx < batch.num_rows() {
-                    let snapshot = self.read_snapshot_from_batch(batch, self.current_row_idx);
+                if self.current_row_idx < batch.len() {
+                    let snapshot = batch.view(self.current_row_idx).to_snapshot();
                     self.current_row_idx += 1;
                     return Some(snapshot);
-                } else {
-                    // Batch exhausted, try next batch
-                    self.current_batch = None;
-                    self.current_row_idx = 0;
                 }
             }
 
-            // Try to get next batch from current reader
-            if let Some(ref mut reader) = self.current_reader {
-                match reader.next() {
-                    Some(Ok(batch)) => {
-                        self.current_batch = Some(batch);
-                        self.current_row_idx = 0;
-                        continue;
-                    }
-                    Some(Err(e)) => return Some(Err(Box::new(e))),
-                    None => {
-                        // Reader exhausted, try next file
-                        self.current_reader = None;
-                    }
+            // Batch exhausted, try next batch
+            self.current_batch = None;
+            match self.next_batch() {
+                Some(Ok(batch)) => {
+                    self.current_batch = Some(batch);
+                    self.current_row_idx = 0;
                 }
+                Some(Err(e)) => return Some(Err(e)),
+                None => return None,
             }
+        }
+    }
+}
 
-            // Try to advance to next file
-            if self.current_reader.is_none() {
-                match self.advance_file() {
-                    Ok(true) => continue,
-                    Ok(false) => return None, // No more files
-                    Err(e) => return Some(Err(e)),
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+    use std::sync::Arc;
+
+    fn write_trade_part(path: &Path, timestamps: &[i64], row_group_size: usize) {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("timestamp_ms", DataType::Timestamp(TimeUnit::Millisecond, None), false),
+            Field::new("price", DataType::Float64, false),
+            Field::new("quantity", DataType::Float64, false),
+            Field::new("is_buyer_maker", DataType::Boolean, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(TimestampMillisecondArray::from(timestamps.to_vec())),
+                Arc::new(Float64Array::from(vec![100.0; timestamps.len()])),
+                Arc::new(Float64Array::from(vec![1.0; timestamps.len()])),
+                Arc::new(BooleanArray::from(vec![false; timestamps.len()])),
+            ],
+        )
+        .unwrap();
+        let props = WriterProperties::builder().set_max_row_group_size(row_group_size).build();
+        let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn write_orderbook_part(path: &Path, timestamps: &[i64], row_group_size: usize) {
+        let mut fields = vec![
+            Field::new("timestamp_ms", DataType::Timestamp(TimeUnit::Millisecond, None), false),
+            Field::new("market", DataType::Utf8, false),
+            Field::new("seq", DataType::Int64, false),
+        ];
+        let mut columns: Vec<arrow::array::ArrayRef> = vec![
+            Arc::new(TimestampMillisecondArray::from(timestamps.to_vec())),
+            Arc::new(arrow::array::StringArray::from(vec!["ETH-USD"; timestamps.len()])),
+            Arc::new(arrow::array::Int64Array::from((0..timestamps.len() as i64).collect::<Vec<_>>())),
+        ];
+        for level in 0..2 {
+            let offset = 0.1 * (level + 1) as f64;
+            let mid = |ts: &i64| 2000.0 + (*ts % 97) as f64 * 0.37;
+            for (name, values) in [
+                ("bid_price", timestamps.iter().map(|ts| Some(mid(ts) - offset)).collect::<Vec<_>>()),
+                ("bid_qty", timestamps.iter().map(|ts| Some(0.5 + (*ts % 7) as f64)).collect()),
+                // The second ask level is missing on every third row
+                ("ask_price", timestamps.iter().map(|ts| (level == 0 || ts % 3 != 0).then(|| mid(ts) + offset)).collect()),
+                ("ask_qty", timestamps.iter().map(|ts| (level == 0 || ts % 3 != 0).then_some(1.25)).collect()),
+            ] {
+                fields.push(Field::new(format!("{}_{}", name, level), DataType::Float64, true));
+                columns.push(Arc::new(Float64Array::from(values)));
             }
         }
+        let schema = Arc::new(Schema::new(fields));
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+        let props = WriterProperties::builder().set_max_row_group_size(row_group_size).build();
+        let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_orderbook_batches_match_snapshots() {
+        let dir = std::env::temp_dir().join(format!("data_loader_batch_test_{}", std::process::id()));
+        let (trades_dir, orderbook_dir) = (dir.join("trades"), dir.join("orderbook"));
+        fs::create_dir_all(&trades_dir).unwrap();
+        fs::create_dir_all(&orderbook_dir).unwrap();
+        let timestamps: Vec<i64> = (0..300).map(|i| 1_000 + i * 10).collect();
+        write_orderbook_part(&orderbook_dir.join("part_0000000001000_0000000000_000000.parquet"), &timestamps, 64);
+        write_trade_part(&trades_dir.join("part_0000000001000_000000.parquet"), &[1_005, 2_005], 64);
+
+        for loader in [
+            DataLoader::new(&trades_dir, &orderbook_dir),
+            DataLoader::new(&trades_dir, &orderbook_dir).with_time_range(1_500, 3_000),
+        ] {
+            let snapshots: Vec<OrderbookSnapshot> = loader.orderbooks_iter().unwrap().map(|r| r.unwrap().1).collect();
+            let rows: Vec<OrderbookRow> = loader
+                .stream()
+                .unwrap()
+                .filter_map(|e| match e.unwrap() {
+                    DataEvent::Orderbook(row) => Some(row),
+                    DataEvent::Trade(_) => None,
+                })
+                .collect();
+            assert_eq!(rows.len(), snapshots.len());
+            for (row, snapshot) in rows.iter().zip(&snapshots) {
+                let book = row.view();
+                assert_eq!(format!("{:?}", book.to_snapshot().unwrap()), format!("{:?}", snapshot));
+                assert_eq!(book.best_bid_decimal(), snapshot.bids.first().map(|(p, _)| *p));
+                assert_eq!(book.far_ask().and_then(Decimal::from_f64), snapshot.asks.last().map(|(p, _)| *p));
+            }
+
+            // Mids come straight from the f64 columns and agree with the Decimal snapshots
+            let mids: Vec<(u64, f64)> = loader.mids_iter().unwrap().map(|r| r.unwrap()).collect();
+            let expected: Vec<(u64, f64)> = rows
+                .iter()
+                .map(|row| {
+                    let book = row.view();
+                    (book.timestamp(), (book.best_bid().unwrap() + book.best_ask().unwrap()) / 2.0)
+                })
+                .collect();
+            assert_eq!(mids, expected);
+            for ((_, mid), s) in mids.iter().zip(&snapshots) {
+                let decimal_mid = (s.bids[0].0.to_f64().unwrap() + s.asks[0].0.to_f64().unwrap()) / 2.0;
+                assert!((mid - decimal_mid).abs() < 1e-9 * decimal_mid);
+            }
+        }
+        assert_eq!(DataLoader::new(&trades_dir, &orderbook_dir).with_time_range(1_500, 3_000).orderbooks_iter().unwrap().count(), 150);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prefetch_depth_does_not_change_stream() {
+        let dir = std::env::temp_dir().join(format!("data_loader_prefetch_test_{}", std::process::id()));
+        let (trades_dir, orderbook_dir) = (dir.join("trades"), dir.join("orderbook"));
+        fs::create_dir_all(&trades_dir).unwrap();
+        fs::create_dir_all(&orderbook_dir).unwrap();
+        for part in 0..6i64 {
+            let first = 1_000 + part * 1_000;
+            let book: Vec<i64> = (0..100).map(|i| first + i * 10).collect();
+            let trades: Vec<i64> = (0..40).map(|i| first + 5 + i * 25).collect();
+            write_orderbook_part(&orderbook_dir.join(format!("part_{:013}_{:010}_{:06}.parquet", first, 0, part)), &book, 32);
+            write_trade_part(&trades_dir.join(format!("part_{:013}_{:06}.parquet", first, part)), &trades, 16);
+        }
+
+        let events = |prefetch_depth: usize| -> Vec<String> {
+            DataLoader::new(&trades_dir, &orderbook_dir)
+                .with_prefetch(prefetch_depth)
+                .stream()
+                .unwrap()
+                .map(|e| match e.unwrap() {
+                    DataEvent::Trade(t) => format!("{:?}", t),
+                    DataEvent::Orderbook(row) => format!("{:?}", row.view().to_snapshot().unwrap()),
+                })
+                .collect()
+        };
+        let sequential = events(0);
+        assert_eq!(sequential.len(), 6 * 140);
+        for depth in [1, 3, 8] {
+            assert_eq!(events(depth), sequential);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_event_cache_replays_stream_until_sources_change() {
+        use crate::event_cache::build_event_cache;
+
+        let dir = std::env::temp_dir().join(format!("data_loader_event_cache_test_{}", std::process::id()));
+        let (trades_dir, orderbook_dir) = (dir.join("trades_parts"), dir.join("orderbook_parts"));
+        fs::create_dir_all(&trades_dir).unwrap();
+        fs::create_dir_all(&orderbook_dir).unwrap();
+        for part in 0..3i64 {
+            let first = 1_000 + part * 1_000;
+            let book: Vec<i64> = (0..100).map(|i| first + i * 10).collect();
+            let trades: Vec<i64> = (0..40).map(|i| first + 5 + i * 25).collect();
+            write_orderbook_part(&orderbook_dir.join(format!("part_{:013}_{:010}_{:06}.parquet", first, 0, part)), &book, 32);
+            write_trade_part(&trades_dir.join(format!("part_{:013}_{:06}.parquet", first, part)), &trades, 16);
+        }
+
+        // Everything a consumer can observe, including null levels
+        fn describe(stream: impl Iterator<Item = Result<DataEvent, Box<dyn Error>>>) -> Vec<String> {
+            stream
+                .map(|e| match e.unwrap() {
+                    DataEvent::Trade(t) => format!("{:?}", t),
+                    DataEvent::Orderbook(row) => {
+                        let book = row.view();
+                        let levels: Vec<_> = (0..book.depth()).map(|l| book.level_values(l)).collect();
+                        format!("{:?} {:?}", book.to_snapshot().unwrap(), levels)
+                    }
+                })
+                .collect()
+        }
+
+        let summary = build_event_cache(&DataLoader::new(&trades_dir, &orderbook_dir), &dir.join("events.cache")).unwrap();
+        assert_eq!((summary.trades, summary.orderbooks, summary.depth), (120, 300, 2));
+        // The synthetic prices mix exact decimals with computed floats, exercising both encodings
+        assert!(summary.raw_orderbooks > 0 && summary.raw_orderbooks < summary.orderbooks);
+        assert!(!dir.join("events.cache.tmp").exists());
+
+        for (start_ms, end_ms) in [(0, u64::MAX), (1_500, 2_750)] {
+            let loader = DataLoader::new(&trades_dir, &orderbook_dir).with_time_range(start_ms, end_ms);
+            let cached = loader.stream().unwrap();
+            assert!(matches!(cached, EventStream::Cache(_)));
+            let expected = describe(loader.source_stream().unwrap());
+            assert!(!expected.is_empty());
+            assert_eq!(describe(cached), expected);
+        }
+        assert!(build_event_cache(&DataLoader::new(&trades_dir, &orderbook_dir).with_time_range(0, 5_000), &dir.join("events.cache")).is_err());
+
+        // Opting out reads the sources
+        let loader = DataLoader::new(&trades_dir, &orderbook_dir).with_event_cache(None);
+        assert!(matches!(loader.stream().unwrap(), EventStream::Sources(_)));
+
+        // A new part makes the cache stale
+        write_trade_part(&trades_dir.join("part_0000000004000_000003.parquet"), &[4_005], 16);
+        let loader = DataLoader::new(&trades_dir, &orderbook_dir);
+        assert!(matches!(loader.stream().unwrap(), EventStream::Sources(_)));
+        assert_eq!(loader.stream().unwrap().count(), 421);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_timestamp_arg() {
+        assert_eq!(parse_timestamp_arg("1700000000000").unwrap(), 1_700_000_000_000);
+        assert_eq!(parse_timestamp_arg("2024-01-02").unwrap(), 1_704_153_600_000);
+        assert_eq!(parse_timestamp_arg("2024-01-02T01:00:00+01:00").unwrap(), 1_704_153_600_000);
+        assert!(parse_timestamp_arg("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_prune_part_files() {
+        let files: Vec<PathBuf> = [
+            "part_0000000001000_000000.parquet",
+            "part_0000000002000_000001.parquet",
+            "part_0000000003000_000002.parquet",
+            "part_0000000004000_000003.parquet",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        assert_eq!(part_first_timestamp(&files[1]), Some(2000));
+
+        // [2500, 3000) can only be in the part starting at 2000
+        let kept = prune_part_files(files.clone(), TimeRange::new(2500, 3000));
+        assert_eq!(kept, vec![files[1].clone()]);
+        // The last part has no upper bound
+        let kept = prune_part_files(files.clone(), TimeRange::new(9000, 10000));
+        assert_eq!(kept, vec![files[3].clone()]);
+        assert!(prune_part_files(files.clone(), TimeRange::new(0, 1000)).is_empty());
+        assert_eq!(prune_part_files(files.clone(), TimeRange::ALL), files);
+    }
+
+    #[test]
+    fn test_time_range_skips_files_and_row_groups() {
+        let dir = std::env::temp_dir().join(format!("data_loader_range_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first: Vec<i64> = (0..100).map(|i| 1000 + i * 10).collect();
+        let second: Vec<i64> = (0..100).map(|i| 5000 + i * 10).collect();
+        write_trade_part(&dir.join("part_0000000001000_000000.parquet"), &first, 25);
+        write_trade_part(&dir.join("part_0000000005000_000001.parquet"), &second, 25);
+
+        // Row groups cover [1000,1240], [1250,1490], ...; [1300, 1500) only touches the second
+        let file = File::open(dir.join("part_0000000001000_000000.parquet")).unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(row_groups_in_range(builder.metadata(), TimeRange::new(1300, 1500)), vec![1]);
+        assert_eq!(row_groups_in_range(builder.metadata(), TimeRange::ALL).len(), 4);
+
+        let iter = ParquetTradeIterator::with_time_range(&dir, true, TimeRange::new(1300, 1500)).unwrap();
+        // The second part was pruned by name: nothing is left after the first
+        assert!(iter.parts.has_data());
+        assert_eq!(iter.parts.unopened_files(), 0);
+        let timestamps: Vec<u64> = iter.map(|t| t.unwrap().timestamp).collect();
+        assert_eq!(timestamps, (1300..1500).step_by(10).collect::<Vec<u64>>());
+
+        // A range with no data is an empty stream
+        let iter = ParquetTradeIterator::with_time_range(&dir, true, TimeRange::new(3000, 4000)).unwrap();
+        assert_eq!(iter.count(), 0);
+
+        let all = ParquetTradeIterator::new(&dir, true).unwrap();
+        assert_eq!(all.count(), 200);
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
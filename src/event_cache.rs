@@ -0,0 +1,521 @@
+//! Memory-mapped event cache
+//!
+//! Backtests over the same market re-read the same parquet parts many times a day, and most
+//! of that time goes into decoding columns and building `Decimal`s. `build_event_cache`
+//! replays a loader's merged trade/orderbook stream once into a single file of fixed-size,
+//! fixed-point records, already in stream order. `DataLoader::stream` maps that file and
+//! replays it instead of the sources as long as the fingerprint in its header matches the
+//! names, sizes and modification times of the source files: a new or rewritten part makes
+//! the cache stale, and the loader reads the sources until the cache is rebuilt. Opening a
+//! cache only reads its header; replays check each record's bounds and values as they go and
+//! return an error at the first corrupt record.
+//!
+//! Layout, in little-endian u64 words:
+//! - header: magic, format version | depth << 32, fingerprint, trade count, orderbook count
+//! - one record per event, starting with a `timestamp_ms << 2 | kind` tag word, followed by
+//!   - trades (kind 0, or 1 when the buyer was maker): price, quantity
+//!   - orderbooks (kind 2): `[bid_price, bid_qty, ask_price, ask_qty]` for every level
+//!
+//! Values are stored as `mantissa << 5 | scale`. Trades keep the mantissa and scale of their
+//! `Decimal`, orderbook values the shortest decimal that converts back to the same f64, so a
+//! replay from the cache is identical to one from the sources. Snapshots holding a value with
+//! no such decimal (a computed price like 2011.1999999999998) keep their raw f64 bits as
+//! kind 3 records instead.
+
+use crate::calibration_cache::{hash_data_path, Fnv64};
+use crate::data_loader::{DataEvent, DataLoader, TimeRange};
+use crate::model_types::TradeEvent;
+use crate::orderbook_batch::{OrderbookBatch, OrderbookBatchBuilder, OrderbookRow};
+use memmap2::Mmap;
+use rust_decimal::Decimal;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Cache file name inside a market's data directory
+pub const EVENT_CACHE_FILE: &str = "events.cache";
+
+const MAGIC: u64 = u64::from_le_bytes(*b"EDCEVENT");
+const FORMAT_VERSION: u64 = 1;
+const HEADER_WORDS: usize = 5;
+/// Tag, price and quantity
+const TRADE_WORDS: usize = 3;
+
+const KIND_TRADE: u64 = 0;
+const KIND_TRADE_BUYER_MAKER: u64 = 1;
+const KIND_ORDERBOOK: u64 = 2;
+const KIND_ORDERBOOK_RAW: u64 = 3;
+
+/// Cached orderbook records decoded into one `OrderbookBatch`
+const CACHE_ORDERBOOK_BATCH_ROWS: usize = 1024;
+
+const SCALE_BITS: u32 = 5;
+const SCALE_MASK: i64 = (1 << SCALE_BITS) - 1;
+/// Missing orderbook value
+const NULL_VALUE: i64 = i64::MIN;
+/// Missing orderbook value in a raw record (a NaN payload no parsed price carries)
+const NULL_RAW_BITS: u64 = 0x7ff4_ed0c_ac4e_0001;
+/// Mantissas stay below 2^53, so they fit the packed field and convert to f64 exactly
+const MAX_MANTISSA: u64 = 1 << 53;
+/// Powers of ten exactly representable as f64, dividing by which rounds correctly
+const POW10: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19,
+    1e20, 1e21, 1e22,
+];
+
+/// Where `build_event_cache` writes a market's cache and `DataLoader` looks for it
+pub fn event_cache_path(market_dir: &Path) -> PathBuf {
+    market_dir.join(EVENT_CACHE_FILE)
+}
+
+/// Hash of the names, sizes and modification times of the files a loader reads
+pub fn source_fingerprint(trades_path: &Path, orderbook_path: &Path) -> Result<u64, Box<dyn Error>> {
+    let mut hasher = Fnv64::new();
+    hash_data_path(&mut hasher, trades_path)?;
+    hash_data_path(&mut hasher, orderbook_path)?;
+    Ok(hasher.0)
+}
+
+fn pack(mantissa: i64, scale: u32) -> i64 {
+    (mantissa << SCALE_BITS) | scale as i64
+}
+
+fn encode_decimal(value: Decimal) -> Result<i64, Box<dyn Error>> {
+    match i64::try_from(value.mantissa()) {
+        Ok(mantissa) if mantissa.unsigned_abs() < MAX_MANTISSA => Ok(pack(mantissa, value.scale())),
+        _ => Err(format!("{} does not fit the event cache's fixed-point range", value).into()),
+    }
+}
+
+fn decode_decimal(packed: i64) -> Result<Decimal, Box<dyn Error>> {
+    Decimal::try_new(packed >> SCALE_BITS, (packed & SCALE_MASK) as u32)
+        .map_err(|e| format!("value {:#x} is not a decimal: {}", packed, e).into())
+}
+
+/// Fewest decimal places that convert back to exactly `value`
+fn encode_f64(value: f64) -> Result<i64, Box<dyn Error>> {
+    for (scale, pow) in POW10.iter().enumerate() {
+        let mantissa = (value * pow).round();
+        if mantissa.abs() < MAX_MANTISSA as f64 && mantissa / pow == value {
+            return Ok(pack(mantissa as i64, scale as u32));
+        }
+    }
+    Err(format!("{} has no exact fixed-point form", value).into())
+}
+
+fn decode_f64(packed: i64) -> Result<f64, Box<dyn Error>> {
+    let scale = (packed & SCALE_MASK) as usize;
+    match POW10.get(scale) {
+        Some(pow) => Ok((packed >> SCALE_BITS) as f64 / pow),
+        None => Err(format!("value {:#x} has scale {} (at most {})", packed, scale, POW10.len() - 1).into()),
+    }
+}
+
+/// One orderbook value of a record of `kind`, None when missing
+fn decode_orderbook_value(kind: u64, value: u64) -> Result<Option<f64>, Box<dyn Error>> {
+    if kind == KIND_ORDERBOOK_RAW {
+        Ok((value != NULL_RAW_BITS).then(|| f64::from_bits(value)))
+    } else if value as i64 == NULL_VALUE {
+        Ok(None)
+    } else {
+        decode_f64(value as i64).map(Some)
+    }
+}
+
+/// Words in a record starting with `tag`
+fn record_words(tag: u64, depth: usize) -> usize {
+    if tag & 3 >= KIND_ORDERBOOK {
+        1 + 4 * depth
+    } else {
+        TRADE_WORDS
+    }
+}
+
+#[inline]
+fn word(bytes: &[u8], index: usize) -> u64 {
+    u64::from_le_bytes(bytes[index * 8..index * 8 + 8].try_into().unwrap())
+}
+
+fn write_word(out: &mut impl Write, value: u64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+/// What `build_event_cache` wrote
+#[derive(Debug, Clone)]
+pub struct EventCacheSummary {
+    pub trades: u64,
+    pub orderbooks: u64,
+    /// Orderbook snapshots stored as raw f64 because a value had no exact fixed-point form
+    pub raw_orderbooks: u64,
+    pub depth: usize,
+    pub bytes: u64,
+}
+
+/// Replay the sources of `loader` into a cache at `output`. The file is written under a
+/// temporary name and renamed into place, so readers never map a partial cache.
+pub fn build_event_cache(loader: &DataLoader, output: &Path) -> Result<EventCacheSummary, Box<dyn Error>> {
+    if !loader.time_range().is_all() {
+        return Err("The event cache covers the whole dataset; build it without a time range".into());
+    }
+    // Taken before reading, so parts arriving during the build leave the cache stale
+    let fingerprint = source_fingerprint(loader.trades_path(), loader.orderbook_path())?;
+
+    let temp_path = output.with_extension("cache.tmp");
+    match write_events(loader, &temp_path, fingerprint) {
+        Ok(summary) => {
+            fs::rename(&temp_path, output)?;
+            Ok(summary)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+fn write_events(loader: &DataLoader, path: &Path, fingerprint: u64) -> Result<EventCacheSummary, Box<dyn Error>> {
+    let mut out = BufWriter::with_capacity(1024 * 1024, File::create(path)?);
+    // Header placeholder, filled in once the counts and depth are known
+    for _ in 0..HEADER_WORDS {
+        write_word(&mut out, 0)?;
+    }
+
+    let (mut trades, mut orderbooks, mut raw_orderbooks) = (0u64, 0u64, 0u64);
+    let mut depth = None;
+    let mut packed = Vec::new();
+    for event in loader.source_stream()? {
+        match event? {
+            DataEvent::Trade(trade) => {
+                let kind = if trade.is_buyer_maker { KIND_TRADE_BUYER_MAKER } else { KIND_TRADE };
+                write_word(&mut out, (trade.timestamp << 2) | kind)?;
+                write_word(&mut out, encode_decimal(trade.price)? as u64)?;
+                write_word(&mut out, encode_decimal(trade.quantity)? as u64)?;
+                trades += 1;
+            }
+            DataEvent::Orderbook(row) => {
+                let book = row.view();
+                let levels = *depth.get_or_insert(book.depth());
+                if book.depth() != levels {
+                    return Err(format!(
+                        "Orderbook depth changes from {} to {} at {}",
+                        levels,
+                        book.depth(),
+                        book.timestamp()
+                    )
+                    .into());
+                }
+                let values: Vec<Option<f64>> = (0..levels).flat_map(|level| book.level_values(level)).collect();
+                packed.clear();
+                for value in &values {
+                    match value.map_or(Ok(NULL_VALUE), encode_f64) {
+                        Ok(v) => packed.push(v as u64),
+                        Err(_) => break,
+                    }
+                }
+
+                if packed.len() == values.len() {
+                    write_word(&mut out, (book.timestamp() << 2) | KIND_ORDERBOOK)?;
+                    for &v in &packed {
+                        write_word(&mut out, v)?;
+                    }
+                } else {
+                    write_word(&mut out, (book.timestamp() << 2) | KIND_ORDERBOOK_RAW)?;
+                    for value in &values {
+                        write_word(&mut out, value.map_or(NULL_RAW_BITS, f64::to_bits))?;
+                    }
+                    raw_orderbooks += 1;
+                }
+                orderbooks += 1;
+            }
+        }
+    }
+
+    let depth = depth.unwrap_or(0);
+    out.seek(SeekFrom::Start(0))?;
+    for value in [MAGIC, FORMAT_VERSION | ((depth as u64) << 32), fingerprint, trades, orderbooks] {
+        write_word(&mut out, value)?;
+    }
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+
+    Ok(EventCacheSummary { trades, orderbooks, raw_orderbooks, depth, bytes: file.metadata()?.len() })
+}
+
+/// A memory-mapped event cache
+pub struct EventCache {
+    map: Arc<Mmap>,
+    depth: usize,
+    fingerprint: u64,
+    trades: u64,
+    orderbooks: u64,
+}
+
+impl EventCache {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        // SAFETY: caches are replaced by renaming a new file over them, never modified in place
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < HEADER_WORDS * 8 || word(&map, 0) != MAGIC {
+            return Err(format!("{} is not an event cache", path.display()).into());
+        }
+        let version = word(&map, 1);
+        if version & 0xffff_ffff != FORMAT_VERSION {
+            return Err(format!(
+                "{} has format version {} (expected {})",
+                path.display(),
+                version & 0xffff_ffff,
+                FORMAT_VERSION
+            )
+            .into());
+        }
+        let depth = (version >> 32) as usize;
+        let (trades, orderbooks) = (word(&map, 3), word(&map, 4));
+        let expected_bytes = (4 * depth as u64)
+            .checked_add(1)
+            .and_then(|words| orderbooks.checked_mul(words))
+            .and_then(|words| trades.checked_mul(TRADE_WORDS as u64)?.checked_add(words))
+            .and_then(|words| words.checked_add(HEADER_WORDS as u64)?.checked_mul(8));
+        if expected_bytes != Some(map.len() as u64) {
+            return Err(format!("{} is truncated or corrupt", path.display()).into());
+        }
+
+        let fingerprint = word(&map, 2);
+        Ok(Self { map: Arc::new(map), depth, fingerprint, trades, orderbooks })
+    }
+
+    /// Fingerprint of the source files the cache was built from
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn trades(&self) -> u64 {
+        self.trades
+    }
+
+    pub fn orderbooks(&self) -> u64 {
+        self.orderbooks
+    }
+
+    /// Orderbook levels per side
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Replay the cached events inside `time_range`, in stream order
+    pub fn events(&self, time_range: TimeRange) -> CachedEvents {
+        CachedEvents {
+            map: Arc::clone(&self.map),
+            depth: self.depth,
+            time_range,
+            position: HEADER_WORDS,
+            records: (0, 0),
+            expected_records: (self.trades, self.orderbooks),
+            scan_position: HEADER_WORDS,
+            orderbook_batch: None,
+            orderbook_row: 0,
+        }
+    }
+}
+
+/// Events replayed from an `EventCache`
+pub struct CachedEvents {
+    map: Arc<Mmap>,
+    depth: usize,
+    time_range: TimeRange,
+    // Word index of the next record to replay
+    position: usize,
+    // (trades, orderbooks) walked so far and announced by the header
+    records: (u64, u64),
+    expected_records: (u64, u64),
+    // Orderbook records are decoded in batches ahead of the replay: the next record to scan,
+    // and the batch being replayed with its next row
+    scan_position: usize,
+    orderbook_batch: Option<Arc<OrderbookBatch>>,
+    orderbook_row: usize,
+}
+
+impl CachedEvents {
+    /// Next orderbook in range, decoding a batch of them when the current one is used up
+    fn next_orderbook_row(&mut self) -> Result<OrderbookRow, Box<dyn Error>> {
+        if let Some(batch) = &self.orderbook_batch {
+            if self.orderbook_row < batch.len() {
+                self.orderbook_row += 1;
+                return Ok(OrderbookRow::new(Arc::clone(batch), self.orderbook_row - 1));
+            }
+        }
+
+        let end = self.map.len() / 8;
+        let mut builder = OrderbookBatchBuilder::new(self.depth, CACHE_ORDERBOOK_BATCH_ROWS);
+        let mut values = Vec::with_capacity(4 * self.depth);
+        while builder.len() < CACHE_ORDERBOOK_BATCH_ROWS && self.scan_position < end {
+            let record = self.scan_position;
+            let tag = word(&self.map, record);
+            let timestamp = tag >> 2;
+            let kind = tag & 3;
+            let words = record_words(tag, self.depth);
+            if record + words > end {
+                return Err(format!("Event cache record at word {} runs past the end of the file", record).into());
+            }
+            if kind >= KIND_ORDERBOOK && self.time_range.contains(timestamp) {
+                values.clear();
+                for i in 1..words {
+                    values.push(decode_orderbook_value(kind, word(&self.map, record + i))?);
+                }
+                builder.push(timestamp, values.iter().copied());
+            }
+            self.scan_position += words;
+        }
+
+        let batch = Arc::new(builder.finish());
+        self.orderbook_batch = Some(Arc::clone(&batch));
+        self.orderbook_row = 1;
+        Ok(OrderbookRow::new(batch, 0))
+    }
+
+    fn decode_trade(&self, record: usize, timestamp: u64, kind: u64) -> Result<TradeEvent, Box<dyn Error>> {
+        if record + TRADE_WORDS > self.map.len() / 8 {
+            return Err(format!("Event cache record at word {} runs past the end of the file", record).into());
+        }
+        Ok(TradeEvent {
+            timestamp,
+            price: decode_decimal(word(&self.map, record + 1) as i64)?,
+            quantity: decode_decimal(word(&self.map, record + 2) as i64)?,
+            is_buyer_maker: kind == KIND_TRADE_BUYER_MAKER,
+        })
+    }
+}
+
+impl Iterator for CachedEvents {
+    type Item = Result<DataEvent, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.map.len() / 8;
+        while self.position < end {
+            let record = self.position;
+            let tag = word(&self.map, record);
+            let words = record_words(tag, self.depth);
+            if record + words > end {
+                self.position = end;
+                return Some(Err(format!("Event cache record at word {} runs past the end of the file", record).into()));
+            }
+            self.position += words;
+            if tag & 3 >= KIND_ORDERBOOK {
+                self.records.1 += 1;
+            } else {
+                self.records.0 += 1;
+            }
+
+            let timestamp = tag >> 2;
+            if !self.time_range.contains(timestamp) {
+                continue;
+            }
+            let event = match tag & 3 {
+                KIND_ORDERBOOK | KIND_ORDERBOOK_RAW => self.next_orderbook_row().map(DataEvent::Orderbook),
+                kind => self.decode_trade(record, timestamp, kind).map(DataEvent::Trade),
+            };
+            if event.is_err() {
+                // Nothing after a corrupt record can be trusted
+                self.position = end;
+            }
+            return Some(event);
+        }
+        if self.records != self.expected_records {
+            let (trades, orderbooks) = std::mem::replace(&mut self.expected_records, self.records);
+            return Some(Err(format!(
+                "Event cache holds {} trades and {} orderbooks, its header {} and {}",
+                self.records.0, self.records.1, trades, orderbooks
+            )
+            .into()));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_fixed_point_round_trip() {
+        for value in [0.0, 1.0, 2035.59, 0.001, 1e-9, 123456.789, -42.5, 2000.0 + 0.37 * 55.0] {
+            let packed = encode_f64(value).unwrap();
+            assert_eq!(decode_f64(packed).unwrap(), value, "{}", value);
+        }
+        // Shortest form: 2035.59 needs two decimal places
+        assert_eq!(encode_f64(2035.59).unwrap(), pack(203559, 2));
+        assert_ne!(encode_f64(-1.5).unwrap(), NULL_VALUE);
+        // Floats that are not the nearest double to a short decimal
+        assert!(encode_f64(0.1 + 0.2).is_err());
+        assert!(encode_f64(2011.1999999999998).is_err());
+        assert!(encode_f64(f64::NAN).is_err());
+        assert!(encode_f64(f64::INFINITY).is_err());
+        assert!(encode_f64(1e300).is_err());
+
+        // Decimals keep their scale, so they print exactly as before
+        for text in ["2035.590", "0.0001", "-3", "12345678.12345678"] {
+            let value = Decimal::from_str(text).unwrap();
+            let decoded = decode_decimal(encode_decimal(value).unwrap()).unwrap();
+            assert_eq!(decoded.to_string(), value.to_string());
+            assert_eq!(decoded.scale(), value.scale());
+        }
+        assert!(encode_decimal(Decimal::MAX).is_err());
+
+        // Scales beyond the f64 table or Decimal's 28 places are errors, not panics
+        assert!(decode_f64(pack(1, 22)).is_ok());
+        assert!(decode_f64(pack(1, 23)).is_err());
+        assert!(decode_decimal(pack(1, 28)).is_ok());
+        assert!(decode_decimal(pack(1, 29)).is_err());
+    }
+
+    /// A depth-1 cache with one orderbook and one trade, its words passed through `corrupt`
+    fn open_cache(name: &str, corrupt: impl FnOnce(&mut Vec<u64>)) -> Result<EventCache, Box<dyn Error>> {
+        let mut words = vec![MAGIC, FORMAT_VERSION | (1 << 32), 7, 1, 1];
+        words.push((1_000 << 2) | KIND_ORDERBOOK);
+        words.extend([2035.5, 1.0, 2035.6, 2.0].map(|v| encode_f64(v).unwrap() as u64));
+        words.extend([(2_000 << 2) | KIND_TRADE, pack(203559, 2) as u64, pack(1, 0) as u64]);
+        corrupt(&mut words);
+
+        let path = std::env::temp_dir().join(format!("event_cache_test_{}_{}.cache", name, std::process::id()));
+        let mut out = File::create(&path).unwrap();
+        for w in words {
+            write_word(&mut out, w).unwrap();
+        }
+        drop(out);
+        let cache = EventCache::open(&path);
+        let _ = fs::remove_file(&path);
+        cache
+    }
+
+    /// Replay of `open_cache`, stopping at the first error
+    fn replay(name: &str, corrupt: impl FnOnce(&mut Vec<u64>)) -> Result<Vec<DataEvent>, Box<dyn Error>> {
+        open_cache(name, corrupt)?.events(TimeRange::ALL).collect()
+    }
+
+    #[test]
+    fn test_corrupt_cache_replay_returns_errors() {
+        assert_eq!(replay("valid", |_| {}).unwrap().len(), 2);
+
+        // Scale past the powers-of-ten table in an orderbook value
+        assert!(replay("scale", |w| w[6] = pack(20355, 30) as u64).is_err());
+        // Scale past Decimal's range in a trade price
+        assert!(replay("decimal", |w| w[11] = pack(203559, 29) as u64).is_err());
+        // Last record, a trade, tagged as an orderbook: it would run past the end
+        assert!(replay("kind", |w| w[10] |= KIND_ORDERBOOK).is_err());
+        // Five trades filling the length of the three orderbooks the header announces
+        let counts = |w: &mut Vec<u64>| {
+            w.truncate(HEADER_WORDS);
+            for _ in 0..5 {
+                w.extend([(2_000 << 2) | KIND_TRADE, pack(203559, 2) as u64, pack(1, 0) as u64]);
+            }
+            w[3] = 0;
+            w[4] = 3;
+        };
+        assert!(open_cache("counts_open", counts).is_ok());
+        assert!(replay("counts", counts).is_err());
+        // Header counts that overflow the expected length are rejected on open
+        assert!(open_cache("overflow", |w| w[3] = u64::MAX / 2).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Out-of-sample evaluation of volatility forecasts
+//!
+//! At each evaluation time every estimator's σ² forecast is compared with the realized
+//! variance per second over the following horizon. Losses are QLIKE (robust to noise in the
+//! realized proxy, penalises under-prediction) and squared error on variance, and each
+//! estimator is tested against a baseline with a Diebold–Mariano test whose long-run
+//! variance uses Bartlett weights, since overlapping horizons make loss differences
+//! autocorrelated.
+
+use serde::Serialize;
+
+/// QLIKE loss r/f − ln(r/f) − 1 of a variance forecast `f` against realized variance `r`
+pub fn qlike(forecast: f64, realized: f64) -> f64 {
+    let ratio = realized / forecast;
+    ratio - ratio.ln() - 1.0
+}
+
+/// Squared error of a variance forecast
+pub fn squared_error(forecast: f64, realized: f64) -> f64 {
+    (forecast - realized).powi(2)
+}
+
+/// Standard normal CDF (erfc rational approximation, relative error < 1.2e-7)
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98 + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let tail = 0.5 * t * poly.exp();
+    if x >= 0.0 { 1.0 - tail } else { tail }
+}
+
+/// Diebold–Mariano test of equal expected loss
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DieboldMariano {
+    /// Mean of loss_a − loss_b (negative: a is better)
+    pub mean_difference: f64,
+    pub statistic: f64,
+    /// Two-sided p-value under the asymptotic normal distribution
+    pub p_value: f64,
+    pub observations: usize,
+}
+
+/// Diebold–Mariano test on paired losses, with a Bartlett-weighted long-run variance
+/// over `lag` autocovariances (horizon/interval − 1 for overlapping horizons)
+pub fn diebold_mariano(loss_a: &[f64], loss_b: &[f64], lag: usize) -> Option<DieboldMariano> {
+    let differences: Vec<f64> = loss_a
+        .iter()
+        .zip(loss_b)
+        .map(|(a, b)| a - b)
+        .filter(|d| d.is_finite())
+        .collect();
+    let n = differences.len();
+    if n < 2 {
+        return None;
+    }
+
+    let mean = differences.iter().sum::<f64>() / n as f64;
+    let autocovariance = |k: usize| {
+        differences[k..]
+            .iter()
+            .zip(&differences)
+            .map(|(x, y)| (x - mean) * (y - mean))
+            .sum::<f64>()
+            / n as f64
+    };
+    let mut long_run_variance = autocovariance(0);
+    for k in 1..=lag.min(n - 1) {
+        long_run_variance += 2.0 * (1.0 - k as f64 / (lag + 1) as f64) * autocovariance(k);
+    }
+    if !(long_run_variance.is_finite() && long_run_variance > 0.0) {
+        return None;
+    }
+
+    let statistic = mean / (long_run_variance / n as f64).sqrt();
+    Some(DieboldMariano {
+        mean_difference: mean,
+        statistic,
+        p_value: 2.0 * (1.0 - normal_cdf(statistic.abs())),
+        observations: n,
+    })
+}
+
+/// Realized variance per second over future intervals, from prefix sums of squared log returns
+pub struct RealizedVarianceAhead {
+    timestamps: Vec<u64>,
+    /// prefix[i] = Σ r² of returns ending at or before timestamps[i]
+    prefix: Vec<f64>,
+}
+
+impl RealizedVarianceAhead {
+    /// `prices` are (timestamp ms, mid) in time order
+    pub fn new(prices: &[(u64, f64)]) -> Self {
+        let mut timestamps = Vec::with_capacity(prices.len());
+        let mut prefix = Vec::with_capacity(prices.len());
+        let mut sum = 0.0;
+        let mut last: Option<f64> = None;
+        for &(ts, price) in prices {
+            if !(price.is_finite() && price > 0.0) {
+                continue;
+            }
+            if let Some(last_price) = last {
+                sum += (price / last_price).ln().powi(2);
+            }
+            timestamps.push(ts);
+            prefix.push(sum);
+            last = Some(price);
+        }
+        Self { timestamps, prefix }
+    }
+
+    /// Variance per second over (start, start + horizon], or None when the data ends earlier
+    /// or contains a gap longer than `max_gap_ms`
+    pub fn variance(&self, start_ms: u64, horizon_ms: u64, max_gap_ms: u64) -> Option<f64> {
+        let end_ms = start_ms.checked_add(horizon_ms)?;
+        if horizon_ms == 0 || self.timestamps.last().is_none_or(|last| *last < end_ms) {
+            return None;
+        }
+        let first = self.timestamps.partition_point(|ts| *ts <= start_ms).checked_sub(1)?;
+        let last = self.timestamps.partition_point(|ts| *ts <= end_ms) - 1;
+
+        let gap_free = self.timestamps[first..=last].windows(2).all(|w| w[1] - w[0] <= max_gap_ms);
+        if !gap_free || last == first {
+            return None;
+        }
+        Some((self.prefix[last] - self.prefix[first]) / (horizon_ms as f64 / 1000.0))
+    }
+}
+
+/// Scores of one estimator over all evaluation points
+#[derive(Debug, Clone, Serialize)]
+pub struct EstimatorScore {
+    pub name: String,
+    /// Evaluation points with a finite loss, the ones behind the means
+    pub observations: usize,
+    /// Evaluation points where the estimator had no forecast and the window's realized σ was used
+    pub fallbacks: usize,
+    pub mean_qlike: f64,
+    pub mean_squared_error: f64,
+    /// Tests against the baseline estimator (None for the baseline itself)
+    pub dm_qlike: Option<DieboldMariano>,
+    pub dm_squared_error: Option<DieboldMariano>,
+}
+
+/// Paired variance forecasts and realized variances
+pub struct ForecastEvaluation {
+    names: Vec<String>,
+    /// forecasts[estimator][point]
+    forecasts: Vec<Vec<f64>>,
+    fallbacks: Vec<usize>,
+    realized: Vec<f64>,
+}
+
+impl ForecastEvaluation {
+    pub fn new(names: Vec<String>) -> Self {
+        let count = names.len();
+        Self { names, forecasts: vec![Vec::new(); count], fallbacks: vec![0; count], realized: Vec::new() }
+    }
+
+    /// Record one evaluation point: σ forecasts (None = estimator fell back to `fallback_sigma`)
+    /// and the realized variance per second that followed
+    pub fn record(&mut self, sigmas: &[Option<f64>], fallback_sigma: f64, realized_variance: f64) {
+        for (i, sigma) in sigmas.iter().enumerate().take(self.names.len()) {
+            if sigma.is_none() {
+                self.fallbacks[i] += 1;
+            }
+            self.forecasts[i].push(sigma.unwrap_or(fallback_sigma).powi(2));
+        }
+        self.realized.push(realized_variance);
+    }
+
+    pub fn len(&self) -> usize {
+        self.realized.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.realized.is_empty()
+    }
+
+    /// Mean losses per estimator and DM tests against `baseline` with `lag` autocovariances.
+    /// Points with a non-positive forecast or realized variance are dropped.
+    pub fn summarize(&self, baseline: &str, lag: usize) -> Vec<EstimatorScore> {
+        let losses = |forecasts: &[f64], loss: fn(f64, f64) -> f64| -> Vec<f64> {
+            forecasts
+                .iter()
+                .zip(&self.realized)
+                .map(|(f, r)| if *f > 0.0 && *r > 0.0 { loss(*f, *r) } else { f64::NAN })
+                .collect()
+        };
+        let mean = |values: &[f64]| {
+            let finite: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+            if finite.is_empty() { f64::NAN } else { finite.iter().sum::<f64>() / finite.len() as f64 }
+        };
+        let squared: fn(f64, f64) -> f64 = squared_error;
+        let baseline_index = self.names.iter().position(|name| name == baseline);
+        let baseline_losses = baseline_index.map(|b| (losses(&self.forecasts[b], qlike), losses(&self.forecasts[b], squared)));
+
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let (q, se) = (losses(&self.forecasts[i], qlike), losses(&self.forecasts[i], squared));
+                let against = |own: &[f64], base: &[f64]| {
+                    if Some(i) == baseline_index { None } else { diebold_mariano(own, base, lag) }
+                };
+                EstimatorScore {
+                    name: name.clone(),
+                    observations: q.iter().filter(|v| v.is_finite()).count(),
+                    fallbacks: self.fallbacks[i],
+                    mean_qlike: mean(&q),
+                    mean_squared_error: mean(&se),
+                    dm_qlike: baseline_losses.as_ref().and_then(|(bq, _)| against(&q, bq)),
+                    dm_squared_error: baseline_losses.as_ref().and_then(|(_, bse)| against(&se, bse)),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::XorShift;
+
+    #[test]
+    fn test_losses_and_normal_cdf() {
+        assert_eq!(qlike(2e-8, 2e-8), 0.0);
+        assert!(qlike(1e-8, 2e-8) > qlike(4e-8, 2e-8)); // under-prediction costs more
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((normal_cdf(1.959_964) - 0.975).abs() < 1e-6);
+        assert!((normal_cdf(-1.0) - 0.158_655_25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_diebold_mariano_prefers_accurate_forecast() {
+        let mut rng = XorShift::new(42);
+
+        let mut evaluation = ForecastEvaluation::new(vec!["truth".into(), "biased".into(), "noisy_truth".into()]);
+        for _ in 0..500 {
+            let true_variance = 1e-8 * (0.5 + rng.uniform());
+            let realized = true_variance * (0.5 + rng.uniform());
+            let noisy = true_variance * (0.98 + 0.04 * rng.uniform());
+            evaluation.record(&[Some(true_variance.sqrt()), Some((2.0 * true_variance).sqrt()), Some(noisy.sqrt())], 0.0, realized);
+        }
+        // Dropped points are not counted: a zero fallback for one estimator, zero realized variance for all
+        evaluation.record(&[Some(1e-4), None, Some(1e-4)], 0.0, 1e-8);
+        evaluation.record(&[Some(1e-4), Some(1e-4), Some(1e-4)], 0.0, 0.0);
+
+        let scores = evaluation.summarize("truth", 0);
+        let observations: Vec<usize> = scores.iter().map(|s| s.observations).collect();
+        assert_eq!(observations, vec![501, 500, 501]);
+        assert!(scores[0].dm_qlike.is_none());
+        let biased = scores[1].dm_qlike.unwrap();
+        assert!(biased.mean_difference > 0.0 && biased.p_value < 1e-6, "{:?}", biased);
+        let noisy = scores[2].dm_qlike.unwrap();
+        assert!(noisy.p_value > 0.01, "{:?}", noisy);
+    }
+
+    #[test]
+    fn test_realized_variance_ahead() {
+        // Alternating ±1e-4 log returns every second, with a 10s gap after t=100s
+        let mut prices = Vec::new();
+        let mut log_price = 0.0;
+        for second in 0..200u64 {
+            if (101..110).contains(&second) {
+                continue;
+            }
+            log_price += if second % 2 == 0 { 1e-4 } else { -1e-4 };
+            prices.push((second * 1000, f64::exp(log_price)));
+        }
+        let ahead = RealizedVarianceAhead::new(&prices);
+
+        let variance = ahead.variance(10_000, 60_000, 5_000).unwrap();
+        assert!((variance / 1e-8 - 1.0).abs() < 1e-6, "{}", variance);
+        assert!(ahead.variance(80_000, 60_000, 5_000).is_none()); // spans the gap
+        assert!(ahead.variance(150_000, 60_000, 5_000).is_none()); // past the data
+    }
+}
@@ -0,0 +1,197 @@
+//! Pluggable risk-aversion (gamma) policies
+//!
+//! Each `GammaMode` maps to a stateless `GammaPolicy`. The spread model builds a
+//! `GammaContext` once per quote and applies the config's gamma bounds to the result.
+
+use crate::model_types::{ASConfig, GammaMode};
+
+/// Maximum allowed gamma to prevent numerical instability
+pub const MAX_GAMMA_LIMIT: f64 = 1e6;
+
+/// Minimum gamma to prevent division issues
+pub const MIN_GAMMA: f64 = 1e-6;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Market and strategy state available to a gamma policy
+#[derive(Debug, Clone, Copy)]
+pub struct GammaContext {
+    pub timestamp: u64,
+    pub mid: f64,
+    /// Clamped σ² (1/s)
+    pub sigma_sq: f64,
+    /// (T - t) in seconds
+    pub t_horizon: f64,
+    /// Horizon of the inventory skew in seconds: (T - t) plus the end-of-session urgency term
+    pub skew_horizon: f64,
+    /// |inventory| / max_inventory, in [0, 1]
+    pub inv_ratio: f64,
+    /// PnL over the loss lookback window (quote currency)
+    pub recent_pnl: f64,
+}
+
+pub trait GammaPolicy: Sync {
+    /// Unbounded gamma for this quote; the caller applies gamma_min/gamma_max
+    fn gamma(&self, ctx: &GammaContext, config: &ASConfig) -> f64;
+}
+
+pub struct ConstantGamma;
+pub struct InventoryScaledGamma;
+pub struct MaxShiftGamma;
+pub struct RiskBudgetGamma;
+pub struct TimeOfDayGamma;
+pub struct LossAdaptiveGamma;
+
+/// Policy implementing the given mode
+pub fn policy_for(mode: &GammaMode) -> &'static dyn GammaPolicy {
+    match mode {
+        GammaMode::Constant => &ConstantGamma,
+        GammaMode::InventoryScaled => &InventoryScaledGamma,
+        GammaMode::MaxShift => &MaxShiftGamma,
+        GammaMode::RiskBudget => &RiskBudgetGamma,
+        GammaMode::TimeOfDay => &TimeOfDayGamma,
+        GammaMode::LossAdaptive => &LossAdaptiveGamma,
+    }
+}
+
+#[inline]
+fn constant_gamma(config: &ASConfig) -> f64 {
+    config.risk_aversion_gamma.max(MIN_GAMMA)
+}
+
+/// Gamma that shifts the reservation price by `max_shift_ticks` at max inventory (return space)
+fn gamma_from_shift(ctx: &GammaContext, config: &ASConfig) -> f64 {
+    let denom = ctx.sigma_sq * ctx.skew_horizon;
+    if ctx.sigma_sq > 1e-12 && ctx.skew_horizon > 0.0 && ctx.mid > 0.0 && denom > 0.0 {
+        let target_shift_return = (config.max_shift_ticks * config.tick_size) / ctx.mid;
+        (target_shift_return / denom).min(MAX_GAMMA_LIMIT)
+    } else {
+        constant_gamma(config)
+    }
+}
+
+impl GammaPolicy for ConstantGamma {
+    fn gamma(&self, _ctx: &GammaContext, config: &ASConfig) -> f64 {
+        constant_gamma(config)
+    }
+}
+
+impl GammaPolicy for InventoryScaledGamma {
+    fn gamma(&self, ctx: &GammaContext, config: &ASConfig) -> f64 {
+        (gamma_from_shift(ctx, config) * ctx.inv_ratio).max(MIN_GAMMA)
+    }
+}
+
+impl GammaPolicy for MaxShiftGamma {
+    fn gamma(&self, ctx: &GammaContext, config: &ASConfig) -> f64 {
+        gamma_from_shift(ctx, config).max(MIN_GAMMA)
+    }
+}
+
+impl GammaPolicy for RiskBudgetGamma {
+    fn gamma(&self, ctx: &GammaContext, config: &ASConfig) -> f64 {
+        // γσ²T·q_max·mid = budget  =>  γ = budget / (σ²T·q_max·mid)
+        let risk_per_gamma = ctx.sigma_sq * ctx.skew_horizon * config.max_inventory * ctx.mid;
+        if risk_per_gamma > 1e-12 && config.risk_budget_usd > 0.0 {
+            (config.risk_budget_usd / risk_per_gamma).clamp(MIN_GAMMA, MAX_GAMMA_LIMIT)
+        } else {
+            constant_gamma(config)
+        }
+    }
+}
+
+impl GammaPolicy for TimeOfDayGamma {
+    fn gamma(&self, ctx: &GammaContext, config: &ASConfig) -> f64 {
+        let schedule = &config.gamma_schedule;
+        if schedule.is_empty() {
+            return constant_gamma(config);
+        }
+
+        // Latest step starting at or before the time of day; before the first step the
+        // previous day's last step is still in effect.
+        let second_of_day = (ctx.timestamp / 1000) % SECONDS_PER_DAY;
+        let entry = schedule
+            .iter()
+            .filter(|e| e.start_seconds % SECONDS_PER_DAY <= second_of_day)
+            .max_by_key(|e| e.start_seconds % SECONDS_PER_DAY)
+            .or_else(|| schedule.iter().max_by_key(|e| e.start_seconds % SECONDS_PER_DAY));
+
+        entry.map(|e| e.gamma.max(MIN_GAMMA)).unwrap_or_else(|| constant_gamma(config))
+    }
+}
+
+impl GammaPolicy for LossAdaptiveGamma {
+    fn gamma(&self, ctx: &GammaContext, config: &ASConfig) -> f64 {
+        let base = constant_gamma(config);
+        let loss = (-ctx.recent_pnl).max(0.0);
+        if loss <= 0.0 || config.loss_gamma_scale_usd <= 0.0 {
+            return base;
+        }
+        let multiplier = (1.0 + loss / config.loss_gamma_scale_usd).min(config.loss_gamma_max_multiplier.max(1.0));
+        base * multiplier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_types::GammaScheduleEntry;
+
+    fn context() -> GammaContext {
+        GammaContext {
+            timestamp: 0,
+            mid: 2000.0,
+            sigma_sq: 1e-8,
+            t_horizon: 60.0,
+            skew_horizon: 60.0,
+            inv_ratio: 0.5,
+            recent_pnl: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_risk_budget_keeps_dollar_risk_constant() {
+        let config = ASConfig::default();
+        let policy = policy_for(&GammaMode::RiskBudget);
+
+        for sigma_sq in [1e-9, 1e-8, 1e-7] {
+            let ctx = GammaContext { sigma_sq, ..context() };
+            let gamma = policy.gamma(&ctx, &config);
+            let dollar_risk = gamma * sigma_sq * ctx.skew_horizon * config.max_inventory * ctx.mid;
+            assert!((dollar_risk - config.risk_budget_usd).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_time_of_day_schedule_wraps() {
+        let config = ASConfig {
+            gamma_schedule: vec![
+                GammaScheduleEntry { start_seconds: 8 * 3600, gamma: 0.2 },
+                GammaScheduleEntry { start_seconds: 20 * 3600, gamma: 1.0 },
+            ],
+            ..ASConfig::default()
+        };
+        let policy = policy_for(&GammaMode::TimeOfDay);
+        let at = |hour: u64| GammaContext { timestamp: hour * 3_600_000, ..context() };
+
+        assert_eq!(policy.gamma(&at(2), &config), 1.0);
+        assert_eq!(policy.gamma(&at(9), &config), 0.2);
+        assert_eq!(policy.gamma(&at(21), &config), 1.0);
+    }
+
+    #[test]
+    fn test_loss_adaptive_raises_gamma_after_losses() {
+        let config = ASConfig::default();
+        let policy = policy_for(&GammaMode::LossAdaptive);
+        let base = config.risk_aversion_gamma;
+
+        let profit = GammaContext { recent_pnl: 100.0, ..context() };
+        assert_eq!(policy.gamma(&profit, &config), base);
+
+        let loss = GammaContext { recent_pnl: -config.loss_gamma_scale_usd, ..context() };
+        assert!((policy.gamma(&loss, &config) - 2.0 * base).abs() < 1e-12);
+
+        let big_loss = GammaContext { recent_pnl: -1e9, ..context() };
+        assert_eq!(policy.gamma(&big_loss, &config), base * config.loss_gamma_max_multiplier);
+    }
+}
@@ -0,0 +1,133 @@
+//! Fill-intensity shapes λ(δ) = A·S(δ) in return space
+//!
+//! `S` is the probability that a market order reaches depth δ (S(0) = 1, decreasing) and
+//! its hazard h(δ) = -S'(δ)/S(δ) is the local decay rate; for the exponential shape h = κ.
+//! The spread model only needs the hazard to solve for the optimal quote depth.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum IntensityShape {
+    /// S(δ) = e^(-κδ)
+    Exponential { kappa: f64 },
+    /// S(δ) = (1 + δ/δ₀)^(-k): heavy tail, hazard k/(δ₀ + δ) falls with depth
+    PowerLaw { delta0: f64, exponent: f64 },
+    /// Piecewise-constant hazard: `hazards[i]` applies on [knots[i], knots[i+1]), the last
+    /// one beyond the last knot. `knots[0]` is 0.
+    Piecewise { knots: Vec<f64>, hazards: Vec<f64> },
+}
+
+impl IntensityShape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Exponential { .. } => "exponential",
+            Self::PowerLaw { .. } => "power_law",
+            Self::Piecewise { .. } => "piecewise",
+        }
+    }
+
+    /// κ of an exponential shape; other shapes have no single decay rate
+    pub fn kappa(&self) -> Option<f64> {
+        match self {
+            Self::Exponential { kappa } => Some(*kappa),
+            _ => None,
+        }
+    }
+
+    /// Shape parameters as `name=value` pairs separated by `;` (lists joined with `|`)
+    pub fn parameters_label(&self) -> String {
+        let join = |values: &[f64]| values.iter().map(|v| format!("{}", v)).collect::<Vec<_>>().join("|");
+        match self {
+            Self::Exponential { kappa } => format!("kappa={}", kappa),
+            Self::PowerLaw { delta0, exponent } => format!("delta0={};exponent={}", delta0, exponent),
+            Self::Piecewise { knots, hazards } => format!("knots={};hazards={}", join(knots), join(hazards)),
+        }
+    }
+
+    /// Number of free shape parameters (A excluded), for information criteria
+    pub fn parameter_count(&self) -> usize {
+        match self {
+            Self::Exponential { .. } => 1,
+            Self::PowerLaw { .. } => 2,
+            Self::Piecewise { hazards, .. } => hazards.len(),
+        }
+    }
+
+    /// Survival S(δ) = λ(δ)/A
+    pub fn survival(&self, delta: f64) -> f64 {
+        let delta = delta.max(0.0);
+        match self {
+            Self::Exponential { kappa } => (-kappa * delta).exp(),
+            Self::PowerLaw { delta0, exponent } => (1.0 + delta / delta0).powf(-exponent),
+            Self::Piecewise { knots, hazards } => {
+                let mut cumulative = 0.0;
+                for (i, hazard) in hazards.iter().enumerate() {
+                    let start = knots[i];
+                    if delta <= start {
+                        break;
+                    }
+                    let end = knots.get(i + 1).copied().unwrap_or(f64::INFINITY).min(delta);
+                    cumulative += hazard * (end - start);
+                }
+                (-cumulative).exp()
+            }
+        }
+    }
+
+    /// Hazard h(δ) = -S'(δ)/S(δ)
+    pub fn hazard(&self, delta: f64) -> f64 {
+        let delta = delta.max(0.0);
+        match self {
+            Self::Exponential { kappa } => *kappa,
+            Self::PowerLaw { delta0, exponent } => exponent / (delta0 + delta),
+            Self::Piecewise { knots, hazards } => {
+                let segment = knots.iter().rposition(|k| *k <= delta).unwrap_or(0);
+                hazards[segment.min(hazards.len() - 1)]
+            }
+        }
+    }
+
+    /// Density of the depth reached by a market order, -S'(δ) = h(δ)·S(δ)
+    pub fn density(&self, delta: f64) -> f64 {
+        self.hazard(delta) * self.survival(delta)
+    }
+
+    /// True when all parameters are finite and positive
+    pub fn is_valid(&self) -> bool {
+        let positive = |x: &f64| x.is_finite() && *x > 0.0;
+        match self {
+            Self::Exponential { kappa } => positive(kappa),
+            Self::PowerLaw { delta0, exponent } => positive(delta0) && positive(exponent),
+            Self::Piecewise { knots, hazards } => {
+                !hazards.is_empty()
+                    && knots.len() == hazards.len()
+                    && knots[0] == 0.0
+                    && knots.windows(2).all(|w| w[1] > w[0])
+                    && hazards.iter().all(positive)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hazard_matches_log_survival_slope() {
+        let shapes = [
+            IntensityShape::Exponential { kappa: 800.0 },
+            IntensityShape::PowerLaw { delta0: 2e-4, exponent: 1.5 },
+            IntensityShape::Piecewise { knots: vec![0.0, 1e-4, 5e-4], hazards: vec![3000.0, 1000.0, 200.0] },
+        ];
+        for shape in &shapes {
+            assert!(shape.is_valid());
+            assert_eq!(shape.survival(0.0), 1.0);
+            for delta in [5e-5, 3e-4, 2e-3] {
+                let h = 1e-8;
+                let slope = -(shape.survival(delta + h).ln() - shape.survival(delta - h).ln()) / (2.0 * h);
+                assert!((slope / shape.hazard(delta) - 1.0).abs() < 1e-4, "{}: {} vs {}", shape.name(), slope, shape.hazard(delta));
+            }
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Cross-market lead-lag estimation and reference pricing
+//!
+//! Estimates how much of a leader market's move (e.g. BTC-USD) shows up in a follower
+//! market (e.g. SOL-USD) over the following bars, and turns recent leader moves into a
+//! fair-value shift for the follower before the follower's own book reacts.
+
+use crate::data_loader::{market_data_dir, DataLoader, MidStream};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Minimum number of aligned bar returns required to estimate betas
+const MIN_BARS_FOR_ESTIMATION: usize = 30;
+
+/// Estimated lead-lag relationship between two markets.
+///
+/// `betas[j - 1]` is the regression coefficient of the follower's bar return on the
+/// leader's bar return `j` bars earlier (strictly lagged, so it is tradable).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeadLagModel {
+    pub leader: String,
+    pub follower: String,
+    /// Bar size used for resampling both mid series (milliseconds)
+    pub bar_ms: u64,
+    /// Contemporaneous beta (same bar), informational only
+    pub beta_contemporaneous: f64,
+    /// Lagged betas for lags 1..=max_lag
+    pub betas: Vec<f64>,
+    /// Correlation of follower return with the leader return at each lag 1..=max_lag
+    pub correlations: Vec<f64>,
+    /// Number of aligned bar returns used in the fit
+    pub observations: usize,
+}
+
+impl LeadLagModel {
+    /// Weight applied to the m-th most recent completed leader bar return.
+    ///
+    /// A leader return observed m bars ago still affects follower returns at lags m..=L,
+    /// so its pending impact is the tail sum of the betas from lag m onwards.
+    pub fn pending_weights(&self) -> Vec<f64> {
+        let mut weights = vec![0.0; self.betas.len()];
+        let mut acc = 0.0;
+        for (i, beta) in self.betas.iter().enumerate().rev() {
+            acc += beta;
+            weights[i] = acc;
+        }
+        weights
+    }
+
+    /// Load all models from a JSON file written by `estimate_lead_lag`
+    pub fn load_all(path: &Path) -> Result<Vec<LeadLagModel>, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Load the model whose follower is `market` from a JSON file written by `estimate_lead_lag`
+    pub fn load_for_follower(path: &Path, market: &str) -> Result<LeadLagModel, Box<dyn Error>> {
+        Self::load_all(path)?
+            .into_iter()
+            .find(|m| m.follower == market)
+            .ok_or_else(|| format!("No lead-lag model with follower {} in {}", market, path.display()).into())
+    }
+
+    /// Loader over the leader's parts in `<data_dir>/<leader>`; `orderbook` overrides the
+    /// orderbook directory
+    pub fn leader_loader(&self, data_dir: &Path, orderbook: Option<&Path>) -> DataLoader {
+        let leader_dir = market_data_dir(data_dir, &self.leader);
+        let orderbook = orderbook.map(Path::to_path_buf).unwrap_or_else(|| leader_dir.join("orderbook_parts"));
+        DataLoader::new(&leader_dir.join("trades_parts"), &orderbook)
+    }
+
+    /// Write models to a JSON file
+    pub fn save_all(models: &[LeadLagModel], path: &Path) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string_pretty(models)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Resample a mid series onto a fixed bar grid using previous-tick interpolation.
+///
+/// Returns (bar_start_ms, last mid observed before the bar closed) for every bar
+/// between `start_ms` (inclusive) and `end_ms` (exclusive).
+fn resample_to_bars(mids: &[(u64, f64)], bar_ms: u64, start_ms: u64, end_ms: u64) -> Vec<f64> {
+    let mut bars = Vec::with_capacity(((end_ms - start_ms) / bar_ms) as usize + 1);
+    let mut idx = 0;
+    let mut last = f64::NAN;
+    let mut bar_start = start_ms;
+
+    while bar_start < end_ms {
+        let bar_end = bar_start + bar_ms;
+        while idx < mids.len() && mids[idx].0 < bar_end {
+            let p = mids[idx].1;
+            if p.is_finite() && p > 0.0 {
+                last = p;
+            }
+            idx += 1;
+        }
+        bars.push(last);
+        bar_start = bar_end;
+    }
+
+    bars
+}
+
+fn bar_log_returns(bars: &[f64]) -> Vec<f64> {
+    bars.windows(2)
+        .map(|w| {
+            let r = (w[1] / w[0]).ln();
+            if r.is_finite() { r } else { 0.0 }
+        })
+        .collect()
+}
+
+/// Estimate lead-lag betas of `follower` on `leader` from raw (timestamp_ms, mid) series.
+///
+/// Both series are resampled to `bar_ms` bars over their common time span and each lag
+/// is fitted by a zero-intercept univariate regression. Returns None if the overlap is
+/// too short.
+pub fn estimate_lead_lag(
+    leader: &str,
+    follower: &str,
+    leader_mids: &[(u64, f64)],
+    follower_mids: &[(u64, f64)],
+    bar_ms: u64,
+    max_lag: usize,
+) -> Option<LeadLagModel> {
+    if bar_ms == 0 || max_lag == 0 || leader_mids.is_empty() || follower_mids.is_empty() {
+        return None;
+    }
+
+    let start_ms = leader_mids[0].0.max(follower_mids[0].0);
+    let end_ms = leader_mids.last()?.0.min(follower_mids.last()?.0);
+    if end_ms <= start_ms {
+        return None;
+    }
+
+    let leader_bars = resample_to_bars(leader_mids, bar_ms, start_ms, end_ms);
+    let follower_bars = resample_to_bars(follower_mids, bar_ms, start_ms, end_ms);
+
+    // Skip leading bars before both series have a valid price
+    let first_valid = leader_bars
+        .iter()
+        .zip(follower_bars.iter())
+        .position(|(l, f)| l.is_finite() && f.is_finite())?;
+    let leader_returns = bar_log_returns(&leader_bars[first_valid..]);
+    let follower_returns = bar_log_returns(&follower_bars[first_valid..]);
+
+    if leader_returns.len() < max_lag + MIN_BARS_FOR_ESTIMATION {
+        return None;
+    }
+
+    let regress = |lag: usize| -> (f64, f64) {
+        let mut sxy = 0.0;
+        let mut sxx = 0.0;
+        let mut syy = 0.0;
+        for t in max_lag..follower_returns.len() {
+            let x = leader_returns[t - lag];
+            let y = follower_returns[t];
+            sxy += x * y;
+            sxx += x * x;
+            syy += y * y;
+        }
+        let beta = if sxx > 0.0 { sxy / sxx } else { 0.0 };
+        let corr = if sxx > 0.0 && syy > 0.0 { sxy / (sxx * syy).sqrt() } else { 0.0 };
+        (beta, corr)
+    };
+
+    let (beta_contemporaneous, _) = regress(0);
+    let (betas, correlations): (Vec<f64>, Vec<f64>) = (1..=max_lag).map(regress).unzip();
+
+    Some(LeadLagModel {
+        leader: leader.to_string(),
+        follower: follower.to_string(),
+        bar_ms,
+        beta_contemporaneous,
+        betas,
+        correlations,
+        observations: follower_returns.len() - max_lag,
+    })
+}
+
+/// Streaming leader reference used during a backtest of the follower market.
+///
+/// Consumes leader mids up to the current event time (never beyond it), builds completed
+/// bar returns and converts them into a return-space fair-value shift for the follower.
+pub struct LeadLagReference {
+    weights: Vec<f64>,
+    bar_ms: u64,
+    leader_stream: MidStream,
+    /// Next leader observation not yet consumed (lookahead buffer)
+    pending: Option<(u64, f64)>,
+    /// Most recent completed bar returns, newest first
+    recent_returns: VecDeque<f64>,
+    current_bar_start: Option<u64>,
+    last_bar_close: Option<f64>,
+    last_mid: Option<f64>,
+}
+
+impl LeadLagReference {
+    pub fn new(model: &LeadLagModel, leader_stream: MidStream) -> Self {
+        let weights = model.pending_weights();
+        Self {
+            recent_returns: VecDeque::with_capacity(weights.len() + 1),
+            weights,
+            bar_ms: model.bar_ms.max(1),
+            leader_stream,
+            pending: None,
+            current_bar_start: None,
+            last_bar_close: None,
+            last_mid: None,
+        }
+    }
+
+    /// Close every bar that ended at or before `ts`.
+    ///
+    /// All bars closing before the next leader observation share the same close, so only
+    /// the first one can carry a non-zero return; the rest are pushed as zeros in bulk.
+    fn roll_bars(&mut self, ts: u64) {
+        let bar_start = match self.current_bar_start {
+            Some(start) => start,
+            None => return,
+        };
+        let closed_bars = ts.saturating_sub(bar_start) / self.bar_ms;
+        if closed_bars == 0 {
+            return;
+        }
+
+        if let Some(close) = self.last_mid {
+            if let Some(prev_close) = self.last_bar_close {
+                let r = (close / prev_close).ln();
+                self.recent_returns.push_front(if r.is_finite() { r } else { 0.0 });
+            }
+            let flat_bars = ((closed_bars - 1) as usize).min(self.weights.len());
+            for _ in 0..flat_bars {
+                self.recent_returns.push_front(0.0);
+            }
+            self.recent_returns.truncate(self.weights.len());
+            self.last_bar_close = Some(close);
+        }
+        self.current_bar_start = Some(bar_start + closed_bars * self.bar_ms);
+    }
+
+    /// Consume all leader observations with timestamp <= `ts`
+    pub fn advance_to(&mut self, ts: u64) -> Result<(), Box<dyn Error>> {
+        loop {
+            if self.pending.is_none() {
+                match self.leader_stream.next() {
+                    Some(Ok(obs)) => self.pending = Some(obs),
+                    Some(Err(e)) => return Err(e),
+                    None => break,
+                }
+            }
+
+            match self.pending {
+                Some((obs_ts, mid)) if obs_ts <= ts => {
+                    self.pending = None;
+                    if !(mid.is_finite() && mid > 0.0) {
+                        continue;
+                    }
+                    if self.current_bar_start.is_none() {
+                        self.current_bar_start = Some(obs_ts - obs_ts % self.bar_ms);
+                    }
+                    self.roll_bars(obs_ts);
+                    self.last_mid = Some(mid);
+                }
+                _ => break,
+            }
+        }
+
+        self.roll_bars(ts);
+        Ok(())
+    }
+
+    /// Expected follower log-return still to come from leader moves already observed
+    pub fn fair_value_shift(&self) -> f64 {
+        self.recent_returns
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(r, w)| r * w)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rng::XorShift;
+
+    fn pseudo_returns(n: usize) -> Vec<f64> {
+        let mut rng = XorShift::new(42);
+        (0..n).map(|_| (rng.uniform() - 0.5) * 0.002).collect()
+    }
+
+    #[test]
+    fn test_pending_weights_are_tail_sums() {
+        let model = LeadLagModel {
+            leader: "BTC-USD".into(),
+            follower: "ETH-USD".into(),
+            bar_ms: 1000,
+            beta_contemporaneous: 0.0,
+            betas: vec![0.5, 0.2, 0.1],
+            correlations: vec![0.0; 3],
+            observations: 100,
+        };
+        let w = model.pending_weights();
+        assert!((w[0] - 0.8).abs() < 1e-12);
+        assert!((w[1] - 0.3).abs() < 1e-12);
+        assert!((w[2] - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_estimate_recovers_lagged_beta() {
+        let leader_returns = pseudo_returns(2000);
+        let mut leader = Vec::new();
+        let mut follower = Vec::new();
+        let mut lp = 100.0;
+        let mut fp = 50.0;
+        for (i, r) in leader_returns.iter().enumerate() {
+            // Follower moves by 0.6x the leader's return one bar later
+            if i > 0 {
+                fp *= (0.6 * leader_returns[i - 1]).exp();
+            }
+            lp *= r.exp();
+            let ts = i as u64 * 1000 + 500;
+            leader.push((ts, lp));
+            follower.push((ts, fp));
+        }
+
+        let model = estimate_lead_lag("BTC-USD", "SOL-USD", &leader, &follower, 1000, 3).unwrap();
+        assert!((model.betas[0] - 0.6).abs() < 0.05, "beta_1 = {}", model.betas[0]);
+        assert!(model.betas[1].abs() < 0.1);
+        assert!(model.correlations[0] > 0.9);
+    }
+
+    #[test]
+    fn test_estimate_requires_overlap() {
+        let leader = vec![(0, 100.0), (1000, 101.0)];
+        let follower = vec![(5000, 50.0), (6000, 51.0)];
+        assert!(estimate_lead_lag("A", "B", &leader, &follower, 1000, 2).is_none());
+    }
+
+    #[test]
+    fn test_reference_shift_uses_only_past_leader_data() {
+        let model = LeadLagModel {
+            leader: "BTC-USD".into(),
+            follower: "ETH-USD".into(),
+            bar_ms: 1000,
+            beta_contemporaneous: 0.0,
+            betas: vec![0.5],
+            correlations: vec![0.0],
+            observations: 100,
+        };
+        let obs: Vec<Result<(u64, f64), Box<dyn Error>>> = vec![
+            Ok((100, 100.0)),
+            Ok((1100, 100.0)),
+            Ok((2100, 101.0)),
+            Ok((5000, 200.0)),
+        ];
+        let mut reference = LeadLagReference::new(&model, Box::new(obs.into_iter()));
+
+        reference.advance_to(2500).unwrap();
+        // Bar [2000, 3000) has not closed yet, so only the flat bar is known
+        assert_eq!(reference.fair_value_shift(), 0.0);
+
+        reference.advance_to(3000).unwrap();
+        let expected = 0.5 * (101.0f64 / 100.0).ln();
+        assert!((reference.fair_value_shift() - expected).abs() < 1e-12);
+    }
+}
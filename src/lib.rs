@@ -6,12 +6,30 @@ pub mod types;
 pub mod websocket;
 pub mod model_types;
 pub mod data_loader;
+pub mod orderbook_batch;
+pub mod parquet_prefetch;
+pub mod event_cache;
 pub mod metrics;
+pub mod optimizer;
 pub mod calibration;
+pub mod intensity_shape;
+pub mod queue_depletion;
+pub mod volatility;
+pub mod forecast_evaluation;
+pub mod gamma_policy;
 pub mod spread_model;
 pub mod backtest_engine;
 pub mod calibration_engine;
+pub mod calibration_cache;
+pub mod parameter_smoothing;
+pub mod adverse_selection;
+pub mod lead_lag;
+pub mod session;
+pub mod seasonality;
+pub mod trading_rules;
 pub mod storage;
+#[cfg(test)]
+mod test_rng;
 
 // Re-export commonly used types
 pub use data_collection_task::{run_data_collection_task, DataCollectionConfig};
@@ -9,6 +9,161 @@ pub enum GammaMode {
     InventoryScaled,
     #[serde(rename = "max_shift")]
     MaxShift,
+    /// Keep γσ²T·q_max·mid (USD, T the skew horizon) at `risk_budget_usd`
+    #[serde(rename = "risk_budget")]
+    RiskBudget,
+    /// Piecewise-constant gamma by UTC time of day from `gamma_schedule`
+    #[serde(rename = "time_of_day")]
+    TimeOfDay,
+    /// Scale `risk_aversion_gamma` up after losses over `loss_lookback_seconds`
+    #[serde(rename = "loss_adaptive")]
+    LossAdaptive,
+}
+
+/// Volatility estimator used by the calibration engine (all return σ in 1/√s)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum VolatilityEstimatorKind {
+    /// GARCH(1,1) forecast on 1s returns over `garch_lookback_seconds`, realized variance as fallback
+    #[serde(rename = "garch")]
+    Garch,
+    /// Realized variance Σr²/Σdt over the calibration window
+    #[serde(rename = "realized")]
+    Realized,
+    /// RiskMetrics-style EWMA of 1s squared returns with `ewma_halflife_seconds`
+    #[serde(rename = "ewma")]
+    Ewma,
+    /// Parkinson high-low range estimator on `range_bar_seconds` bars
+    #[serde(rename = "parkinson")]
+    Parkinson,
+    /// Garman-Klass OHLC estimator on `range_bar_seconds` bars
+    #[serde(rename = "garman_klass")]
+    GarmanKlass,
+    /// Bipower variation on 1s returns (robust to jumps)
+    #[serde(rename = "bipower")]
+    Bipower,
+    /// Two-scale realized variance on raw ticks (robust to microstructure noise)
+    #[serde(rename = "tsrv")]
+    Tsrv,
+}
+
+impl VolatilityEstimatorKind {
+    pub const ALL: [Self; 7] = [
+        Self::Garch,
+        Self::Realized,
+        Self::Ewma,
+        Self::Parkinson,
+        Self::GarmanKlass,
+        Self::Bipower,
+        Self::Tsrv,
+    ];
+}
+
+/// Arrival model behind the calibrated fill intensity A
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum IntensityModel {
+    /// Independent (Poisson) arrivals: A is the mean trade rate at δ=0
+    #[serde(rename = "poisson")]
+    Poisson,
+    /// Self-exciting (Hawkes) arrivals with an exponential kernel: A is scaled to the
+    /// cluster (baseline) rate, since a resting quote fills at most once per burst
+    #[serde(rename = "hawkes")]
+    Hawkes,
+}
+
+/// Fill-intensity family fitted per side
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum IntensityShapeKind {
+    /// λ(δ) = A·e^(-κδ), closed-form quotes
+    #[serde(rename = "exponential")]
+    Exponential,
+    /// λ(δ) = A/(1 + δ/δ₀)^k
+    #[serde(rename = "power_law")]
+    PowerLaw,
+    /// Piecewise-constant hazard on `intensity_piecewise_segments` empirical depth quantiles
+    #[serde(rename = "piecewise")]
+    Piecewise,
+    /// Fit all families and keep the best per side by `intensity_selection_criterion`
+    #[serde(rename = "auto")]
+    Auto,
+}
+
+/// Observations the exponential intensity fit is estimated from
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum IntensitySource {
+    /// Depth of each trade print relative to the prevailing mid
+    #[serde(rename = "trades")]
+    Trades,
+    /// Deepest level consumed by executions between consecutive snapshots, with size
+    /// decreases not explained by the trade tape treated as cancellations
+    #[serde(rename = "queue_depletion")]
+    QueueDepletion,
+}
+
+/// Information criterion used to compare intensity shapes
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum InformationCriterion {
+    #[serde(rename = "aic")]
+    Aic,
+    #[serde(rename = "bic")]
+    Bic,
+}
+
+/// Smoothing of calibrated kappa/A across calibration windows (log space)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum ParameterSmoothing {
+    /// Use each window's estimates as they are
+    #[serde(rename = "none")]
+    None,
+    /// Exponential moving average with `smoothing_halflife_calibrations`
+    #[serde(rename = "ewma")]
+    Ewma,
+    /// Local-level Kalman filter: process noise `kalman_process_std_log`, measurement
+    /// variance from the fit's standard errors
+    #[serde(rename = "kalman")]
+    Kalman,
+}
+
+/// How size-aware calibration counts parent orders against the clip notional
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum ClipWeighting {
+    /// Only parents with notional >= the clip count, each as one fill
+    #[serde(rename = "threshold")]
+    Threshold,
+    /// Every parent counts with weight min(notional / clip, 1), the fraction of the clip it would fill
+    #[serde(rename = "weight")]
+    Weight,
+}
+
+/// Clip notional used by size-aware calibration when neither the config nor a backtest's
+/// order notional sets one (matches the binaries' default `--notional`)
+pub const DEFAULT_CLIP_NOTIONAL_USD: f64 = 20.0;
+
+/// One step of a time-of-day gamma schedule
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct GammaScheduleEntry {
+    /// Start of this step in seconds after 00:00 UTC
+    pub start_seconds: u64,
+    pub gamma: f64,
+}
+
+/// How the AS horizon T is defined.
+///
+/// `None` keeps `inventory_horizon_seconds` as a constant (T - t). The other modes use the
+/// time remaining until the end of the current trading session, so (T - t) decays to zero
+/// within each session and resets at the boundary.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum SessionMode {
+    #[serde(rename = "none")]
+    None,
+    /// Sessions end at every funding timestamp (multiples of `session_funding_interval_seconds` since UTC midnight)
+    #[serde(rename = "funding")]
+    Funding,
+    /// One session per day ending at `session_daily_cutoff_seconds` after UTC midnight
+    #[serde(rename = "daily")]
+    Daily,
+    /// Sessions end at each of `session_boundaries_seconds` (seconds after UTC midnight)
+    #[serde(rename = "custom")]
+    Custom,
 }
 
 /// Configuration for the Avellaneda-Stoikov calculator
@@ -26,6 +181,10 @@ pub struct ASConfig {
     pub gamma_log_spacing: bool,
     pub max_inventory: f64,
     pub tick_size: f64,
+    /// Exchange minimum order size in base units (0 = no minimum)
+    pub min_order_size: f64,
+    /// Exchange order size increment in base units (0 = no snapping)
+    pub order_size_step: f64,
     pub max_shift_ticks: f64,
     pub gamma_mode: GammaMode,
     pub min_spread_bps: f64,
@@ -43,6 +202,68 @@ pub struct ASConfig {
     pub warmup_period_seconds: u64,
     #[serde(default = "default_num_threads")]
     pub num_threads: usize,
+    pub session_mode: SessionMode,
+    pub session_funding_interval_seconds: u64,
+    pub session_daily_cutoff_seconds: u64,
+    pub session_boundaries_seconds: Vec<u64>,
+    /// Close any open inventory at the taker fee when a session ends (backtest only)
+    pub flatten_at_session_end: bool,
+    /// Inventory skew at the session end relative to the session start. The reservation
+    /// price uses the horizon (T - t) + ratio·(t - session start), so skew decays to zero
+    /// at 0 (pure AS), stays flat at 1 and rises into the session end above 1
+    pub session_end_skew_ratio: f64,
+    /// Budget in USD for the `risk_budget` gamma mode, which sets γ so that γσ²T·q_max·mid
+    /// equals it: σ² the per-second return variance, T the skew horizon in seconds (see
+    /// `session_end_skew_ratio`), q_max = `max_inventory` in base units and mid in USD
+    pub risk_budget_usd: f64,
+    pub gamma_schedule: Vec<GammaScheduleEntry>,
+    /// Window over which the `loss_adaptive` gamma mode measures PnL
+    pub loss_lookback_seconds: u64,
+    /// Loss (in quote currency) that adds one `risk_aversion_gamma` to gamma
+    pub loss_gamma_scale_usd: f64,
+    pub loss_gamma_max_multiplier: f64,
+    /// Widen the per-side spread floor from measured markouts of near-touch trades
+    pub adverse_selection_enabled: bool,
+    /// Markout horizon after each trade
+    pub adverse_selection_horizon_seconds: u64,
+    /// Half-life over which the measured adverse selection decays back to zero
+    pub adverse_selection_halflife_seconds: u64,
+    /// Only trades within this distance of the mid count as near-touch
+    pub adverse_selection_touch_bps: f64,
+    /// Scale applied to the measured markout cost when setting the floor
+    pub adverse_selection_multiplier: f64,
+    pub volatility_estimator: VolatilityEstimatorKind,
+    pub ewma_halflife_seconds: f64,
+    /// Bar size for the Parkinson and Garman-Klass estimators
+    pub range_bar_seconds: u64,
+    /// Slow-scale subsampling factor K for TSRV (0 = n^(2/3))
+    pub tsrv_slow_scale: usize,
+    /// Lookback of 1s returns kept for GARCH refits (bounds memory on long runs)
+    pub garch_lookback_seconds: u64,
+    pub intensity_model: IntensityModel,
+    pub intensity_source: IntensitySource,
+    /// Branching ratio above which a Hawkes fit is flagged as near-critical
+    pub hawkes_critical_branching: f64,
+    pub intensity_shape: IntensityShapeKind,
+    pub intensity_selection_criterion: InformationCriterion,
+    pub intensity_piecewise_segments: usize,
+    pub parameter_smoothing: ParameterSmoothing,
+    /// EWMA half-life, in calibrations
+    pub smoothing_halflife_calibrations: f64,
+    /// Kalman process noise: std dev of ln κ / ln A drift per calibration
+    pub kalman_process_std_log: f64,
+    /// Shrink bid/ask kappa and A toward their pooled estimate
+    pub intensity_shrinkage: bool,
+    /// Calibrate intensity on parent orders measured against `intensity_clip_notional_usd`
+    pub size_aware_intensity: bool,
+    /// Clip notional for size-aware calibration; None follows the backtest's order notional
+    /// (see `resolve_clip_notional`)
+    pub intensity_clip_notional_usd: Option<f64>,
+    pub intensity_clip_weighting: ClipWeighting,
+    /// Parent-order notional edges for the per-size-bucket intensity curves (empty = off)
+    pub intensity_size_buckets_usd: Vec<f64>,
+    /// Hour-of-week multipliers for A and σ written by `estimate_seasonality` (None = off)
+    pub seasonality_path: Option<String>,
 }
 
 fn default_gap_threshold() -> u64 {
@@ -76,6 +297,8 @@ impl Default for ASConfig {
             gamma_log_spacing: false,
             max_inventory: 10.0,
             tick_size: 0.01,
+            min_order_size: 0.0,
+            order_size_step: 0.0,
             max_shift_ticks: 100.0,
             gamma_mode: GammaMode::InventoryScaled,
             min_spread_bps: 2.0,
@@ -89,6 +312,52 @@ impl Default for ASConfig {
             gap_threshold_seconds: 1800,
             warmup_period_seconds: 900,
             num_threads: 4,
+            session_mode: SessionMode::None,
+            session_funding_interval_seconds: 8 * 3600, // Extended funding every 8h
+            session_daily_cutoff_seconds: 0,            // 00:00 UTC
+            session_boundaries_seconds: Vec::new(),
+            flatten_at_session_end: false,
+            session_end_skew_ratio: 0.0,
+            risk_budget_usd: 5.0,
+            gamma_schedule: Vec::new(),
+            loss_lookback_seconds: 3600,
+            loss_gamma_scale_usd: 50.0,
+            loss_gamma_max_multiplier: 5.0,
+            adverse_selection_enabled: false,
+            adverse_selection_horizon_seconds: 5,
+            adverse_selection_halflife_seconds: 600,
+            adverse_selection_touch_bps: 10.0,
+            adverse_selection_multiplier: 1.0,
+            volatility_estimator: VolatilityEstimatorKind::Garch,
+            ewma_halflife_seconds: 300.0,
+            range_bar_seconds: 60,
+            tsrv_slow_scale: 0,
+            garch_lookback_seconds: 14_400,
+            intensity_model: IntensityModel::Poisson,
+            intensity_source: IntensitySource::Trades,
+            hawkes_critical_branching: 0.9,
+            intensity_shape: IntensityShapeKind::Exponential,
+            intensity_selection_criterion: InformationCriterion::Bic,
+            intensity_piecewise_segments: 4,
+            parameter_smoothing: ParameterSmoothing::None,
+            smoothing_halflife_calibrations: 5.0,
+            kalman_process_std_log: 0.05,
+            intensity_shrinkage: false,
+            size_aware_intensity: false,
+            intensity_clip_notional_usd: None,
+            intensity_clip_weighting: ClipWeighting::Threshold,
+            intensity_size_buckets_usd: Vec::new(),
+            seasonality_path: None,
+        }
+    }
+}
+
+impl ASConfig {
+    /// Default an unset clip notional to the notional actually quoted, so size-aware
+    /// calibration measures fills against the orders being backtested
+    pub fn resolve_clip_notional(&mut self, order_notional: f64) {
+        if self.intensity_clip_notional_usd.is_none() {
+            self.intensity_clip_notional_usd = Some(order_notional);
         }
     }
 }
@@ -132,6 +401,79 @@ pub struct OptimalQuote {
     pub gamma: f64,
 }
 
+/// Clamps that were binding when a quote was computed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ActiveClamps {
+    pub volatility_min: bool,
+    pub volatility_max: bool,
+    pub gamma_min: bool,
+    pub gamma_max: bool,
+    pub bid_min_spread: bool,
+    pub bid_max_spread: bool,
+    pub ask_min_spread: bool,
+    pub ask_max_spread: bool,
+    /// The binding minimum spread came from the maker fee floor (2 * maker_fee_bps)
+    pub fee_floor: bool,
+    /// The binding minimum spread came from the adverse-selection floor
+    pub adverse_selection_floor: bool,
+    /// |inventory| is at or above max_inventory, so the skew no longer grows with it
+    pub inventory_cap: bool,
+}
+
+impl ActiveClamps {
+    /// Names of the active clamps joined by ';', or "none"
+    pub fn labels(&self) -> String {
+        let flags = [
+            (self.volatility_min, "volatility_min"),
+            (self.volatility_max, "volatility_max"),
+            (self.gamma_min, "gamma_min"),
+            (self.gamma_max, "gamma_max"),
+            (self.bid_min_spread, "bid_min_spread"),
+            (self.bid_max_spread, "bid_max_spread"),
+            (self.ask_min_spread, "ask_min_spread"),
+            (self.ask_max_spread, "ask_max_spread"),
+            (self.fee_floor, "fee_floor"),
+            (self.adverse_selection_floor, "adverse_selection_floor"),
+            (self.inventory_cap, "inventory_cap"),
+        ];
+        let active: Vec<&str> = flags.iter().filter(|(on, _)| *on).map(|(_, name)| *name).collect();
+        if active.is_empty() {
+            "none".to_string()
+        } else {
+            active.join(";")
+        }
+    }
+}
+
+/// Partial derivatives of the AS quote (in price units) with respect to its inputs.
+///
+/// Derivatives are taken on the unclamped AS formulas at the effective (bounded) gamma and
+/// clamped sigma; `clamps` shows where a clamp overrides them in the final quote. Sigma is
+/// in 1/√s, kappa and gamma are dimensionless, inventory is in base units.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct QuoteSensitivities {
+    pub bid_half_spread_d_sigma: f64,
+    pub bid_half_spread_d_kappa: f64,
+    pub bid_half_spread_d_gamma: f64,
+    pub ask_half_spread_d_sigma: f64,
+    pub ask_half_spread_d_kappa: f64,
+    pub ask_half_spread_d_gamma: f64,
+    pub reservation_d_sigma: f64,
+    pub reservation_d_gamma: f64,
+    pub reservation_d_inventory: f64,
+    pub clamps: ActiveClamps,
+}
+
+/// Strategy state fed into the quote alongside market inputs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuoteAdjustments {
+    /// Mark-to-market PnL change over the last `loss_lookback_seconds` (quote currency)
+    pub recent_pnl: f64,
+    /// Extra minimum bid/ask spread in bps (0 = static floor only)
+    pub bid_floor_bps: f64,
+    pub ask_floor_bps: f64,
+}
+
 /// A trade event for calibration
 #[derive(Debug, Clone)]
 pub struct TradeEvent {
@@ -153,6 +495,16 @@ mod tests {
         assert_eq!(config.num_threads, 4);
     }
 
+    #[test]
+    fn test_session_mode_defaults_to_none() {
+        let config: ASConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.session_mode, SessionMode::None);
+        assert!(!config.flatten_at_session_end);
+
+        let config: ASConfig = serde_json::from_str(r#"{"session_mode": "funding"}"#).unwrap();
+        assert_eq!(config.session_mode, SessionMode::Funding);
+    }
+
     #[test]
     fn test_gamma_mode_serialization() {
         let mode = GammaMode::InventoryScaled;
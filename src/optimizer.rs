@@ -0,0 +1,149 @@
+//! Derivative-free minimisation (Nelder–Mead simplex)
+//!
+//! Used for small likelihood fits (e.g. GARCH) where parameters are mapped to an
+//! unconstrained space by the caller.
+
+/// Result of a minimisation
+#[derive(Debug, Clone)]
+pub struct Minimum {
+    pub x: Vec<f64>,
+    pub value: f64,
+    pub evaluations: usize,
+    pub converged: bool,
+}
+
+/// Settings for `nelder_mead`
+#[derive(Debug, Clone, Copy)]
+pub struct NelderMeadOptions {
+    /// Initial simplex edge length along each axis
+    pub initial_step: f64,
+    pub max_evaluations: usize,
+    /// Stop when the spread of simplex values falls below this
+    pub value_tolerance: f64,
+}
+
+impl Default for NelderMeadOptions {
+    fn default() -> Self {
+        Self {
+            initial_step: 0.5,
+            max_evaluations: 500,
+            value_tolerance: 1e-9,
+        }
+    }
+}
+
+/// Minimise `f` from `x0` with the Nelder–Mead simplex method.
+///
+/// Non-finite function values are treated as +∞, so `f` can reject infeasible points.
+pub fn nelder_mead<F>(mut f: F, x0: &[f64], options: NelderMeadOptions) -> Minimum
+where
+    F: FnMut(&[f64]) -> f64,
+{
+    const REFLECT: f64 = 1.0;
+    const EXPAND: f64 = 2.0;
+    const CONTRACT: f64 = 0.5;
+    const SHRINK: f64 = 0.5;
+
+    let n = x0.len();
+    let mut evaluations = 0;
+    let mut eval = |x: &[f64], evaluations: &mut usize| {
+        *evaluations += 1;
+        let v = f(x);
+        if v.is_finite() { v } else { f64::INFINITY }
+    };
+
+    // Initial simplex: x0 plus one step along each axis
+    let mut simplex: Vec<(Vec<f64>, f64)> = Vec::with_capacity(n + 1);
+    simplex.push((x0.to_vec(), eval(x0, &mut evaluations)));
+    for i in 0..n {
+        let mut x = x0.to_vec();
+        x[i] += options.initial_step;
+        let v = eval(&x, &mut evaluations);
+        simplex.push((x, v));
+    }
+
+    let mut converged = false;
+    while evaluations < options.max_evaluations {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let (best, worst) = (simplex[0].1, simplex[n].1);
+        if (worst - best).abs() <= options.value_tolerance * (1.0 + best.abs()) {
+            converged = true;
+            break;
+        }
+
+        // Centroid of all but the worst vertex
+        let mut centroid = vec![0.0; n];
+        for (x, _) in &simplex[..n] {
+            for (c, xi) in centroid.iter_mut().zip(x) {
+                *c += xi / n as f64;
+            }
+        }
+        let toward = |coef: f64, from: &[f64]| -> Vec<f64> {
+            centroid.iter().zip(from).map(|(c, w)| c + coef * (w - c)).collect()
+        };
+
+        let reflected = toward(-REFLECT, &simplex[n].0);
+        let reflected_value = eval(&reflected, &mut evaluations);
+
+        if reflected_value < simplex[0].1 {
+            let expanded = toward(-EXPAND, &simplex[n].0);
+            let expanded_value = eval(&expanded, &mut evaluations);
+            simplex[n] = if expanded_value < reflected_value {
+                (expanded, expanded_value)
+            } else {
+                (reflected, reflected_value)
+            };
+        } else if reflected_value < simplex[n - 1].1 {
+            simplex[n] = (reflected, reflected_value);
+        } else {
+            // Contract toward the better of the worst and reflected points
+            let (anchor, anchor_value) = if reflected_value < simplex[n].1 {
+                (reflected, reflected_value)
+            } else {
+                (simplex[n].0.clone(), simplex[n].1)
+            };
+            let contracted = toward(CONTRACT, &anchor);
+            let contracted_value = eval(&contracted, &mut evaluations);
+
+            if contracted_value < anchor_value {
+                simplex[n] = (contracted, contracted_value);
+            } else {
+                // Shrink everything toward the best vertex
+                let best_x = simplex[0].0.clone();
+                for vertex in simplex.iter_mut().skip(1) {
+                    let x: Vec<f64> = best_x.iter().zip(&vertex.0).map(|(b, v)| b + SHRINK * (v - b)).collect();
+                    let v = eval(&x, &mut evaluations);
+                    *vertex = (x, v);
+                }
+            }
+        }
+    }
+
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let (x, value) = simplex.swap_remove(0);
+    Minimum { x, value, evaluations, converged }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nelder_mead_rosenbrock() {
+        let rosenbrock = |x: &[f64]| (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2);
+        let options = NelderMeadOptions { max_evaluations: 5000, value_tolerance: 1e-14, ..Default::default() };
+        let result = nelder_mead(rosenbrock, &[-1.2, 1.0], options);
+
+        assert!(result.converged);
+        assert!((result.x[0] - 1.0).abs() < 1e-3 && (result.x[1] - 1.0).abs() < 1e-3, "{:?}", result.x);
+    }
+
+    #[test]
+    fn test_nelder_mead_rejects_infeasible() {
+        // Minimum of (x-2)² restricted to x < 1 sits at the boundary
+        let f = |x: &[f64]| if x[0] < 1.0 { (x[0] - 2.0).powi(2) } else { f64::NAN };
+        let result = nelder_mead(f, &[0.0], NelderMeadOptions::default());
+        assert!(result.x[0] < 1.0 && result.x[0] > 0.99, "{:?}", result.x);
+    }
+}
@@ -0,0 +1,420 @@
+//! Columnar orderbook snapshots
+//!
+//! Parquet orderbook parts store one Float64 column per level and side. `OrderbookBatch`
+//! keeps those Arrow arrays as they were decoded and `OrderbookView` reads one row from them
+//! as f64, so replaying the book costs no per-snapshot allocation or `Decimal` conversion.
+//! Consumers convert to `Decimal` only the prices they actually use; `to_snapshot` rebuilds
+//! the owned `OrderbookSnapshot` with exactly the conversion the row reader used to apply.
+
+use crate::data_loader::OrderbookSnapshot;
+use arrow::array::{Array, Float64Array, Float64Builder, TimestampMillisecondArray};
+use arrow::record_batch::RecordBatch;
+use rust_decimal::prelude::*;
+use rust_decimal::Decimal;
+use std::error::Error;
+use std::sync::Arc;
+
+/// Timestamp and per-level `[bid_price, bid_qty, ask_price, ask_qty]` of one snapshot,
+/// None for a missing level
+pub type RawOrderbookRow = (u64, Vec<Option<[f64; 4]>>);
+
+/// Price and quantity columns of one side of one level
+#[derive(Debug, Clone)]
+struct LevelColumns {
+    bid_price: Float64Array,
+    bid_qty: Float64Array,
+    ask_price: Float64Array,
+    ask_qty: Float64Array,
+}
+
+/// A batch of orderbook snapshots held column-wise
+#[derive(Debug, Clone)]
+pub struct OrderbookBatch {
+    timestamps: TimestampMillisecondArray,
+    levels: Vec<LevelColumns>,
+}
+
+impl OrderbookBatch {
+    /// Wrap the columns of a parquet orderbook record batch (no data is copied)
+    pub fn from_record_batch(batch: &RecordBatch, max_levels: usize) -> Result<Self, Box<dyn Error>> {
+        let column = |name: &str| -> Result<Float64Array, Box<dyn Error>> {
+            Ok(batch
+                .column_by_name(name)
+                .ok_or_else(|| format!("Missing {} column", name))?
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .ok_or_else(|| format!("Invalid {} column", name))?
+                .clone())
+        };
+        let timestamps = batch
+            .column_by_name("timestamp_ms")
+            .ok_or("Missing timestamp_ms column")?
+            .as_any()
+            .downcast_ref::<TimestampMillisecondArray>()
+            .ok_or("Invalid timestamp column")?
+            .clone();
+
+        let mut levels = Vec::with_capacity(max_levels);
+        for i in 0..max_levels {
+            levels.push(LevelColumns {
+                bid_price: column(&format!("bid_price_{}", i))?,
+                bid_qty: column(&format!("bid_qty_{}", i))?,
+                ask_price: column(&format!("ask_price_{}", i))?,
+                ask_qty: column(&format!("ask_qty_{}", i))?,
+            });
+        }
+        Ok(Self { timestamps, levels })
+    }
+
+    /// Build a batch from raw rows, missing levels being null
+    pub fn from_rows(rows: &[RawOrderbookRow], max_levels: usize) -> Self {
+        let timestamps = TimestampMillisecondArray::from(rows.iter().map(|(ts, _)| *ts as i64).collect::<Vec<_>>());
+        let levels = (0..max_levels)
+            .map(|level| {
+                let mut builders: [Float64Builder; 4] = std::array::from_fn(|_| Float64Builder::with_capacity(rows.len()));
+                for (_, row) in rows {
+                    let values = row.get(level).copied().flatten();
+                    for (k, builder) in builders.iter_mut().enumerate() {
+                        builder.append_option(values.map(|v| v[k]));
+                    }
+                }
+                let [mut bid_price, mut bid_qty, mut ask_price, mut ask_qty] = builders;
+                LevelColumns {
+                    bid_price: bid_price.finish(),
+                    bid_qty: bid_qty.finish(),
+                    ask_price: ask_price.finish(),
+                    ask_qty: ask_qty.finish(),
+                }
+            })
+            .collect();
+        Self { timestamps, levels }
+    }
+
+    /// Build a batch from owned snapshots (prices and quantities go through `to_f64`)
+    pub fn from_snapshots(snapshots: &[OrderbookSnapshot]) -> Self {
+        let max_levels = snapshots.iter().map(|s| s.bids.len().max(s.asks.len())).max().unwrap_or(0);
+        let rows: Vec<RawOrderbookRow> = snapshots
+            .iter()
+            .map(|s| {
+                let levels = (0..max_levels)
+                    .map(|i| {
+                        let (bid_price, bid_qty) = s.bids.get(i).map_or((0.0, 0.0), |(p, q)| decimal_pair(*p, *q));
+                        let (ask_price, ask_qty) = s.asks.get(i).map_or((0.0, 0.0), |(p, q)| decimal_pair(*p, *q));
+                        Some([bid_price, bid_qty, ask_price, ask_qty])
+                    })
+                    .collect();
+                (s.timestamp, levels)
+            })
+            .collect();
+        Self::from_rows(&rows, max_levels)
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Number of levels per side
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    #[inline]
+    pub fn timestamp(&self, row: usize) -> u64 {
+        self.timestamps.value(row) as u64
+    }
+
+    #[inline]
+    pub fn view(&self, row: usize) -> OrderbookView<'_> {
+        OrderbookView { batch: self, row }
+    }
+
+    pub fn views(&self) -> impl Iterator<Item = OrderbookView<'_>> {
+        (0..self.len()).map(move |row| self.view(row))
+    }
+
+    /// Bid prices of `level` for every row (null entries hold an unspecified value)
+    pub fn bid_prices(&self, level: usize) -> &[f64] {
+        self.levels[level].bid_price.values()
+    }
+
+    pub fn bid_quantities(&self, level: usize) -> &[f64] {
+        self.levels[level].bid_qty.values()
+    }
+
+    pub fn ask_prices(&self, level: usize) -> &[f64] {
+        self.levels[level].ask_price.values()
+    }
+
+    pub fn ask_quantities(&self, level: usize) -> &[f64] {
+        self.levels[level].ask_qty.values()
+    }
+
+    /// Rows `offset..offset + length`, sharing the same buffers
+    pub fn slice(&self, offset: usize, length: usize) -> Self {
+        Self {
+            timestamps: self.timestamps.slice(offset, length),
+            levels: self
+                .levels
+                .iter()
+                .map(|l| LevelColumns {
+                    bid_price: l.bid_price.slice(offset, length),
+                    bid_qty: l.bid_qty.slice(offset, length),
+                    ask_price: l.ask_price.slice(offset, length),
+                    ask_qty: l.ask_qty.slice(offset, length),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Builds an `OrderbookBatch` one snapshot at a time, keeping every value as given
+pub struct OrderbookBatchBuilder {
+    timestamps: Vec<i64>,
+    // Four builders per level: bid price, bid qty, ask price, ask qty
+    columns: Vec<Float64Builder>,
+}
+
+impl OrderbookBatchBuilder {
+    pub fn new(depth: usize, capacity: usize) -> Self {
+        Self {
+            timestamps: Vec::with_capacity(capacity),
+            columns: (0..depth * 4).map(|_| Float64Builder::with_capacity(capacity)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Append a snapshot; `values` holds `[bid_price, bid_qty, ask_price, ask_qty]` per level
+    pub fn push(&mut self, timestamp: u64, values: impl IntoIterator<Item = Option<f64>>) {
+        self.timestamps.push(timestamp as i64);
+        for (builder, value) in self.columns.iter_mut().zip(values) {
+            builder.append_option(value);
+        }
+    }
+
+    pub fn finish(mut self) -> OrderbookBatch {
+        let levels = self
+            .columns
+            .chunks_exact_mut(4)
+            .map(|c| LevelColumns {
+                bid_price: c[0].finish(),
+                bid_qty: c[1].finish(),
+                ask_price: c[2].finish(),
+                ask_qty: c[3].finish(),
+            })
+            .collect();
+        OrderbookBatch { timestamps: TimestampMillisecondArray::from(self.timestamps), levels }
+    }
+}
+
+fn decimal_pair(price: Decimal, qty: Decimal) -> (f64, f64) {
+    (price.to_f64().unwrap_or(0.0), qty.to_f64().unwrap_or(0.0))
+}
+
+/// One snapshot of an `OrderbookBatch`, borrowed
+#[derive(Debug, Clone, Copy)]
+pub struct OrderbookView<'a> {
+    batch: &'a OrderbookBatch,
+    row: usize,
+}
+
+impl<'a> OrderbookView<'a> {
+    #[inline]
+    pub fn timestamp(&self) -> u64 {
+        self.batch.timestamp(self.row)
+    }
+
+    /// Number of levels per side, present or not
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.batch.depth()
+    }
+
+    /// `[bid_price, bid_qty, ask_price, ask_qty]` of a level exactly as stored, None for nulls
+    pub fn level_values(&self, level: usize) -> [Option<f64>; 4] {
+        let l = &self.batch.levels[level];
+        [&l.bid_price, &l.bid_qty, &l.ask_price, &l.ask_qty]
+            .map(|column| (!column.is_null(self.row)).then(|| column.value(self.row)))
+    }
+
+    /// (price, qty) of a bid level; None when the level is empty or has no positive price
+    #[inline]
+    pub fn bid(&self, level: usize) -> Option<(f64, f64)> {
+        let l = self.batch.levels.get(level)?;
+        present_level(&l.bid_price, &l.bid_qty, self.row)
+    }
+
+    #[inline]
+    pub fn ask(&self, level: usize) -> Option<(f64, f64)> {
+        let l = self.batch.levels.get(level)?;
+        present_level(&l.ask_price, &l.ask_qty, self.row)
+    }
+
+    /// Bid levels present in this snapshot, best first
+    pub fn bids(&self) -> impl Iterator<Item = (f64, f64)> + 'a {
+        let (batch, row) = (self.batch, self.row);
+        batch.levels.iter().filter_map(move |l| present_level(&l.bid_price, &l.bid_qty, row))
+    }
+
+    /// Ask levels present in this snapshot, best first
+    pub fn asks(&self) -> impl Iterator<Item = (f64, f64)> + 'a {
+        let (batch, row) = (self.batch, self.row);
+        batch.levels.iter().filter_map(move |l| present_level(&l.ask_price, &l.ask_qty, row))
+    }
+
+    #[inline]
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids().next().map(|(p, _)| p)
+    }
+
+    #[inline]
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks().next().map(|(p, _)| p)
+    }
+
+    /// Deepest bid price present
+    pub fn far_bid(&self) -> Option<f64> {
+        self.bids().last().map(|(p, _)| p)
+    }
+
+    /// Deepest ask price present
+    pub fn far_ask(&self) -> Option<f64> {
+        self.asks().last().map(|(p, _)| p)
+    }
+
+    /// Best bid as the `Decimal` the owned snapshot would hold
+    #[inline]
+    pub fn best_bid_decimal(&self) -> Option<Decimal> {
+        self.best_bid().and_then(Decimal::from_f64)
+    }
+
+    #[inline]
+    pub fn best_ask_decimal(&self) -> Option<Decimal> {
+        self.best_ask().and_then(Decimal::from_f64)
+    }
+
+    /// Owned snapshot with every level converted to `Decimal`
+    pub fn to_snapshot(&self) -> Result<OrderbookSnapshot, Box<dyn Error>> {
+        let mut bids = Vec::new();
+        let mut asks = Vec::new();
+        for l in &self.batch.levels {
+            if !l.bid_price.is_null(self.row) && !l.bid_qty.is_null(self.row) {
+                let bid_price = Decimal::from_f64(l.bid_price.value(self.row)).ok_or("Failed to convert bid price")?;
+                let bid_qty = Decimal::from_f64(l.bid_qty.value(self.row)).ok_or("Failed to convert bid qty")?;
+                if bid_price > Decimal::ZERO {
+                    bids.push((bid_price, bid_qty));
+                }
+            }
+            if !l.ask_price.is_null(self.row) && !l.ask_qty.is_null(self.row) {
+                let ask_price = Decimal::from_f64(l.ask_price.value(self.row)).ok_or("Failed to convert ask price")?;
+                let ask_qty = Decimal::from_f64(l.ask_qty.value(self.row)).ok_or("Failed to convert ask qty")?;
+                if ask_price > Decimal::ZERO {
+                    asks.push((ask_price, ask_qty));
+                }
+            }
+        }
+        Ok(OrderbookSnapshot { timestamp: self.timestamp(), bids, asks })
+    }
+}
+
+#[inline]
+fn present_level(price: &Float64Array, qty: &Float64Array, row: usize) -> Option<(f64, f64)> {
+    if price.is_null(row) || qty.is_null(row) {
+        return None;
+    }
+    let p = price.value(row);
+    (p > 0.0).then(|| (p, qty.value(row)))
+}
+
+/// Owned handle on one snapshot of a shared `OrderbookBatch`
+#[derive(Debug, Clone)]
+pub struct OrderbookRow {
+    batch: Arc<OrderbookBatch>,
+    row: usize,
+}
+
+impl OrderbookRow {
+    pub fn new(batch: Arc<OrderbookBatch>, row: usize) -> Self {
+        Self { batch, row }
+    }
+
+    /// Single-row batch holding `snapshot`
+    pub fn from_snapshot(snapshot: &OrderbookSnapshot) -> Self {
+        Self::new(Arc::new(OrderbookBatch::from_snapshots(std::slice::from_ref(snapshot))), 0)
+    }
+
+    #[inline]
+    pub fn timestamp(&self) -> u64 {
+        self.batch.timestamp(self.row)
+    }
+
+    #[inline]
+    pub fn view(&self) -> OrderbookView<'_> {
+        self.batch.view(self.row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_view_skips_empty_levels_and_matches_snapshot() {
+        let rows = vec![
+            (1_000, vec![Some([99.9, 1.0, 100.1, 2.0]), None, Some([99.7, 3.0, 100.3, 1.5])]),
+            (2_000, vec![Some([0.0, 0.0, 100.2, 1.0]), Some([99.8, 2.0, 0.0, 0.0]), None]),
+        ];
+        let batch = OrderbookBatch::from_rows(&rows, 3);
+        assert_eq!((batch.len(), batch.depth()), (2, 3));
+        assert_eq!(batch.bid_prices(0), &[99.9, 0.0]);
+
+        let first = batch.view(0);
+        assert_eq!(first.timestamp(), 1_000);
+        assert_eq!(first.bids().collect::<Vec<_>>(), vec![(99.9, 1.0), (99.7, 3.0)]);
+        assert_eq!((first.best_bid(), first.far_bid()), (Some(99.9), Some(99.7)));
+        assert_eq!(first.ask(1), None);
+
+        // Zero prices are absent, so the best bid of the second row is its second level
+        let second = batch.view(1);
+        assert_eq!(second.best_bid(), Some(99.8));
+        assert_eq!(second.far_ask(), Some(100.2));
+        assert_eq!(second.best_bid_decimal(), Some(dec("99.8")));
+
+        let snapshot = second.to_snapshot().unwrap();
+        assert_eq!(snapshot.timestamp, 2_000);
+        assert_eq!(snapshot.bids, vec![(dec("99.8"), dec("2"))]);
+        assert_eq!(snapshot.asks, vec![(dec("100.2"), dec("1"))]);
+
+        let tail = batch.slice(1, 1);
+        assert_eq!(tail.len(), 1);
+        assert_eq!(format!("{:?}", tail.view(0).to_snapshot().unwrap()), format!("{:?}", snapshot));
+    }
+
+    #[test]
+    fn test_row_from_snapshot_round_trips() {
+        let snapshot = OrderbookSnapshot {
+            timestamp: 42,
+            bids: vec![(dec("2000.25"), dec("0.5")), (dec("2000.2"), dec("1.25"))],
+            asks: vec![(dec("2000.3"), dec("2"))],
+        };
+        let row = OrderbookRow::from_snapshot(&snapshot);
+        assert_eq!(row.timestamp(), 42);
+        assert_eq!(row.view().asks().count(), 1);
+        assert_eq!(format!("{:?}", row.view().to_snapshot().unwrap()), format!("{:?}", snapshot));
+    }
+}
@@ -0,0 +1,288 @@
+//! Smoothing of calibrated intensity parameters across calibration windows
+//!
+//! Each window's kappa/A are noisy point estimates, so quotes jump whenever a burst of
+//! trades enters or leaves the window. The smoother filters ln κ and ln A per side with an
+//! EWMA or a local-level Kalman filter whose measurement variance is (se/θ)² from the fit.
+//!
+//! With shrinkage enabled, bid and ask estimates are pulled toward their precision-weighted
+//! pooled value by an empirical-Bayes factor. A side that could not be fitted is shrunk toward
+//! its own prior state: the other side's value, widened by the between-side dispersion, is
+//! combined with that state by precision instead of being copied.
+
+use crate::calibration::{IntensityFit, IntensityFitSource, SideIntensityFit};
+use crate::model_types::{ASConfig, ParameterSmoothing};
+
+/// Log-space variance used when a fitted side has no finite standard error
+const UNKNOWN_MEASUREMENT_VARIANCE: f64 = 1.0;
+/// Prior between-side dispersion of ln κ / ln A (a ~50% bid/ask difference)
+const INITIAL_SIDE_DISPERSION: f64 = 0.25;
+/// Weight of each window's between-side dispersion estimate in the running value
+const DISPERSION_UPDATE_WEIGHT: f64 = 0.2;
+
+const KAPPA: usize = 0;
+const INTENSITY_A: usize = 1;
+const BID: usize = 0;
+const ASK: usize = 1;
+
+/// Measurement or filtered state in log space
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LogLevel {
+    value: f64,
+    variance: f64,
+}
+
+/// Smoothed intensity parameters for both sides
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmoothedIntensity {
+    pub bid_kappa: f64,
+    pub bid_a: f64,
+    pub ask_kappa: f64,
+    pub ask_a: f64,
+}
+
+pub struct ParameterSmoother {
+    mode: ParameterSmoothing,
+    /// EWMA weight on the previous value, 0.5^(1/halflife)
+    ewma_decay: f64,
+    /// Kalman process variance per calibration (log space)
+    process_variance: f64,
+    shrinkage: bool,
+    /// Filtered state indexed [parameter][side]
+    levels: [[Option<LogLevel>; 2]; 2],
+    /// Running between-side variance per parameter
+    side_dispersion: [f64; 2],
+}
+
+impl ParameterSmoother {
+    pub fn new(config: &ASConfig) -> Self {
+        let halflife = config.smoothing_halflife_calibrations.max(f64::MIN_POSITIVE);
+        Self {
+            mode: config.parameter_smoothing.clone(),
+            ewma_decay: 0.5_f64.powf(1.0 / halflife),
+            process_variance: config.kalman_process_std_log.powi(2),
+            shrinkage: config.intensity_shrinkage,
+            levels: [[None; 2]; 2],
+            side_dispersion: [INITIAL_SIDE_DISPERSION; 2],
+        }
+    }
+
+    /// True when the config asks for smoothing or shrinkage
+    pub fn is_enabled(config: &ASConfig) -> bool {
+        config.parameter_smoothing != ParameterSmoothing::None || config.intensity_shrinkage
+    }
+
+    /// Feed one window's fit and return the smoothed parameters
+    pub fn update(&mut self, fit: &IntensityFit) -> SmoothedIntensity {
+        let sides = [&fit.bid, &fit.ask];
+        for parameter in [KAPPA, INTENSITY_A] {
+            let measurements = self.measurements(parameter, sides);
+            for side in [BID, ASK] {
+                if let Some(measurement) = measurements[side] {
+                    self.filter(parameter, side, measurement);
+                }
+            }
+        }
+
+        let value = |parameter: usize, side: usize, raw: f64| {
+            self.levels[parameter][side].map(|level| level.value.exp()).unwrap_or(raw)
+        };
+        SmoothedIntensity {
+            bid_kappa: value(KAPPA, BID, fit.bid.kappa),
+            bid_a: value(INTENSITY_A, BID, fit.bid.a),
+            ask_kappa: value(KAPPA, ASK, fit.ask.kappa),
+            ask_a: value(INTENSITY_A, ASK, fit.ask.a),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.levels = [[None; 2]; 2];
+        self.side_dispersion = [INITIAL_SIDE_DISPERSION; 2];
+    }
+
+    /// Log-space measurements for one parameter on both sides (None = no information)
+    fn measurements(&mut self, parameter: usize, sides: [&SideIntensityFit; 2]) -> [Option<LogLevel>; 2] {
+        let own = |fit: &SideIntensityFit| -> Option<LogLevel> {
+            if fit.source != IntensityFitSource::Fitted {
+                return None;
+            }
+            let (value, se) = if parameter == KAPPA { (fit.kappa, fit.kappa_se) } else { (fit.a, fit.a_se) };
+            if !(value.is_finite() && value > 0.0) {
+                return None;
+            }
+            let relative = se / value;
+            let variance = if relative.is_finite() && relative > 0.0 { relative * relative } else { UNKNOWN_MEASUREMENT_VARIANCE };
+            Some(LogLevel { value: value.ln(), variance })
+        };
+        let mut measured = [own(sides[BID]), own(sides[ASK])];
+
+        if !self.shrinkage {
+            // Keep the fit's own fallback: the copied side carries the source side's variance
+            for side in [BID, ASK] {
+                if measured[side].is_none() && sides[side].source == IntensityFitSource::OtherSide {
+                    measured[side] = measured[1 - side];
+                }
+            }
+            return measured;
+        }
+
+        let priors = self.levels[parameter];
+        let process_variance = self.process_variance;
+        let dispersion = &mut self.side_dispersion[parameter];
+
+        // Missing side: the other side's value as a dispersion-widened measurement, combined by
+        // precision with the side's own prior state so `none` smoothing does not copy it
+        let fill = |prior: Option<LogLevel>, fitted: LogLevel, dispersion: f64| {
+            let other = LogLevel { value: fitted.value, variance: fitted.variance + dispersion };
+            let Some(prior) = prior else {
+                return other;
+            };
+            let prior_variance = prior.variance + process_variance;
+            let precision = 1.0 / prior_variance + 1.0 / other.variance;
+            LogLevel {
+                value: (prior.value / prior_variance + other.value / other.variance) / precision,
+                variance: 1.0 / precision,
+            }
+        };
+
+        match measured {
+            [Some(bid), Some(ask)] => {
+                let pooled_precision = 1.0 / bid.variance + 1.0 / ask.variance;
+                let pooled = (bid.value / bid.variance + ask.value / ask.variance) / pooled_precision;
+
+                // Method-of-moments between-side variance, smoothed across windows
+                let window_dispersion =
+                    (0.5 * (bid.value - ask.value).powi(2) - 0.5 * (bid.variance + ask.variance)).max(0.0);
+                *dispersion += DISPERSION_UPDATE_WEIGHT * (window_dispersion - *dispersion);
+
+                let shrink = |m: LogLevel| {
+                    if *dispersion <= 0.0 {
+                        return LogLevel { value: pooled, variance: 1.0 / pooled_precision };
+                    }
+                    let weight = *dispersion / (*dispersion + m.variance);
+                    LogLevel {
+                        value: pooled + weight * (m.value - pooled),
+                        variance: 1.0 / (1.0 / m.variance + 1.0 / *dispersion),
+                    }
+                };
+                [Some(shrink(bid)), Some(shrink(ask))]
+            }
+            [Some(fitted), None] => [Some(fitted), Some(fill(priors[ASK], fitted, *dispersion))],
+            [None, Some(fitted)] => [Some(fill(priors[BID], fitted, *dispersion)), Some(fitted)],
+            [None, None] => [None, None],
+        }
+    }
+
+    fn filter(&mut self, parameter: usize, side: usize, measurement: LogLevel) {
+        let level = &mut self.levels[parameter][side];
+        let Some(state) = level.as_mut() else {
+            *level = Some(measurement);
+            return;
+        };
+
+        match self.mode {
+            ParameterSmoothing::None => *state = measurement,
+            ParameterSmoothing::Ewma => {
+                state.value = self.ewma_decay * state.value + (1.0 - self.ewma_decay) * measurement.value;
+            }
+            ParameterSmoothing::Kalman => {
+                let predicted = state.variance + self.process_variance;
+                let gain = predicted / (predicted + measurement.variance);
+                state.value += gain * (measurement.value - state.value);
+                state.variance = (1.0 - gain) * predicted;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn side(kappa: f64, se_ratio: f64, source: IntensityFitSource) -> SideIntensityFit {
+        SideIntensityFit {
+            kappa,
+            a: 10.0,
+            kappa_se: kappa * se_ratio,
+            a_se: 10.0 * se_ratio,
+            ks_statistic: 0.01,
+            trades: 100,
+            source,
+        }
+    }
+
+    fn fit(bid: SideIntensityFit, ask: SideIntensityFit) -> IntensityFit {
+        IntensityFit { bid, ask }
+    }
+
+    #[test]
+    fn test_kalman_discounts_noisy_outlier_but_follows_precise_jump() {
+        let config = ASConfig { parameter_smoothing: ParameterSmoothing::Kalman, ..ASConfig::default() };
+        let fitted = IntensityFitSource::Fitted;
+        let mut smoother = ParameterSmoother::new(&config);
+        for _ in 0..10 {
+            smoother.update(&fit(side(1000.0, 0.05, fitted), side(1000.0, 0.05, fitted)));
+        }
+
+        // Noisy 3x outlier on the bid, precise 3x jump on the ask
+        let out = smoother.update(&fit(side(3000.0, 1.0, fitted), side(3000.0, 0.01, fitted)));
+        assert!(out.bid_kappa < 1100.0, "{:?}", out);
+        assert!(out.ask_kappa > 2500.0, "{:?}", out);
+    }
+
+    #[test]
+    fn test_ewma_halflife_one_is_geometric_midpoint() {
+        let config = ASConfig {
+            parameter_smoothing: ParameterSmoothing::Ewma,
+            smoothing_halflife_calibrations: 1.0,
+            ..ASConfig::default()
+        };
+        let fitted = IntensityFitSource::Fitted;
+        let mut smoother = ParameterSmoother::new(&config);
+        smoother.update(&fit(side(100.0, 0.1, fitted), side(100.0, 0.1, fitted)));
+        let out = smoother.update(&fit(side(400.0, 0.1, fitted), side(100.0, 0.1, fitted)));
+        assert!((out.bid_kappa - 200.0).abs() < 1e-9, "{:?}", out);
+    }
+
+    #[test]
+    fn test_shrinkage_replaces_copy_of_other_side() {
+        let config = ASConfig {
+            parameter_smoothing: ParameterSmoothing::Kalman,
+            intensity_shrinkage: true,
+            ..ASConfig::default()
+        };
+        let fitted = IntensityFitSource::Fitted;
+        let mut smoother = ParameterSmoother::new(&config);
+        for _ in 0..10 {
+            smoother.update(&fit(side(1000.0, 0.05, fitted), side(500.0, 0.05, fitted)));
+        }
+
+        // Ask cannot be fitted: the old fallback would copy the bid's 1000
+        let out = smoother.update(&fit(side(1000.0, 0.05, fitted), side(1000.0, f64::NAN, IntensityFitSource::OtherSide)));
+        assert!(out.ask_kappa < 750.0, "{:?}", out);
+
+        // Shrinkage pulls a noisy fitted side toward the pooled estimate
+        let mut raw = ParameterSmoother::new(&ASConfig { intensity_shrinkage: true, ..ASConfig::default() });
+        let out = raw.update(&fit(side(1000.0, 0.02, fitted), side(2000.0, 2.0, fitted)));
+        assert!(out.ask_kappa < 1500.0 && out.bid_kappa > 990.0, "{:?}", out);
+    }
+
+    #[test]
+    fn test_shrinkage_without_smoothing_keeps_missing_side_near_its_prior() {
+        let config = ASConfig { intensity_shrinkage: true, ..ASConfig::default() };
+        assert_eq!(config.parameter_smoothing, ParameterSmoothing::None);
+        let fitted = IntensityFitSource::Fitted;
+        let mut smoother = ParameterSmoother::new(&config);
+        for _ in 0..10 {
+            smoother.update(&fit(side(1000.0, 0.05, fitted), side(500.0, 0.05, fitted)));
+        }
+
+        // No filter state smooths the measurement, so a copy of the bid would show up as 1000
+        let out = smoother.update(&fit(side(1000.0, 0.05, fitted), side(1000.0, f64::NAN, IntensityFitSource::OtherSide)));
+        assert!(out.ask_kappa > 450.0 && out.ask_kappa < 750.0, "{:?}", out);
+
+        // Without any prior state the other side's value is the only information
+        let mut fresh = ParameterSmoother::new(&config);
+        let out = fresh.update(&fit(side(1000.0, 0.05, fitted), side(1000.0, f64::NAN, IntensityFitSource::OtherSide)));
+        assert!((out.ask_kappa - 1000.0).abs() < 1e-6, "{:?}", out);
+    }
+}
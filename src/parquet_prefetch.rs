@@ -0,0 +1,326 @@
+//! Prefetching reader over parquet part files
+//!
+//! Decoding a part file is the bulk of the time spent loading history, and the backtest loop
+//! itself is single-threaded. `PartReader` queues the next `prefetch_depth` files for a pool
+//! of `prefetch_depth` worker threads, each decoding a file into a bounded channel, and drains
+//! the channels in file order: record batches come out exactly as a sequential read would
+//! produce them, while up to `prefetch_depth` files decode in parallel ahead of the consumer.
+//! A decoder that panics surfaces as an error for its file rather than a silently short read;
+//! the worker catches the panic and stays in the pool.
+
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use std::collections::VecDeque;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Default number of part files decoded ahead of the consumer
+pub const DEFAULT_PREFETCH_DEPTH: usize = 4;
+
+/// Record batches buffered per prefetched file before its worker waits for the consumer
+const PREFETCH_CHANNEL_BATCHES: usize = 8;
+
+/// Decoded record batches of one part file
+pub type PartBatches = Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>> + Send>;
+
+/// Opens a part file for decoding; None skips the file (the opener reports why)
+pub type PartOpener = Arc<dyn Fn(&Path) -> Option<PartBatches> + Send + Sync>;
+
+/// Output of a worker for one file; `Done` tells a finished file from a short one
+enum PartMessage {
+    Batch(Result<RecordBatch, ArrowError>),
+    Done,
+    /// Decoding the file panicked; the worker caught it and moves on to the next file
+    Panicked(String),
+}
+
+/// A file handed to the worker pool and the channel its batches go to
+struct PartJob {
+    path: PathBuf,
+    sender: SyncSender<PartMessage>,
+}
+
+struct InFlight {
+    path: PathBuf,
+    receiver: Receiver<PartMessage>,
+}
+
+enum PartSource {
+    Local(PartBatches),
+    Prefetched(InFlight),
+}
+
+/// Record batches of a list of part files, in file order
+pub struct PartReader {
+    pending: VecDeque<PathBuf>,
+    open: PartOpener,
+    prefetch_depth: usize,
+    current: Option<PartSource>,
+    in_flight: VecDeque<InFlight>,
+    /// Job queue of the worker pool; workers exit once it is dropped
+    jobs: Option<Sender<PartJob>>,
+    job_queue: Arc<Mutex<Receiver<PartJob>>>,
+    workers: Vec<JoinHandle<()>>,
+    /// Set when the reader is dropped so workers skip queued files
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PartReader {
+    /// Open the first readable file of `files` on the calling thread; later files are
+    /// decoded by a pool of `prefetch_depth` worker threads, at most `prefetch_depth` files
+    /// ahead, once reading starts (0 reads every file on the calling thread)
+    pub fn new(files: Vec<PathBuf>, open: PartOpener, prefetch_depth: usize) -> Self {
+        let (jobs, job_queue) = channel();
+        let mut reader = Self {
+            pending: files.into(),
+            open,
+            prefetch_depth,
+            current: None,
+            in_flight: VecDeque::new(),
+            jobs: Some(jobs),
+            job_queue: Arc::new(Mutex::new(job_queue)),
+            workers: Vec::new(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        reader.current = reader.open_next_local().map(PartSource::Local);
+        reader
+    }
+
+    pub fn set_prefetch_depth(&mut self, prefetch_depth: usize) {
+        self.prefetch_depth = prefetch_depth;
+    }
+
+    /// Whether any file could be opened
+    pub fn has_data(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Files neither opened nor handed to a worker yet
+    pub fn unopened_files(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn open_next_local(&mut self) -> Option<PartBatches> {
+        while let Some(path) = self.pending.pop_front() {
+            if let Some(batches) = (self.open)(&path) {
+                return Some(batches);
+            }
+        }
+        None
+    }
+
+    fn spawn_worker(&mut self) {
+        let open = Arc::clone(&self.open);
+        let queue = Arc::clone(&self.job_queue);
+        let cancelled = Arc::clone(&self.cancelled);
+        self.workers.push(thread::spawn(move || loop {
+            // The lock is released before decoding so other workers can take jobs
+            let job = queue.lock().map_err(|_| ()).and_then(|queue| queue.recv().map_err(|_| ()));
+            let Ok(PartJob { path, sender }) = job else {
+                break;
+            };
+            if cancelled.load(Ordering::Relaxed) {
+                continue;
+            }
+            // A failed send means the reader dropped this file: move on
+            let decoded = panic::catch_unwind(AssertUnwindSafe(|| match open(&path) {
+                Some(mut batches) => batches.all(|batch| sender.send(PartMessage::Batch(batch)).is_ok()),
+                None => true,
+            }));
+            let _ = match decoded {
+                Ok(true) => sender.send(PartMessage::Done),
+                Ok(false) => continue,
+                Err(payload) => sender.send(PartMessage::Panicked(panic_message(payload))),
+            };
+        }));
+    }
+
+    /// Queue files for the pool until `prefetch_depth` are in flight
+    fn fill_prefetch(&mut self) {
+        while self.workers.len() < self.prefetch_depth {
+            self.spawn_worker();
+        }
+        while self.in_flight.len() < self.prefetch_depth {
+            let Some(jobs) = self.jobs.as_ref() else {
+                break;
+            };
+            let Some(path) = self.pending.pop_front() else {
+                break;
+            };
+            let (sender, receiver) = sync_channel(PREFETCH_CHANNEL_BATCHES);
+            if jobs.send(PartJob { path: path.clone(), sender }).is_err() {
+                self.pending.push_front(path);
+                break;
+            }
+            self.in_flight.push_back(InFlight { path, receiver });
+        }
+    }
+
+    fn next_source(&mut self) -> Option<PartSource> {
+        if let Some(in_flight) = self.in_flight.pop_front() {
+            self.fill_prefetch();
+            return Some(PartSource::Prefetched(in_flight));
+        }
+        self.open_next_local().map(PartSource::Local)
+    }
+}
+
+/// Text of a caught panic
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+impl Iterator for PartReader {
+    type Item = Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fill_prefetch();
+        loop {
+            let batch = match self.current.as_mut()? {
+                PartSource::Local(batches) => batches.next(),
+                PartSource::Prefetched(in_flight) => match in_flight.receiver.recv() {
+                    Ok(PartMessage::Batch(batch)) => Some(batch),
+                    // The worker finished (or skipped) its file
+                    Ok(PartMessage::Done) => None,
+                    failed => {
+                        let reason = match failed {
+                            Ok(PartMessage::Panicked(message)) => message,
+                            _ => "worker exited".to_string(),
+                        };
+                        let error = ArrowError::ExternalError(
+                            format!("prefetch worker failed to decode {:?}: {}", in_flight.path, reason).into(),
+                        );
+                        self.current = self.next_source();
+                        return Some(Err(error));
+                    }
+                },
+            };
+            match batch {
+                Some(batch) => return Some(batch),
+                None => self.current = self.next_source(),
+            }
+        }
+    }
+}
+
+impl Drop for PartReader {
+    /// Stop the pool: queued files are skipped, files being decoded stop at their next
+    /// batch, and the workers are joined
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.jobs = None;
+        self.in_flight.clear();
+        self.current = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Int64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::atomic::AtomicUsize;
+
+    /// Decrements the live decode count when a file's batch iterator is dropped
+    struct LiveDecode(Arc<AtomicUsize>);
+
+    impl Drop for LiveDecode {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    fn opener() -> PartOpener {
+        counting_opener(Arc::new(AtomicUsize::new(0)))
+    }
+
+    /// Files named by a number decode into that many single-row batches; "skip" is unreadable
+    /// and "panic" panics while decoding. `live` counts files whose decoder is still alive.
+    fn counting_opener(live: Arc<AtomicUsize>) -> PartOpener {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)]));
+        Arc::new(move |path: &Path| {
+            let name = path.to_str().unwrap().to_string();
+            if name == "panic" {
+                panic!("corrupt part file");
+            }
+            let count: i64 = name.parse().ok()?;
+            let schema = schema.clone();
+            // Later files finish first if decoded concurrently
+            thread::sleep(std::time::Duration::from_millis((20 - count.min(20)) as u64));
+            live.fetch_add(1, Ordering::SeqCst);
+            let live = LiveDecode(Arc::clone(&live));
+            Some(Box::new((0..count).map(move |i| {
+                let _ = &live;
+                Ok(RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![count * 100 + i]))]).unwrap())
+            })) as PartBatches)
+        })
+    }
+
+    fn values(reader: PartReader) -> Vec<i64> {
+        reader
+            .map(|b| b.unwrap().column(0).as_any().downcast_ref::<Int64Array>().unwrap().value(0))
+            .collect()
+    }
+
+    #[test]
+    fn test_prefetched_order_matches_sequential() {
+        let files: Vec<PathBuf> = ["skip", "3", "1", "skip", "12", "2", "20", "5"].iter().map(PathBuf::from).collect();
+        let sequential = values(PartReader::new(files.clone(), opener(), 0));
+        assert_eq!(sequential.len(), 3 + 1 + 12 + 2 + 20 + 5);
+        assert_eq!(&sequential[..4], &[300, 301, 302, 100]);
+        for depth in [1, 2, 4, 16] {
+            assert_eq!(values(PartReader::new(files.clone(), opener(), depth)), sequential);
+        }
+
+        let empty = PartReader::new(vec![PathBuf::from("skip")], opener(), 4);
+        assert!(!empty.has_data());
+        assert_eq!(values(empty).len(), 0);
+    }
+
+    #[test]
+    fn test_dropping_reader_stops_workers() {
+        let files: Vec<PathBuf> = (0..8).map(|_| PathBuf::from("20")).collect();
+        let live = Arc::new(AtomicUsize::new(0));
+        let mut reader = PartReader::new(files, counting_opener(Arc::clone(&live)), 4);
+        assert!(reader.next().is_some());
+        assert_eq!(reader.workers.len(), 4);
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(live.load(Ordering::SeqCst) > 1);
+        drop(reader);
+
+        // Drop joined the pool, so no worker is left holding a decoder
+        assert_eq!(live.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_worker_panic_is_an_error() {
+        let files: Vec<PathBuf> = ["3", "panic", "2"].iter().map(PathBuf::from).collect();
+        let batches: Vec<_> = PartReader::new(files, opener(), 2).collect();
+        let errors: Vec<String> = batches.iter().filter_map(|b| b.as_ref().err()).map(|e| e.to_string()).collect();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("corrupt part file"), "{}", errors[0]);
+        // The files around the failed one are still read
+        assert_eq!(batches.len(), 3 + 1 + 2);
+
+        // The worker survives the panic: a single-worker pool decodes every file after it
+        let files: Vec<PathBuf> = ["1", "panic", "2", "panic", "3", "4"].iter().map(PathBuf::from).collect();
+        let mut reader = PartReader::new(files, opener(), 1);
+        let batches: Vec<_> = reader.by_ref().collect();
+        assert_eq!(batches.iter().filter(|b| b.is_err()).count(), 2);
+        assert_eq!(batches.len(), 1 + 1 + 2 + 1 + 3 + 4);
+        assert_eq!(reader.workers.len(), 1);
+        assert!(reader.workers.iter().all(|worker| !worker.is_finished()));
+    }
+}
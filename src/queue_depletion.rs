@@ -0,0 +1,235 @@
+//! Intensity calibration from orderbook queue depletion
+//!
+//! Between two consecutive snapshots, size that disappeared from a price level was either
+//! executed or cancelled. The trade tape tells how much was executed on each side: that
+//! volume is attributed to the depleted levels from the touch outward (a market order
+//! consumes the best level first), and the remainder of each decrease is a cancellation.
+//! The deepest level reached by executions in an interval is one fill at that depth.
+//!
+//! Depths are measured against the mid of the earlier snapshot, in return space, and fitted
+//! with the same exposure MLE as the trade-based calibrator, so kappa/A share its units.
+
+use crate::calibration::{fit_intensity_from_deltas, CalibrationTrade, IntensityFit, OrderbookPoint};
+use crate::data_loader::OrderbookSnapshot;
+use rust_decimal::prelude::*;
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+
+/// Executions and cancellations on one side between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SideDepletion {
+    /// Deepest level (return space) consumed by executions, None without executions
+    pub delta: Option<f64>,
+    /// Depleted size attributed to trades
+    pub executed: Decimal,
+    /// Depleted size not explained by trades
+    pub cancelled: Decimal,
+}
+
+/// One side's depletion over a snapshot interval ending at `timestamp`
+#[derive(Debug, Clone, Copy)]
+pub struct DepletionEvent {
+    pub timestamp: u64,
+    pub is_bid: bool,
+    pub depletion: SideDepletion,
+}
+
+/// Split the size decreases of one side between `prev` and `next` into executions and
+/// cancellations, given the traded volume hitting that side in between.
+///
+/// Levels beyond the visible depth of `next` are not compared, since their size is unknown.
+pub fn attribute_side_depletion(
+    prev_levels: &[(Decimal, Decimal)],
+    next_levels: &[(Decimal, Decimal)],
+    mid: Decimal,
+    traded: Decimal,
+    is_bid: bool,
+) -> SideDepletion {
+    let mut result = SideDepletion { delta: None, executed: Decimal::ZERO, cancelled: Decimal::ZERO };
+    if mid <= Decimal::ZERO {
+        return result;
+    }
+
+    // Farthest price still visible in the next snapshot; a market order emptying the book
+    // leaves every previous level known to be gone
+    let visible_limit = next_levels.last().map(|(p, _)| *p);
+    let mut remaining = traded.max(Decimal::ZERO);
+
+    for &(price, size) in prev_levels {
+        if let Some(limit) = visible_limit {
+            let beyond = if is_bid { price < limit } else { price > limit };
+            if beyond {
+                break;
+            }
+        }
+
+        let next_size = next_levels
+            .iter()
+            .find(|(p, _)| *p == price)
+            .map(|(_, q)| *q)
+            .unwrap_or(Decimal::ZERO);
+        let decrease = (size - next_size).max(Decimal::ZERO);
+        let executed = decrease.min(remaining);
+        remaining -= executed;
+        result.executed += executed;
+        result.cancelled += decrease - executed;
+
+        if executed > Decimal::ZERO {
+            let distance = if is_bid { mid - price } else { price - mid };
+            result.delta = (distance / mid).to_f64().filter(|d| *d > 0.0).or(result.delta);
+        }
+    }
+
+    result
+}
+
+/// Rolling window of depletion events built from consecutive snapshots and the trades
+/// between them
+pub struct QueueDepletionTracker {
+    previous: Option<(OrderbookSnapshot, Decimal)>,
+    /// Trades since the previous snapshot
+    pending: Vec<CalibrationTrade>,
+    events: VecDeque<DepletionEvent>,
+}
+
+impl QueueDepletionTracker {
+    pub fn new() -> Self {
+        Self { previous: None, pending: Vec::new(), events: VecDeque::new() }
+    }
+
+    pub fn add_trade(&mut self, trade: CalibrationTrade) {
+        if self.previous.is_some() {
+            self.pending.push(trade);
+        }
+    }
+
+    /// Compare `snapshot` with the previous one and record both sides' depletion
+    pub fn add_snapshot(&mut self, snapshot: &OrderbookSnapshot, mid: Decimal) {
+        if let Some((prev, prev_mid)) = &self.previous {
+            for is_bid in [true, false] {
+                let traded: Decimal = self
+                    .pending
+                    .iter()
+                    .filter(|t| t.is_buyer_maker == is_bid)
+                    .map(|t| t.quantity)
+                    .sum();
+                let (prev_levels, next_levels) = if is_bid {
+                    (&prev.bids, &snapshot.bids)
+                } else {
+                    (&prev.asks, &snapshot.asks)
+                };
+                let depletion = attribute_side_depletion(prev_levels, next_levels, *prev_mid, traded, is_bid);
+                if depletion.executed > Decimal::ZERO || depletion.cancelled > Decimal::ZERO {
+                    self.events.push_back(DepletionEvent { timestamp: snapshot.timestamp, is_bid, depletion });
+                }
+            }
+        }
+        self.pending.clear();
+        self.previous = Some((snapshot.clone(), mid));
+    }
+
+    /// Drop events older than the calibration window
+    pub fn prune(&mut self, current_ts: u64, window_ms: u64) {
+        while self.events.front().is_some_and(|e| current_ts.saturating_sub(e.timestamp) > window_ms) {
+            self.events.pop_front();
+        }
+    }
+
+    /// Execution depths on one side over the window
+    pub fn execution_deltas(&self, is_bid: bool) -> Vec<f64> {
+        self.events
+            .iter()
+            .filter(|e| e.is_bid == is_bid)
+            .filter_map(|e| e.depletion.delta)
+            .collect()
+    }
+
+    /// Share of depleted size that was executed rather than cancelled, per side (bid, ask)
+    pub fn execution_share(&self) -> (f64, f64) {
+        let share = |is_bid: bool| {
+            let (executed, cancelled) = self
+                .events
+                .iter()
+                .filter(|e| e.is_bid == is_bid)
+                .fold((Decimal::ZERO, Decimal::ZERO), |(x, c), e| (x + e.depletion.executed, c + e.depletion.cancelled));
+            let total = executed + cancelled;
+            if total > Decimal::ZERO {
+                (executed / total).to_f64().unwrap_or(f64::NAN)
+            } else {
+                f64::NAN
+            }
+        };
+        (share(true), share(false))
+    }
+
+    /// Fit kappa/A per side from the window's execution depths
+    pub fn fit(&self, orderbooks: &[OrderbookPoint], window_end_ts: u64) -> IntensityFit {
+        fit_intensity_from_deltas(&self.execution_deltas(true), &self.execution_deltas(false), orderbooks, window_end_ts)
+    }
+
+    pub fn clear(&mut self) {
+        self.previous = None;
+        self.pending.clear();
+        self.events.clear();
+    }
+}
+
+impl Default for QueueDepletionTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn levels(raw: &[(&str, &str)]) -> Vec<(Decimal, Decimal)> {
+        raw.iter().map(|(p, q)| (dec(p), dec(q))).collect()
+    }
+
+    #[test]
+    fn test_executions_consume_from_touch_and_rest_is_cancelled() {
+        let prev = levels(&[("99.9", "1.0"), ("99.8", "2.0"), ("99.7", "3.0"), ("99.6", "1.0")]);
+        // Touch emptied, 1.5 gone from the second level, 0.5 cancelled at the third
+        let next = levels(&[("99.8", "0.5"), ("99.7", "2.5"), ("99.6", "1.0")]);
+        let out = attribute_side_depletion(&prev, &next, dec("100"), dec("2.5"), true);
+        assert_eq!(out.executed, dec("2.5"));
+        assert_eq!(out.cancelled, dec("0.5"));
+        assert!((out.delta.unwrap() - 0.002).abs() < 1e-12);
+
+        // Without trades every decrease is a cancellation
+        let out = attribute_side_depletion(&prev, &next, dec("100"), Decimal::ZERO, true);
+        assert_eq!(out.delta, None);
+        assert_eq!(out.cancelled, dec("3.0"));
+    }
+
+    #[test]
+    fn test_tracker_collects_execution_depths_per_side() {
+        let snapshot = |timestamp: u64, bids: &[(&str, &str)], asks: &[(&str, &str)]| OrderbookSnapshot {
+            timestamp,
+            bids: levels(bids),
+            asks: levels(asks),
+        };
+        let mut tracker = QueueDepletionTracker::new();
+        tracker.add_snapshot(&snapshot(0, &[("99.9", "1"), ("99.8", "1")], &[("100.1", "1"), ("100.2", "1")]), dec("100"));
+        tracker.add_trade(CalibrationTrade { timestamp: 500, price: dec("100.1"), quantity: dec("1.5"), is_buyer_maker: false });
+        tracker.add_snapshot(&snapshot(1_000, &[("99.9", "0.4"), ("99.8", "1")], &[("100.2", "0.5"), ("100.3", "1")]), dec("100"));
+
+        assert!(tracker.execution_deltas(true).is_empty());
+        let ask = tracker.execution_deltas(false);
+        assert_eq!(ask.len(), 1);
+        assert!((ask[0] - 0.002).abs() < 1e-12);
+        let (bid_share, ask_share) = tracker.execution_share();
+        assert_eq!(bid_share, 0.0);
+        assert_eq!(ask_share, 1.0);
+
+        tracker.prune(5_000, 1_000);
+        assert!(tracker.execution_deltas(false).is_empty());
+    }
+}
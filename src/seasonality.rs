@@ -0,0 +1,328 @@
+//! Hour-of-week seasonality of fill intensity and volatility
+//!
+//! Trade rates and variance follow the trading day and the funding schedule, so a one-hour
+//! calibration window lags them. Multipliers for A and σ are estimated per UTC hour of the
+//! week from the full history (normalised so the time-weighted mean is 1) and saved as
+//! JSON. The calibration engine divides its window estimates by the window's average
+//! multiplier and rescales them with the average over the upcoming interval.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+
+pub const HOURS_PER_WEEK: usize = 168;
+const MS_PER_HOUR: u64 = 3_600_000;
+/// 1970-01-01 was a Thursday: offset of the Unix epoch from Monday 00:00, in hours
+const EPOCH_HOUR_OF_WEEK: u64 = 3 * 24;
+/// Buckets observed for less than this keep a neutral multiplier
+const MIN_BUCKET_SECONDS: f64 = 600.0;
+/// Floor on estimated multipliers, so a bucket without trades or price moves cannot zero a rate
+const MIN_MULTIPLIER: f64 = 0.05;
+
+/// UTC hour of the week of a millisecond timestamp (0 = Monday 00:00-01:00)
+pub fn hour_of_week(timestamp_ms: u64) -> usize {
+    ((timestamp_ms / MS_PER_HOUR + EPOCH_HOUR_OF_WEEK) % HOURS_PER_WEEK as u64) as usize
+}
+
+/// Multipliers per hour of week, in the order of `hour_of_week`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SeasonalityProfile {
+    pub a_multipliers: Vec<f64>,
+    pub sigma_multipliers: Vec<f64>,
+    /// Seconds of data behind each bucket
+    pub observed_seconds: Vec<f64>,
+}
+
+/// Average multipliers over the calibration window and over the upcoming interval
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SeasonalAdjustment {
+    pub a_window: f64,
+    pub a_next: f64,
+    pub sigma_window: f64,
+    pub sigma_next: f64,
+}
+
+impl SeasonalAdjustment {
+    /// Factor taking a window estimate of A to the upcoming interval
+    pub fn a_ratio(&self) -> f64 {
+        Self::ratio(self.a_next, self.a_window)
+    }
+
+    /// Factor taking a window estimate of σ to the upcoming interval
+    pub fn sigma_ratio(&self) -> f64 {
+        Self::ratio(self.sigma_next, self.sigma_window)
+    }
+
+    /// next / window, or 1 (no adjustment) when either multiplier is not positive
+    fn ratio(next: f64, window: f64) -> f64 {
+        let ratio = next / window;
+        if next > 0.0 && window > 0.0 && ratio.is_finite() { ratio } else { 1.0 }
+    }
+}
+
+impl SeasonalityProfile {
+    /// Profile with all multipliers equal to 1
+    pub fn flat() -> Self {
+        Self {
+            a_multipliers: vec![1.0; HOURS_PER_WEEK],
+            sigma_multipliers: vec![1.0; HOURS_PER_WEEK],
+            observed_seconds: vec![0.0; HOURS_PER_WEEK],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        let profile: Self = serde_json::from_str(&contents)?;
+        if profile.a_multipliers.len() != HOURS_PER_WEEK || profile.sigma_multipliers.len() != HOURS_PER_WEEK {
+            return Err(format!("{:?}: expected {} hour-of-week multipliers", path, HOURS_PER_WEEK).into());
+        }
+        let positive = |m: &f64| m.is_finite() && *m > 0.0;
+        if !profile.a_multipliers.iter().all(positive) || !profile.sigma_multipliers.iter().all(positive) {
+            return Err(format!("{:?}: multipliers must be finite and positive", path).into());
+        }
+        Ok(profile)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Time-weighted mean of `values` over [start_ms, end_ms)
+    fn mean_over(values: &[f64], start_ms: u64, end_ms: u64) -> f64 {
+        if end_ms <= start_ms {
+            return values[hour_of_week(start_ms)];
+        }
+        let (mut weighted, mut ts) = (0.0, start_ms);
+        while ts < end_ms {
+            let hour_end = ((ts / MS_PER_HOUR) + 1) * MS_PER_HOUR;
+            let next = hour_end.min(end_ms);
+            weighted += values[hour_of_week(ts)] * (next - ts) as f64;
+            ts = next;
+        }
+        weighted / (end_ms - start_ms) as f64
+    }
+
+    /// Mean A multiplier over [start_ms, end_ms)
+    pub fn a_factor(&self, start_ms: u64, end_ms: u64) -> f64 {
+        Self::mean_over(&self.a_multipliers, start_ms, end_ms)
+    }
+
+    /// σ multiplier over [start_ms, end_ms): root of the mean variance multiplier
+    pub fn sigma_factor(&self, start_ms: u64, end_ms: u64) -> f64 {
+        let variance: Vec<f64> = self.sigma_multipliers.iter().map(|m| m * m).collect();
+        Self::mean_over(&variance, start_ms, end_ms).sqrt()
+    }
+
+    /// Adjustment for a window [window_start, now) quoted over [now, next_end)
+    pub fn adjustment(&self, window_start_ms: u64, now_ms: u64, next_end_ms: u64) -> SeasonalAdjustment {
+        SeasonalAdjustment {
+            a_window: self.a_factor(window_start_ms, now_ms),
+            a_next: self.a_factor(now_ms, next_end_ms),
+            sigma_window: self.sigma_factor(window_start_ms, now_ms),
+            sigma_next: self.sigma_factor(now_ms, next_end_ms),
+        }
+    }
+}
+
+/// Accumulates trade counts, squared log returns and observed time per hour of week
+///
+/// Trades are only counted when they fall inside an observed mid interval, so trades
+/// during a data gap do not inflate a bucket whose gap time is excluded.
+pub struct SeasonalityEstimator {
+    /// Mid gaps longer than this are treated as missing data
+    max_gap_ms: u64,
+    trade_timestamps: Vec<u64>,
+    squared_returns: Vec<f64>,
+    observed_seconds: Vec<f64>,
+    /// Observed [start, end] mid intervals in time order, adjacent ones merged
+    observed_intervals: Vec<(u64, u64)>,
+    last_mid: Option<(u64, f64)>,
+}
+
+impl SeasonalityEstimator {
+    pub fn new(max_gap_seconds: u64) -> Self {
+        Self {
+            max_gap_ms: max_gap_seconds.saturating_mul(1000),
+            trade_timestamps: Vec::new(),
+            squared_returns: vec![0.0; HOURS_PER_WEEK],
+            observed_seconds: vec![0.0; HOURS_PER_WEEK],
+            observed_intervals: Vec::new(),
+            last_mid: None,
+        }
+    }
+
+    /// Add a mid observation; mids must arrive in time order
+    pub fn observe_mid(&mut self, timestamp: u64, mid: f64) {
+        if !(mid.is_finite() && mid > 0.0) {
+            return;
+        }
+        if let Some((last_ts, last_mid)) = self.last_mid {
+            if timestamp > last_ts && timestamp - last_ts <= self.max_gap_ms {
+                let bucket = hour_of_week(last_ts);
+                self.observed_seconds[bucket] += (timestamp - last_ts) as f64 / 1000.0;
+                self.squared_returns[bucket] += (mid / last_mid).ln().powi(2);
+                match self.observed_intervals.last_mut() {
+                    Some((_, end)) if *end == last_ts => *end = timestamp,
+                    _ => self.observed_intervals.push((last_ts, timestamp)),
+                }
+            }
+        }
+        self.last_mid = Some((timestamp, mid));
+    }
+
+    /// Add a trade; trades may arrive before or after the mids around them
+    pub fn observe_trade(&mut self, timestamp: u64) {
+        self.trade_timestamps.push(timestamp);
+    }
+
+    /// Trades per hour of week that fall inside an observed mid interval
+    fn observed_trades(&self) -> Vec<f64> {
+        let mut trades = vec![0.0; HOURS_PER_WEEK];
+        for &ts in &self.trade_timestamps {
+            let index = self.observed_intervals.partition_point(|(start, _)| *start <= ts);
+            if index > 0 && ts <= self.observed_intervals[index - 1].1 {
+                trades[hour_of_week(ts)] += 1.0;
+            }
+        }
+        trades
+    }
+
+    /// Multipliers relative to the overall rates; thin buckets stay at 1
+    pub fn finish(&self) -> SeasonalityProfile {
+        let bucket_trades = self.observed_trades();
+        let observed = |b: usize| self.observed_seconds[b] >= MIN_BUCKET_SECONDS;
+        let (mut seconds, mut trades, mut squared) = (0.0, 0.0, 0.0);
+        for b in (0..HOURS_PER_WEEK).filter(|b| observed(*b)) {
+            seconds += self.observed_seconds[b];
+            trades += bucket_trades[b];
+            squared += self.squared_returns[b];
+        }
+
+        let mut profile = SeasonalityProfile::flat();
+        profile.observed_seconds = self.observed_seconds.clone();
+        if seconds <= 0.0 {
+            return profile;
+        }
+        let (trade_rate, variance_rate) = (trades / seconds, squared / seconds);
+        for b in (0..HOURS_PER_WEEK).filter(|b| observed(*b)) {
+            if trade_rate > 0.0 {
+                profile.a_multipliers[b] = (bucket_trades[b] / self.observed_seconds[b] / trade_rate).max(MIN_MULTIPLIER);
+            }
+            if variance_rate > 0.0 {
+                profile.sigma_multipliers[b] =
+                    (self.squared_returns[b] / self.observed_seconds[b] / variance_rate).sqrt().max(MIN_MULTIPLIER);
+            }
+        }
+        profile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Monday 2024-01-01 00:00 UTC
+    const MONDAY_MS: u64 = 1_704_067_200_000;
+
+    #[test]
+    fn test_hour_of_week_starts_monday() {
+        assert_eq!(hour_of_week(MONDAY_MS), 0);
+        assert_eq!(hour_of_week(MONDAY_MS + 10 * MS_PER_HOUR + 1), 10);
+        assert_eq!(hour_of_week(MONDAY_MS - 1), HOURS_PER_WEEK - 1);
+    }
+
+    #[test]
+    fn test_busy_hour_multipliers_and_window_adjustment() {
+        let mut estimator = SeasonalityEstimator::new(60);
+        let mut mid = 100.0;
+        for second in 0..(HOURS_PER_WEEK as u64 * 3600) {
+            let ts = MONDAY_MS + second * 1000;
+            let busy = hour_of_week(ts) == 10;
+            // Alternating ±r returns; the busy hour has twice the volatility and trade rate
+            let r: f64 = if busy { 2e-4 } else { 1e-4 };
+            mid *= if second % 2 == 0 { r.exp() } else { (-r).exp() };
+            estimator.observe_mid(ts, mid);
+            if second % 10 == 0 || (busy && second % 10 == 5) {
+                estimator.observe_trade(ts);
+            }
+        }
+
+        let profile = estimator.finish();
+        let ratio_a = profile.a_multipliers[10] / profile.a_multipliers[11];
+        let ratio_sigma = profile.sigma_multipliers[10] / profile.sigma_multipliers[11];
+        assert!((ratio_a - 2.0).abs() < 0.01, "{}", ratio_a);
+        assert!((ratio_sigma - 2.0).abs() < 0.01, "{}", ratio_sigma);
+
+        // Calibrated over hour 9, quoting through hour 10
+        let now = MONDAY_MS + 10 * MS_PER_HOUR;
+        let adjustment = profile.adjustment(now - MS_PER_HOUR, now, now + MS_PER_HOUR);
+        assert!((adjustment.a_ratio() - 2.0).abs() < 0.01, "{:?}", adjustment);
+        assert!((adjustment.sigma_ratio() - 2.0).abs() < 0.01, "{:?}", adjustment);
+
+        // Half an hour inside the busy hour averages the variance, not σ
+        let half = profile.sigma_factor(now - MS_PER_HOUR / 2, now + MS_PER_HOUR / 2) / profile.sigma_multipliers[11];
+        assert!((half - 2.5_f64.sqrt()).abs() < 0.01, "{}", half);
+    }
+
+    #[test]
+    fn test_empty_bucket_keeps_adjustment_finite() {
+        let mut estimator = SeasonalityEstimator::new(60);
+        let mut mid = 100.0;
+        for second in 0..(3 * 3600) {
+            let ts = MONDAY_MS + second * 1000;
+            // Hour 1 has neither trades nor price moves
+            if hour_of_week(ts) != 1 {
+                mid *= if second % 2 == 0 { 1e-4_f64.exp() } else { (-1e-4_f64).exp() };
+                if second % 10 == 0 {
+                    estimator.observe_trade(ts);
+                }
+            }
+            estimator.observe_mid(ts, mid);
+        }
+
+        let profile = estimator.finish();
+        assert_eq!(profile.a_multipliers[1], MIN_MULTIPLIER);
+        assert_eq!(profile.sigma_multipliers[1], MIN_MULTIPLIER);
+
+        // Calibrated over the empty hour, quoting through hour 2
+        let now = MONDAY_MS + 2 * MS_PER_HOUR;
+        let adjustment = profile.adjustment(now - MS_PER_HOUR, now, now + MS_PER_HOUR);
+        assert!(adjustment.a_ratio().is_finite() && adjustment.sigma_ratio().is_finite(), "{:?}", adjustment);
+
+        // A zero multiplier (e.g. from a hand-edited profile) leaves the estimate unadjusted
+        let zero = SeasonalAdjustment { a_window: 0.0, a_next: 1.0, sigma_window: 0.0, sigma_next: 0.0 };
+        assert_eq!(zero.a_ratio(), 1.0);
+        assert_eq!(zero.sigma_ratio(), 1.0);
+
+        let mut invalid = SeasonalityProfile::flat();
+        invalid.a_multipliers[5] = 0.0;
+        let path = std::env::temp_dir().join(format!("seasonality_invalid_{}.json", std::process::id()));
+        invalid.save(&path).unwrap();
+        let loaded = SeasonalityProfile::load(&path);
+        std::fs::remove_file(&path).ok();
+        assert!(loaded.is_err());
+    }
+
+    #[test]
+    fn test_trades_inside_mid_gaps_are_not_counted() {
+        let mut estimator = SeasonalityEstimator::new(60);
+        // Hour 0 fully observed; hour 1 has mids only in its first 20 minutes
+        for second in (0..3600).chain(3600..4800) {
+            estimator.observe_mid(MONDAY_MS + second * 1000, 100.0 + (second % 2) as f64 * 0.01);
+        }
+        estimator.observe_mid(MONDAY_MS + 7200 * 1000, 100.0);
+        for second in (0..7200).step_by(10) {
+            estimator.observe_trade(MONDAY_MS + second * 1000);
+        }
+
+        // Same trade rate over the observed time in both hours
+        let profile = estimator.finish();
+        assert!((profile.a_multipliers[0] - profile.a_multipliers[1]).abs() < 0.01, "{:?}", &profile.a_multipliers[..2]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Finite trading sessions for the AS horizon
+//!
+//! Maps a timestamp to the end of its session (funding interval, daily cutoff or a custom
+//! intraday schedule) so the spread model can use the remaining time (T - t) instead of a
+//! constant horizon, and the time elapsed in the session for the end-of-session skew.
+
+use crate::model_types::{ASConfig, SessionMode};
+
+const MS_PER_DAY: u64 = 86_400_000;
+
+/// End of the session containing `timestamp_ms` (epoch ms, strictly after the timestamp).
+///
+/// Returns None when sessions are disabled or the schedule is empty/invalid.
+pub fn session_end_ms(timestamp_ms: u64, config: &ASConfig) -> Option<u64> {
+    match config.session_mode {
+        SessionMode::None => None,
+        SessionMode::Funding => {
+            let interval_ms = config.session_funding_interval_seconds.saturating_mul(1000);
+            if interval_ms == 0 {
+                return None;
+            }
+            Some((timestamp_ms / interval_ms + 1) * interval_ms)
+        }
+        SessionMode::Daily => {
+            let cutoff_ms = (config.session_daily_cutoff_seconds % 86_400) * 1000;
+            Some(next_boundary(timestamp_ms, &[cutoff_ms]))
+        }
+        SessionMode::Custom => {
+            let boundaries = custom_boundaries_ms(config)?;
+            Some(next_boundary(timestamp_ms, &boundaries))
+        }
+    }
+}
+
+/// Start of the session containing `timestamp_ms` (at or before the timestamp)
+pub fn session_start_ms(timestamp_ms: u64, config: &ASConfig) -> Option<u64> {
+    match config.session_mode {
+        SessionMode::None => None,
+        SessionMode::Funding | SessionMode::Daily => {
+            let end_ms = session_end_ms(timestamp_ms, config)?;
+            let length_ms = match config.session_mode {
+                SessionMode::Funding => config.session_funding_interval_seconds.saturating_mul(1000),
+                _ => MS_PER_DAY,
+            };
+            Some(end_ms.saturating_sub(length_ms))
+        }
+        SessionMode::Custom => {
+            let boundaries = custom_boundaries_ms(config)?;
+            Some(previous_boundary(timestamp_ms, &boundaries))
+        }
+    }
+}
+
+/// Seconds elapsed in the current session at `timestamp_ms`; 0 without sessions
+pub fn session_elapsed_seconds(timestamp_ms: u64, config: &ASConfig) -> f64 {
+    match session_start_ms(timestamp_ms, config) {
+        Some(start_ms) => timestamp_ms.saturating_sub(start_ms) as f64 / 1000.0,
+        None => 0.0,
+    }
+}
+
+/// Sorted, deduplicated ms-of-day session ends of the custom schedule
+fn custom_boundaries_ms(config: &ASConfig) -> Option<Vec<u64>> {
+    if config.session_boundaries_seconds.is_empty() {
+        return None;
+    }
+    let mut boundaries: Vec<u64> = config
+        .session_boundaries_seconds
+        .iter()
+        .map(|s| (s % 86_400) * 1000)
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    Some(boundaries)
+}
+
+/// Next boundary strictly after `timestamp_ms`, given sorted ms-of-day boundaries
+fn next_boundary(timestamp_ms: u64, boundaries_ms_of_day: &[u64]) -> u64 {
+    let day_start = timestamp_ms - timestamp_ms % MS_PER_DAY;
+    let ms_of_day = timestamp_ms - day_start;
+
+    match boundaries_ms_of_day.iter().find(|b| **b > ms_of_day) {
+        Some(b) => day_start + b,
+        None => day_start + MS_PER_DAY + boundaries_ms_of_day[0],
+    }
+}
+
+/// Last boundary at or before `timestamp_ms`, given sorted ms-of-day boundaries
+fn previous_boundary(timestamp_ms: u64, boundaries_ms_of_day: &[u64]) -> u64 {
+    let day_start = timestamp_ms - timestamp_ms % MS_PER_DAY;
+    let ms_of_day = timestamp_ms - day_start;
+
+    match boundaries_ms_of_day.iter().rev().find(|b| **b <= ms_of_day) {
+        Some(b) => day_start + b,
+        None => (day_start + boundaries_ms_of_day[boundaries_ms_of_day.len() - 1]).saturating_sub(MS_PER_DAY),
+    }
+}
+
+/// Horizon (T - t) in seconds used by the spread model at `timestamp_ms`.
+///
+/// Falls back to the constant `inventory_horizon_seconds` when no session is configured.
+#[inline]
+pub fn time_to_horizon_seconds(timestamp_ms: u64, config: &ASConfig) -> f64 {
+    match session_end_ms(timestamp_ms, config) {
+        Some(end_ms) => end_ms.saturating_sub(timestamp_ms) as f64 / 1000.0,
+        None => config.inventory_horizon_seconds as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_MS: u64 = 3_600_000;
+
+    #[test]
+    fn test_no_session_uses_constant_horizon() {
+        let config = ASConfig::default();
+        assert_eq!(session_end_ms(123_456, &config), None);
+        assert_eq!(time_to_horizon_seconds(123_456, &config), config.inventory_horizon_seconds as f64);
+    }
+
+    #[test]
+    fn test_funding_sessions_decay_and_reset() {
+        let config = ASConfig {
+            session_mode: SessionMode::Funding,
+            ..ASConfig::default()
+        };
+        let day = 20_000 * MS_PER_DAY;
+
+        assert_eq!(session_end_ms(day + HOUR_MS, &config), Some(day + 8 * HOUR_MS));
+        assert_eq!(time_to_horizon_seconds(day + 7 * HOUR_MS, &config), 3600.0);
+        // Exactly on a boundary starts a new full session
+        assert_eq!(time_to_horizon_seconds(day + 8 * HOUR_MS, &config), 8.0 * 3600.0);
+    }
+
+    #[test]
+    fn test_custom_boundaries_wrap_to_next_day() {
+        let config = ASConfig {
+            session_mode: SessionMode::Custom,
+            session_boundaries_seconds: vec![18 * 3600, 6 * 3600],
+            ..ASConfig::default()
+        };
+        let day = 20_000 * MS_PER_DAY;
+
+        assert_eq!(session_end_ms(day + HOUR_MS, &config), Some(day + 6 * HOUR_MS));
+        assert_eq!(session_end_ms(day + 12 * HOUR_MS, &config), Some(day + 18 * HOUR_MS));
+        assert_eq!(session_end_ms(day + 20 * HOUR_MS, &config), Some(day + MS_PER_DAY + 6 * HOUR_MS));
+
+        // Sessions start at the previous boundary, wrapping to the day before
+        assert_eq!(session_start_ms(day + HOUR_MS, &config), Some(day - 6 * HOUR_MS));
+        assert_eq!(session_start_ms(day + 6 * HOUR_MS, &config), Some(day + 6 * HOUR_MS));
+        assert_eq!(session_elapsed_seconds(day, &config), 6.0 * 3600.0);
+    }
+
+    #[test]
+    fn test_session_elapsed() {
+        let day = 20_000 * MS_PER_DAY;
+        assert_eq!(session_elapsed_seconds(day + HOUR_MS, &ASConfig::default()), 0.0);
+
+        let funding = ASConfig { session_mode: SessionMode::Funding, ..ASConfig::default() };
+        assert_eq!(session_elapsed_seconds(day + 8 * HOUR_MS, &funding), 0.0);
+        assert_eq!(session_elapsed_seconds(day + 14 * HOUR_MS, &funding), 6.0 * 3600.0);
+
+        let daily = ASConfig { session_mode: SessionMode::Daily, session_daily_cutoff_seconds: 16 * 3600, ..ASConfig::default() };
+        assert_eq!(session_elapsed_seconds(day + 4 * HOUR_MS, &daily), 12.0 * 3600.0);
+    }
+
+    #[test]
+    fn test_daily_cutoff() {
+        let config = ASConfig {
+            session_mode: SessionMode::Daily,
+            session_daily_cutoff_seconds: 16 * 3600,
+            ..ASConfig::default()
+        };
+        let day = 20_000 * MS_PER_DAY;
+
+        assert_eq!(session_end_ms(day + 17 * HOUR_MS, &config), Some(day + MS_PER_DAY + 16 * HOUR_MS));
+    }
+}
@@ -1,13 +1,10 @@
-use crate::model_types::{ASConfig, GammaMode, OptimalQuote};
+use crate::gamma_policy::{policy_for, GammaContext, MAX_GAMMA_LIMIT, MIN_GAMMA};
+use crate::intensity_shape::IntensityShape;
+use crate::model_types::{ActiveClamps, ASConfig, OptimalQuote, QuoteAdjustments, QuoteSensitivities};
+use crate::session::{session_elapsed_seconds, time_to_horizon_seconds};
 use rust_decimal::Decimal;
 use rust_decimal::prelude::*;
 
-/// Maximum allowed gamma to prevent numerical instability
-const MAX_GAMMA_LIMIT: f64 = 1e6;
-
-/// Minimum gamma to prevent division issues
-const MIN_GAMMA: f64 = 1e-6;
-
 fn clamp_sigma(sigma_pct: f64, config: &ASConfig) -> f64 {
     let min_v = config.min_volatility;
     let max_v = config.max_volatility;
@@ -41,20 +38,37 @@ fn round_up_to_tick(price: Decimal, tick: Decimal) -> Decimal {
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-#[inline]
-pub fn compute_optimal_quote(
+/// Model inputs after volatility/gamma clamping, shared by the quote and its sensitivities
+struct ResolvedInputs {
+    sigma_pct: f64,
+    sigma_sq: f64,
+    t_horizon: f64,
+    /// Horizon of the inventory skew: (T - t) plus the end-of-session urgency term
+    skew_horizon: f64,
+    mid_f64: f64,
+    inv_ratio_signed: f64,
+    gamma: f64,
+    volatility_min_active: bool,
+    volatility_max_active: bool,
+    gamma_min_active: bool,
+    gamma_max_active: bool,
+}
+
+fn resolve_inputs(
     timestamp: u64,
     mid_price: Decimal,
     inventory: Decimal,
-    sigma_pct_raw: f64, // volatility in units of 1/√seconds (so σ²T is dimensionless)
-    bid_kappa: f64,     // intensity decay, dimensionless (calibrated in return space)
-    ask_kappa: f64,     // intensity decay, dimensionless (calibrated in return space)
+    sigma_pct_raw: f64,
     config: &ASConfig,
-) -> OptimalQuote {
+    adjustments: &QuoteAdjustments,
+) -> ResolvedInputs {
     let sigma_pct = clamp_sigma(sigma_pct_raw, config).max(0.0);
+    let sigma_bounded = config.max_volatility > config.min_volatility;
     let mid_f64 = mid_price.to_f64().unwrap_or(0.0);
-    let t_horizon = config.inventory_horizon_seconds as f64;
+    // (T - t): constant horizon, or time left in the current session when sessions are enabled
+    let t_horizon = time_to_horizon_seconds(timestamp, config);
+    // Horizon of the inventory skew: (T - t) plus the end-of-session urgency term
+    let skew_horizon = t_horizon + config.session_end_skew_ratio.max(0.0) * session_elapsed_seconds(timestamp, config);
     let sigma_sq = sigma_pct.powi(2);
 
     let inv_abs = inventory.abs().to_f64().unwrap_or(0.0);
@@ -70,112 +84,238 @@ pub fn compute_optimal_quote(
     };
 
     // Gamma is dimensionless; all terms computed in return space.
-    let gamma_constant = config.risk_aversion_gamma.max(MIN_GAMMA);
-
-    // Calculate gamma_from_shift in return space so gamma stays dimensionless
-    let gamma_from_shift = {
-        if sigma_sq > 1e-12 && t_horizon > 0.0 && mid_f64 > 0.0 {
-            let target_shift_return = (config.max_shift_ticks * config.tick_size) / mid_f64;
-            let denom = sigma_sq * t_horizon;
-            if denom > 0.0 {
-                (target_shift_return / denom).min(MAX_GAMMA_LIMIT)
-            } else {
-                gamma_constant
-            }
-        } else {
-            gamma_constant
-        }
-    };
-
-    let mut gamma = match config.gamma_mode {
-        GammaMode::Constant => gamma_constant,
-        GammaMode::InventoryScaled => (gamma_from_shift * inv_ratio).max(MIN_GAMMA),
-        GammaMode::MaxShift => gamma_from_shift.max(MIN_GAMMA),
+    let gamma_context = GammaContext {
+        timestamp,
+        mid: mid_f64,
+        sigma_sq,
+        t_horizon,
+        skew_horizon,
+        inv_ratio,
+        recent_pnl: adjustments.recent_pnl,
     };
+    let mut gamma = policy_for(&config.gamma_mode).gamma(&gamma_context, config);
 
     // Apply gamma bounds from config
+    let mut gamma_min_active = false;
+    let mut gamma_max_active = false;
     if config.gamma_max > config.gamma_min && config.gamma_max > 0.0 {
         let min_g = config.gamma_min.max(MIN_GAMMA);
         let max_g = config.gamma_max.min(MAX_GAMMA_LIMIT);
+        gamma_min_active = gamma < min_g;
+        gamma_max_active = gamma > max_g;
         gamma = gamma.clamp(min_g, max_g);
     }
 
-    // Kappa is already dimensionless (calibrated in return space), no conversion needed.
-    // Calculate separate bid and ask spreads using side-specific kappa values (all in return space)
-    let vol_risk_term_ret = gamma * sigma_sq * t_horizon;
+    ResolvedInputs {
+        sigma_pct,
+        sigma_sq,
+        t_horizon,
+        skew_horizon,
+        mid_f64,
+        inv_ratio_signed,
+        gamma,
+        volatility_min_active: sigma_bounded && sigma_pct_raw < config.min_volatility,
+        volatility_max_active: sigma_bounded && sigma_pct_raw > config.max_volatility,
+        gamma_min_active,
+        gamma_max_active,
+    }
+}
+
+/// Static minimum spread in bps: the configured minimum, never below twice the maker fee
+#[inline]
+fn static_min_spread_bps(config: &ASConfig) -> f64 {
+    config.min_spread_bps.max(2.0 * config.maker_fee_bps.max(0.0))
+}
+
+/// Per-side (min, max) spread bounds in bps.
+///
+/// `floor_bps` is an extra per-side minimum (e.g. from adverse selection); it can widen the
+/// static minimum but never past the maximum.
+fn spread_bounds_bps(spread_bps: f64, config: &ASConfig, floor_bps: f64) -> (f64, f64) {
+    let static_min = static_min_spread_bps(config);
+    let min_bps = static_min.max(floor_bps);
+    let max_bps = if config.max_spread_bps > 0.0 {
+        config.max_spread_bps
+    } else {
+        spread_bps.max(min_bps)
+    };
+    (min_bps.min(max_bps.max(static_min)), max_bps)
+}
+
+/// Clamp a side's spread (price units) into the configured bps bounds
+fn clamp_side_spread(spread: Decimal, mid_price: Decimal, config: &ASConfig, floor_bps: f64) -> Decimal {
+    if spread <= Decimal::ZERO || mid_price <= Decimal::ZERO {
+        return spread;
+    }
+
+    let spread_bps = (spread / mid_price) * Decimal::from(10000);
+    let spread_bps_f64 = spread_bps.to_f64().unwrap_or(0.0);
+    let (min_bps, max_bps) = spread_bounds_bps(spread_bps_f64, config, floor_bps);
+
+    if max_bps > 0.0 {
+        let clamped_bps = spread_bps_f64.clamp(min_bps, max_bps);
+        let spread_bps = Decimal::from_f64(clamped_bps).unwrap_or(spread_bps);
+        (spread_bps * mid_price) / Decimal::from(10000)
+    } else {
+        spread
+    }
+}
 
-    // Bid spread calculation.
+#[inline]
+fn kappa_or_default(kappa: f64) -> f64 {
     // Default kappa of 1.0 (dimensionless) gives reasonable spread when calibration fails.
     // With γ=0.1, κ=1.0: spread term = (2/0.1)ln(1.1) ≈ 1.9 (as return fraction).
-    let bid_kappa_eff = if bid_kappa > 0.0 { bid_kappa } else { 1.0 };
-    let bid_term = (1.0 + (gamma / bid_kappa_eff)).max(MIN_GAMMA);
-    let bid_spread_ret = vol_risk_term_ret + (2.0 / gamma) * bid_term.ln();
+    if kappa > 0.0 { kappa } else { 1.0 }
+}
 
-    // Ask spread calculation (same default logic as bid)
-    let ask_kappa_eff = if ask_kappa > 0.0 { ask_kappa } else { 1.0 };
-    let ask_term = (1.0 + (gamma / ask_kappa_eff)).max(MIN_GAMMA);
-    let ask_spread_ret = vol_risk_term_ret + (2.0 / gamma) * ask_term.ln();
+/// Full AS spread for one side in return space
+#[inline]
+fn side_spread_ret(inputs: &ResolvedInputs, kappa_eff: f64) -> f64 {
+    let gamma = inputs.gamma;
+    let term = (1.0 + (gamma / kappa_eff)).max(MIN_GAMMA);
+    gamma * inputs.sigma_sq * inputs.t_horizon + (2.0 / gamma) * term.ln()
+}
+
+/// Largest quote depth considered by the numerical solver (return space)
+const MAX_OPTIMAL_DEPTH_RET: f64 = 1.0;
+
+/// Optimal quote depth δ* (return space) for a general intensity shape.
+///
+/// Maximising λ(δ)·(1 − e^(-γδ)) gives h(δ)·(e^(γδ) − 1) = γ, with h the shape's hazard;
+/// for the exponential shape this is the closed form ln(1 + γ/κ)/γ. Solved by bisection.
+pub fn optimal_depth_ret(shape: &IntensityShape, gamma: f64) -> f64 {
+    if let IntensityShape::Exponential { kappa } = shape {
+        let term = (1.0 + gamma / kappa_or_default(*kappa)).max(MIN_GAMMA);
+        return term.ln() / gamma;
+    }
+    if !shape.is_valid() {
+        return optimal_depth_ret(&IntensityShape::Exponential { kappa: 0.0 }, gamma);
+    }
+
+    // Normalised first-order condition h(δ)·expm1(γδ)/γ − 1: negative at 0, increasing with δ
+    let foc = |delta: f64| shape.hazard(delta) * (gamma * delta).exp_m1() / gamma - 1.0;
+    let mut high = 1e-4;
+    while foc(high) < 0.0 {
+        if high >= MAX_OPTIMAL_DEPTH_RET {
+            return MAX_OPTIMAL_DEPTH_RET;
+        }
+        high *= 2.0;
+    }
+
+    let mut low = 0.0;
+    for _ in 0..60 {
+        let mid = 0.5 * (low + high);
+        if foc(mid) < 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// Full AS spread for one side in return space with a general intensity shape
+#[inline]
+fn side_spread_ret_shaped(inputs: &ResolvedInputs, shape: &IntensityShape) -> f64 {
+    match shape {
+        IntensityShape::Exponential { kappa } => side_spread_ret(inputs, kappa_or_default(*kappa)),
+        _ => inputs.gamma * inputs.sigma_sq * inputs.t_horizon + 2.0 * optimal_depth_ret(shape, inputs.gamma),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[inline]
+pub fn compute_optimal_quote(
+    timestamp: u64,
+    mid_price: Decimal,
+    inventory: Decimal,
+    sigma_pct_raw: f64, // volatility in units of 1/√seconds (so σ²T is dimensionless)
+    bid_kappa: f64,     // intensity decay, dimensionless (calibrated in return space)
+    ask_kappa: f64,     // intensity decay, dimensionless (calibrated in return space)
+    config: &ASConfig,
+) -> OptimalQuote {
+    compute_optimal_quote_adjusted(
+        timestamp,
+        mid_price,
+        inventory,
+        sigma_pct_raw,
+        bid_kappa,
+        ask_kappa,
+        config,
+        &QuoteAdjustments::default(),
+    )
+}
+
+/// `compute_optimal_quote` with strategy state (e.g. recent PnL for loss-adaptive gamma)
+#[allow(clippy::too_many_arguments)]
+#[inline]
+pub fn compute_optimal_quote_adjusted(
+    timestamp: u64,
+    mid_price: Decimal,
+    inventory: Decimal,
+    sigma_pct_raw: f64,
+    bid_kappa: f64,
+    ask_kappa: f64,
+    config: &ASConfig,
+    adjustments: &QuoteAdjustments,
+) -> OptimalQuote {
+    compute_optimal_quote_shaped(
+        timestamp,
+        mid_price,
+        inventory,
+        sigma_pct_raw,
+        &IntensityShape::Exponential { kappa: bid_kappa },
+        &IntensityShape::Exponential { kappa: ask_kappa },
+        config,
+        adjustments,
+    )
+}
+
+/// `compute_optimal_quote_adjusted` for arbitrary per-side intensity shapes; non-exponential
+/// shapes are quoted at the numerically solved optimal depth
+#[allow(clippy::too_many_arguments)]
+pub fn compute_optimal_quote_shaped(
+    timestamp: u64,
+    mid_price: Decimal,
+    inventory: Decimal,
+    sigma_pct_raw: f64,
+    bid_shape: &IntensityShape,
+    ask_shape: &IntensityShape,
+    config: &ASConfig,
+    adjustments: &QuoteAdjustments,
+) -> OptimalQuote {
+    let inputs = resolve_inputs(timestamp, mid_price, inventory, sigma_pct_raw, config, adjustments);
+    let ResolvedInputs { sigma_sq, skew_horizon, mid_f64, inv_ratio_signed, gamma, .. } = inputs;
+
+    // Kappa is already dimensionless (calibrated in return space), no conversion needed.
+    // Calculate separate bid and ask spreads using side-specific shapes (all in return space)
+    let bid_spread_ret = side_spread_ret_shaped(&inputs, bid_shape);
+    let ask_spread_ret = side_spread_ret_shaped(&inputs, ask_shape);
 
     // Convert spreads back to price space
     let bid_spread_f64 = if mid_f64 > 0.0 { bid_spread_ret * mid_f64 } else { bid_spread_ret };
     let ask_spread_f64 = if mid_f64 > 0.0 { ask_spread_ret * mid_f64 } else { ask_spread_ret };
 
     // Convert to Decimal and validate
-    let mut bid_spread = if bid_spread_f64.is_finite() && bid_spread_f64 > 0.0 {
+    let bid_spread = if bid_spread_f64.is_finite() && bid_spread_f64 > 0.0 {
         Decimal::from_f64(bid_spread_f64).unwrap_or(Decimal::ZERO)
     } else {
         Decimal::ZERO
     };
 
-    let mut ask_spread = if ask_spread_f64.is_finite() && ask_spread_f64 > 0.0 {
+    let ask_spread = if ask_spread_f64.is_finite() && ask_spread_f64 > 0.0 {
         Decimal::from_f64(ask_spread_f64).unwrap_or(Decimal::ZERO)
     } else {
         Decimal::ZERO
     };
 
-    // Apply spread bounds in basis points for bid spread
-    if bid_spread > Decimal::ZERO && mid_price > Decimal::ZERO {
-        let mut spread_bps = (bid_spread / mid_price) * Decimal::from(10000);
-        let spread_bps_f64 = spread_bps.to_f64().unwrap_or(0.0);
+    // Apply spread bounds in basis points per side
+    let bid_spread = clamp_side_spread(bid_spread, mid_price, config, adjustments.bid_floor_bps);
+    let ask_spread = clamp_side_spread(ask_spread, mid_price, config, adjustments.ask_floor_bps);
 
-        let fee_floor_bps = config.maker_fee_bps.max(0.0);
-        let min_bps = config.min_spread_bps.max(2.0 * fee_floor_bps);
-        let max_bps = if config.max_spread_bps > 0.0 {
-            config.max_spread_bps
-        } else {
-            spread_bps_f64.max(min_bps)
-        };
-
-        if max_bps > 0.0 {
-            let clamped_bps = spread_bps_f64.clamp(min_bps, max_bps);
-            spread_bps = Decimal::from_f64(clamped_bps).unwrap_or(spread_bps);
-            bid_spread = (spread_bps * mid_price) / Decimal::from(10000);
-        }
-    }
-
-    // Apply spread bounds in basis points for ask spread
-    if ask_spread > Decimal::ZERO && mid_price > Decimal::ZERO {
-        let mut spread_bps = (ask_spread / mid_price) * Decimal::from(10000);
-        let spread_bps_f64 = spread_bps.to_f64().unwrap_or(0.0);
-
-        let fee_floor_bps = config.maker_fee_bps.max(0.0);
-        let min_bps = config.min_spread_bps.max(2.0 * fee_floor_bps);
-        let max_bps = if config.max_spread_bps > 0.0 {
-            config.max_spread_bps
-        } else {
-            spread_bps_f64.max(min_bps)
-        };
-
-        if max_bps > 0.0 {
-            let clamped_bps = spread_bps_f64.clamp(min_bps, max_bps);
-            spread_bps = Decimal::from_f64(clamped_bps).unwrap_or(spread_bps);
-            ask_spread = (spread_bps * mid_price) / Decimal::from(10000);
-        }
-    }
-
-    // Calculate reservation price adjustment
-    // Reservation price adjustment in return space
-    let risk_adjustment_ret = inv_ratio_signed * gamma * sigma_sq * t_horizon;
+    // Reservation price adjustment in return space. Within a session the skew horizon adds
+    // an urgency term for the time already elapsed, so skew does not vanish at the session end
+    let risk_adjustment_ret = inv_ratio_signed * gamma * sigma_sq * skew_horizon;
     let risk_adjustment = if mid_f64 > 0.0 {
         Decimal::from_f64(risk_adjustment_ret * mid_f64).unwrap_or(Decimal::ZERO)
     } else {
@@ -232,9 +372,135 @@ pub fn compute_optimal_quote(
     }
 }
 
+/// Partial derivatives of the quote computed by `compute_optimal_quote_adjusted` for the same inputs.
+///
+/// Half-spread h = mid/2 * (γσ²T + (2/γ)ln(1 + γ/κ)) per side and reservation price
+/// r = mid * (1 - (q/q_max)γσ²T_s), with T_s the skew horizon (T - t) + ratio·elapsed.
+/// Also reports which clamps were binding.
+#[allow(clippy::too_many_arguments)]
+pub fn compute_quote_sensitivities(
+    timestamp: u64,
+    mid_price: Decimal,
+    inventory: Decimal,
+    sigma_pct_raw: f64,
+    bid_kappa: f64,
+    ask_kappa: f64,
+    config: &ASConfig,
+    adjustments: &QuoteAdjustments,
+) -> QuoteSensitivities {
+    compute_quote_sensitivities_shaped(
+        timestamp,
+        mid_price,
+        inventory,
+        sigma_pct_raw,
+        &IntensityShape::Exponential { kappa: bid_kappa },
+        &IntensityShape::Exponential { kappa: ask_kappa },
+        config,
+        adjustments,
+    )
+}
+
+/// Relative step of the central difference of the numerically solved depth in γ
+const DEPTH_GAMMA_STEP: f64 = 1e-4;
+
+/// `compute_quote_sensitivities` for the quote of `compute_optimal_quote_shaped`.
+///
+/// Non-exponential sides use h = mid/2 * (γσ²T + 2δ*(γ)) with ∂δ*/∂γ taken by central
+/// differences through the depth solver; they have no κ, so their κ derivative is NaN.
+#[allow(clippy::too_many_arguments)]
+pub fn compute_quote_sensitivities_shaped(
+    timestamp: u64,
+    mid_price: Decimal,
+    inventory: Decimal,
+    sigma_pct_raw: f64,
+    bid_shape: &IntensityShape,
+    ask_shape: &IntensityShape,
+    config: &ASConfig,
+    adjustments: &QuoteAdjustments,
+) -> QuoteSensitivities {
+    let inputs = resolve_inputs(timestamp, mid_price, inventory, sigma_pct_raw, config, adjustments);
+    let ResolvedInputs { sigma_pct, sigma_sq, t_horizon, skew_horizon, mid_f64, inv_ratio_signed, gamma, .. } = inputs;
+    let scale = if mid_f64 > 0.0 { mid_f64 } else { 1.0 };
+
+    let half_spread_partials = |shape: &IntensityShape| {
+        let d_sigma = scale * gamma * sigma_pct * t_horizon;
+        match shape {
+            IntensityShape::Exponential { kappa } => {
+                let kappa = kappa_or_default(*kappa);
+                let d_kappa = -scale / (kappa * (kappa + gamma));
+                let d_gamma = scale
+                    * (0.5 * sigma_sq * t_horizon - (1.0 + gamma / kappa).ln() / gamma.powi(2)
+                        + 1.0 / (gamma * (kappa + gamma)));
+                (d_sigma, d_kappa, d_gamma)
+            }
+            _ => {
+                let step = gamma * DEPTH_GAMMA_STEP;
+                let d_depth = (optimal_depth_ret(shape, gamma + step) - optimal_depth_ret(shape, gamma - step)) / (2.0 * step);
+                (d_sigma, f64::NAN, scale * (0.5 * sigma_sq * t_horizon + d_depth))
+            }
+        }
+    };
+    let (bid_d_sigma, bid_d_kappa, bid_d_gamma) = half_spread_partials(bid_shape);
+    let (ask_d_sigma, ask_d_kappa, ask_d_gamma) = half_spread_partials(ask_shape);
+
+    let inventory_cap = config.max_inventory > 0.0 && inv_ratio_signed.abs() >= 1.0;
+    let reservation_d_inventory = if config.max_inventory > 0.0 && !inventory_cap {
+        -scale * gamma * sigma_sq * skew_horizon / config.max_inventory
+    } else {
+        0.0
+    };
+
+    // Which side bounds bind, evaluated on the same bps the quote clamps
+    let side_clamps = |shape: &IntensityShape, floor_bps: f64| -> (bool, bool, bool, bool) {
+        let spread_bps = side_spread_ret_shaped(&inputs, shape) * 10_000.0;
+        if !(spread_bps.is_finite() && spread_bps > 0.0 && mid_f64 > 0.0) {
+            return (false, false, false, false);
+        }
+        let (min_bps, max_bps) = spread_bounds_bps(spread_bps, config, floor_bps);
+        if max_bps <= 0.0 {
+            return (false, false, false, false);
+        }
+        let min_active = spread_bps < min_bps;
+        let static_min = static_min_spread_bps(config);
+        let adaptive_floor = min_active && min_bps > static_min;
+        let fee_floor = min_active && !adaptive_floor && 2.0 * config.maker_fee_bps.max(0.0) > config.min_spread_bps;
+        (min_active, spread_bps > max_bps, fee_floor, adaptive_floor)
+    };
+    let (bid_min_spread, bid_max_spread, bid_fee_floor, bid_adaptive_floor) =
+        side_clamps(bid_shape, adjustments.bid_floor_bps);
+    let (ask_min_spread, ask_max_spread, ask_fee_floor, ask_adaptive_floor) =
+        side_clamps(ask_shape, adjustments.ask_floor_bps);
+
+    QuoteSensitivities {
+        bid_half_spread_d_sigma: bid_d_sigma,
+        bid_half_spread_d_kappa: bid_d_kappa,
+        bid_half_spread_d_gamma: bid_d_gamma,
+        ask_half_spread_d_sigma: ask_d_sigma,
+        ask_half_spread_d_kappa: ask_d_kappa,
+        ask_half_spread_d_gamma: ask_d_gamma,
+        reservation_d_sigma: -scale * 2.0 * inv_ratio_signed * gamma * sigma_pct * skew_horizon,
+        reservation_d_gamma: -scale * inv_ratio_signed * sigma_sq * skew_horizon,
+        reservation_d_inventory,
+        clamps: ActiveClamps {
+            volatility_min: inputs.volatility_min_active,
+            volatility_max: inputs.volatility_max_active,
+            gamma_min: inputs.gamma_min_active,
+            gamma_max: inputs.gamma_max_active,
+            bid_min_spread,
+            bid_max_spread,
+            ask_min_spread,
+            ask_max_spread,
+            fee_floor: bid_fee_floor || ask_fee_floor,
+            adverse_selection_floor: bid_adaptive_floor || ask_adaptive_floor,
+            inventory_cap,
+        },
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model_types::GammaMode;
     use rust_decimal::prelude::FromStr;
 
     #[test]
@@ -279,4 +545,210 @@ mod tests {
         // Should still produce valid quotes due to internal clamping
         assert!(quote.gamma <= MAX_GAMMA_LIMIT);
     }
+
+    #[test]
+    fn compute_quote_session_skew_grows_toward_session_end() {
+        let config = ASConfig {
+            session_mode: crate::model_types::SessionMode::Funding,
+            gamma_mode: GammaMode::Constant,
+            gamma_min: 0.1,
+            gamma_max: 0.1,
+            risk_aversion_gamma: 0.1,
+            session_end_skew_ratio: 2.0,
+            ..ASConfig::default()
+        };
+        let mid = Decimal::from_str("100.0").unwrap();
+        let inv = Decimal::from_str("5.0").unwrap();
+        let session_start = 8 * 3_600_000;
+        let near_end = session_start + 8 * 3_600_000 - 60_000;
+
+        let early = compute_optimal_quote(session_start, mid, inv, 0.001, 100.0, 100.0, &config);
+        let late = compute_optimal_quote(near_end, mid, inv, 0.001, 100.0, 100.0, &config);
+        // The urgency term outgrows the decaying AS term: skew rises into the session end
+        assert!(mid - late.reservation_price > mid - early.reservation_price);
+
+        // Without it the AS skew alone decays with (T - t)
+        let config = ASConfig { session_end_skew_ratio: 0.0, ..config };
+        let early = compute_optimal_quote(session_start, mid, inv, 0.001, 100.0, 100.0, &config);
+        let late = compute_optimal_quote(near_end, mid, inv, 0.001, 100.0, 100.0, &config);
+        assert!(mid - late.reservation_price < mid - early.reservation_price);
+    }
+
+    #[test]
+    fn compute_quote_session_skew_keeps_max_shift_target() {
+        let config = ASConfig {
+            session_mode: crate::model_types::SessionMode::Funding,
+            session_end_skew_ratio: 2.0,
+            ..ASConfig::default()
+        };
+        let mid = Decimal::from(2000);
+        let inv = Decimal::from_f64(config.max_inventory).unwrap();
+        let target = config.max_shift_ticks * config.tick_size;
+        let session_start = 8 * 3_600_000;
+
+        // The inventory-scaled policy solves γ on the skew horizon, so the shift at max
+        // inventory stays at max_shift while the urgency term grows
+        for minutes_left in [480, 240, 60, 10, 1] {
+            let ts = session_start + (480 - minutes_left) * 60_000;
+            let quote = compute_optimal_quote(ts, mid, inv, 1e-4, 100.0, 100.0, &config);
+            assert!(quote.gamma > config.gamma_min && quote.gamma < config.gamma_max, "{} min left: gamma {}", minutes_left, quote.gamma);
+            let shift = (mid - quote.reservation_price).to_f64().unwrap();
+            assert!(shift <= target * (1.0 + 1e-6), "{} min left: shift {} > {}", minutes_left, shift, target);
+            assert!(shift > 0.99 * target, "{} min left: shift {}", minutes_left, shift);
+        }
+    }
+
+    fn unclamped_config() -> ASConfig {
+        ASConfig {
+            gamma_mode: GammaMode::Constant,
+            risk_aversion_gamma: 0.5,
+            gamma_min: 1e-3,
+            gamma_max: 100.0,
+            tick_size: 0.0,
+            min_spread_bps: 0.0,
+            max_spread_bps: 1e6,
+            maker_fee_bps: 0.0,
+            inventory_horizon_seconds: 600,
+            ..ASConfig::default()
+        }
+    }
+
+    #[test]
+    fn sensitivities_match_finite_differences() {
+        let config = unclamped_config();
+        let mid = Decimal::from(100);
+        let inv = Decimal::from(2);
+        let (sigma, kappa) = (0.002, 50.0);
+
+        let half_bid = |sigma: f64, kappa: f64, config: &ASConfig| {
+            let q = compute_optimal_quote(0, mid, inv, sigma, kappa, kappa, config);
+            (q.reservation_price - q.bid_price).to_f64().unwrap()
+        };
+        let reservation = |sigma: f64, inv: Decimal, config: &ASConfig| {
+            let q = compute_optimal_quote(0, mid, inv, sigma, kappa, kappa, config);
+            q.reservation_price.to_f64().unwrap()
+        };
+
+        let sens = compute_quote_sensitivities(0, mid, inv, sigma, kappa, kappa, &config, &QuoteAdjustments::default());
+        assert_eq!(sens.clamps, ActiveClamps::default());
+
+        let eps = 1e-5;
+        let fd_sigma = (half_bid(sigma + eps, kappa, &config) - half_bid(sigma - eps, kappa, &config)) / (2.0 * eps);
+        assert!((fd_sigma - sens.bid_half_spread_d_sigma).abs() < 1e-3 * fd_sigma.abs().max(1.0));
+
+        let fd_kappa = (half_bid(sigma, kappa + 0.01, &config) - half_bid(sigma, kappa - 0.01, &config)) / 0.02;
+        assert!((fd_kappa - sens.bid_half_spread_d_kappa).abs() < 1e-3 * fd_kappa.abs().max(1e-3));
+
+        let mut up = config.clone();
+        let mut down = config.clone();
+        up.risk_aversion_gamma += 1e-4;
+        down.risk_aversion_gamma -= 1e-4;
+        let fd_gamma = (half_bid(sigma, kappa, &up) - half_bid(sigma, kappa, &down)) / 2e-4;
+        assert!((fd_gamma - sens.bid_half_spread_d_gamma).abs() < 1e-3 * fd_gamma.abs().max(1e-3));
+
+        let fd_r_sigma = (reservation(sigma + eps, inv, &config) - reservation(sigma - eps, inv, &config)) / (2.0 * eps);
+        assert!((fd_r_sigma - sens.reservation_d_sigma).abs() < 1e-3 * fd_r_sigma.abs().max(1e-3));
+
+        let fd_r_inv = reservation(sigma, Decimal::from(3), &config) - reservation(sigma, Decimal::from(1), &config);
+        assert!((fd_r_inv / 2.0 - sens.reservation_d_inventory).abs() < 1e-6);
+    }
+
+    #[test]
+    fn numerical_depth_matches_closed_form_and_first_order_condition() {
+        let gamma = 0.05;
+        let exponential = IntensityShape::Exponential { kappa: 800.0 };
+        let single_hazard = IntensityShape::Piecewise { knots: vec![0.0], hazards: vec![800.0] };
+        let closed_form = optimal_depth_ret(&exponential, gamma);
+        assert!((optimal_depth_ret(&single_hazard, gamma) - closed_form).abs() < 1e-12);
+
+        let power_law = IntensityShape::PowerLaw { delta0: 2e-4, exponent: 1.5 };
+        let depth = optimal_depth_ret(&power_law, gamma);
+        let foc = power_law.hazard(depth) * (gamma * depth).exp_m1() - gamma;
+        assert!(foc.abs() < 1e-9, "depth {} foc {}", depth, foc);
+
+        // Heavier tail than an exponential with the same hazard at the touch quotes wider
+        let matched = IntensityShape::Exponential { kappa: power_law.hazard(0.0) };
+        assert!(depth > optimal_depth_ret(&matched, gamma));
+
+        let config = unclamped_config();
+        let mid = Decimal::from_str("100.0").unwrap();
+        let quote = compute_optimal_quote_shaped(0, mid, Decimal::ZERO, 0.001, &power_law, &matched, &config, &QuoteAdjustments::default());
+        assert!(quote.reservation_price - quote.bid_price > quote.ask_price - quote.reservation_price);
+    }
+
+    #[test]
+    fn shaped_sensitivities_follow_the_shaped_quote() {
+        let config = unclamped_config();
+        let mid = Decimal::from(100);
+        let power_law = IntensityShape::PowerLaw { delta0: 2e-4, exponent: 1.5 };
+        let exponential = IntensityShape::Exponential { kappa: 50.0 };
+        let half_bid = |config: &ASConfig| {
+            let q = compute_optimal_quote_shaped(0, mid, Decimal::ZERO, 0.002, &power_law, &exponential, config, &QuoteAdjustments::default());
+            (q.reservation_price - q.bid_price).to_f64().unwrap()
+        };
+
+        let sens = compute_quote_sensitivities_shaped(0, mid, Decimal::ZERO, 0.002, &power_law, &exponential, &config, &QuoteAdjustments::default());
+        assert!(sens.bid_half_spread_d_kappa.is_nan());
+        let exponential_sens = compute_quote_sensitivities(0, mid, Decimal::ZERO, 0.002, 50.0, 50.0, &config, &QuoteAdjustments::default());
+        assert_eq!(sens.ask_half_spread_d_kappa, exponential_sens.ask_half_spread_d_kappa);
+
+        let mut up = config.clone();
+        let mut down = config.clone();
+        up.risk_aversion_gamma += 1e-4;
+        down.risk_aversion_gamma -= 1e-4;
+        let fd_gamma = (half_bid(&up) - half_bid(&down)) / 2e-4;
+        assert!((fd_gamma - sens.bid_half_spread_d_gamma).abs() < 1e-3 * fd_gamma.abs().max(1e-3), "{} vs {}", fd_gamma, sens.bid_half_spread_d_gamma);
+
+        // Clamps are judged on the shaped spread: a max between the two sides binds one only
+        let bid_bps = side_spread_ret_shaped(&resolve_inputs(0, mid, Decimal::ZERO, 0.002, &config, &QuoteAdjustments::default()), &power_law) * 1e4;
+        let capped = ASConfig { max_spread_bps: bid_bps * 0.5, ..config.clone() };
+        let sens = compute_quote_sensitivities_shaped(0, mid, Decimal::ZERO, 0.002, &power_law, &exponential, &capped, &QuoteAdjustments::default());
+        let ask_bps = side_spread_ret(&resolve_inputs(0, mid, Decimal::ZERO, 0.002, &config, &QuoteAdjustments::default()), 50.0) * 1e4;
+        assert_eq!(sens.clamps.bid_max_spread, bid_bps > capped.max_spread_bps);
+        assert_eq!(sens.clamps.ask_max_spread, ask_bps > capped.max_spread_bps);
+    }
+
+    #[test]
+    fn sensitivities_report_active_clamps() {
+        let config = ASConfig {
+            min_spread_bps: 1.0,
+            maker_fee_bps: 50.0,
+            max_volatility: 0.001,
+            ..unclamped_config()
+        };
+        let mid = Decimal::from(100);
+        let sens = compute_quote_sensitivities(0, mid, Decimal::from(20), 0.002, 1e6, 1e6, &config, &QuoteAdjustments::default());
+
+        assert!(sens.clamps.volatility_max);
+        assert!(sens.clamps.bid_min_spread && sens.clamps.ask_min_spread);
+        assert!(sens.clamps.fee_floor);
+        assert!(sens.clamps.inventory_cap);
+        assert_eq!(sens.reservation_d_inventory, 0.0);
+        assert!(sens.clamps.labels().contains("fee_floor"));
+    }
+
+    #[test]
+    fn adverse_selection_floor_widens_side_up_to_max() {
+        let config = ASConfig {
+            min_spread_bps: 2.0,
+            max_spread_bps: 30.0,
+            maker_fee_bps: 0.0,
+            ..unclamped_config()
+        };
+        let mid = Decimal::from(100);
+        let adjustments = QuoteAdjustments {
+            bid_floor_bps: 20.0,
+            ask_floor_bps: 500.0,
+            ..QuoteAdjustments::default()
+        };
+
+        let quote = compute_optimal_quote_adjusted(0, mid, Decimal::ZERO, 0.0001, 1e6, 1e6, &config, &adjustments);
+        let bid_bps = ((quote.reservation_price - quote.bid_price) / mid * Decimal::from(20000)).to_f64().unwrap();
+        let ask_bps = ((quote.ask_price - quote.reservation_price) / mid * Decimal::from(20000)).to_f64().unwrap();
+        assert!((bid_bps - 20.0).abs() < 1e-6, "bid side spread {}", bid_bps);
+        assert!((ask_bps - 30.0).abs() < 1e-6, "ask side spread {}", ask_bps);
+
+        let sens = compute_quote_sensitivities(0, mid, Decimal::ZERO, 0.0001, 1e6, 1e6, &config, &adjustments);
+        assert!(sens.clamps.adverse_selection_floor);
+    }
 }
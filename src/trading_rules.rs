@@ -0,0 +1,180 @@
+//! Per-market exchange trading rules (tick size, minimum order size, size step)
+//!
+//! Rules are fetched from the REST market config while online and cached in a local JSON
+//! file, so the spread model and backtester can snap prices and sizes offline.
+
+use crate::model_types::ASConfig;
+use crate::rest::RestClient;
+use crate::types::TradingConfig;
+use rust_decimal::prelude::*;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Default location of the trading rules cache
+pub const DEFAULT_TRADING_RULES_PATH: &str = "data/trading_rules.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradingRules {
+    pub market: String,
+    /// Minimum price increment
+    pub tick_size: Decimal,
+    pub min_order_size: Decimal,
+    /// Order size increment
+    pub order_size_step: Decimal,
+    /// When the rules were fetched (epoch ms)
+    pub updated_at: u64,
+}
+
+impl TradingRules {
+    pub fn from_trading_config(
+        market: &str,
+        trading_config: &TradingConfig,
+        updated_at: u64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let parse = |field: &str, value: &str| {
+            Decimal::from_str(value).map_err(|e| format!("Invalid {} '{}' for {}: {}", field, value, market, e))
+        };
+
+        Ok(Self {
+            market: market.to_string(),
+            tick_size: parse("minPriceChange", &trading_config.min_price_change)?,
+            min_order_size: parse("minOrderSize", &trading_config.min_order_size)?,
+            order_size_step: parse("minOrderSizeChange", &trading_config.min_order_size_change)?,
+            updated_at,
+        })
+    }
+
+    /// Copy tick/size rules into the model config
+    pub fn apply_to_config(&self, config: &mut ASConfig) {
+        config.tick_size = self.tick_size.to_f64().unwrap_or(config.tick_size);
+        config.min_order_size = self.min_order_size.to_f64().unwrap_or(0.0);
+        config.order_size_step = self.order_size_step.to_f64().unwrap_or(0.0);
+    }
+}
+
+/// Round `size` down to a multiple of `step` and reject it below `min_size`.
+///
+/// A non-positive step leaves the size unchanged; returns None when the order is too small.
+pub fn snap_order_size(size: Decimal, step: Decimal, min_size: Decimal) -> Option<Decimal> {
+    let snapped = if step > Decimal::ZERO {
+        (size / step).floor() * step
+    } else {
+        size
+    };
+
+    if snapped <= Decimal::ZERO || snapped < min_size {
+        None
+    } else {
+        Some(snapped)
+    }
+}
+
+/// Trading rules for several markets, keyed by market name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TradingRulesCache {
+    pub markets: BTreeMap<String, TradingRules>,
+}
+
+impl TradingRulesCache {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, market: &str) -> Option<&TradingRules> {
+        self.markets.get(market)
+    }
+
+    pub fn insert(&mut self, rules: TradingRules) {
+        self.markets.insert(rules.market.clone(), rules);
+    }
+
+    /// Fetch the rules for `markets` over REST and merge them into the cache at `path`.
+    ///
+    /// Markets that fail to fetch keep their previously cached rules.
+    pub async fn refresh(client: &RestClient, markets: &[String], path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut cache = if path.exists() { Self::load(path)? } else { Self::default() };
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+
+        for market in markets {
+            match client.get_market_config(market).await {
+                Ok(market_config) => {
+                    cache.insert(TradingRules::from_trading_config(market, &market_config.trading_config, now)?);
+                }
+                Err(e) => eprintln!("[WARN] Failed to fetch trading rules for {}: {}", market, e),
+            }
+        }
+
+        cache.save(path)?;
+        Ok(cache)
+    }
+}
+
+/// Apply cached rules for `market` to `config` if the cache exists and has the market
+pub fn apply_cached_rules(config: &mut ASConfig, path: &Path, market: &str) -> Option<TradingRules> {
+    let cache = TradingRulesCache::load(path).ok()?;
+    let rules = cache.get(market)?.clone();
+    rules.apply_to_config(config);
+    Some(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn btc_trading_config() -> TradingConfig {
+        TradingConfig {
+            min_order_size: "0.0001".to_string(),
+            min_order_size_change: "0.00001".to_string(),
+            min_price_change: "1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rules_apply_to_config() {
+        let rules = TradingRules::from_trading_config("BTC-USD", &btc_trading_config(), 0).unwrap();
+        let mut config = ASConfig::default();
+        rules.apply_to_config(&mut config);
+
+        assert_eq!(config.tick_size, 1.0);
+        assert_eq!(config.min_order_size, 0.0001);
+        assert_eq!(config.order_size_step, 0.00001);
+    }
+
+    #[test]
+    fn test_snap_order_size() {
+        let step = Decimal::from_str("0.01").unwrap();
+        let min = Decimal::from_str("0.05").unwrap();
+
+        assert_eq!(snap_order_size(Decimal::from_str("0.1234").unwrap(), step, min), Some(Decimal::from_str("0.12").unwrap()));
+        assert_eq!(snap_order_size(Decimal::from_str("0.0499").unwrap(), step, min), None);
+        assert_eq!(snap_order_size(Decimal::from_str("0.1234").unwrap(), Decimal::ZERO, Decimal::ZERO), Some(Decimal::from_str("0.1234").unwrap()));
+    }
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let mut cache = TradingRulesCache::default();
+        cache.insert(TradingRules::from_trading_config("BTC-USD", &btc_trading_config(), 42).unwrap());
+
+        let path = std::env::temp_dir().join(format!("trading_rules_test_{}.json", std::process::id()));
+        cache.save(&path).unwrap();
+        let loaded = TradingRulesCache::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.get("BTC-USD"), cache.get("BTC-USD"));
+    }
+}